@@ -0,0 +1,7 @@
+-- Todo names are now unique per owner instead of globally. Existing rows
+-- were globally unique, so they already satisfy the narrower constraint.
+BEGIN;
+  ALTER TABLE todo DROP CONSTRAINT todo_name_key;
+
+  ALTER TABLE todo ADD CONSTRAINT todo_user_id_name_key UNIQUE (user_id, name);
+COMMIT;
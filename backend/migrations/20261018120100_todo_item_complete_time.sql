@@ -0,0 +1,16 @@
+-- Completed items are listed by complete_time, so every completed item must
+-- carry one. Older rows without it fall back to their last update.
+BEGIN;
+  UPDATE todo_item SET complete_time = update_time
+  WHERE is_complete = TRUE AND complete_time IS NULL;
+
+  UPDATE todo_item SET complete_time = NULL
+  WHERE is_complete = FALSE AND complete_time IS NOT NULL;
+
+  ALTER TABLE todo_item ADD CONSTRAINT todo_item_complete_time_check
+    CHECK (is_complete = (complete_time IS NOT NULL));
+
+  CREATE INDEX todo_item_todo_id_complete_time_idx
+    ON todo_item (todo_id, complete_time DESC)
+    WHERE is_complete = TRUE;
+COMMIT;
@@ -0,0 +1,9 @@
+-- A template repeats either by a fixed interval or by an RFC 5545 RRULE.
+BEGIN;
+  ALTER TABLE recurring_template ALTER COLUMN recurrence_period DROP NOT NULL;
+
+  ALTER TABLE recurring_template ADD COLUMN recurrence_rule TEXT NULL;
+
+  ALTER TABLE recurring_template ADD CONSTRAINT recurring_template_recurrence_check
+    CHECK ((recurrence_period IS NULL) <> (recurrence_rule IS NULL));
+COMMIT;
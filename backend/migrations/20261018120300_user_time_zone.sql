@@ -0,0 +1,5 @@
+-- Dates like "today" are computed in the user's IANA time zone. Names are
+-- validated against pg_timezone_names when they are set.
+BEGIN;
+  ALTER TABLE users ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
+COMMIT;
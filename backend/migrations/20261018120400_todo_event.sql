@@ -0,0 +1,67 @@
+-- Changes to the items and templates of a todo, streamed by
+-- GET /todo/{todo}/events. Rows are written by triggers next to the
+-- update_time ones and announced on the `todo_event` channel with the todo id
+-- as payload once the transaction commits, so every backend instance sees
+-- them. Clients resume from the event id they last saw.
+BEGIN;
+  CREATE TYPE todo_event_entity AS ENUM ('item', 'template');
+  CREATE TYPE todo_event_kind AS ENUM ('created', 'updated', 'completed', 'deleted');
+
+  -- No foreign key on the todo, the events of its items are written while
+  -- a deleted todo cascades to them.
+  CREATE TABLE todo_event (
+      event_id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+      todo_id uuid NOT NULL,
+      entity todo_event_entity NOT NULL,
+      entity_id uuid NOT NULL,
+      kind todo_event_kind NOT NULL,
+      create_time timestamptz NOT NULL DEFAULT NOW()
+  );
+
+  CREATE INDEX todo_event_todo_id_event_id_idx ON todo_event (todo_id, event_id);
+
+  -- TG_ARGV[0] is the entity, TG_ARGV[1] the column holding its id.
+  CREATE OR REPLACE FUNCTION todo_event_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      changed jsonb;
+      kind todo_event_kind;
+  BEGIN
+      IF TG_OP = 'INSERT' THEN
+          changed = to_jsonb(NEW);
+          kind = 'created';
+      ELSIF TG_OP = 'DELETE' THEN
+          changed = to_jsonb(OLD);
+          kind = 'deleted';
+      ELSE
+          changed = to_jsonb(NEW);
+          kind = 'updated';
+          IF (changed ->> 'is_complete')::boolean
+             AND NOT (to_jsonb(OLD) ->> 'is_complete')::boolean THEN
+              kind = 'completed';
+          END IF;
+      END IF;
+
+      -- Held until commit: the writers of a todo's events commit one after
+      -- the other, so a reader never sees an event id after a larger one.
+      PERFORM pg_advisory_xact_lock(hashtextextended(changed ->> 'todo_id', 0));
+
+      INSERT INTO todo_event (todo_id, entity, entity_id, kind)
+      VALUES (
+          (changed ->> 'todo_id')::uuid,
+          TG_ARGV[0]::todo_event_entity,
+          (changed ->> TG_ARGV[1])::uuid,
+          kind
+      );
+      PERFORM pg_notify('todo_event', changed ->> 'todo_id');
+      RETURN NULL;
+  END;
+  $$;
+
+  CREATE TRIGGER trig_todo_item_event AFTER INSERT OR UPDATE OR DELETE ON todo_item
+      FOR EACH ROW EXECUTE PROCEDURE todo_event_trigger('item', 'todo_item_id');
+
+  CREATE TRIGGER trig_recurring_template_event AFTER INSERT OR UPDATE OR DELETE ON recurring_template
+      FOR EACH ROW EXECUTE PROCEDURE todo_event_trigger('template', 'template_id');
+COMMIT;
@@ -0,0 +1,109 @@
+-- Every write to a todo, an item or a template takes the next value of
+-- change_seq, and every delete leaves a tombstone holding its own value.
+-- GET /sync returns what changed in a user's todos after the last value a
+-- client saw.
+BEGIN;
+  CREATE SEQUENCE change_seq AS BIGINT;
+
+  -- Existing rows get a value in no particular order, they are all part of
+  -- the first sync anyway.
+  ALTER TABLE todo ADD COLUMN change_seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+  ALTER TABLE todo_item ADD COLUMN change_seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+  ALTER TABLE recurring_template ADD COLUMN change_seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+
+  CREATE INDEX todo_user_id_change_seq_idx ON todo (user_id, change_seq);
+  CREATE INDEX todo_item_todo_id_change_seq_idx ON todo_item (todo_id, change_seq);
+  CREATE INDEX recurring_template_todo_id_change_seq_idx ON recurring_template (todo_id, change_seq);
+
+  CREATE TYPE sync_entity AS ENUM ('todo', 'item', 'template');
+
+  -- user_id is the owner of the todo. No foreign key on it, deleting a user
+  -- cascades to their todos which write tombstones while the user is gone.
+  CREATE TABLE tombstone (
+      change_seq BIGINT PRIMARY KEY,
+      user_id INTEGER NOT NULL,
+      entity sync_entity NOT NULL,
+      entity_id uuid NOT NULL,
+      todo_id uuid NOT NULL,
+      delete_time timestamptz NOT NULL DEFAULT NOW()
+  );
+
+  CREATE INDEX tombstone_user_id_change_seq_idx ON tombstone (user_id, change_seq);
+
+  -- Values are handed out under a lock per owner held until commit: the
+  -- writers of an owner's todos commit one after the other, so a sync never
+  -- sees a value while a smaller one of the same owner is uncommitted.
+  CREATE OR REPLACE FUNCTION next_change_seq(owner_id INTEGER) RETURNS BIGINT
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      -- Two keys, apart from the single key locks of todo_event_trigger
+      PERFORM pg_advisory_xact_lock(hashtext('change_seq'), owner_id);
+      RETURN nextval('change_seq');
+  END;
+  $$;
+
+  CREATE OR REPLACE FUNCTION change_seq_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      owner_id INTEGER;
+  BEGIN
+      IF TG_TABLE_NAME = 'todo' THEN
+          owner_id = NEW.user_id;
+      ELSE
+          SELECT user_id INTO owner_id FROM todo WHERE todo_id = NEW.todo_id;
+      END IF;
+      NEW.change_seq = next_change_seq(owner_id);
+      RETURN NEW;
+  END;
+  $$;
+
+  -- TG_ARGV[0] is the entity, TG_ARGV[1] the column holding its id. Runs
+  -- before the delete so the owner lock is always taken before the todo lock
+  -- of todo_event_trigger.
+  CREATE OR REPLACE FUNCTION tombstone_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      owner_id INTEGER;
+  BEGIN
+      IF TG_TABLE_NAME = 'todo' THEN
+          owner_id = OLD.user_id;
+      ELSE
+          SELECT user_id INTO owner_id FROM todo WHERE todo_id = OLD.todo_id;
+          -- Deleted with its todo, whose tombstone covers it
+          IF owner_id IS NULL THEN
+              RETURN OLD;
+          END IF;
+      END IF;
+      INSERT INTO tombstone (change_seq, user_id, entity, entity_id, todo_id)
+      VALUES (
+          next_change_seq(owner_id),
+          owner_id,
+          TG_ARGV[0]::sync_entity,
+          (to_jsonb(OLD) ->> TG_ARGV[1])::uuid,
+          OLD.todo_id
+      );
+      RETURN OLD;
+  END;
+  $$;
+
+  CREATE TRIGGER trig_todo_change_seq BEFORE INSERT OR UPDATE ON todo
+      FOR EACH ROW EXECUTE PROCEDURE change_seq_trigger();
+
+  CREATE TRIGGER trig_todo_item_change_seq BEFORE INSERT OR UPDATE ON todo_item
+      FOR EACH ROW EXECUTE PROCEDURE change_seq_trigger();
+
+  CREATE TRIGGER trig_recurring_template_change_seq BEFORE INSERT OR UPDATE ON recurring_template
+      FOR EACH ROW EXECUTE PROCEDURE change_seq_trigger();
+
+  CREATE TRIGGER trig_todo_tombstone BEFORE DELETE ON todo
+      FOR EACH ROW EXECUTE PROCEDURE tombstone_trigger('todo', 'todo_id');
+
+  CREATE TRIGGER trig_todo_item_tombstone BEFORE DELETE ON todo_item
+      FOR EACH ROW EXECUTE PROCEDURE tombstone_trigger('item', 'todo_item_id');
+
+  CREATE TRIGGER trig_recurring_template_tombstone BEFORE DELETE ON recurring_template
+      FOR EACH ROW EXECUTE PROCEDURE tombstone_trigger('template', 'template_id');
+COMMIT;
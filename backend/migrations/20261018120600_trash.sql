@@ -0,0 +1,63 @@
+-- Deleting a todo or an item moves it to the trash of the todo's owner by
+-- setting deleted_at, a job purges it for good after the retention. The
+-- items and templates of a trashed todo stay as they are and come back
+-- with it.
+BEGIN;
+  ALTER TABLE todo ADD COLUMN deleted_at timestamptz NULL;
+  ALTER TABLE todo_item ADD COLUMN deleted_at timestamptz NULL;
+
+  -- A trashed todo doesn't hold on to its name, restoring it fails while
+  -- another todo uses the name.
+  ALTER TABLE todo DROP CONSTRAINT todo_user_id_name_key;
+  CREATE UNIQUE INDEX todo_user_id_name_key ON todo (user_id, name) WHERE deleted_at IS NULL;
+
+  CREATE INDEX todo_trash_idx ON todo (user_id, deleted_at) WHERE deleted_at IS NOT NULL;
+  CREATE INDEX todo_item_trash_idx ON todo_item (deleted_at) WHERE deleted_at IS NOT NULL;
+
+  -- Event streams see an item moved to the trash as deleted and a restored
+  -- one as created, purging it later is not announced again.
+  CREATE OR REPLACE FUNCTION todo_event_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      changed jsonb;
+      kind todo_event_kind;
+  BEGIN
+      IF TG_OP = 'INSERT' THEN
+          changed = to_jsonb(NEW);
+          kind = 'created';
+      ELSIF TG_OP = 'DELETE' THEN
+          changed = to_jsonb(OLD);
+          kind = 'deleted';
+          IF changed ->> 'deleted_at' IS NOT NULL THEN
+              RETURN NULL;
+          END IF;
+      ELSE
+          changed = to_jsonb(NEW);
+          kind = 'updated';
+          IF changed ->> 'deleted_at' IS NOT NULL AND to_jsonb(OLD) ->> 'deleted_at' IS NULL THEN
+              kind = 'deleted';
+          ELSIF changed ->> 'deleted_at' IS NULL AND to_jsonb(OLD) ->> 'deleted_at' IS NOT NULL THEN
+              kind = 'created';
+          ELSIF (changed ->> 'is_complete')::boolean
+             AND NOT (to_jsonb(OLD) ->> 'is_complete')::boolean THEN
+              kind = 'completed';
+          END IF;
+      END IF;
+
+      -- Held until commit: the writers of a todo's events commit one after
+      -- the other, so a reader never sees an event id after a larger one.
+      PERFORM pg_advisory_xact_lock(hashtextextended(changed ->> 'todo_id', 0));
+
+      INSERT INTO todo_event (todo_id, entity, entity_id, kind)
+      VALUES (
+          (changed ->> 'todo_id')::uuid,
+          TG_ARGV[0]::todo_event_entity,
+          (changed ->> TG_ARGV[1])::uuid,
+          kind
+      );
+      PERFORM pg_notify('todo_event', changed ->> 'todo_id');
+      RETURN NULL;
+  END;
+  $$;
+COMMIT;
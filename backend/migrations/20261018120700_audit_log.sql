@@ -0,0 +1,101 @@
+-- Every insert, update and delete of a todo, an item or a template writes an
+-- audit row in the same transaction, with the row before and after the
+-- change. The handler sets the actor and the x-request-id of the request as
+-- transaction settings first; writes of background jobs have neither.
+-- GET /todo/{todo}/activity lists the rows of a todo.
+BEGIN;
+  CREATE TYPE audit_action AS ENUM (
+      'created', 'updated', 'completed', 'reopened', 'deleted', 'restored'
+  );
+
+  -- No foreign keys, the log outlives what it describes.
+  CREATE TABLE audit_log (
+      audit_id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+      todo_id uuid NOT NULL,
+      actor_id INTEGER NULL,
+      request_id TEXT NULL,
+      entity sync_entity NOT NULL,
+      entity_id uuid NOT NULL,
+      action audit_action NOT NULL,
+      before jsonb NULL,
+      after jsonb NULL,
+      create_time timestamptz NOT NULL DEFAULT NOW()
+  );
+
+  CREATE INDEX audit_log_todo_id_audit_id_idx ON audit_log (todo_id, audit_id);
+
+  -- TG_ARGV[0] is the entity, TG_ARGV[1] the column holding its id.
+  -- Bookkeeping columns are left out, an update that only moves them, like
+  -- the children of a restored todo, is not recorded.
+  CREATE OR REPLACE FUNCTION audit_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      before_row jsonb;
+      after_row jsonb;
+      changed jsonb;
+      action audit_action;
+  BEGIN
+      IF TG_OP <> 'INSERT' THEN
+          before_row = to_jsonb(OLD) - 'change_seq' - 'update_time';
+      END IF;
+      IF TG_OP <> 'DELETE' THEN
+          after_row = to_jsonb(NEW) - 'change_seq' - 'update_time';
+      END IF;
+      changed = COALESCE(after_row, before_row);
+
+      IF TG_OP = 'INSERT' THEN
+          action = 'created';
+      ELSIF TG_OP = 'DELETE' THEN
+          action = 'deleted';
+          -- Purged from the trash, moving it there was recorded
+          IF before_row ->> 'deleted_at' IS NOT NULL THEN
+              RETURN NULL;
+          END IF;
+          -- Deleted with its todo
+          IF TG_TABLE_NAME <> 'todo'
+             AND NOT EXISTS (SELECT 1 FROM todo WHERE todo_id = OLD.todo_id) THEN
+              RETURN NULL;
+          END IF;
+      ELSE
+          IF before_row = after_row THEN
+              RETURN NULL;
+          END IF;
+          action = 'updated';
+          IF after_row ->> 'deleted_at' IS NOT NULL AND before_row ->> 'deleted_at' IS NULL THEN
+              action = 'deleted';
+          ELSIF after_row ->> 'deleted_at' IS NULL AND before_row ->> 'deleted_at' IS NOT NULL THEN
+              action = 'restored';
+          ELSIF (after_row ->> 'is_complete')::boolean
+             AND NOT (before_row ->> 'is_complete')::boolean THEN
+              action = 'completed';
+          ELSIF NOT (after_row ->> 'is_complete')::boolean
+             AND (before_row ->> 'is_complete')::boolean THEN
+              action = 'reopened';
+          END IF;
+      END IF;
+
+      INSERT INTO audit_log (todo_id, actor_id, request_id, entity, entity_id, action, before, after)
+      VALUES (
+          (changed ->> 'todo_id')::uuid,
+          NULLIF(current_setting('audit.actor_id', true), '')::INTEGER,
+          NULLIF(current_setting('audit.request_id', true), ''),
+          TG_ARGV[0]::sync_entity,
+          (changed ->> TG_ARGV[1])::uuid,
+          action,
+          before_row,
+          after_row
+      );
+      RETURN NULL;
+  END;
+  $$;
+
+  CREATE TRIGGER trig_todo_audit AFTER INSERT OR UPDATE OR DELETE ON todo
+      FOR EACH ROW EXECUTE PROCEDURE audit_trigger('todo', 'todo_id');
+
+  CREATE TRIGGER trig_todo_item_audit AFTER INSERT OR UPDATE OR DELETE ON todo_item
+      FOR EACH ROW EXECUTE PROCEDURE audit_trigger('item', 'todo_item_id');
+
+  CREATE TRIGGER trig_recurring_template_audit AFTER INSERT OR UPDATE OR DELETE ON recurring_template
+      FOR EACH ROW EXECUTE PROCEDURE audit_trigger('template', 'template_id');
+COMMIT;
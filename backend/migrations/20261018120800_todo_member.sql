@@ -0,0 +1,35 @@
+-- Todos are shared with other users as members. Roles are ordered:
+-- viewers read, editors also change items and templates, owners also
+-- change the todo and its members. The creator of a todo (todo.user_id)
+-- always owns it and has no member row. Public todos give every other user
+-- the viewer role.
+BEGIN;
+  CREATE TYPE todo_role AS ENUM ('viewer', 'editor', 'owner');
+
+  CREATE TABLE todo_member (
+      todo_id uuid NOT NULL REFERENCES todo (todo_id) ON DELETE CASCADE,
+      user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+      role todo_role NOT NULL,
+      create_time timestamptz NOT NULL DEFAULT NOW(),
+      update_time timestamptz NOT NULL DEFAULT NOW(),
+      PRIMARY KEY (todo_id, user_id)
+  );
+
+  CREATE INDEX todo_member_user_id_idx ON todo_member (user_id);
+
+  CREATE TRIGGER trig_todo_member_update_time BEFORE UPDATE ON todo_member
+      FOR EACH ROW EXECUTE PROCEDURE update_time_trigger();
+
+  -- The role of a user on a todo, NULL when they can't see it.
+  CREATE OR REPLACE FUNCTION todo_role_of(t todo, member_id INTEGER) RETURNS todo_role
+      LANGUAGE sql STABLE AS
+  $$
+      SELECT CASE
+          WHEN t.user_id = member_id THEN 'owner'::todo_role
+          ELSE COALESCE(
+              (SELECT role FROM todo_member WHERE todo_id = t.todo_id AND user_id = member_id),
+              CASE WHEN t.visibility = 'public' THEN 'viewer'::todo_role END
+          )
+      END
+  $$;
+COMMIT;
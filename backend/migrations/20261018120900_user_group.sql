@@ -0,0 +1,49 @@
+-- Groups, like a household, own todos together. Members of a group have a
+-- role on it, which they also have on every todo of the group. Owners of the
+-- group manage it and its members. A todo keeps the user who created it and
+-- goes back to them when its group is deleted.
+BEGIN;
+  CREATE TABLE user_group (
+      group_id uuid PRIMARY KEY,
+      name TEXT NOT NULL,
+      create_time timestamptz NOT NULL DEFAULT NOW(),
+      update_time timestamptz NOT NULL DEFAULT NOW()
+  );
+
+  CREATE TRIGGER trig_user_group_update_time BEFORE UPDATE ON user_group
+      FOR EACH ROW EXECUTE PROCEDURE update_time_trigger();
+
+  CREATE TABLE group_member (
+      group_id uuid NOT NULL REFERENCES user_group (group_id) ON DELETE CASCADE,
+      user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+      role todo_role NOT NULL,
+      create_time timestamptz NOT NULL DEFAULT NOW(),
+      update_time timestamptz NOT NULL DEFAULT NOW(),
+      PRIMARY KEY (group_id, user_id)
+  );
+
+  CREATE INDEX group_member_user_id_idx ON group_member (user_id);
+
+  CREATE TRIGGER trig_group_member_update_time BEFORE UPDATE ON group_member
+      FOR EACH ROW EXECUTE PROCEDURE update_time_trigger();
+
+  ALTER TABLE todo ADD COLUMN group_id uuid NULL
+      REFERENCES user_group (group_id) ON DELETE SET NULL;
+
+  CREATE INDEX todo_group_id_idx ON todo (group_id);
+
+  -- The highest of the roles given by a todo membership, by a membership of
+  -- the group of the todo and by a public todo.
+  CREATE OR REPLACE FUNCTION todo_role_of(t todo, member_id INTEGER) RETURNS todo_role
+      LANGUAGE sql STABLE AS
+  $$
+      SELECT CASE
+          WHEN t.user_id = member_id THEN 'owner'::todo_role
+          ELSE GREATEST(
+              (SELECT role FROM todo_member WHERE todo_id = t.todo_id AND user_id = member_id),
+              (SELECT role FROM group_member WHERE group_id = t.group_id AND user_id = member_id),
+              CASE WHEN t.visibility = 'public' THEN 'viewer'::todo_role END
+          )
+      END
+  $$;
+COMMIT;
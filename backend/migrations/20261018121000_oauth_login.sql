@@ -0,0 +1,12 @@
+-- Logins started with GET /auth/google and not yet called back. The state
+-- also sits in a cookie of the browser that started the login, the callback
+-- must bring both back. The PKCE verifier never leaves the server.
+BEGIN;
+  CREATE TABLE oauth_login (
+      state TEXT PRIMARY KEY,
+      pkce_verifier TEXT NOT NULL,
+      create_time timestamptz NOT NULL
+  );
+
+  CREATE INDEX oauth_login_create_time_idx ON oauth_login (create_time);
+COMMIT;
@@ -0,0 +1,8 @@
+-- Logins go through Google or one of the OpenID Connect providers, the
+-- callback must come from the one the login started with. OpenID Connect
+-- logins also send a nonce the ID token has to carry back.
+BEGIN;
+  ALTER TABLE oauth_login ADD COLUMN provider TEXT NOT NULL DEFAULT 'google';
+  ALTER TABLE oauth_login ALTER COLUMN provider DROP DEFAULT;
+  ALTER TABLE oauth_login ADD COLUMN nonce TEXT NULL;
+COMMIT;
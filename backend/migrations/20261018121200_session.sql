@@ -0,0 +1,20 @@
+-- Sessions opened by logins. Clients get short-lived access tokens naming
+-- the session and a refresh token to get new ones with. Refresh tokens are
+-- only stored hashed and change on every refresh; the previous one is kept
+-- to notice when a stolen token is used after the client refreshed.
+BEGIN;
+  CREATE TABLE session (
+      session_id uuid PRIMARY KEY,
+      user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+      refresh_token_hash TEXT NOT NULL UNIQUE,
+      previous_refresh_token_hash TEXT NULL,
+      user_agent TEXT NULL,
+      create_time timestamptz NOT NULL,
+      refresh_time timestamptz NOT NULL,
+      expire_time timestamptz NOT NULL,
+      revoke_time timestamptz NULL
+  );
+
+  CREATE INDEX session_user_id_idx ON session (user_id);
+  CREATE INDEX session_previous_refresh_token_hash_idx ON session (previous_refresh_token_hash);
+COMMIT;
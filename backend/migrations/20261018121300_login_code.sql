@@ -0,0 +1,12 @@
+-- Logins completed by a provider callback, waiting for the frontend to
+-- exchange the code it was redirected with for the tokens of a session.
+-- Codes are only stored hashed and valid once.
+BEGIN;
+  CREATE TABLE login_code (
+      code_hash TEXT PRIMARY KEY,
+      user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+      create_time timestamptz NOT NULL
+  );
+
+  CREATE INDEX login_code_create_time_idx ON login_code (create_time);
+COMMIT;
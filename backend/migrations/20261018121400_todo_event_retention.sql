@@ -0,0 +1,4 @@
+-- Events are kept for `events.retention`, an hourly job purges the older
+-- ones. A client resuming its stream with a Last-Event-ID that was purged
+-- restarts from the latest event of the todo.
+CREATE INDEX todo_event_create_time_idx ON todo_event (create_time);
@@ -0,0 +1,255 @@
+-- GET /sync covers every todo a user syncs: the ones they created, the ones
+-- shared with them and the ones of their groups. Public todos of other users
+-- are read but not synced, anyone can see them so their writers can't lock
+-- every reader.
+--
+-- change_seq values were handed out under a lock per owner, which kept a sync
+-- from skipping a value only for the owner. They are now handed out under
+-- the lock of every user syncing the todo, held until commit: a sync never
+-- sees a value while a smaller one of the same user is uncommitted.
+--
+-- A user who gets access to a todo gets it again in full, its rows are moved
+-- past every token like a restored todo. A user who loses access to a todo
+-- that is still there gets a tombstone of the todo.
+BEGIN;
+  -- The users syncing a todo.
+  CREATE OR REPLACE FUNCTION todo_audience(for_todo_id uuid) RETURNS SETOF INTEGER
+      LANGUAGE sql STABLE AS
+  $$
+      SELECT user_id FROM todo WHERE todo_id = for_todo_id
+      UNION
+      SELECT user_id FROM todo_member WHERE todo_id = for_todo_id
+      UNION
+      SELECT gm.user_id
+      FROM todo as t
+      INNER JOIN group_member as gm ON gm.group_id = t.group_id
+      WHERE t.todo_id = for_todo_id
+  $$;
+
+  -- The todos synced to a user, trashed ones included.
+  CREATE OR REPLACE FUNCTION synced_todo_ids(member_id INTEGER) RETURNS SETOF uuid
+      LANGUAGE sql STABLE AS
+  $$
+      SELECT todo_id FROM todo WHERE user_id = member_id
+      UNION
+      SELECT todo_id FROM todo_member WHERE user_id = member_id
+      UNION
+      SELECT t.todo_id
+      FROM group_member as gm
+      INNER JOIN todo as t ON t.group_id = gm.group_id
+      WHERE gm.user_id = member_id
+  $$;
+
+  -- Takes the change_seq lock of every user syncing the todo and of
+  -- extra_user_ids, held until commit. The audience is read again once
+  -- locked: a membership granted in between took the locks of the todo
+  -- before committing, so its user shows up and is locked too. Every round
+  -- locks in user order, postgres breaks the rare deadlock with a concurrent
+  -- membership change by failing one of them.
+  CREATE OR REPLACE FUNCTION lock_todo_audience(for_todo_id uuid, extra_user_ids INTEGER[])
+      RETURNS void
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      locked INTEGER[] = '{}';
+      missing INTEGER[];
+      audience_user INTEGER;
+  BEGIN
+      LOOP
+          SELECT array_agg(u ORDER BY u) INTO missing
+          FROM (
+              SELECT a as u FROM todo_audience(for_todo_id) as a
+              UNION
+              SELECT unnest(extra_user_ids)
+          ) as audience
+          WHERE u IS NOT NULL AND NOT u = ANY (locked);
+          EXIT WHEN missing IS NULL;
+          FOREACH audience_user IN ARRAY missing LOOP
+              -- Two keys, apart from the single key locks of todo_event_trigger
+              PERFORM pg_advisory_xact_lock(hashtext('change_seq'), audience_user);
+          END LOOP;
+          locked = locked || missing;
+      END LOOP;
+  END;
+  $$;
+
+  CREATE OR REPLACE FUNCTION next_change_seq(for_todo_id uuid, extra_user_ids INTEGER[] DEFAULT '{}')
+      RETURNS BIGINT
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      PERFORM lock_todo_audience(for_todo_id, extra_user_ids);
+      RETURN nextval('change_seq');
+  END;
+  $$;
+
+  CREATE OR REPLACE FUNCTION change_seq_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      IF TG_TABLE_NAME = 'todo' THEN
+          -- A new todo isn't there yet, a todo moving to a group gets its
+          -- members
+          NEW.change_seq = next_change_seq(
+              NEW.todo_id,
+              ARRAY[NEW.user_id]
+                  || ARRAY(SELECT user_id FROM group_member WHERE group_id = NEW.group_id)
+          );
+      ELSE
+          NEW.change_seq = next_change_seq(NEW.todo_id);
+      END IF;
+      RETURN NEW;
+  END;
+  $$;
+
+  -- The tombstone of a todo is written for every user syncing it, the
+  -- tombstones of items and templates keep the owner in user_id and are
+  -- synced along with their todo.
+  CREATE OR REPLACE FUNCTION tombstone_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      owner_id INTEGER;
+  BEGIN
+      IF TG_TABLE_NAME = 'todo' THEN
+          PERFORM lock_todo_audience(OLD.todo_id, '{}');
+          INSERT INTO tombstone (change_seq, user_id, entity, entity_id, todo_id)
+          SELECT nextval('change_seq'), audience_user, 'todo', OLD.todo_id, OLD.todo_id
+          FROM todo_audience(OLD.todo_id) as audience_user;
+          RETURN OLD;
+      END IF;
+
+      SELECT user_id INTO owner_id FROM todo WHERE todo_id = OLD.todo_id;
+      -- Deleted with its todo, whose tombstone covers it
+      IF owner_id IS NULL THEN
+          RETURN OLD;
+      END IF;
+      INSERT INTO tombstone (change_seq, user_id, entity, entity_id, todo_id)
+      VALUES (
+          next_change_seq(OLD.todo_id),
+          owner_id,
+          TG_ARGV[0]::sync_entity,
+          (to_jsonb(OLD) ->> TG_ARGV[1])::uuid,
+          OLD.todo_id
+      );
+      RETURN OLD;
+  END;
+  $$;
+
+  DROP FUNCTION next_change_seq(INTEGER);
+
+  CREATE INDEX tombstone_todo_id_change_seq_idx ON tombstone (todo_id, change_seq);
+
+  -- Moves the items and templates of a todo past every token, for the users
+  -- who just got access to it.
+  CREATE OR REPLACE FUNCTION resend_todo_content(for_todo_id uuid) RETURNS void
+      LANGUAGE sql AS
+  $$
+      UPDATE todo_item SET change_seq = change_seq
+      WHERE todo_id = for_todo_id AND deleted_at IS NULL;
+      UPDATE recurring_template SET change_seq = change_seq WHERE todo_id = for_todo_id;
+  $$;
+
+  -- A user who lost access to a todo forgets it, unless the todo or the user
+  -- is gone or the user still syncs it another way.
+  CREATE OR REPLACE FUNCTION revoke_todo(for_todo_id uuid, revoked_user_id INTEGER) RETURNS void
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      IF NOT EXISTS (SELECT 1 FROM todo WHERE todo_id = for_todo_id)
+         OR NOT EXISTS (SELECT 1 FROM users WHERE user_id = revoked_user_id)
+         OR revoked_user_id IN (SELECT todo_audience(for_todo_id)) THEN
+          RETURN;
+      END IF;
+      INSERT INTO tombstone (change_seq, user_id, entity, entity_id, todo_id)
+      VALUES (
+          next_change_seq(for_todo_id, ARRAY[revoked_user_id]),
+          revoked_user_id,
+          'todo',
+          for_todo_id,
+          for_todo_id
+      );
+  END;
+  $$;
+
+  -- Grants move the todo past every token under the locks of its audience,
+  -- new member included: a concurrent write to the todo either commits
+  -- before the grant or locks the new member as well.
+  CREATE OR REPLACE FUNCTION todo_member_sync_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      IF TG_OP = 'DELETE' THEN
+          PERFORM revoke_todo(OLD.todo_id, OLD.user_id);
+          RETURN NULL;
+      END IF;
+      -- The todo carries the role of the member
+      UPDATE todo SET change_seq = change_seq WHERE todo_id = NEW.todo_id;
+      IF TG_OP = 'INSERT' THEN
+          PERFORM resend_todo_content(NEW.todo_id);
+      END IF;
+      RETURN NULL;
+  END;
+  $$;
+
+  CREATE OR REPLACE FUNCTION group_member_sync_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      IF TG_OP = 'DELETE' THEN
+          PERFORM revoke_todo(todo_id, OLD.user_id) FROM todo WHERE group_id = OLD.group_id;
+          RETURN NULL;
+      END IF;
+      UPDATE todo SET change_seq = change_seq WHERE group_id = NEW.group_id;
+      IF TG_OP = 'INSERT' THEN
+          PERFORM resend_todo_content(todo_id) FROM todo WHERE group_id = NEW.group_id;
+      END IF;
+      RETURN NULL;
+  END;
+  $$;
+
+  -- The todo row itself took a new change_seq with the members of its new
+  -- group.
+  CREATE OR REPLACE FUNCTION todo_group_sync_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      PERFORM revoke_todo(NEW.todo_id, user_id) FROM group_member WHERE group_id = OLD.group_id;
+      IF NEW.group_id IS NOT NULL THEN
+          PERFORM resend_todo_content(NEW.todo_id);
+      END IF;
+      RETURN NULL;
+  END;
+  $$;
+
+  -- Deleting a group sets the group of its todos to NULL and deletes its
+  -- members in no set order, the todos leave it first while the members are
+  -- still there to be revoked.
+  CREATE OR REPLACE FUNCTION user_group_sync_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      UPDATE todo SET group_id = NULL WHERE group_id = OLD.group_id;
+      RETURN OLD;
+  END;
+  $$;
+
+  CREATE TRIGGER trig_todo_member_sync AFTER INSERT OR UPDATE OF role OR DELETE ON todo_member
+      FOR EACH ROW EXECUTE PROCEDURE todo_member_sync_trigger();
+
+  CREATE TRIGGER trig_group_member_sync AFTER INSERT OR UPDATE OF role OR DELETE ON group_member
+      FOR EACH ROW EXECUTE PROCEDURE group_member_sync_trigger();
+
+  CREATE TRIGGER trig_todo_group_sync AFTER UPDATE OF group_id ON todo
+      FOR EACH ROW WHEN (OLD.group_id IS DISTINCT FROM NEW.group_id)
+      EXECUTE PROCEDURE todo_group_sync_trigger();
+
+  CREATE TRIGGER trig_user_group_sync BEFORE DELETE ON user_group
+      FOR EACH ROW EXECUTE PROCEDURE user_group_sync_trigger();
+
+  -- Members only synced their own todos until now
+  UPDATE todo SET change_seq = change_seq
+  WHERE group_id IS NOT NULL OR todo_id IN (SELECT todo_id FROM todo_member);
+  SELECT resend_todo_content(todo_id) FROM todo
+  WHERE group_id IS NOT NULL OR todo_id IN (SELECT todo_id FROM todo_member);
+COMMIT;
@@ -0,0 +1,77 @@
+-- Granting, changing and revoking access to a todo is recorded in its
+-- activity like the writes to its items. A member row is audited with the
+-- todo as entity_id, the member is the user_id of its before and after.
+BEGIN;
+  CREATE TYPE audit_entity AS ENUM ('todo', 'item', 'template', 'member');
+
+  ALTER TABLE audit_log ALTER COLUMN entity TYPE audit_entity USING entity::text::audit_entity;
+
+  -- TG_ARGV[0] is the entity, TG_ARGV[1] the column holding its id.
+  -- Bookkeeping columns are left out, an update that only moves them, like
+  -- the children of a restored todo, is not recorded.
+  CREATE OR REPLACE FUNCTION audit_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      before_row jsonb;
+      after_row jsonb;
+      changed jsonb;
+      action audit_action;
+  BEGIN
+      IF TG_OP <> 'INSERT' THEN
+          before_row = to_jsonb(OLD) - 'change_seq' - 'update_time';
+      END IF;
+      IF TG_OP <> 'DELETE' THEN
+          after_row = to_jsonb(NEW) - 'change_seq' - 'update_time';
+      END IF;
+      changed = COALESCE(after_row, before_row);
+
+      IF TG_OP = 'INSERT' THEN
+          action = 'created';
+      ELSIF TG_OP = 'DELETE' THEN
+          action = 'deleted';
+          -- Purged from the trash, moving it there was recorded
+          IF before_row ->> 'deleted_at' IS NOT NULL THEN
+              RETURN NULL;
+          END IF;
+          -- Deleted with its todo
+          IF TG_TABLE_NAME <> 'todo'
+             AND NOT EXISTS (SELECT 1 FROM todo WHERE todo_id = (changed ->> 'todo_id')::uuid) THEN
+              RETURN NULL;
+          END IF;
+      ELSE
+          IF before_row = after_row THEN
+              RETURN NULL;
+          END IF;
+          action = 'updated';
+          IF after_row ->> 'deleted_at' IS NOT NULL AND before_row ->> 'deleted_at' IS NULL THEN
+              action = 'deleted';
+          ELSIF after_row ->> 'deleted_at' IS NULL AND before_row ->> 'deleted_at' IS NOT NULL THEN
+              action = 'restored';
+          ELSIF (after_row ->> 'is_complete')::boolean
+             AND NOT (before_row ->> 'is_complete')::boolean THEN
+              action = 'completed';
+          ELSIF NOT (after_row ->> 'is_complete')::boolean
+             AND (before_row ->> 'is_complete')::boolean THEN
+              action = 'reopened';
+          END IF;
+      END IF;
+
+      INSERT INTO audit_log (todo_id, actor_id, request_id, entity, entity_id, action, before, after)
+      VALUES (
+          (changed ->> 'todo_id')::uuid,
+          NULLIF(current_setting('audit.actor_id', true), '')::INTEGER,
+          NULLIF(current_setting('audit.request_id', true), ''),
+          TG_ARGV[0]::audit_entity,
+          (changed ->> TG_ARGV[1])::uuid,
+          action,
+          before_row,
+          after_row
+      );
+      RETURN NULL;
+  END;
+  $$;
+
+  CREATE TRIGGER trig_todo_member_audit AFTER INSERT OR UPDATE OR DELETE ON todo_member
+      FOR EACH ROW EXECUTE PROCEDURE audit_trigger('member', 'todo_id');
+COMMIT;
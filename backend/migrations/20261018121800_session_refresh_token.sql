@@ -0,0 +1,18 @@
+-- Every refresh token swapped out of a session is kept, not only the
+-- previous one: a stolen token used after the client refreshed several
+-- times still revokes the session. They go away with their session.
+BEGIN;
+  CREATE TABLE session_refresh_token (
+      refresh_token_hash TEXT PRIMARY KEY,
+      session_id uuid NOT NULL REFERENCES session (session_id) ON DELETE CASCADE
+  );
+
+  CREATE INDEX session_refresh_token_session_id_idx ON session_refresh_token (session_id);
+
+  INSERT INTO session_refresh_token (refresh_token_hash, session_id)
+  SELECT previous_refresh_token_hash, session_id
+  FROM session
+  WHERE previous_refresh_token_hash IS NOT NULL;
+
+  ALTER TABLE session DROP COLUMN previous_refresh_token_hash;
+COMMIT;
@@ -0,0 +1,67 @@
+-- Resending a row to sync only moves its change_seq, like the children of a
+-- restored todo or the rows of a todo shared with a new member. Such an
+-- update is not a change: update_time, and the ETag built on it, stays and
+-- no event is streamed, the way audit_trigger already leaves it out.
+BEGIN;
+  CREATE OR REPLACE FUNCTION update_time_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  BEGIN
+      IF to_jsonb(NEW) - 'change_seq' - 'update_time'
+         = to_jsonb(OLD) - 'change_seq' - 'update_time' THEN
+          RETURN NEW;
+      END IF;
+      NEW.update_time = NOW();
+      RETURN NEW;
+  END;
+  $$;
+
+  CREATE OR REPLACE FUNCTION todo_event_trigger() RETURNS trigger
+      LANGUAGE plpgsql AS
+  $$
+  DECLARE
+      changed jsonb;
+      kind todo_event_kind;
+  BEGIN
+      IF TG_OP = 'INSERT' THEN
+          changed = to_jsonb(NEW);
+          kind = 'created';
+      ELSIF TG_OP = 'DELETE' THEN
+          changed = to_jsonb(OLD);
+          kind = 'deleted';
+          IF changed ->> 'deleted_at' IS NOT NULL THEN
+              RETURN NULL;
+          END IF;
+      ELSE
+          changed = to_jsonb(NEW);
+          kind = 'updated';
+          IF changed - 'change_seq' - 'update_time'
+             = to_jsonb(OLD) - 'change_seq' - 'update_time' THEN
+              RETURN NULL;
+          END IF;
+          IF changed ->> 'deleted_at' IS NOT NULL AND to_jsonb(OLD) ->> 'deleted_at' IS NULL THEN
+              kind = 'deleted';
+          ELSIF changed ->> 'deleted_at' IS NULL AND to_jsonb(OLD) ->> 'deleted_at' IS NOT NULL THEN
+              kind = 'created';
+          ELSIF (changed ->> 'is_complete')::boolean
+             AND NOT (to_jsonb(OLD) ->> 'is_complete')::boolean THEN
+              kind = 'completed';
+          END IF;
+      END IF;
+
+      -- Held until commit: the writers of a todo's events commit one after
+      -- the other, so a reader never sees an event id after a larger one.
+      PERFORM pg_advisory_xact_lock(hashtextextended(changed ->> 'todo_id', 0));
+
+      INSERT INTO todo_event (todo_id, entity, entity_id, kind)
+      VALUES (
+          (changed ->> 'todo_id')::uuid,
+          TG_ARGV[0]::todo_event_entity,
+          (changed ->> TG_ARGV[1])::uuid,
+          kind
+      );
+      PERFORM pg_notify('todo_event', changed ->> 'todo_id');
+      RETURN NULL;
+  END;
+  $$;
+COMMIT;
@@ -0,0 +1,29 @@
+-- Public todos give every other user the editor role again: anyone
+-- authenticated changes their items and templates, as before todos had
+-- members, and the viewer role of 20261018120800 took that away. The todo
+-- itself and its members stay with its owners, a user who only sees the
+-- todo because it is public gets a 404 for them as before.
+BEGIN;
+  -- The role a user has on a todo through owning it or a membership, NULL
+  -- when they have none.
+  CREATE OR REPLACE FUNCTION todo_member_role_of(t todo, member_id INTEGER) RETURNS todo_role
+      LANGUAGE sql STABLE AS
+  $$
+      SELECT CASE
+          WHEN t.user_id = member_id THEN 'owner'::todo_role
+          ELSE GREATEST(
+              (SELECT role FROM todo_member WHERE todo_id = t.todo_id AND user_id = member_id),
+              (SELECT role FROM group_member WHERE group_id = t.group_id AND user_id = member_id)
+          )
+      END
+  $$;
+
+  CREATE OR REPLACE FUNCTION todo_role_of(t todo, member_id INTEGER) RETURNS todo_role
+      LANGUAGE sql STABLE AS
+  $$
+      SELECT GREATEST(
+          todo_member_role_of(t, member_id),
+          CASE WHEN t.visibility = 'public' THEN 'editor'::todo_role END
+      )
+  $$;
+COMMIT;
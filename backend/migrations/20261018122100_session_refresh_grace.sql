@@ -0,0 +1,20 @@
+-- Two refreshes of a session sent at once with the same token, like from two
+-- tabs, must both succeed: the one losing the race would otherwise reuse the
+-- token the other swapped out and revoke the session. For a short grace
+-- period after the swap the old token gets the same new token again, which
+-- is why new refresh tokens are derived from the old one with a salt of the
+-- session, kept from clients. Holding the old token alone doesn't give the
+-- new one, and only hashes of tokens are stored.
+BEGIN;
+  ALTER TABLE session ADD COLUMN refresh_token_salt TEXT NULL;
+  UPDATE session
+  SET refresh_token_salt = replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
+  ALTER TABLE session ALTER COLUMN refresh_token_salt SET NOT NULL;
+
+  -- The tokens kept so far were swapped out at the last refresh at the latest
+  ALTER TABLE session_refresh_token ADD COLUMN swap_time timestamptz NULL;
+  UPDATE session_refresh_token as r SET swap_time = s.refresh_time
+  FROM session as s
+  WHERE s.session_id = r.session_id;
+  ALTER TABLE session_refresh_token ALTER COLUMN swap_time SET NOT NULL;
+COMMIT;
@@ -1,7 +1,17 @@
-use eyre::{eyre, Result};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use eyre::{bail, eyre, Context, Result};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
+use simple_asn1::{oid, ASN1Block};
 use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::configuration::{AuthSettings, JwtKeyAlgorithm, JwtKeySettings, JwtKeysSettings};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -9,43 +19,352 @@ pub struct Claims {
     pub email: String,
     pub exp: i64,
     pub iat: i64,
+    /// The session the token was issued for, tokens without one can't be
+    /// revoked and are only accepted from the `jwt` auth type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+}
+
+/// The public part of a key, as published in a JWK.
+#[derive(Debug, Clone)]
+pub enum PublicKeyParams {
+    /// Big-endian modulus and exponent
+    Rsa {
+        n: Vec<u8>,
+        e: Vec<u8>,
+    },
+    Ed25519 {
+        x: Vec<u8>,
+    },
+}
+
+/// A key other services can verify tokens with.
+#[derive(Debug, Clone)]
+pub struct PublishedKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub params: PublicKeyParams,
+}
+
+struct PublicKey {
+    published: PublishedKey,
+    decoding_key: DecodingKey,
+    retire_time: Option<OffsetDateTime>,
+}
+
+/// How tokens are checked: with the shared secret that signed them, or
+/// with the public key named by their `kid`.
+enum Verifier {
+    Secret(DecodingKey),
+    PublicKeys(HashMap<String, PublicKey>),
+}
+
+struct Keys {
+    signing_kid: Option<String>,
+    signing_algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    verifier: Verifier,
+    retired_key_grace_period: time::Duration,
 }
 
 #[derive(Clone)]
 pub struct JwtService {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
+    keys: Arc<Keys>,
     expiration_hours: u64,
 }
 
 impl JwtService {
+    /// Signs and checks tokens with HS256 and the shared `secret`.
     pub fn new(secret: &str, expiration_hours: u64) -> Self {
         Self {
-            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
-            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            keys: Arc::new(Keys {
+                signing_kid: None,
+                signing_algorithm: Algorithm::HS256,
+                encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+                verifier: Verifier::Secret(DecodingKey::from_secret(secret.as_bytes())),
+                retired_key_grace_period: time::Duration::ZERO,
+            }),
             expiration_hours,
         }
     }
 
-    pub fn generate_token(&self, user_id: i32, email: &str) -> Result<String> {
-        let now = OffsetDateTime::now_utc();
-        let exp = now + time::Duration::hours(self.expiration_hours as i64);
+    /// Signs tokens with the signing key of `settings` and checks them with
+    /// any key that isn't past its grace period. Tokens signed with the
+    /// shared secret are no longer accepted.
+    pub fn with_keys(settings: &JwtKeysSettings, expiration_hours: u64) -> Result<Self> {
+        let signing_key = settings
+            .keys
+            .iter()
+            .find(|key| key.signs())
+            .ok_or_else(|| eyre!("No JWT key signs tokens"))?;
+        let private_key_path = signing_key
+            .private_key_path
+            .as_ref()
+            .expect("A signing key has a private key");
+        let private_key = std::fs::read(private_key_path)
+            .with_context(|| format!("Failed to read {}", private_key_path.display()))?;
+        let encoding_key = match signing_key.algorithm {
+            JwtKeyAlgorithm::RS256 => EncodingKey::from_rsa_pem(&private_key),
+            JwtKeyAlgorithm::EdDSA => EncodingKey::from_ed_pem(&private_key),
+        }
+        .with_context(|| format!("Invalid private key {}", private_key_path.display()))?;
+
+        let public_keys = settings
+            .keys
+            .iter()
+            .map(|key| Ok((key.kid.clone(), load_public_key(key)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            keys: Arc::new(Keys {
+                signing_kid: Some(signing_key.kid.clone()),
+                signing_algorithm: algorithm(signing_key.algorithm),
+                encoding_key,
+                verifier: Verifier::PublicKeys(public_keys),
+                retired_key_grace_period: time::Duration::try_from(
+                    settings.retired_key_grace_period,
+                )?,
+            }),
+            expiration_hours,
+        })
+    }
+
+    /// The service of the auth type: with its keys if any, else with its
+    /// shared secret.
+    pub fn from_auth_settings(auth: &AuthSettings) -> Result<Self> {
+        let (jwt_secret, jwt_expiration_hours, jwt_keys) = match auth {
+            AuthSettings::Jwt {
+                jwt_secret,
+                jwt_expiration_hours,
+                jwt_keys,
+            }
+            | AuthSettings::GoogleOAuth {
+                jwt_secret,
+                jwt_expiration_hours,
+                jwt_keys,
+                ..
+            }
+            | AuthSettings::Oidc {
+                jwt_secret,
+                jwt_expiration_hours,
+                jwt_keys,
+                ..
+            } => (jwt_secret, *jwt_expiration_hours, jwt_keys),
+        };
+        if jwt_keys.keys.is_empty() {
+            Ok(Self::new(jwt_secret.expose_secret(), jwt_expiration_hours))
+        } else {
+            Self::with_keys(jwt_keys, jwt_expiration_hours)
+        }
+    }
+
+    pub fn generate_token(&self, user_id: i32, email: &str, now: OffsetDateTime) -> Result<String> {
+        let lifetime = time::Duration::hours(self.expiration_hours as i64);
+        self.encode_token(user_id, email, None, now, lifetime)
+    }
+
+    /// A short-lived access token of the session, the session must still be
+    /// active when it is used.
+    pub fn generate_session_token(
+        &self,
+        user_id: i32,
+        email: &str,
+        session_id: Uuid,
+        now: OffsetDateTime,
+        lifetime: std::time::Duration,
+    ) -> Result<String> {
+        let lifetime = time::Duration::try_from(lifetime)
+            .map_err(|e| eyre!("Invalid access token lifetime: {}", e))?;
+        self.encode_token(user_id, email, Some(session_id), now, lifetime)
+    }
+
+    fn encode_token(
+        &self,
+        user_id: i32,
+        email: &str,
+        sid: Option<Uuid>,
+        now: OffsetDateTime,
+        lifetime: time::Duration,
+    ) -> Result<String> {
+        let exp = now + lifetime;
 
         let claims = Claims {
             sub: user_id.to_string(),
             email: email.to_string(),
             exp: exp.unix_timestamp(),
             iat: now.unix_timestamp(),
+            sid,
         };
 
-        encode(&Header::default(), &claims, &self.encoding_key)
+        let mut header = Header::new(self.keys.signing_algorithm);
+        header.kid = self.keys.signing_kid.clone();
+        encode(&header, &claims, &self.keys.encoding_key)
             .map_err(|e| eyre!("Failed to generate JWT token: {}", e))
     }
 
-    pub fn validate_token(&self, token: &str) -> Result<Claims> {
-        let token_data = decode::<Claims>(token, &self.decoding_key, &Validation::default())
+    /// The claims of a token signed by a key trusted at `now` and not
+    /// expired at `now`.
+    pub fn validate_token(&self, token: &str, now: OffsetDateTime) -> Result<Claims> {
+        let (decoding_key, mut validation) = match &self.keys.verifier {
+            Verifier::Secret(decoding_key) => (decoding_key, Validation::default()),
+            Verifier::PublicKeys(public_keys) => {
+                let header = decode_header(token).map_err(|e| eyre!("Invalid JWT token: {}", e))?;
+                let kid = header
+                    .kid
+                    .ok_or_else(|| eyre!("Invalid JWT token: no kid"))?;
+                let public_key = public_keys
+                    .get(&kid)
+                    .filter(|key| self.is_trusted(key, now))
+                    .ok_or_else(|| eyre!("Invalid JWT token: unknown or retired key {}", kid))?;
+                (
+                    &public_key.decoding_key,
+                    Validation::new(public_key.published.algorithm),
+                )
+            }
+        };
+        // Checked against `now` below rather than the system time
+        validation.validate_exp = false;
+        let token_data = decode::<Claims>(token, decoding_key, &validation)
             .map_err(|e| eyre!("Invalid JWT token: {}", e))?;
+        if token_data.claims.exp < now.unix_timestamp() - validation.leeway as i64 {
+            bail!("Invalid JWT token: expired");
+        }
 
         Ok(token_data.claims)
     }
+
+    /// The keys other services can verify the tokens with, none when they
+    /// are signed with the shared secret.
+    pub fn published_keys(&self, now: OffsetDateTime) -> Vec<PublishedKey> {
+        match &self.keys.verifier {
+            Verifier::Secret(_) => Vec::new(),
+            Verifier::PublicKeys(public_keys) => {
+                let mut keys: Vec<_> = public_keys
+                    .values()
+                    .filter(|key| self.is_trusted(key, now))
+                    .map(|key| key.published.clone())
+                    .collect();
+                keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+                keys
+            }
+        }
+    }
+
+    pub fn publishes_keys(&self) -> bool {
+        matches!(self.keys.verifier, Verifier::PublicKeys(_))
+    }
+
+    fn is_trusted(&self, key: &PublicKey, now: OffsetDateTime) -> bool {
+        match key.retire_time {
+            Some(retire_time) => now < retire_time + self.keys.retired_key_grace_period,
+            None => true,
+        }
+    }
+}
+
+fn algorithm(algorithm: JwtKeyAlgorithm) -> Algorithm {
+    match algorithm {
+        JwtKeyAlgorithm::RS256 => Algorithm::RS256,
+        JwtKeyAlgorithm::EdDSA => Algorithm::EdDSA,
+    }
+}
+
+fn load_public_key(settings: &JwtKeySettings) -> Result<PublicKey> {
+    let path = &settings.public_key_path;
+    let pem = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let decoding_key = match settings.algorithm {
+        JwtKeyAlgorithm::RS256 => DecodingKey::from_rsa_pem(&pem),
+        JwtKeyAlgorithm::EdDSA => DecodingKey::from_ed_pem(&pem),
+    }
+    .with_context(|| format!("Invalid public key {}", path.display()))?;
+    let params = public_key_params(settings.algorithm, &pem)
+        .with_context(|| format!("Invalid public key {}", path.display()))?;
+    Ok(PublicKey {
+        published: PublishedKey {
+            kid: settings.kid.clone(),
+            algorithm: algorithm(settings.algorithm),
+            params,
+        },
+        decoding_key,
+        retire_time: settings.retire_time,
+    })
+}
+
+/// Reads the parameters of a `PUBLIC KEY` PEM, a DER encoded
+/// `SubjectPublicKeyInfo`.
+fn public_key_params(algorithm: JwtKeyAlgorithm, pem: &[u8]) -> Result<PublicKeyParams> {
+    let pem = pem::parse(pem)?;
+    if pem.tag() != "PUBLIC KEY" {
+        bail!("Expected a PUBLIC KEY, found a {}", pem.tag());
+    }
+    // SubjectPublicKeyInfo ::= SEQUENCE {
+    //     algorithm SEQUENCE { algorithm OBJECT IDENTIFIER, parameters ANY OPTIONAL },
+    //     subjectPublicKey BIT STRING }
+    let blocks = simple_asn1::from_der(pem.contents())?;
+    let Some(ASN1Block::Sequence(_, info)) = blocks.first() else {
+        bail!("Not a SubjectPublicKeyInfo");
+    };
+    let [ASN1Block::Sequence(_, key_algorithm), ASN1Block::BitString(_, _, public_key)] =
+        info.as_slice()
+    else {
+        bail!("Not a SubjectPublicKeyInfo");
+    };
+    let Some(ASN1Block::ObjectIdentifier(_, key_oid)) = key_algorithm.first() else {
+        bail!("Not a SubjectPublicKeyInfo");
+    };
+    match algorithm {
+        JwtKeyAlgorithm::EdDSA => {
+            if *key_oid != oid!(1, 3, 101, 112) || public_key.len() != 32 {
+                bail!("Not an Ed25519 key");
+            }
+            Ok(PublicKeyParams::Ed25519 {
+                x: public_key.clone(),
+            })
+        }
+        JwtKeyAlgorithm::RS256 => {
+            if *key_oid != oid!(1, 2, 840, 113549, 1, 1, 1) {
+                bail!("Not an RSA key");
+            }
+            // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+            let blocks = simple_asn1::from_der(public_key)?;
+            let Some(ASN1Block::Sequence(_, rsa_key)) = blocks.first() else {
+                bail!("Not an RSA key");
+            };
+            let [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] = rsa_key.as_slice() else {
+                bail!("Not an RSA key");
+            };
+            let (Some(n), Some(e)) = (n.to_biguint(), e.to_biguint()) else {
+                bail!("Not an RSA key");
+            };
+            Ok(PublicKeyParams::Rsa {
+                n: n.to_bytes_be(),
+                e: e.to_bytes_be(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{public_key_params, PublicKeyParams};
+    use crate::configuration::JwtKeyAlgorithm;
+
+    const ED25519_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEAzZ+rut39cdgNy+7TjeD4YC7KSfk5Rn8jkq8WLM08+V4=\n-----END PUBLIC KEY-----\n";
+
+    #[test]
+    fn ed25519_public_key_params() {
+        let params = public_key_params(JwtKeyAlgorithm::EdDSA, ED25519_PUBLIC_KEY.as_bytes());
+        let PublicKeyParams::Ed25519 { x } = params.unwrap() else {
+            panic!("Expected Ed25519 params");
+        };
+        assert_eq!(x.len(), 32);
+        assert_eq!(x[0], 0xcd);
+    }
+
+    #[test]
+    fn public_key_of_another_algorithm_is_rejected() {
+        let params = public_key_params(JwtKeyAlgorithm::RS256, ED25519_PUBLIC_KEY.as_bytes());
+        assert!(params.is_err());
+        let params = public_key_params(JwtKeyAlgorithm::EdDSA, b"not a pem");
+        assert!(params.is_err());
+    }
 }
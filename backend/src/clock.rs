@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+
+use time::{Duration, OffsetDateTime};
+
+/// Source of the current time. Everything that needs "now" asks the clock,
+/// so tests can pin or move time instead of depending on the wall clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// Reads the system wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct FixedClock {
+    now: Arc<Mutex<OffsetDateTime>>,
+}
+
+impl FixedClock {
+    pub fn new(now: OffsetDateTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: OffsetDateTime) {
+        *self.now.lock().expect("clock lock poisoned") = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().expect("clock lock poisoned") += duration;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> OffsetDateTime {
+        *self.now.lock().expect("clock lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+
+    use super::{Clock, FixedClock};
+
+    #[test]
+    fn fixed_clock_advances() {
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let clock = FixedClock::new(start);
+        let shared = clock.clone();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::weeks(3));
+        assert_eq!(shared.now(), start + Duration::weeks(3));
+
+        shared.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
@@ -1,7 +1,10 @@
 use eyre::{bail, Report, Result, WrapErr};
 use secrecy::ExposeSecret;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::time::Duration;
+use time::OffsetDateTime;
 
 const CONFIGURATION_FILE: &str = "base.yaml";
 
@@ -10,6 +13,8 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub recurring: RecurringSettings,
+    pub trash: TrashSettings,
+    pub events: EventSettings,
     pub auth: AuthSettings,
 }
 
@@ -58,6 +63,21 @@ pub struct RecurringSettings {
     pub look_ahead_duration: Duration,
 }
 
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TrashSettings {
+    /// How long deleted todos and items stay in the trash before being purged
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct EventSettings {
+    /// How long the events of todo items and templates are kept for clients
+    /// resuming their stream
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+}
+
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct DatabaseSettings {
     pub host: String,
@@ -79,13 +99,117 @@ pub enum AuthSettings {
     Jwt {
         jwt_secret: secrecy::SecretString,
         jwt_expiration_hours: u64,
+        #[serde(default)]
+        jwt_keys: JwtKeysSettings,
     },
     #[serde(rename = "google_oauth")]
     GoogleOAuth {
         jwt_secret: secrecy::SecretString,
         jwt_expiration_hours: u64,
+        #[serde(default)]
+        jwt_keys: JwtKeysSettings,
         google_oauth: GoogleOAuthSettings,
+        #[serde(default)]
+        session: SessionSettings,
     },
+    #[serde(rename = "oidc")]
+    Oidc {
+        jwt_secret: secrecy::SecretString,
+        jwt_expiration_hours: u64,
+        #[serde(default)]
+        jwt_keys: JwtKeysSettings,
+        providers: Vec<OidcProviderSettings>,
+        #[serde(default)]
+        session: SessionSettings,
+    },
+}
+
+impl AuthSettings {
+    /// How long the sessions opened by logins last, `None` when users don't
+    /// log in through the API.
+    pub fn session(&self) -> Option<&SessionSettings> {
+        match self {
+            AuthSettings::Jwt { .. } => None,
+            AuthSettings::GoogleOAuth { session, .. } | AuthSettings::Oidc { session, .. } => {
+                Some(session)
+            }
+        }
+    }
+}
+
+/// Asymmetric keys signing the bearer tokens instead of `jwt_secret`. Other
+/// services verify the tokens with the public keys, published at
+/// `/.well-known/jwks.json`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct JwtKeysSettings {
+    /// Exactly one key that isn't retired has a private key and signs new
+    /// tokens
+    #[serde(default)]
+    pub keys: Vec<JwtKeySettings>,
+    /// How long the tokens signed by a retired key stay valid, at least the
+    /// lifetime of the tokens
+    #[serde(with = "humantime_serde", default = "default_retired_key_grace_period")]
+    pub retired_key_grace_period: Duration,
+}
+
+impl Default for JwtKeysSettings {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            retired_key_grace_period: default_retired_key_grace_period(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct JwtKeySettings {
+    /// Named in the `kid` header of the tokens the key signs
+    pub kid: String,
+    pub algorithm: JwtKeyAlgorithm,
+    /// PEM file of the public key, as `PUBLIC KEY`
+    pub public_key_path: PathBuf,
+    /// PEM file of the private key, as PKCS#8 `PRIVATE KEY`, only needed to
+    /// sign
+    #[serde(default)]
+    pub private_key_path: Option<PathBuf>,
+    /// When the key stopped signing tokens. It is still trusted and
+    /// published for the grace period.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub retire_time: Option<OffsetDateTime>,
+}
+
+impl JwtKeySettings {
+    pub fn signs(&self) -> bool {
+        self.private_key_path.is_some() && self.retire_time.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum JwtKeyAlgorithm {
+    RS256,
+    EdDSA,
+}
+
+/// Every login opens a session. The client gets a short-lived access token
+/// and a refresh token to get new ones with, until the session expires or is
+/// revoked.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SessionSettings {
+    /// How long an access token is valid
+    #[serde(with = "humantime_serde")]
+    pub access_token_lifetime: Duration,
+    /// How long a session lasts without being refreshed
+    #[serde(with = "humantime_serde")]
+    pub refresh_token_lifetime: Duration,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            access_token_lifetime: Duration::from_secs(15 * 60),
+            refresh_token_lifetime: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -103,6 +227,24 @@ pub struct GoogleOAuthSettings {
     pub userinfo_url: String,
 }
 
+/// An OpenID Connect provider users log in with at `/auth/oidc/{name}`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct OidcProviderSettings {
+    /// Name of the provider in the login routes
+    pub name: String,
+    /// Endpoints and keys are discovered from its
+    /// `/.well-known/openid-configuration`
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: secrecy::SecretString,
+    pub redirect_uri: String,
+    #[serde(default = "default_frontend_callback_url")]
+    pub frontend_callback_url: String,
+    /// Requested along with `openid`
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+}
+
 fn validate_settings(settings: &Settings) -> Result<()> {
     if let AuthSettings::GoogleOAuth { google_oauth, .. } = &settings.auth {
         let mut missing = Vec::new();
@@ -119,6 +261,47 @@ fn validate_settings(settings: &Settings) -> Result<()> {
             );
         }
     }
+    if let AuthSettings::Oidc { providers, .. } = &settings.auth {
+        if providers.is_empty() {
+            bail!("OpenID Connect is enabled but auth.providers is empty");
+        }
+        let mut names = HashSet::new();
+        for provider in providers {
+            if !names.insert(provider.name.as_str()) {
+                bail!(
+                    "OpenID Connect provider {} is configured twice",
+                    provider.name
+                );
+            }
+            if provider.client_id.is_empty() || provider.client_secret.expose_secret().is_empty() {
+                bail!(
+                    "OpenID Connect provider {} is missing its client_id or client_secret",
+                    provider.name
+                );
+            }
+        }
+    }
+
+    let jwt_keys = match &settings.auth {
+        AuthSettings::Jwt { jwt_keys, .. }
+        | AuthSettings::GoogleOAuth { jwt_keys, .. }
+        | AuthSettings::Oidc { jwt_keys, .. } => jwt_keys,
+    };
+    if !jwt_keys.keys.is_empty() {
+        let mut kids = HashSet::new();
+        for key in &jwt_keys.keys {
+            if !kids.insert(key.kid.as_str()) {
+                bail!("JWT key {} is configured twice", key.kid);
+            }
+        }
+        let signing_keys = jwt_keys.keys.iter().filter(|key| key.signs()).count();
+        if signing_keys != 1 {
+            bail!(
+                "auth.jwt_keys needs exactly one key with a private key that isn't retired, found {}",
+                signing_keys
+            );
+        }
+    }
 
     Ok(())
 }
@@ -131,6 +314,14 @@ fn default_frontend_callback_url() -> String {
     "http://localhost:5173/auth/callback".to_string()
 }
 
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["email".to_string(), "profile".to_string()]
+}
+
+fn default_retired_key_grace_period() -> Duration {
+    Duration::from_secs(24 * 60 * 60)
+}
+
 fn default_token_url() -> String {
     "https://www.googleapis.com/oauth2/v3/token".to_string()
 }
@@ -0,0 +1,58 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::domain::Cursor;
+
+/// What an audit row of a todo describes. The rows of groups and their
+/// members belong to no todo and are not read back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "audit_entity", rename_all = "snake_case")]
+pub enum AuditEntity {
+    Todo,
+    Item,
+    Template,
+    /// A user's membership of the todo
+    Member,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Completed,
+    Reopened,
+    Deleted,
+    Restored,
+}
+
+/// Who made the changes of a transaction. Changes without an actor come
+/// from background jobs.
+#[derive(Debug, Clone)]
+pub struct AuditActor {
+    pub user_id: i32,
+    pub request_id: Option<String>,
+}
+
+/// A write to a todo, an item, a template or a member, recorded by the
+/// `audit_trigger` with the row before and after it.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub todo_id: Uuid,
+    pub actor_id: Option<i32>,
+    pub actor_email: Option<String>,
+    pub request_id: Option<String>,
+    pub entity: AuditEntity,
+    pub entity_id: Uuid,
+    pub action: AuditAction,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub create_time: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListActivity {
+    pub items: Vec<AuditEntry>,
+    pub next_cursor: Option<Cursor>,
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+
+use time::OffsetDateTime;
+
+use crate::error::APIError;
+
+/// Version of a row, derived from its `update_time` which the
+/// `update_time_trigger` bumps on every update. Rendered as a quoted strong
+/// entity tag holding the microseconds since the epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ETag(OffsetDateTime);
+
+impl ETag {
+    /// The tag and whether it is weak. Weak tags carry the same version, we
+    /// never emit them but clients and proxies may weaken ours.
+    fn parse(tag: &str) -> Option<(Self, bool)> {
+        let tag = tag.trim();
+        let (tag, weak) = match tag.strip_prefix("W/") {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let micros: i128 = tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok()?;
+        let update_time =
+            OffsetDateTime::from_unix_timestamp_nanos(micros.checked_mul(1000)?).ok()?;
+        Some((Self(update_time), weak))
+    }
+}
+
+impl From<OffsetDateTime> for ETag {
+    fn from(update_time: OffsetDateTime) -> Self {
+        Self(update_time)
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0.unix_timestamp_nanos() / 1000)
+    }
+}
+
+/// The value of an `If-Match` or `If-None-Match` header: either `*` or a list
+/// of entity tags, each with whether it is weak. Tags we could not have
+/// issued are dropped, they never match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ETagCondition {
+    Any,
+    Tags(Vec<(ETag, bool)>),
+}
+
+impl ETagCondition {
+    pub fn parse(value: &str) -> Self {
+        if value.trim() == "*" {
+            return Self::Any;
+        }
+        Self::Tags(value.split(',').filter_map(ETag::parse).collect())
+    }
+
+    /// Weak comparison, as for `If-None-Match`: weak tags match too.
+    pub fn matches(&self, etag: &ETag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|(tag, _)| tag == etag),
+        }
+    }
+
+    /// Strong comparison, as for `If-Match`: weak tags never match (RFC 7232
+    /// section 3.1).
+    pub fn matches_strongly(&self, etag: &ETag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|(tag, weak)| !weak && tag == etag),
+        }
+    }
+
+    /// Fails with a 412 when an `If-Match` condition doesn't cover the
+    /// current version of the resource.
+    pub fn check(&self, etag: &ETag) -> Result<(), APIError> {
+        if self.matches_strongly(etag) {
+            return Ok(());
+        }
+        Err(APIError::PreconditionFailed(format!(
+            "The resource has been modified, its current ETag is {}",
+            etag
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::{ETag, ETagCondition};
+
+    fn update_time() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp_nanos(1_739_193_600_123_456_000).unwrap()
+    }
+
+    #[test]
+    fn etag_round_trips() {
+        let etag = ETag::from(update_time());
+        assert_eq!(etag.to_string(), "\"1739193600123456\"");
+        assert_eq!(ETag::parse(&etag.to_string()), Some((etag, false)));
+        assert_eq!(ETag::parse("W/\"1739193600123456\""), Some((etag, true)));
+    }
+
+    #[test]
+    fn etag_condition_matches() {
+        let etag = ETag::from(update_time());
+        let test_cases = vec![
+            ("*", true, true),
+            ("\"1739193600123456\"", true, true),
+            ("\"1\", \"1739193600123456\"", true, true),
+            ("W/\"1739193600123456\"", true, false),
+            ("W/\"1739193600123456\", \"1739193600123456\"", true, true),
+            ("\"1739193600123457\"", false, false),
+            ("1739193600123456", false, false),
+            ("\"banana\"", false, false),
+            ("", false, false),
+        ];
+        for (value, weakly, strongly) in test_cases {
+            let condition = ETagCondition::parse(value);
+            assert_eq!(condition.matches(&etag), weakly, "{value}");
+            assert_eq!(condition.matches_strongly(&etag), strongly, "{value}");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{Cursor, TodoRole};
+use crate::error::APIError;
+
+/// Users owning todos together, like a household. Members have the same role
+/// on every todo of the group as on the group itself.
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub group_id: Uuid,
+    pub name: GroupName,
+    /// The role of the user who asked for the group
+    pub role: TodoRole,
+    pub create_time: OffsetDateTime,
+    pub update_time: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListGroup {
+    pub items: Vec<Group>,
+    pub next_cursor: Option<Cursor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GroupName(String);
+
+const MAX_GROUP_NAME_LENGTH: usize = 40;
+
+impl TryFrom<String> for GroupName {
+    type Error = APIError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(APIError::BadRequest("Name cannot be empty".to_string()));
+        }
+        if s.len() > MAX_GROUP_NAME_LENGTH {
+            return Err(APIError::BadRequest(format!(
+                "Name is too long cannot exceed: {}",
+                MAX_GROUP_NAME_LENGTH
+            )));
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl AsRef<str> for GroupName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GroupMember {
+    pub user_id: i32,
+    pub email: String,
+    pub role: TodoRole,
+    pub create_time: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListGroupMember {
+    pub items: Vec<GroupMember>,
+    pub next_cursor: Option<Cursor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewGroupMemberRequest {
+    pub email: String,
+    pub role: TodoRole,
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_err;
+
+    use super::{GroupName, MAX_GROUP_NAME_LENGTH};
+
+    #[test]
+    fn name_is_trimmed() {
+        let name: GroupName = "  home  ".to_string().try_into().unwrap();
+        assert_eq!(name.as_ref(), "home");
+    }
+
+    #[test]
+    fn name_is_not_ok() {
+        assert_err!(GroupName::try_from("   ".to_string()));
+        assert_err!(GroupName::try_from("a".repeat(MAX_GROUP_NAME_LENGTH + 1)));
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+use super::OpaqueToken;
+
+/// How long the frontend has to exchange the code it was redirected with.
+pub const LOGIN_CODE_TTL: Duration = Duration::from_secs(60);
+
+/// The code the frontend gets in the callback URL instead of tokens, only
+/// valid once. The tokens never show in the browser history, proxy logs or
+/// `Referer` headers.
+pub type LoginCode = OpaqueToken;
+
+/// A completed login waiting for the frontend to exchange its code.
+#[derive(Debug, Clone)]
+pub struct CompletedLogin {
+    pub user_id: i32,
+    pub create_time: OffsetDateTime,
+}
+
+impl CompletedLogin {
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        now - self.create_time > LOGIN_CODE_TTL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::{CompletedLogin, LOGIN_CODE_TTL};
+
+    #[test]
+    fn login_code_expires_after_ttl() {
+        let now = OffsetDateTime::now_utc();
+        let login = CompletedLogin {
+            user_id: 1,
+            create_time: now,
+        };
+        assert!(!login.is_expired(now + LOGIN_CODE_TTL));
+        assert!(login.is_expired(now + LOGIN_CODE_TTL + time::Duration::SECOND));
+    }
+}
@@ -1,9 +1,35 @@
+mod audit;
+mod etag;
+mod group;
+mod login_code;
+mod oauth_login;
+mod opaque_token;
+mod pagination;
 mod recurring_template;
+mod rrule;
+mod session;
+mod sync;
 mod todo;
+mod todo_event;
 mod todo_item;
+mod todo_member;
+mod trash;
 mod user;
 
+pub use audit::*;
+pub use etag::*;
+pub use group::*;
+pub use login_code::*;
+pub use oauth_login::*;
+pub use opaque_token::*;
+pub use pagination::*;
 pub use recurring_template::*;
+pub use rrule::*;
+pub use session::*;
+pub use sync::*;
 pub use todo::*;
+pub use todo_event::*;
 pub use todo_item::*;
+pub use todo_member::*;
+pub use trash::*;
 pub use user::*;
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+/// How long a user has to get through the consent screen of the provider.
+pub const OAUTH_LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A login waiting for the provider to call back.
+#[derive(Debug, Clone)]
+pub struct OAuthLogin {
+    /// The `state` sent to the provider, also kept in a cookie
+    pub state: String,
+    /// `google` or the name of an OpenID Connect provider
+    pub provider: String,
+    pub pkce_verifier: String,
+    /// The nonce the ID token must carry, for OpenID Connect providers
+    pub nonce: Option<String>,
+    pub create_time: OffsetDateTime,
+}
+
+impl OAuthLogin {
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        now - self.create_time > OAUTH_LOGIN_TTL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::{OAuthLogin, OAUTH_LOGIN_TTL};
+
+    #[test]
+    fn login_expires_after_ttl() {
+        let now = OffsetDateTime::now_utc();
+        let login = OAuthLogin {
+            state: "state".to_string(),
+            provider: "google".to_string(),
+            pkce_verifier: "verifier".to_string(),
+            nonce: None,
+            create_time: now,
+        };
+        assert!(!login.is_expired(now + OAUTH_LOGIN_TTL));
+        assert!(login.is_expired(now + OAUTH_LOGIN_TTL + time::Duration::SECOND));
+    }
+}
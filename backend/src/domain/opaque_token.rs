@@ -0,0 +1,86 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+const OPAQUE_TOKEN_BYTES: usize = 32;
+
+/// A random secret handed to a client, such as a refresh token. Only its
+/// hash is stored, to find it again when the client sends it back.
+#[derive(Clone)]
+pub struct OpaqueToken(String);
+
+impl OpaqueToken {
+    pub fn new_random() -> Self {
+        let mut bytes = [0u8; OPAQUE_TOKEN_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn secret(&self) -> &str {
+        &self.0
+    }
+
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+
+    /// The token replacing this one, always the same for the same secret
+    /// `salt`. Without the salt, holding this token doesn't give the next.
+    pub fn successor(&self, salt: &str) -> Self {
+        let digest = Sha256::new()
+            .chain_update(salt.as_bytes())
+            .chain_update(b":")
+            .chain_update(self.0.as_bytes())
+            .finalize();
+        Self(URL_SAFE_NO_PAD.encode(digest))
+    }
+}
+
+impl From<String> for OpaqueToken {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Debug for OpaqueToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OpaqueToken(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpaqueToken;
+
+    #[test]
+    fn tokens_are_random_and_hashed() {
+        let token = OpaqueToken::new_random();
+        let other = OpaqueToken::new_random();
+        assert_ne!(token.secret(), other.secret());
+        assert_eq!(token.secret().len(), 43);
+
+        let hash = token.hash();
+        assert_eq!(hash.len(), 64);
+        assert_ne!(hash, token.secret());
+        assert_eq!(hash, OpaqueToken::from(token.secret().to_string()).hash());
+        assert_ne!(hash, other.hash());
+    }
+
+    #[test]
+    fn successors_depend_on_the_token_and_the_salt() {
+        let token = OpaqueToken::new_random();
+        let salt = OpaqueToken::new_random();
+        let successor = token.successor(salt.secret());
+        assert_eq!(successor.secret().len(), 43);
+        assert_ne!(successor.secret(), token.secret());
+        assert_eq!(successor.secret(), token.successor(salt.secret()).secret());
+        assert_ne!(
+            successor.secret(),
+            token.successor(OpaqueToken::new_random().secret()).secret()
+        );
+        assert_ne!(
+            successor.secret(),
+            OpaqueToken::new_random().successor(salt.secret()).secret()
+        );
+    }
+}
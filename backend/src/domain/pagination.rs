@@ -0,0 +1,159 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::APIError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// Number of rows returned in a single page. Values above `MAX_PAGE_SIZE` are
+/// clamped to it.
+#[derive(Debug, Clone, Copy)]
+pub struct PageSize(i64);
+
+impl TryFrom<Option<i64>> for PageSize {
+    type Error = APIError;
+
+    fn try_from(value: Option<i64>) -> Result<Self, Self::Error> {
+        match value {
+            None => Ok(Self(DEFAULT_PAGE_SIZE)),
+            Some(x) if x < 1 => Err(APIError::BadRequest(
+                "Limit must be greater than 0".to_string(),
+            )),
+            Some(x) => Ok(Self(x.min(MAX_PAGE_SIZE))),
+        }
+    }
+}
+
+impl PageSize {
+    pub fn get(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Opaque keyset cursor. It encodes the sort key of the last row of a page,
+/// the next page starts right after it.
+#[derive(Debug, Clone)]
+pub struct Cursor(String);
+
+impl Cursor {
+    pub fn encode<K: Serialize>(key: &K) -> Self {
+        let json = serde_json::to_vec(key).expect("cursor keys are always serializable");
+        Self(URL_SAFE_NO_PAD.encode(json))
+    }
+
+    pub fn decode<K: DeserializeOwned>(&self) -> Result<K, APIError> {
+        URL_SAFE_NO_PAD
+            .decode(&self.0)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| APIError::BadRequest("Invalid cursor".to_string()))
+    }
+}
+
+impl From<String> for Cursor {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(value: Cursor) -> Self {
+        value.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub size: PageSize,
+    pub cursor: Option<Cursor>,
+}
+
+impl PageRequest {
+    /// Number of rows to fetch, one more than the page size to detect whether
+    /// a next page exists.
+    pub fn fetch_limit(&self) -> i64 {
+        self.size.get() + 1
+    }
+
+    pub fn decode_cursor<K: DeserializeOwned>(&self) -> Result<Option<K>, APIError> {
+        self.cursor.as_ref().map(|c| c.decode()).transpose()
+    }
+
+    /// Trims the rows fetched with `fetch_limit` down to a page and computes
+    /// the cursor of the next page from the last row kept.
+    pub fn paginate<T, K: Serialize>(
+        &self,
+        mut rows: Vec<T>,
+        key: impl Fn(&T) -> K,
+    ) -> (Vec<T>, Option<Cursor>) {
+        let size = self.size.get() as usize;
+        if rows.len() <= size {
+            return (rows, None);
+        }
+        rows.truncate(size);
+        let next_cursor = rows.last().map(|row| Cursor::encode(&key(row)));
+        (rows, next_cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::{Cursor, PageRequest, PageSize, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+    #[test]
+    fn page_size_is_ok() {
+        let test_cases = vec![
+            (None, DEFAULT_PAGE_SIZE),
+            (Some(1), 1),
+            (Some(MAX_PAGE_SIZE), MAX_PAGE_SIZE),
+            (Some(MAX_PAGE_SIZE + 1), MAX_PAGE_SIZE),
+        ];
+        for test_case in test_cases {
+            let result: Result<PageSize, _> = test_case.0.try_into();
+            assert_ok!(&result);
+            assert_eq!(result.unwrap().get(), test_case.1);
+        }
+    }
+
+    #[test]
+    fn page_size_is_not_ok() {
+        for test_case in [Some(0), Some(-1)] {
+            let result: Result<PageSize, _> = test_case.try_into();
+            assert_err!(&result);
+        }
+    }
+
+    #[test]
+    fn cursor_roundtrip() {
+        let key = (42, "banana".to_string());
+        let cursor: Cursor = String::from(Cursor::encode(&key)).into();
+        let decoded: (i32, String) = cursor.decode().unwrap();
+        assert_eq!(decoded, key);
+    }
+
+    #[test]
+    fn invalid_cursor_is_rejected() {
+        for test_case in ["", "not base64!", "YmFuYW5h"] {
+            let cursor: Cursor = test_case.to_string().into();
+            assert_err!(cursor.decode::<(i32, String)>());
+        }
+    }
+
+    #[test]
+    fn paginate_sets_next_cursor_on_full_page() {
+        let page = PageRequest {
+            size: Some(2).try_into().unwrap(),
+            cursor: None,
+        };
+        let (rows, next_cursor) = page.paginate(vec![1, 2, 3], |x| *x);
+        assert_eq!(rows, vec![1, 2]);
+        assert_eq!(next_cursor.unwrap().decode::<i32>().unwrap(), 2);
+
+        let (rows, next_cursor) = page.paginate(vec![1, 2], |x| *x);
+        assert_eq!(rows, vec![1, 2]);
+        assert!(next_cursor.is_none());
+    }
+}
@@ -1,14 +1,14 @@
 use sqlx::postgres::types::PgInterval;
-use time::{Date, OffsetDateTime};
+use time::{Date, Duration, Month, OffsetDateTime};
 use uuid::Uuid;
 
-use super::TodoName;
+use super::{Cursor, RecurrenceRule, TodoName, TodoRef};
 
 #[derive(Debug, Clone)]
 pub struct NewRecurringTemplateRequest {
-    pub todo_name: TodoName,
+    pub todo_ref: TodoRef,
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence: Recurrence,
     pub start_date: Date,
     pub end_date: Option<Date>,
 }
@@ -16,7 +16,7 @@ pub struct NewRecurringTemplateRequest {
 #[derive(Debug, Clone)]
 pub struct UpdateRecurringTemplateRequest {
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence: Recurrence,
     pub start_date: Date,
     pub end_date: Option<Date>,
     pub is_active: bool,
@@ -25,9 +25,10 @@ pub struct UpdateRecurringTemplateRequest {
 #[derive(Debug, Clone)]
 pub struct RecurringTemplate {
     pub todo_name: TodoName,
+    pub todo_owner_id: i32,
     pub template_id: Uuid,
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence: Recurrence,
     pub start_date: Date,
     pub end_date: Option<Date>,
     pub last_generated_date: Option<Date>,
@@ -39,14 +40,16 @@ pub struct RecurringTemplate {
 #[derive(Debug, Clone)]
 pub struct ListRecurringTemplate {
     pub items: Vec<ListRecurringTemplateSingle>,
+    pub next_cursor: Option<Cursor>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ListRecurringTemplateSingle {
     pub todo_name: TodoName,
+    pub todo_owner_id: i32,
     pub template_id: Uuid,
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence: Recurrence,
     pub start_date: Date,
     pub end_date: Option<Date>,
     pub last_generated_date: Option<Date>,
@@ -59,9 +62,10 @@ impl From<&RecurringTemplate> for ListRecurringTemplateSingle {
     fn from(template: &RecurringTemplate) -> Self {
         Self {
             todo_name: template.todo_name.clone(),
+            todo_owner_id: template.todo_owner_id,
             template_id: template.template_id,
             title: template.title.clone(),
-            recurrence_interval: template.recurrence_interval.clone(),
+            recurrence: template.recurrence.clone(),
             start_date: template.start_date,
             end_date: template.end_date,
             last_generated_date: template.last_generated_date,
@@ -72,6 +76,25 @@ impl From<&RecurringTemplate> for ListRecurringTemplateSingle {
     }
 }
 
+/// How a template repeats: either a fixed interval after the previous due
+/// date, or an RFC 5545 rule anchored on the template's start date.
+#[derive(Debug, Clone)]
+pub enum Recurrence {
+    Interval(RecurrenceInterval),
+    Rule(RecurrenceRule),
+}
+
+impl Recurrence {
+    /// The due date of the occurrence following the one due on `due_date`,
+    /// `None` once the recurrence is exhausted.
+    pub fn next_after(&self, start_date: Date, due_date: Date) -> Option<Date> {
+        match self {
+            Recurrence::Interval(interval) => interval.advance(due_date),
+            Recurrence::Rule(rule) => rule.next_after(start_date, due_date),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RecurrenceInterval {
     pub months: i32,
@@ -79,6 +102,23 @@ pub struct RecurrenceInterval {
     pub microseconds: i64,
 }
 
+impl RecurrenceInterval {
+    /// Moves `date` forward by the interval the way Postgres' `date + interval`
+    /// does: months first, clamping to the end of a shorter month, then days.
+    /// Sub-day remainders are dropped. Returns `None` when out of range.
+    pub fn advance(&self, date: Date) -> Option<Date> {
+        let months = i64::from(date.year()) * 12
+            + i64::from(date.month() as u8 - 1)
+            + i64::from(self.months);
+        let year = i32::try_from(months.div_euclid(12)).ok()?;
+        let month = Month::try_from(months.rem_euclid(12) as u8 + 1).ok()?;
+        let day = date.day().min(month.length(year));
+        let date = Date::from_calendar_date(year, month, day).ok()?;
+        let days = i64::from(self.days) + self.microseconds / 86_400_000_000;
+        date.checked_add(Duration::days(days))
+    }
+}
+
 impl From<&sqlx::postgres::types::PgInterval> for RecurrenceInterval {
     fn from(value: &sqlx::postgres::types::PgInterval) -> Self {
         if value.months < 0 {
@@ -108,3 +148,77 @@ impl From<RecurrenceInterval> for PgInterval {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use time::{Date, Month};
+
+    use super::RecurrenceInterval;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn recurrence_interval_advance_is_ok() {
+        let test_cases = vec![
+            (
+                (0, 1, 0),
+                date(2025, Month::January, 31),
+                date(2025, Month::February, 1),
+            ),
+            (
+                (0, 7, 0),
+                date(2025, Month::December, 28),
+                date(2026, Month::January, 4),
+            ),
+            (
+                (1, 0, 0),
+                date(2025, Month::January, 31),
+                date(2025, Month::February, 28),
+            ),
+            (
+                (1, 0, 0),
+                date(2024, Month::January, 31),
+                date(2024, Month::February, 29),
+            ),
+            (
+                (12, 0, 0),
+                date(2024, Month::February, 29),
+                date(2025, Month::February, 28),
+            ),
+            (
+                (1, 1, 0),
+                date(2025, Month::January, 31),
+                date(2025, Month::March, 1),
+            ),
+            (
+                (0, 0, 36 * 3_600_000_000),
+                date(2025, Month::March, 1),
+                date(2025, Month::March, 2),
+            ),
+        ];
+        for ((months, days, microseconds), from, expected) in test_cases {
+            let interval = RecurrenceInterval {
+                months,
+                days,
+                microseconds,
+            };
+            assert_eq!(
+                interval.advance(from),
+                Some(expected),
+                "{interval:?} {from}"
+            );
+        }
+    }
+
+    #[test]
+    fn recurrence_interval_advance_out_of_range() {
+        let interval = RecurrenceInterval {
+            months: 0,
+            days: 1,
+            microseconds: 0,
+        };
+        assert_eq!(interval.advance(Date::MAX), None);
+    }
+}
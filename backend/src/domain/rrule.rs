@@ -0,0 +1,674 @@
+//! The subset of RFC 5545 recurrence rules that makes sense for todo items:
+//! occurrences are dates, the rule is anchored on the template's start date
+//! and weeks start on Monday. Like most implementations the start date is only
+//! an occurrence when it matches the rule.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use time::{Date, Duration, Month, Weekday};
+
+use crate::error::APIError;
+
+/// Give up on a rule after this many consecutive periods without occurrences,
+/// e.g. `FREQ=MONTHLY;BYDAY=5MO;BYMONTHDAY=1` never matches.
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+}
+
+/// A `BYDAY` entry, e.g. `TU`, `2TU` or `-1FR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekdayNum {
+    ordinal: Option<i8>,
+    weekday: Weekday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    freq: Frequency,
+    interval: u32,
+    by_day: Vec<WeekdayNum>,
+    by_month_day: Vec<i8>,
+    by_set_pos: Vec<i16>,
+    count: Option<u32>,
+    until: Option<Date>,
+}
+
+fn invalid(message: impl fmt::Display) -> APIError {
+    APIError::BadRequest(format!("Invalid RRULE: {}", message))
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, APIError> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("{} must be a number, got '{}'", key, value)))
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "MO" => Some(Weekday::Monday),
+        "TU" => Some(Weekday::Tuesday),
+        "WE" => Some(Weekday::Wednesday),
+        "TH" => Some(Weekday::Thursday),
+        "FR" => Some(Weekday::Friday),
+        "SA" => Some(Weekday::Saturday),
+        "SU" => Some(Weekday::Sunday),
+        _ => None,
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Monday => "MO",
+        Weekday::Tuesday => "TU",
+        Weekday::Wednesday => "WE",
+        Weekday::Thursday => "TH",
+        Weekday::Friday => "FR",
+        Weekday::Saturday => "SA",
+        Weekday::Sunday => "SU",
+    }
+}
+
+fn parse_weekday_num(value: &str) -> Result<WeekdayNum, APIError> {
+    if !value.is_ascii() {
+        return Err(invalid(format!("unknown BYDAY '{}'", value)));
+    }
+    let split = value.len().saturating_sub(2);
+    let (ordinal, weekday) = value.split_at(split);
+    let weekday =
+        parse_weekday(weekday).ok_or_else(|| invalid(format!("unknown BYDAY '{}'", value)))?;
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => {
+            let ordinal: i8 = parse_number("BYDAY", ordinal)?;
+            if ordinal == 0 || !(-53..=53).contains(&ordinal) {
+                return Err(invalid(format!(
+                    "BYDAY ordinal out of range in '{}'",
+                    value
+                )));
+            }
+            Some(ordinal)
+        }
+    };
+    Ok(WeekdayNum { ordinal, weekday })
+}
+
+/// `UNTIL` is either a date or a date-time, only the date is kept.
+fn parse_until(value: &str) -> Result<Date, APIError> {
+    let date = value.split('T').next().unwrap_or_default();
+    let parse = || -> Option<Date> {
+        if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let year = date[0..4].parse().ok()?;
+        let month = Month::try_from(date[4..6].parse::<u8>().ok()?).ok()?;
+        let day = date[6..8].parse().ok()?;
+        Date::from_calendar_date(year, month, day).ok()
+    };
+    parse().ok_or_else(|| invalid(format!("UNTIL must be YYYYMMDD, got '{}'", value)))
+}
+
+impl TryFrom<&str> for RecurrenceRule {
+    type Error = APIError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = value.trim();
+        let value = value
+            .strip_prefix("RRULE:")
+            .or_else(|| value.strip_prefix("rrule:"))
+            .unwrap_or(value);
+
+        let mut freq = None;
+        let mut interval = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+        let mut by_set_pos = None;
+        let mut count = None;
+        let mut until = None;
+
+        for part in value.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("'{}' is not KEY=VALUE", part)))?;
+            let key = key.to_ascii_uppercase();
+            let value = value.to_ascii_uppercase();
+            let duplicate = match key.as_str() {
+                "FREQ" => freq
+                    .replace(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid(format!("unsupported FREQ '{}'", value))),
+                    })
+                    .is_some(),
+                "INTERVAL" => {
+                    let n: u32 = parse_number(&key, &value)?;
+                    if n == 0 {
+                        return Err(invalid("INTERVAL must be greater than 0"));
+                    }
+                    interval.replace(n).is_some()
+                }
+                "BYDAY" => by_day
+                    .replace(
+                        value
+                            .split(',')
+                            .map(parse_weekday_num)
+                            .collect::<Result<Vec<_>, _>>()?,
+                    )
+                    .is_some(),
+                "BYMONTHDAY" => {
+                    let days = value
+                        .split(',')
+                        .map(|d| parse_number::<i8>(&key, d))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if days.iter().any(|d| *d == 0 || !(-31..=31).contains(d)) {
+                        return Err(invalid("BYMONTHDAY must be within -31..-1 or 1..31"));
+                    }
+                    by_month_day.replace(days).is_some()
+                }
+                "BYSETPOS" => {
+                    let positions = value
+                        .split(',')
+                        .map(|p| parse_number::<i16>(&key, p))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if positions
+                        .iter()
+                        .any(|p| *p == 0 || !(-366..=366).contains(p))
+                    {
+                        return Err(invalid("BYSETPOS must be within -366..-1 or 1..366"));
+                    }
+                    by_set_pos.replace(positions).is_some()
+                }
+                "COUNT" => {
+                    let n: u32 = parse_number(&key, &value)?;
+                    if n == 0 {
+                        return Err(invalid("COUNT must be greater than 0"));
+                    }
+                    count.replace(n).is_some()
+                }
+                "UNTIL" => until.replace(parse_until(&value)?).is_some(),
+                _ => return Err(invalid(format!("{} is not supported", key))),
+            };
+            if duplicate {
+                return Err(invalid(format!("{} is given more than once", key)));
+            }
+        }
+
+        let freq = freq.ok_or_else(|| invalid("FREQ is required"))?;
+        let by_day = by_day.unwrap_or_default();
+        let by_month_day = by_month_day.unwrap_or_default();
+        let by_set_pos = by_set_pos.unwrap_or_default();
+
+        if count.is_some() && until.is_some() {
+            return Err(invalid("COUNT and UNTIL can't be used together"));
+        }
+        if freq == Frequency::Weekly && !by_month_day.is_empty() {
+            return Err(invalid("BYMONTHDAY can't be used with FREQ=WEEKLY"));
+        }
+        if matches!(freq, Frequency::Daily | Frequency::Weekly)
+            && by_day.iter().any(|d| d.ordinal.is_some())
+        {
+            return Err(invalid(
+                "BYDAY ordinals are only allowed with FREQ=MONTHLY or FREQ=YEARLY",
+            ));
+        }
+        if !by_set_pos.is_empty() && by_day.is_empty() && by_month_day.is_empty() {
+            return Err(invalid("BYSETPOS requires BYDAY or BYMONTHDAY"));
+        }
+
+        Ok(Self {
+            freq,
+            interval: interval.unwrap_or(1),
+            by_day,
+            by_month_day,
+            by_set_pos,
+            count,
+            until,
+        })
+    }
+}
+
+impl TryFrom<String> for RecurrenceRule {
+    type Error = APIError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.as_str().try_into()
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FREQ={}", self.freq.as_str())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|d| match d.ordinal {
+                    Some(n) => format!("{}{}", n, weekday_code(d.weekday)),
+                    None => weekday_code(d.weekday).to_string(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(|d| d.to_string()).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+        if !self.by_set_pos.is_empty() {
+            let positions: Vec<String> = self.by_set_pos.iter().map(|p| p.to_string()).collect();
+            write!(f, ";BYSETPOS={}", positions.join(","))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(
+                f,
+                ";UNTIL={:04}{:02}{:02}",
+                until.year(),
+                until.month() as u8,
+                until.day()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn month_dates(year: i32, month: Month) -> Vec<Date> {
+    (1..=month.length(year))
+        .filter_map(|day| Date::from_calendar_date(year, month, day).ok())
+        .collect()
+}
+
+/// Picks the `BYDAY` matches out of `dates`, which are the days of one month
+/// or one year in order. Ordinals count within that span.
+fn select_by_day(dates: &[Date], by_day: &[WeekdayNum]) -> Vec<Date> {
+    let mut selected = Vec::new();
+    for entry in by_day {
+        let matching: Vec<Date> = dates
+            .iter()
+            .copied()
+            .filter(|d| d.weekday() == entry.weekday)
+            .collect();
+        match entry.ordinal {
+            None => selected.extend(matching),
+            Some(n) => {
+                let index = if n > 0 {
+                    Some(n as usize - 1)
+                } else {
+                    matching.len().checked_sub(n.unsigned_abs() as usize)
+                };
+                if let Some(date) = index.and_then(|i| matching.get(i)) {
+                    selected.push(*date);
+                }
+            }
+        }
+    }
+    selected
+}
+
+fn matches_month_day(date: Date, by_month_day: &[i8]) -> bool {
+    let length = date.month().length(date.year()) as i16;
+    by_month_day.iter().any(|d| {
+        let day = if *d > 0 {
+            *d as i16
+        } else {
+            length + 1 + *d as i16
+        };
+        day == date.day() as i16
+    })
+}
+
+impl RecurrenceRule {
+    /// The candidate dates of the `index`-th period after `start`, before
+    /// `BYSETPOS` and the bounds are applied. `None` once out of range.
+    fn period(&self, start: Date, index: u32) -> Option<Vec<Date>> {
+        let step = i64::from(index) * i64::from(self.interval);
+        let by_weekday = |date: &Date| {
+            self.by_day.is_empty() || self.by_day.iter().any(|d| d.weekday == date.weekday())
+        };
+        let by_month_day = |date: &Date| {
+            self.by_month_day.is_empty() || matches_month_day(*date, &self.by_month_day)
+        };
+
+        let dates = match self.freq {
+            Frequency::Daily => {
+                let date = start.checked_add(Duration::days(step))?;
+                vec![date]
+                    .into_iter()
+                    .filter(by_weekday)
+                    .filter(by_month_day)
+                    .collect()
+            }
+            Frequency::Weekly => {
+                let monday = start
+                    .checked_sub(Duration::days(
+                        start.weekday().number_days_from_monday() as i64
+                    ))?
+                    .checked_add(Duration::weeks(step))?;
+                let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![start.weekday()]
+                } else {
+                    self.by_day.iter().map(|d| d.weekday).collect()
+                };
+                weekdays
+                    .into_iter()
+                    .filter_map(|w| {
+                        monday.checked_add(Duration::days(w.number_days_from_monday() as i64))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months =
+                    i64::from(start.year()) * 12 + i64::from(start.month() as u8 - 1) + step;
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = Month::try_from(months.rem_euclid(12) as u8 + 1).ok()?;
+                Date::from_calendar_date(year, month, 1).ok()?;
+                let days = month_dates(year, month);
+                if self.by_day.is_empty() && self.by_month_day.is_empty() {
+                    days.into_iter()
+                        .filter(|d| d.day() == start.day())
+                        .collect()
+                } else if self.by_day.is_empty() {
+                    days.into_iter().filter(by_month_day).collect()
+                } else {
+                    select_by_day(&days, &self.by_day)
+                        .into_iter()
+                        .filter(by_month_day)
+                        .collect()
+                }
+            }
+            Frequency::Yearly => {
+                let year = i32::try_from(i64::from(start.year()) + step).ok()?;
+                Date::from_calendar_date(year, Month::January, 1).ok()?;
+                if self.by_day.is_empty() && self.by_month_day.is_empty() {
+                    Date::from_calendar_date(year, start.month(), start.day())
+                        .into_iter()
+                        .collect()
+                } else {
+                    let mut month = Month::January;
+                    let mut days = Vec::new();
+                    for _ in 0..12 {
+                        days.extend(month_dates(year, month));
+                        month = month.next();
+                    }
+                    if self.by_day.is_empty() {
+                        days.into_iter().filter(by_month_day).collect()
+                    } else {
+                        select_by_day(&days, &self.by_day)
+                            .into_iter()
+                            .filter(by_month_day)
+                            .collect()
+                    }
+                }
+            }
+        };
+        Some(dates)
+    }
+
+    /// Occurrences of the rule anchored on `start`, in order.
+    pub fn occurrences(&self, start: Date) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            start,
+            period: 0,
+            pending: VecDeque::new(),
+            emitted: 0,
+            done: false,
+        }
+    }
+
+    /// The first occurrence on or after `date`.
+    pub fn first_on_or_after(&self, start: Date, date: Date) -> Option<Date> {
+        self.occurrences(start).find(|d| *d >= date)
+    }
+
+    /// The first occurrence strictly after `date`.
+    pub fn next_after(&self, start: Date, date: Date) -> Option<Date> {
+        self.occurrences(start).find(|d| *d > date)
+    }
+}
+
+pub struct Occurrences<'a> {
+    rule: &'a RecurrenceRule,
+    start: Date,
+    period: u32,
+    pending: VecDeque<Date>,
+    emitted: u32,
+    done: bool,
+}
+
+impl Occurrences<'_> {
+    fn fill(&mut self) {
+        let mut empty_periods = 0;
+        while self.pending.is_empty() && !self.done {
+            let Some(mut dates) = self.rule.period(self.start, self.period) else {
+                self.done = true;
+                return;
+            };
+            self.period += 1;
+            dates.sort();
+            dates.dedup();
+            if !self.rule.by_set_pos.is_empty() {
+                let mut selected: Vec<Date> = self
+                    .rule
+                    .by_set_pos
+                    .iter()
+                    .filter_map(|p| {
+                        let index = if *p > 0 {
+                            Some(*p as usize - 1)
+                        } else {
+                            dates.len().checked_sub(p.unsigned_abs() as usize)
+                        };
+                        index.and_then(|i| dates.get(i)).copied()
+                    })
+                    .collect();
+                selected.sort();
+                selected.dedup();
+                dates = selected;
+            }
+            self.pending
+                .extend(dates.into_iter().filter(|d| *d >= self.start));
+            if self.pending.is_empty() {
+                empty_periods += 1;
+                if empty_periods >= MAX_EMPTY_PERIODS {
+                    self.done = true;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = Date;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rule.count.is_some_and(|c| self.emitted >= c) {
+            return None;
+        }
+        self.fill();
+        let date = self.pending.pop_front()?;
+        if self.rule.until.is_some_and(|until| date > until) {
+            self.done = true;
+            self.pending.clear();
+            return None;
+        }
+        self.emitted += 1;
+        Some(date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use time::{Date, Month};
+
+    use super::RecurrenceRule;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn take(rule: &str, start: Date, n: usize) -> Vec<String> {
+        let rule = RecurrenceRule::try_from(rule).unwrap();
+        rule.occurrences(start)
+            .take(n)
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn rrule_is_ok() {
+        let test_cases = vec![
+            ("FREQ=DAILY", "FREQ=DAILY"),
+            ("RRULE:FREQ=WEEKLY;INTERVAL=2", "FREQ=WEEKLY;INTERVAL=2"),
+            (
+                "freq=weekly;byday=mo,tu,we,th,fr",
+                "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR",
+            ),
+            ("FREQ=MONTHLY;BYDAY=2TU", "FREQ=MONTHLY;BYDAY=2TU"),
+            ("FREQ=MONTHLY;BYMONTHDAY=-1", "FREQ=MONTHLY;BYMONTHDAY=-1"),
+            (
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+            ),
+            ("FREQ=DAILY;COUNT=3", "FREQ=DAILY;COUNT=3"),
+            (
+                "FREQ=DAILY;UNTIL=20250131T235959Z",
+                "FREQ=DAILY;UNTIL=20250131",
+            ),
+        ];
+        for (rule, canonical) in test_cases {
+            let parsed = assert_ok!(RecurrenceRule::try_from(rule), "{}", rule);
+            assert_eq!(parsed.to_string(), canonical);
+        }
+    }
+
+    #[test]
+    fn rrule_is_not_ok() {
+        let test_cases = vec![
+            "",
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;FREQ=WEEKLY",
+            "FREQ=DAILY;BYDAY=XX",
+            "FREQ=WEEKLY;BYDAY=2TU",
+            "FREQ=MONTHLY;BYDAY=0TU",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=WEEKLY;BYMONTHDAY=1",
+            "FREQ=MONTHLY;BYSETPOS=1",
+            "FREQ=DAILY;COUNT=2;UNTIL=20250101",
+            "FREQ=DAILY;UNTIL=2025-01-01",
+            "FREQ=DAILY;BYMONTH=1",
+            "FREQ=MONTHLY;BYDAY=1é",
+            "FREQ",
+        ];
+        for rule in test_cases {
+            assert_err!(RecurrenceRule::try_from(rule), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn rrule_occurrences() {
+        // 2025-01-01 is a Wednesday
+        let start = date(2025, Month::January, 1);
+        let test_cases = vec![
+            (
+                "FREQ=DAILY;INTERVAL=3",
+                vec!["2025-01-01", "2025-01-04", "2025-01-07", "2025-01-10"],
+            ),
+            (
+                "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR",
+                vec!["2025-01-01", "2025-01-02", "2025-01-03", "2025-01-06"],
+            ),
+            (
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO",
+                vec!["2025-01-13", "2025-01-27", "2025-02-10", "2025-02-24"],
+            ),
+            (
+                "FREQ=MONTHLY;BYDAY=2TU",
+                vec!["2025-01-14", "2025-02-11", "2025-03-11", "2025-04-08"],
+            ),
+            (
+                "FREQ=MONTHLY;BYMONTHDAY=-1",
+                vec!["2025-01-31", "2025-02-28", "2025-03-31", "2025-04-30"],
+            ),
+            (
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+                vec!["2025-01-31", "2025-02-28", "2025-03-31", "2025-04-30"],
+            ),
+            (
+                "FREQ=MONTHLY;BYMONTHDAY=31",
+                vec!["2025-01-31", "2025-03-31", "2025-05-31", "2025-07-31"],
+            ),
+            (
+                "FREQ=YEARLY;BYDAY=-1FR",
+                vec!["2025-12-26", "2026-12-25", "2027-12-31", "2028-12-29"],
+            ),
+            ("FREQ=DAILY;COUNT=2", vec!["2025-01-01", "2025-01-02"]),
+            (
+                "FREQ=WEEKLY;UNTIL=20250115",
+                vec!["2025-01-01", "2025-01-08", "2025-01-15"],
+            ),
+        ];
+        for (rule, expected) in test_cases {
+            assert_eq!(take(rule, start, 4), expected, "{}", rule);
+        }
+
+        let leap = date(2024, Month::February, 29);
+        assert_eq!(
+            take("FREQ=YEARLY", leap, 2),
+            vec!["2024-02-29", "2028-02-29"]
+        );
+        let monthly = date(2025, Month::January, 31);
+        assert_eq!(
+            take("FREQ=MONTHLY", monthly, 3),
+            vec!["2025-01-31", "2025-03-31", "2025-05-31"]
+        );
+    }
+
+    #[test]
+    fn rrule_never_matching_terminates() {
+        let start = date(2025, Month::January, 1);
+        assert!(take("FREQ=MONTHLY;BYDAY=5MO;BYMONTHDAY=1", start, 1).is_empty());
+    }
+
+    #[test]
+    fn rrule_next_after() {
+        let rule = RecurrenceRule::try_from("FREQ=MONTHLY;BYDAY=2TU").unwrap();
+        let start = date(2025, Month::January, 1);
+        assert_eq!(
+            rule.next_after(start, date(2025, Month::January, 14)),
+            Some(date(2025, Month::February, 11))
+        );
+        assert_eq!(
+            rule.first_on_or_after(start, date(2025, Month::January, 14)),
+            Some(date(2025, Month::January, 14))
+        );
+
+        let rule = RecurrenceRule::try_from("FREQ=DAILY;COUNT=2").unwrap();
+        assert_eq!(rule.next_after(start, date(2025, Month::January, 2)), None);
+    }
+}
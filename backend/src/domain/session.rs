@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::OpaqueToken;
+
+/// An active session opened by a login, kept alive by refreshing its access
+/// token.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub session_id: Uuid,
+    pub user_id: i32,
+    /// The `User-Agent` of the browser that logged in
+    pub user_agent: Option<String>,
+    pub create_time: OffsetDateTime,
+    /// When the refresh token was last used, or the login
+    pub refresh_time: OffsetDateTime,
+    pub expire_time: OffsetDateTime,
+}
+
+/// How long a refresh token swapped out of its session still gets the token
+/// it was swapped for, so that refreshes racing with the same token all
+/// succeed. Using it later revokes the session.
+pub const REFRESH_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct NewSession {
+    pub user_id: i32,
+    pub user_agent: Option<String>,
+    pub refresh_token: RefreshToken,
+    /// Secret of the session the refresh tokens after the first one are
+    /// derived with, see `OpaqueToken::successor`
+    pub refresh_token_salt: OpaqueToken,
+    pub create_time: OffsetDateTime,
+    pub expire_time: OffsetDateTime,
+}
+
+/// The secret a client gets new access tokens of its session with.
+pub type RefreshToken = OpaqueToken;
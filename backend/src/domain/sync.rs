@@ -0,0 +1,243 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::domain::{Cursor, PageSize, RecurringTemplate, Todo, TodoItem};
+use crate::error::APIError;
+
+/// Opaque position in the change sequence. Every write to a todo, an item
+/// or a template takes the next value, a sync returns the rows whose last
+/// change is after the token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncToken(i64);
+
+impl SyncToken {
+    pub fn change_seq(&self) -> i64 {
+        self.0
+    }
+}
+
+impl TryFrom<String> for SyncToken {
+    type Error = APIError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Cursor::from(value)
+            .decode()
+            .ok()
+            .filter(|change_seq: &i64| *change_seq >= 0)
+            .map(Self)
+            .ok_or_else(|| APIError::BadRequest("Invalid sync token".to_string()))
+    }
+}
+
+impl From<SyncToken> for String {
+    fn from(value: SyncToken) -> Self {
+        Cursor::encode(&value.0).into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "sync_entity", rename_all = "snake_case")]
+pub enum SyncEntity {
+    Todo,
+    Item,
+    Template,
+}
+
+/// A todo, item or template that was moved to the trash or deleted for good.
+/// The items and templates of a deleted todo have no tombstone of their own.
+#[derive(Debug, Clone)]
+pub struct Tombstone {
+    pub change_seq: i64,
+    pub entity: SyncEntity,
+    pub entity_id: Uuid,
+    pub todo_id: Uuid,
+    pub delete_time: OffsetDateTime,
+}
+
+/// A row along with the position of its last change.
+#[derive(Debug, Clone)]
+pub struct Changed<T> {
+    pub change_seq: i64,
+    pub todo_id: Uuid,
+    pub value: T,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncRequest {
+    /// `None` for a first sync, which skips the tombstones.
+    pub since: Option<SyncToken>,
+    pub size: PageSize,
+}
+
+impl SyncRequest {
+    pub fn since_change_seq(&self) -> i64 {
+        self.since.unwrap_or_default().change_seq()
+    }
+
+    /// Number of rows to fetch of every kind, one more than the page size to
+    /// detect whether a next page exists.
+    pub fn fetch_limit(&self) -> i64 {
+        self.size.get() + 1
+    }
+}
+
+/// The changes of a user's todos after a token, in at most `size` rows. When
+/// `has_more` is set the client syncs again right away with `next_token`.
+#[derive(Debug, Clone)]
+pub struct Changes {
+    pub todos: Vec<Changed<Todo>>,
+    pub items: Vec<Changed<TodoItem>>,
+    pub templates: Vec<Changed<RecurringTemplate>>,
+    pub tombstones: Vec<Tombstone>,
+    pub next_token: SyncToken,
+    pub has_more: bool,
+}
+
+impl Changes {
+    /// Merges the rows fetched with `fetch_limit` from every table, each
+    /// sorted by `change_seq`, keeping the first `size` changes overall so
+    /// the next token never skips a row.
+    pub fn paginate(
+        req: &SyncRequest,
+        mut todos: Vec<Changed<Todo>>,
+        mut items: Vec<Changed<TodoItem>>,
+        mut templates: Vec<Changed<RecurringTemplate>>,
+        mut tombstones: Vec<Tombstone>,
+    ) -> Self {
+        let mut change_seqs: Vec<i64> = todos
+            .iter()
+            .map(|c| c.change_seq)
+            .chain(items.iter().map(|c| c.change_seq))
+            .chain(templates.iter().map(|c| c.change_seq))
+            .chain(tombstones.iter().map(|t| t.change_seq))
+            .collect();
+        change_seqs.sort_unstable();
+
+        // The page size is at least 1
+        let size = req.size.get() as usize;
+        let has_more = change_seqs.len() > size;
+        change_seqs.truncate(size);
+        let Some(&last) = change_seqs.last() else {
+            return Self {
+                todos,
+                items,
+                templates,
+                tombstones,
+                next_token: req.since.unwrap_or_default(),
+                has_more,
+            };
+        };
+        todos.retain(|c| c.change_seq <= last);
+        items.retain(|c| c.change_seq <= last);
+        templates.retain(|c| c.change_seq <= last);
+        tombstones.retain(|t| t.change_seq <= last);
+        Self {
+            todos,
+            items,
+            templates,
+            tombstones,
+            next_token: SyncToken(last),
+            has_more,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use time::{Date, Month, OffsetDateTime};
+    use uuid::Uuid;
+
+    use crate::domain::TodoItem;
+
+    use super::{Changed, Changes, SyncEntity, SyncRequest, SyncToken, Tombstone};
+
+    fn item(change_seq: i64) -> Changed<TodoItem> {
+        Changed {
+            change_seq,
+            todo_id: Uuid::new_v4(),
+            value: TodoItem {
+                todo_item_id: Uuid::new_v4(),
+                title: "banana".to_string(),
+                due_date: Date::from_calendar_date(2020, Month::October, 1).unwrap(),
+                is_complete: false,
+                complete_time: None,
+                recurring_template_id: None,
+                create_time: OffsetDateTime::UNIX_EPOCH,
+                update_time: OffsetDateTime::UNIX_EPOCH,
+            },
+        }
+    }
+
+    fn tombstone(change_seq: i64) -> Tombstone {
+        Tombstone {
+            change_seq,
+            entity: SyncEntity::Item,
+            entity_id: Uuid::new_v4(),
+            todo_id: Uuid::new_v4(),
+            delete_time: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    fn request(since: Option<i64>, size: i64) -> SyncRequest {
+        SyncRequest {
+            since: since.map(SyncToken),
+            size: Some(size).try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn sync_token_roundtrip() {
+        let token = SyncToken(42);
+        let decoded = SyncToken::try_from(String::from(token));
+        assert_eq!(decoded.unwrap(), token);
+    }
+
+    #[test]
+    fn invalid_sync_token_is_rejected() {
+        assert_ok!(SyncToken::try_from("MA".to_string()));
+        // Not base64, not a number, a negative number
+        for test_case in ["", "not base64!", "YmFuYW5h", "LTE"] {
+            assert_err!(SyncToken::try_from(test_case.to_string()));
+        }
+    }
+
+    #[test]
+    fn paginate_keeps_the_first_changes_of_every_kind() {
+        let changes = Changes::paginate(
+            &request(Some(1), 3),
+            vec![],
+            vec![item(3), item(4)],
+            vec![],
+            vec![tombstone(2), tombstone(5), tombstone(6)],
+        );
+        let items: Vec<i64> = changes.items.iter().map(|c| c.change_seq).collect();
+        let tombstones: Vec<i64> = changes.tombstones.iter().map(|t| t.change_seq).collect();
+        assert_eq!(items, vec![3, 4]);
+        assert_eq!(tombstones, vec![2]);
+        assert_eq!(changes.next_token, SyncToken(4));
+        assert!(changes.has_more);
+    }
+
+    #[test]
+    fn paginate_last_page() {
+        let changes = Changes::paginate(
+            &request(None, 3),
+            vec![],
+            vec![item(3)],
+            vec![],
+            vec![tombstone(8)],
+        );
+        assert_eq!(changes.items.len(), 1);
+        assert_eq!(changes.tombstones.len(), 1);
+        assert_eq!(changes.next_token, SyncToken(8));
+        assert!(!changes.has_more);
+    }
+
+    #[test]
+    fn paginate_without_changes_keeps_the_token() {
+        let changes = Changes::paginate(&request(Some(7), 3), vec![], vec![], vec![], vec![]);
+        assert_eq!(changes.next_token, SyncToken(7));
+        assert!(!changes.has_more);
+    }
+}
@@ -1,6 +1,7 @@
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::{Cursor, TodoRole};
 use crate::error::APIError;
 
 #[derive(Debug, Clone, sqlx::Type)]
@@ -14,19 +15,26 @@ pub enum TodoVisibility {
 pub struct NewTodoRequest {
     pub name: TodoName,
     pub visibility: TodoVisibility,
+    pub group_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone)]
 pub struct UpdateTodoRequest {
     pub name: TodoName,
     pub visibility: TodoVisibility,
+    pub group_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Todo {
     pub todo_id: Uuid,
+    pub owner_id: i32,
     pub name: TodoName,
     pub visibility: TodoVisibility,
+    /// The group owning the todo with its creator
+    pub group_id: Option<Uuid>,
+    /// The role of the user who asked for the todo
+    pub role: TodoRole,
     pub create_time: OffsetDateTime,
     pub update_time: OffsetDateTime,
 }
@@ -44,16 +52,33 @@ impl TryFrom<String> for TodoName {
         if s.is_empty() {
             return Err(APIError::BadRequest("Name cannot be empty".to_string()));
         }
+        // A uuid addresses the todo by `todo_id`, a name like it couldn't be
+        // reached
+        if s.parse::<Uuid>().is_ok() {
+            return Err(APIError::BadRequest("Name cannot be a uuid".to_string()));
+        }
         if s.len() > MAX_TODO_NAME_LENGTH {
             return Err(APIError::BadRequest(format!(
                 "Name is too long cannot exceed: {}",
                 MAX_TODO_NAME_LENGTH
             )));
         }
+        // Reserved for `owner_id:name` references
+        if s.contains(':') {
+            return Err(APIError::BadRequest("Name cannot contain ':'".to_string()));
+        }
         Ok(Self(s.to_string()))
     }
 }
 
+impl TodoName {
+    /// A name read back from the database. Rows written before a rule came
+    /// in may break it and stay readable, only new names are validated.
+    pub fn from_stored(name: String) -> Self {
+        Self(name)
+    }
+}
+
 impl TryFrom<&str> for TodoName {
     type Error = APIError;
 
@@ -75,6 +100,75 @@ impl From<TodoName> for String {
     }
 }
 
+/// Reference to a todo as it appears in the `/todo/{todo_id}` routes.
+///
+/// The stable `todo_id` uuid survives renames. Names are only unique per
+/// owner. A bare `name` resolves to the caller's own todo first and falls back
+/// to a todo of another user shared with them or public. `owner_id:name`
+/// addresses the todo of a specific owner, which is why names can't contain
+/// `:`.
+#[derive(Debug, Clone)]
+pub enum TodoRef {
+    Id(Uuid),
+    Name {
+        owner_id: Option<i32>,
+        name: TodoName,
+    },
+}
+
+impl TodoRef {
+    pub fn owned_by(owner_id: i32, name: TodoName) -> Self {
+        Self::Name {
+            owner_id: Some(owner_id),
+            name,
+        }
+    }
+}
+
+impl TryFrom<String> for TodoRef {
+    type Error = APIError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if let Ok(todo_id) = s.trim().parse::<Uuid>() {
+            return Ok(Self::Id(todo_id));
+        }
+        if let Some((owner, name)) = s.split_once(':') {
+            if let Ok(owner_id) = owner.parse::<i32>() {
+                return Ok(Self::owned_by(owner_id, name.try_into()?));
+            }
+        }
+        Ok(Self::Name {
+            owner_id: None,
+            name: s.try_into()?,
+        })
+    }
+}
+
+impl TryFrom<&str> for TodoRef {
+    type Error = APIError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let s = value.to_string();
+        s.try_into()
+    }
+}
+
+impl std::fmt::Display for TodoRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TodoRef::Id(todo_id) => write!(f, "{}", todo_id),
+            TodoRef::Name {
+                owner_id: Some(owner_id),
+                name,
+            } => write!(f, "{}:{}", owner_id, name.as_ref()),
+            TodoRef::Name {
+                owner_id: None,
+                name,
+            } => write!(f, "{}", name.as_ref()),
+        }
+    }
+}
+
 impl TryFrom<String> for TodoVisibility {
     type Error = APIError;
 
@@ -117,12 +211,19 @@ impl From<TodoVisibility> for String {
 #[derive(Debug, Clone)]
 pub struct ListTodo {
     pub items: Vec<ListTodoSingle>,
+    pub next_cursor: Option<Cursor>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ListTodoSingle {
+    pub todo_id: Uuid,
+    pub owner_id: i32,
     pub name: TodoName,
     pub visibility: TodoVisibility,
+    /// The group owning the todo with its creator
+    pub group_id: Option<Uuid>,
+    /// The role of the user who asked for the todo
+    pub role: TodoRole,
     pub create_time: OffsetDateTime,
     pub update_time: OffsetDateTime,
 }
@@ -133,7 +234,7 @@ mod tests {
 
     use crate::error::APIError;
 
-    use super::{TodoName, MAX_TODO_NAME_LENGTH};
+    use super::{TodoName, TodoRef, MAX_TODO_NAME_LENGTH};
     use claims::{assert_err, assert_ok};
 
     #[test]
@@ -159,6 +260,11 @@ mod tests {
                 "123456789012345678901",
                 format!("Name is too long cannot exceed: {}", MAX_TODO_NAME_LENGTH),
             ),
+            ("2024:taxes", "Name cannot contain ':'".to_string()),
+            (
+                "67e55044-10b1-426f-9247-bb680e5fe0c8",
+                "Name cannot be a uuid".to_string(),
+            ),
         ];
         for test_case in test_cases {
             let result: Result<TodoName, _> = test_case.0.try_into();
@@ -169,4 +275,44 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn todo_ref_is_ok() {
+        let test_cases = vec![
+            ("banana", None, "banana"),
+            ("12:banana", Some(12), "banana"),
+            ("12: banana ", Some(12), "banana"),
+        ];
+        for test_case in test_cases {
+            let result: Result<TodoRef, _> = test_case.0.try_into();
+            assert_ok!(&result);
+            match result.unwrap() {
+                TodoRef::Name { owner_id, name } => {
+                    assert_eq!(owner_id, test_case.1);
+                    assert_eq!(name.as_ref(), test_case.2);
+                }
+                x => panic!("Unexpected todo ref: {}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn todo_ref_with_uuid_is_ok() {
+        let todo_id = uuid::Uuid::new_v4();
+        let result: Result<TodoRef, _> = todo_id.to_string().try_into();
+        assert_ok!(&result);
+        match result.unwrap() {
+            TodoRef::Id(x) => assert_eq!(x, todo_id),
+            x => panic!("Unexpected todo ref: {}", x),
+        }
+    }
+
+    #[test]
+    fn todo_ref_is_not_ok() {
+        let test_cases = vec!["", "12:", "12:   ", "abc:banana", "12:3:banana"];
+        for test_case in test_cases {
+            let result: Result<TodoRef, _> = test_case.try_into();
+            assert_err!(&result);
+        }
+    }
 }
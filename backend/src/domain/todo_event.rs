@@ -0,0 +1,31 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "todo_event_entity", rename_all = "snake_case")]
+pub enum TodoEventEntity {
+    Item,
+    Template,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "todo_event_kind", rename_all = "snake_case")]
+pub enum TodoEventKind {
+    Created,
+    Updated,
+    Completed,
+    Deleted,
+}
+
+/// A change to an item or a template of a todo, recorded by the
+/// `todo_event_trigger`. Event ids only grow, per todo they are visible in
+/// the order they were written.
+#[derive(Debug, Clone)]
+pub struct TodoEvent {
+    pub event_id: i64,
+    pub todo_id: Uuid,
+    pub entity: TodoEventEntity,
+    pub entity_id: Uuid,
+    pub kind: TodoEventKind,
+    pub create_time: OffsetDateTime,
+}
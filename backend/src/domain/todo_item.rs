@@ -1,6 +1,9 @@
 use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
+use super::Cursor;
+use crate::error::APIError;
+
 #[derive(Debug, Clone)]
 pub struct NewTodoItemRequest {
     pub title: String,
@@ -15,6 +18,7 @@ pub struct TodoItem {
     pub due_date: Date,
     pub is_complete: bool,
     pub complete_time: Option<OffsetDateTime>,
+    pub recurring_template_id: Option<Uuid>,
     pub create_time: OffsetDateTime,
     pub update_time: OffsetDateTime,
 }
@@ -22,6 +26,7 @@ pub struct TodoItem {
 #[derive(Debug, Clone)]
 pub struct ListTodoItem {
     pub items: Vec<ListTodoItemSingle>,
+    pub next_cursor: Option<Cursor>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,3 +45,103 @@ pub struct UpdateTodoItemRequest {
     pub title: String,
     pub due_date: Date,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TodoItemStatus {
+    #[default]
+    Open,
+    Completed,
+    All,
+}
+
+/// Which items a listing returns. The completion range is inclusive of
+/// `completed_since` and exclusive of `completed_before`, and only applies to
+/// completed items.
+#[derive(Debug, Clone, Default)]
+pub struct TodoItemFilter {
+    status: TodoItemStatus,
+    completed_since: Option<OffsetDateTime>,
+    completed_before: Option<OffsetDateTime>,
+}
+
+impl TodoItemFilter {
+    pub fn new(
+        status: TodoItemStatus,
+        completed_since: Option<OffsetDateTime>,
+        completed_before: Option<OffsetDateTime>,
+    ) -> Result<Self, APIError> {
+        let has_range = completed_since.is_some() || completed_before.is_some();
+        if status == TodoItemStatus::Open && has_range {
+            return Err(APIError::BadRequest(
+                "completed_since and completed_before can't be used with status=open".to_string(),
+            ));
+        }
+        if let (Some(since), Some(before)) = (completed_since, completed_before) {
+            if since >= before {
+                return Err(APIError::BadRequest(
+                    "completed_since must be before completed_before".to_string(),
+                ));
+            }
+        }
+        Ok(Self {
+            status,
+            completed_since,
+            completed_before,
+        })
+    }
+
+    pub fn includes_open(&self) -> bool {
+        self.status != TodoItemStatus::Completed
+    }
+
+    pub fn includes_completed(&self) -> bool {
+        self.status != TodoItemStatus::Open
+    }
+
+    pub fn completed_since(&self) -> Option<OffsetDateTime> {
+        self.completed_since
+    }
+
+    pub fn completed_before(&self) -> Option<OffsetDateTime> {
+        self.completed_before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use time::{Duration, OffsetDateTime};
+
+    use super::{TodoItemFilter, TodoItemStatus};
+
+    #[test]
+    fn todo_item_filter_is_ok() {
+        let since = OffsetDateTime::UNIX_EPOCH;
+        let before = since + Duration::weeks(1);
+        let test_cases = vec![
+            (TodoItemStatus::Open, None, None),
+            (TodoItemStatus::Completed, None, None),
+            (TodoItemStatus::Completed, Some(since), Some(before)),
+            (TodoItemStatus::All, Some(since), None),
+            (TodoItemStatus::All, None, Some(before)),
+        ];
+        for (status, since, before) in test_cases {
+            assert_ok!(TodoItemFilter::new(status, since, before));
+        }
+    }
+
+    #[test]
+    fn todo_item_filter_is_not_ok() {
+        let since = OffsetDateTime::UNIX_EPOCH;
+        let before = since + Duration::weeks(1);
+        let test_cases = vec![
+            (TodoItemStatus::Open, Some(since), None),
+            (TodoItemStatus::Open, None, Some(before)),
+            (TodoItemStatus::Completed, Some(before), Some(since)),
+            (TodoItemStatus::All, Some(since), Some(since)),
+        ];
+        for (status, since, before) in test_cases {
+            assert_err!(TodoItemFilter::new(status, since, before));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use time::OffsetDateTime;
+
+use super::Cursor;
+
+/// What a user may do with a todo, each role can do everything the previous
+/// ones can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type)]
+#[sqlx(type_name = "todo_role", rename_all = "snake_case")]
+pub enum TodoRole {
+    /// Reads the todo, its items and its templates
+    Viewer,
+    /// Also creates, changes and deletes items and templates
+    Editor,
+    /// Also changes and deletes the todo and manages its members
+    Owner,
+}
+
+impl AsRef<str> for TodoRole {
+    fn as_ref(&self) -> &str {
+        match self {
+            TodoRole::Viewer => "viewer",
+            TodoRole::Editor => "editor",
+            TodoRole::Owner => "owner",
+        }
+    }
+}
+
+/// A user a todo is shared with. The creator of the todo is listed as an
+/// owner without being a member.
+#[derive(Debug, Clone)]
+pub struct TodoMember {
+    pub user_id: i32,
+    pub email: String,
+    pub role: TodoRole,
+    /// Whether this is the creator of the todo, who can't be changed or
+    /// removed
+    pub is_creator: bool,
+    pub create_time: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListTodoMember {
+    pub items: Vec<TodoMember>,
+    pub next_cursor: Option<Cursor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTodoMemberRequest {
+    pub email: String,
+    pub role: TodoRole,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TodoRole;
+
+    #[test]
+    fn roles_are_ordered() {
+        assert!(TodoRole::Viewer < TodoRole::Editor);
+        assert!(TodoRole::Editor < TodoRole::Owner);
+        assert_eq!(
+            [TodoRole::Owner, TodoRole::Viewer, TodoRole::Editor]
+                .into_iter()
+                .max(),
+            Some(TodoRole::Owner)
+        );
+    }
+}
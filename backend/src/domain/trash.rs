@@ -0,0 +1,31 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::domain::Cursor;
+
+/// What can be in the trash, stored as a `sync_entity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "sync_entity", rename_all = "snake_case")]
+pub enum TrashEntity {
+    Todo,
+    Item,
+}
+
+/// A todo, or an item of a todo that is not itself in the trash. Items of a
+/// trashed todo are restored along with it.
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    pub entity: TrashEntity,
+    /// The `todo_id` or `todo_item_id`
+    pub id: Uuid,
+    pub todo_id: Uuid,
+    /// The name of a todo or the title of an item
+    pub title: String,
+    pub deleted_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListTrash {
+    pub items: Vec<TrashEntry>,
+    pub next_cursor: Option<Cursor>,
+}
@@ -1,10 +1,86 @@
 use serde::{Deserialize, Serialize};
 use sqlx::types::time::OffsetDateTime;
 
+use crate::error::APIError;
+
+const MAX_TIME_ZONE_LENGTH: usize = 64;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub user_id: i32,
     pub email: String,
+    pub time_zone: String,
     pub create_time: OffsetDateTime,
     pub update_time: OffsetDateTime,
 }
+
+/// An IANA time zone name such as `America/New_York`. Only its shape is
+/// checked here, whether the zone exists is up to the database.
+#[derive(Debug, Clone)]
+pub struct TimeZoneName(String);
+
+impl TryFrom<String> for TimeZoneName {
+    type Error = APIError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(APIError::BadRequest("Time zone can't be empty".to_string()));
+        }
+        if value.len() > MAX_TIME_ZONE_LENGTH {
+            return Err(APIError::BadRequest(format!(
+                "Time zone can't be longer than {} characters",
+                MAX_TIME_ZONE_LENGTH
+            )));
+        }
+        if !value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'))
+        {
+            return Err(APIError::BadRequest(format!(
+                "Time zone: {} is not a valid IANA name",
+                value
+            )));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl AsRef<str> for TimeZoneName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::TimeZoneName;
+
+    #[test]
+    fn time_zone_name_is_ok() {
+        for name in [
+            "UTC",
+            "America/New_York",
+            "America/Argentina/Buenos_Aires",
+            "Etc/GMT+5",
+        ] {
+            assert_ok!(TimeZoneName::try_from(name.to_string()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn time_zone_name_is_not_ok() {
+        let too_long = "A".repeat(65);
+        for name in [
+            "",
+            "  ",
+            "America/New York",
+            "'; DROP TABLE users",
+            too_long.as_str(),
+        ] {
+            assert_err!(TimeZoneName::try_from(name.to_string()), "{}", name);
+        }
+    }
+}
@@ -1,6 +1,128 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
 use eyre::ErrReport;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::REQUEST_ID_HEADER;
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+// Bodies of error responses not produced by `APIError`, like the extractor
+// rejections, are plain text and short.
+const MAX_FALLBACK_DETAIL_BYTES: usize = 16 * 1024;
+
+/// An RFC 7807 problem details body. `code` is stable and meant for
+/// programs, `detail` is meant for humans and may change.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub title: String,
+    pub status: u16,
+    pub code: String,
+    pub detail: String,
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// A validation failure tied to a single field of the request.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        Self {
+            type_: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            code: code.to_string(),
+            detail: detail.into(),
+            request_id: None,
+            errors: vec![],
+        }
+    }
+
+    /// A problem for an error response that only has a status, the code is
+    /// derived from the status reason, e.g. `payload_too_large`.
+    pub fn from_status(status: StatusCode, detail: impl Into<String>) -> Self {
+        let code = status
+            .canonical_reason()
+            .unwrap_or("error")
+            .to_lowercase()
+            .replace([' ', '-'], "_");
+        Self::new(status, &code, detail)
+    }
+
+    pub fn internal() -> Self {
+        Self::from_status(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> axum::response::Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = serde_json::to_vec(&self).unwrap_or_default();
+        let mut response = (
+            status,
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+            )],
+            body,
+        )
+            .into_response();
+        // Kept so `problem_details` can add the request id
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Turns every error response into a problem details body tagged with the
+/// `x-request-id` of the request. Responses built from a `Problem` are
+/// re-rendered, other error responses (extractor rejections, timeouts...)
+/// are wrapped with their text as the detail.
+pub async fn problem_details(request: Request, next: Next) -> axum::response::Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut problem = match parts.extensions.remove::<Problem>() {
+        Some(problem) => problem,
+        None => {
+            let detail = match axum::body::to_bytes(body, MAX_FALLBACK_DETAIL_BYTES).await {
+                Ok(bytes) if !bytes.is_empty() => String::from_utf8_lossy(&bytes).into_owned(),
+                _ => status.canonical_reason().unwrap_or("Error").to_string(),
+            };
+            Problem::from_status(status, detail)
+        }
+    };
+    problem.request_id = request_id;
+
+    let mut response = problem.into_response();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    response.headers_mut().extend(parts.headers);
+    response
+}
 
 #[derive(Debug, Error)]
 #[error(transparent)]
@@ -9,11 +131,7 @@ pub struct InternalError(#[from] eyre::Report);
 impl IntoResponse for InternalError {
     fn into_response(self) -> axum::response::Response {
         tracing::error!("Internal server error: {}: {}", self.0, self.0.root_cause());
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Internal server error".to_string(),
-        )
-            .into_response()
+        Problem::internal().into_response()
     }
 }
 
@@ -21,10 +139,20 @@ impl IntoResponse for InternalError {
 pub enum APIError {
     #[error("bad request: {0}")]
     BadRequest(String),
+    #[error("validation error: {0:?}")]
+    Validation(Vec<FieldError>),
     #[error("already exists error: {0}")]
     AlreadyExists(String),
     #[error("not found error: {0}")]
     NotFound(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("precondition failed: {0}")]
+    PreconditionFailed(String),
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("forbidden: {0}")]
+    Forbidden(String),
     #[error("internal server error: {0}")]
     Internal(#[from] InternalError),
 }
@@ -32,11 +160,47 @@ pub enum APIError {
 impl IntoResponse for APIError {
     fn into_response(self) -> axum::response::Response {
         match self {
-            APIError::BadRequest(x) => (StatusCode::BAD_REQUEST, x).into_response(),
-            APIError::AlreadyExists(x) => (StatusCode::BAD_REQUEST, x).into_response(),
-            APIError::NotFound(x) => (StatusCode::NOT_FOUND, x).into_response(),
-            APIError::Internal(x) => x.into_response(),
+            APIError::BadRequest(x) => Problem::new(StatusCode::BAD_REQUEST, "bad_request", x),
+            APIError::Validation(errors) => {
+                let detail = errors
+                    .iter()
+                    .map(|e| format!("{}: {}", e.field, e.message))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                Problem {
+                    errors,
+                    ..Problem::new(StatusCode::BAD_REQUEST, "validation_failed", detail)
+                }
+            }
+            APIError::AlreadyExists(x) => Problem::new(StatusCode::CONFLICT, "already_exists", x),
+            APIError::NotFound(x) => Problem::new(StatusCode::NOT_FOUND, "not_found", x),
+            APIError::Conflict(x) => Problem::new(StatusCode::CONFLICT, "conflict", x),
+            APIError::PreconditionFailed(x) => {
+                Problem::new(StatusCode::PRECONDITION_FAILED, "precondition_failed", x)
+            }
+            APIError::Unauthorized(x) => Problem::new(StatusCode::UNAUTHORIZED, "unauthorized", x),
+            APIError::Forbidden(x) => Problem::new(StatusCode::FORBIDDEN, "forbidden", x),
+            APIError::Internal(x) => return x.into_response(),
         }
+        .into_response()
+    }
+}
+
+/// Attaches the offending request field to a validation failure, turning a
+/// `BadRequest` into a field-level `Validation` error.
+pub trait FieldContext<T> {
+    fn field(self, field: &str) -> Result<T, APIError>;
+}
+
+impl<T> FieldContext<T> for Result<T, APIError> {
+    fn field(self, field: &str) -> Result<T, APIError> {
+        self.map_err(|e| match e {
+            APIError::BadRequest(message) => APIError::Validation(vec![FieldError {
+                field: field.to_string(),
+                message,
+            }]),
+            e => e,
+        })
     }
 }
 
@@ -46,9 +210,28 @@ impl From<sqlx::Error> for InternalError {
     }
 }
 
+/// Unique violations become `AlreadyExists`, and transactions that lost a
+/// race against another one become `Conflict`, everything else is internal.
 impl From<sqlx::Error> for APIError {
     fn from(value: sqlx::Error) -> Self {
-        APIError::Internal(value.into())
+        let sqlx::Error::Database(e) = &value else {
+            return APIError::Internal(value.into());
+        };
+        if e.is_unique_violation() {
+            let detail = match e.constraint() {
+                Some("todo_user_id_name_key") => "A todo with this name already exists",
+                Some("users_email_key") => "A user with this email already exists",
+                _ => "The resource already exists",
+            };
+            return APIError::AlreadyExists(detail.to_string());
+        }
+        match e.code().as_deref() {
+            // serialization_failure, deadlock_detected
+            Some("40001") | Some("40P01") => APIError::Conflict(
+                "The resource was modified concurrently, retry the request".to_string(),
+            ),
+            _ => APIError::Internal(value.into()),
+        }
     }
 }
 
@@ -1,10 +1,19 @@
 use axum::{
     extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    http::{header, request::Parts, HeaderName},
 };
+use eyre::Context;
+
+use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::{
-    configuration::RecurringSettings, error::InternalError, AppState,
+    clock::Clock,
+    configuration::{RecurringSettings, TrashSettings},
+    domain::{AuditActor, ETagCondition},
+    error::{APIError, FieldContext, InternalError},
+    services::TodoEvents,
+    AppState,
 };
 
 pub struct AppRecurringSettings(pub RecurringSettings);
@@ -20,13 +29,128 @@ impl FromRequestParts<AppState> for AppRecurringSettings {
     }
 }
 
+pub struct AppTrashSettings(pub TrashSettings);
+
+impl FromRequestParts<AppState> for AppTrashSettings {
+    type Rejection = InternalError;
+
+    async fn from_request_parts(
+        _parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(state.trash_settings.clone()))
+    }
+}
+
+pub struct AppClock(pub Arc<dyn Clock>);
+
+impl FromRequestParts<AppState> for AppClock {
+    type Rejection = InternalError;
+
+    async fn from_request_parts(
+        _parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(state.clock.clone()))
+    }
+}
+
+pub struct AppTodoEvents(pub TodoEvents);
+
+impl FromRequestParts<AppState> for AppTodoEvents {
+    type Rejection = InternalError;
+
+    async fn from_request_parts(
+        _parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(state.todo_events.clone()))
+    }
+}
+
+/// The id of the last event an event stream client received, sent when it
+/// reconnects.
+#[derive(Debug)]
+pub struct LastEventId(pub Option<i64>);
+
+impl<S: Send + Sync> FromRequestParts<S> for LastEventId {
+    type Rejection = APIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get("last-event-id") else {
+            return Ok(Self(None));
+        };
+        let event_id = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|event_id| *event_id >= 0)
+            .ok_or_else(|| APIError::BadRequest("Invalid event id".to_string()))
+            .field("Last-Event-ID")?;
+        Ok(Self(Some(event_id)))
+    }
+}
+
+fn etag_condition(parts: &Parts, name: HeaderName) -> Result<Option<ETagCondition>, APIError> {
+    parts
+        .headers
+        .get(&name)
+        .map(|value| {
+            value
+                .to_str()
+                .map(ETagCondition::parse)
+                .map_err(|_| APIError::BadRequest(format!("Invalid {} header", name)))
+        })
+        .transpose()
+}
+
+/// The `If-Match` precondition of a PUT or DELETE, if any.
+#[derive(Debug)]
+pub struct IfMatch(pub Option<ETagCondition>);
+
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = APIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(etag_condition(parts, header::IF_MATCH)?))
+    }
+}
+
+/// The `If-None-Match` condition of a GET, if any.
+#[derive(Debug)]
+pub struct IfNoneMatch(pub Option<ETagCondition>);
+
+impl<S: Send + Sync> FromRequestParts<S> for IfNoneMatch {
+    type Rejection = APIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(etag_condition(parts, header::IF_NONE_MATCH)?))
+    }
+}
+
 #[derive(Debug)]
 pub struct AuthenticatedUser {
     pub user_id: i32,
+    /// The session the bearer token was issued for, always set when users
+    /// log in through the API
+    pub session_id: Option<Uuid>,
+    /// The `x-request-id` of the request, set by the request id layer when
+    /// the client didn't send one
+    pub request_id: Option<String>,
+}
+
+impl AuthenticatedUser {
+    /// The user and the request the audit log attributes changes to.
+    pub fn audit_actor(&self) -> AuditActor {
+        AuditActor {
+            user_id: self.user_id,
+            request_id: self.request_id.clone(),
+        }
+    }
 }
 
 impl FromRequestParts<AppState> for AuthenticatedUser {
-    type Rejection = StatusCode;
+    type Rejection = APIError;
 
     async fn from_request_parts(
         parts: &mut Parts,
@@ -37,22 +161,57 @@ impl FromRequestParts<AppState> for AuthenticatedUser {
             .get("Authorization")
             .and_then(|header| header.to_str().ok())
             .and_then(|header| header.strip_prefix("Bearer "))
-            .ok_or(StatusCode::UNAUTHORIZED)?;
+            .ok_or_else(|| APIError::Unauthorized("Missing bearer token".to_string()))?;
 
+        let now = state.clock.now();
         let claims = state
             .jwt_service
-            .validate_token(auth_header)
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+            .validate_token(auth_header, now)
+            .map_err(|_| APIError::Unauthorized("Invalid bearer token".to_string()))?;
 
-        let mut transaction = state.tx_state.transaction().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut transaction = state
+            .tx_state
+            .transaction()
+            .await
+            .context("Failed to start the authentication transaction")?;
         let user_exists = crate::repos::find_by_email(&mut transaction, &claims.email)
             .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        match user_exists {
-            Some(user) => Ok(AuthenticatedUser {
-                user_id: user.user_id,
-            }),
-            None => Err(StatusCode::FORBIDDEN),
+            .context("Failed to find the authenticated user")?;
+        let Some(user) = user_exists else {
+            return Err(APIError::Forbidden("Unknown user".to_string()));
+        };
+        match &claims.sid {
+            Some(session_id) => {
+                let active = crate::repos::is_session_active(
+                    &mut transaction,
+                    session_id,
+                    user.user_id,
+                    now,
+                )
+                .await?;
+                if !active {
+                    return Err(APIError::Unauthorized(
+                        "Session expired or revoked".to_string(),
+                    ));
+                }
+            }
+            // Where users log in through the API every token it issues has a
+            // session, so that revoking the session revokes all of them
+            None if state.auth.session().is_some() => {
+                return Err(APIError::Unauthorized(
+                    "Bearer token without a session".to_string(),
+                ));
+            }
+            None => {}
         }
+        Ok(AuthenticatedUser {
+            user_id: user.user_id,
+            session_id: claims.sid,
+            request_id: parts
+                .headers
+                .get(crate::REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+        })
     }
 }
@@ -1,34 +1,34 @@
 use axum::{
     error_handling::HandleErrorLayer,
     extract::DefaultBodyLimit,
-    http::{HeaderName, Request, StatusCode},
-    routing::{delete, get, post, put},
+    http::{header, HeaderName, Request, StatusCode},
+    middleware,
+    routing::get,
     serve::Serve,
     BoxError, Router,
 };
 use eyre::Result;
 use sqlx::postgres::Postgres;
 use sqlx::Pool;
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::configuration::RecurringSettings;
+use crate::clock::Clock;
+use crate::configuration::{RecurringSettings, TrashSettings};
 
 use axum::http::Method;
-use routes::{
-    complete_todo_item, create_recurring_template_handler, create_todo, create_todo_item,
-    delete_recurring_template_handler, delete_todo, delete_todo_item,
-    get_recurring_template_handler, get_todo, get_todo_item, google_callback, google_login,
-    health_check, list_recurring_templates_handler, list_todo, list_todo_items,
-    update_recurring_template_handler, update_todo, update_todo_item,
-};
+use routes::{openapi_json, ApiDoc, ProblemResponses};
 use tower::timeout::TimeoutLayer;
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
 use tracing::{error, info_span};
+use utoipa::{Modify, OpenApi};
+use utoipa_axum::{router::OpenApiRouter, routes};
 
 pub mod auth;
+pub mod clock;
 pub mod configuration;
 mod domain;
 mod error;
@@ -48,8 +48,13 @@ const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 pub struct AppState {
     pub tx_state: tx::state::State,
     pub recurring_settings: RecurringSettings,
+    pub trash_settings: TrashSettings,
     pub auth: configuration::AuthSettings,
     pub jwt_service: auth::JwtService,
+    pub clock: Arc<dyn Clock>,
+    pub openapi: Arc<utoipa::openapi::OpenApi>,
+    pub todo_events: services::TodoEvents,
+    pub oidc_providers: services::OidcProviders,
 }
 
 pub type Server = Serve<tokio::net::TcpListener, Router, Router>;
@@ -58,9 +63,18 @@ pub async fn run(
     listener: tokio::net::TcpListener,
     pg_pool: Pool<Postgres>,
     recurring_settings: RecurringSettings,
+    trash_settings: TrashSettings,
     auth: configuration::AuthSettings,
     jwt_service: auth::JwtService,
+    clock: Arc<dyn Clock>,
 ) -> Result<Server> {
+    let todo_events = services::TodoEvents::listen(pg_pool.clone());
+    let oidc_providers = match &auth {
+        configuration::AuthSettings::Oidc { providers, .. } => {
+            services::OidcProviders::new(providers)
+        }
+        _ => services::OidcProviders::default(),
+    };
     let (tx_state, tx_layer) = tx::setup(pg_pool);
     let x_request_id = HeaderName::from_static(REQUEST_ID_HEADER);
     let request_middleware = ServiceBuilder::new()
@@ -86,6 +100,7 @@ pub async fn run(
             }),
         )
         .layer(PropagateRequestIdLayer::new(x_request_id))
+        .layer(middleware::from_fn(error::problem_details))
         .layer(HandleErrorLayer::new(|error: BoxError| async move {
             if error.is::<tower::timeout::error::Elapsed>() {
                 StatusCode::REQUEST_TIMEOUT
@@ -100,64 +115,121 @@ pub async fn run(
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         // allow requests from any origin
         .allow_origin(Any)
-        .allow_headers(Any);
-    let mut app = Router::new().route("/health_check", get(health_check));
+        .allow_headers(Any)
+        // let clients read the version of what they fetched
+        .expose_headers([header::ETAG]);
+    // Paths and methods come from the `#[utoipa::path]` of each handler,
+    // which also documents them in the OpenAPI document.
+    let mut app =
+        OpenApiRouter::with_openapi(ApiDoc::openapi()).routes(routes!(routes::health_check));
 
     // Conditionally add authentication routes based on auth type
     match &auth {
         configuration::AuthSettings::GoogleOAuth { .. } => {
             app = app
-                .route("/auth/google", get(google_login))
-                .route("/auth/google/callback", get(google_callback));
+                .routes(routes!(routes::google_login))
+                .routes(routes!(routes::google_callback));
+        }
+        configuration::AuthSettings::Oidc { .. } => {
+            app = app
+                .routes(routes!(routes::oidc_login))
+                .routes(routes!(routes::oidc_callback));
         }
         configuration::AuthSettings::Jwt { .. } => {
             // JWT auth doesn't need special login routes - tokens are handled via the extractor
         }
     }
+    // Other services verify the tokens with the published keys
+    if jwt_service.publishes_keys() {
+        app = app.routes(routes!(routes::jwks));
+    }
+    // Logins open sessions, managed the same way whatever the provider
+    if auth.session().is_some() {
+        app = app
+            .routes(routes!(routes::exchange_login_code))
+            .routes(routes!(routes::refresh_session))
+            .routes(routes!(routes::logout))
+            .routes(routes!(routes::list_sessions))
+            .routes(routes!(routes::revoke_session));
+    }
 
-    let app = app
-        .route("/todo", post(create_todo))
-        .route("/todo", get(list_todo))
-        .route("/todo/{todo_id}", delete(delete_todo))
-        .route("/todo/{todo_id}", get(get_todo))
-        .route("/todo/{todo_id}", put(update_todo))
-        .route("/todo/{todo_id}/item", post(create_todo_item))
-        .route("/todo/{todo_id}/item", get(list_todo_items))
-        .route("/todo/{todo_id}/item/{item_id}", get(get_todo_item))
-        .route("/todo/{todo_id}/item/{item_id}", put(update_todo_item))
-        .route("/todo/{todo_id}/item/{item_id}", delete(delete_todo_item))
-        .route(
-            "/todo/{todo_id}/item/{item_id}/complete",
-            post(complete_todo_item),
-        )
-        .route(
-            "/todo/{todo_id}/recurring",
-            post(create_recurring_template_handler),
-        )
-        .route(
-            "/todo/{todo_id}/recurring",
-            get(list_recurring_templates_handler),
-        )
-        .route(
-            "/todo/{todo_id}/recurring/{template_id}",
-            get(get_recurring_template_handler),
-        )
-        .route(
-            "/todo/{todo_id}/recurring/{template_id}",
-            put(update_recurring_template_handler),
-        )
-        .route(
-            "/todo/{todo_id}/recurring/{template_id}",
-            delete(delete_recurring_template_handler),
-        )
+    let (app, mut openapi) = app
+        .routes(routes!(
+            routes::get_current_user,
+            routes::update_current_user
+        ))
+        .routes(routes!(routes::create_group, routes::list_groups))
+        .routes(routes!(
+            routes::get_group,
+            routes::update_group,
+            routes::delete_group
+        ))
+        .routes(routes!(
+            routes::list_group_members,
+            routes::add_group_member
+        ))
+        .routes(routes!(
+            routes::update_group_member,
+            routes::remove_group_member
+        ))
+        .routes(routes!(routes::create_todo, routes::list_todo))
+        .routes(routes!(
+            routes::delete_todo,
+            routes::get_todo,
+            routes::update_todo
+        ))
+        .routes(routes!(routes::todo_events))
+        .routes(routes!(routes::todo_activity))
+        .routes(routes!(routes::list_todo_members, routes::add_todo_member))
+        .routes(routes!(
+            routes::update_todo_member,
+            routes::remove_todo_member
+        ))
+        .routes(routes!(routes::create_todo_item, routes::list_todo_items))
+        .routes(routes!(routes::batch_todo_items))
+        .routes(routes!(
+            routes::get_todo_item,
+            routes::update_todo_item,
+            routes::delete_todo_item
+        ))
+        .routes(routes!(routes::complete_todo_item))
+        .routes(routes!(routes::reopen_todo_item))
+        .routes(routes!(
+            routes::create_recurring_template_handler,
+            routes::list_recurring_templates_handler
+        ))
+        .routes(routes!(
+            routes::get_recurring_template_handler,
+            routes::update_recurring_template_handler,
+            routes::delete_recurring_template_handler
+        ))
+        .routes(routes!(routes::sync))
+        .routes(routes!(routes::list_trash))
+        .routes(routes!(routes::restore_from_trash))
+        .split_for_parts();
+    ProblemResponses.modify(&mut openapi);
+
+    let app = app.route("/openapi.json", get(openapi_json));
+    #[cfg(feature = "api-viewer")]
+    let app = {
+        use utoipa_scalar::{Scalar, Servable};
+        app.merge(Scalar::with_url("/docs", openapi.clone()))
+    };
+
+    let app: Router = app
         .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
         .layer(request_middleware)
         .layer(cors)
         .with_state(AppState {
             tx_state,
             recurring_settings,
+            trash_settings,
             auth,
             jwt_service,
+            clock,
+            openapi: Arc::new(openapi),
+            todo_events,
+            oidc_providers,
         });
     Ok(axum::serve(listener, app))
 }
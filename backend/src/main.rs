@@ -31,6 +31,10 @@ fn log_startup_configuration(configuration: &Settings) {
         recurring_look_ahead = ?configuration.recurring.look_ahead_duration,
         "Recurring templates configuration loaded"
     );
+    info!(
+        trash_retention = ?configuration.trash.retention,
+        "Trash configuration loaded"
+    );
 
     match &configuration.auth {
         AuthSettings::Jwt {
@@ -61,6 +65,26 @@ fn log_startup_configuration(configuration: &Settings) {
                 "Google OAuth configuration loaded"
             );
         }
+        AuthSettings::Oidc {
+            jwt_expiration_hours,
+            providers,
+            ..
+        } => {
+            info!(
+                auth_type = "oidc",
+                jwt_expiration_hours, "Auth configuration loaded"
+            );
+            for provider in providers {
+                info!(
+                    name = %provider.name,
+                    issuer_url = %provider.issuer_url,
+                    client_id = %provider.client_id,
+                    redirect_uri = %provider.redirect_uri,
+                    frontend_callback_url = %provider.frontend_callback_url,
+                    "OpenID Connect provider configuration loaded"
+                );
+            }
+        }
     }
 }
 
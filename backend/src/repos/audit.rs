@@ -0,0 +1,54 @@
+use sqlx::PgTransaction;
+use uuid::Uuid;
+
+use crate::{
+    domain::{AuditAction, AuditActor, AuditEntity, AuditEntry, ListActivity, PageRequest},
+    error::APIError,
+};
+
+/// Attributes the writes of the rest of the transaction to the actor, the
+/// `audit_trigger` reads it back from the transaction settings.
+#[tracing::instrument(name = "Set audit actor", skip(transaction))]
+pub async fn set_audit_actor(
+    transaction: &mut PgTransaction<'_>,
+    actor: &AuditActor,
+) -> Result<(), APIError> {
+    sqlx::query!(
+        r#"SELECT set_config('audit.actor_id', $1, true) as actor_id,
+                  set_config('audit.request_id', $2, true) as request_id;"#,
+        actor.user_id.to_string(),
+        actor.request_id.as_deref().unwrap_or_default(),
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// The audit rows of the todo, its items, its templates and its members,
+/// latest first.
+#[tracing::instrument(name = "List activity in the database", skip(transaction))]
+pub async fn list_activity(
+    transaction: &mut PgTransaction<'_>,
+    todo_id: &Uuid,
+    page: &PageRequest,
+) -> Result<ListActivity, APIError> {
+    let cursor: Option<i64> = page.decode_cursor()?;
+    let result = sqlx::query_as!(
+        AuditEntry,
+        r#"SELECT a.audit_id, a.todo_id as "todo_id!", a.actor_id, u.email as "actor_email?", a.request_id,
+                  a.entity as "entity: AuditEntity", a.entity_id, a.action as "action: AuditAction",
+                  a.before, a.after, a.create_time
+           FROM audit_log as a
+           LEFT JOIN users as u ON u.user_id = a.actor_id
+           WHERE a.todo_id = $1 AND ($2::bigint IS NULL OR a.audit_id < $2)
+           ORDER BY a.audit_id DESC
+           LIMIT $3;"#,
+        todo_id,
+        cursor,
+        page.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let (items, next_cursor) = page.paginate(result, |row| row.audit_id);
+    Ok(ListActivity { items, next_cursor })
+}
@@ -0,0 +1,296 @@
+use sqlx::PgTransaction;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        Group, GroupMember, GroupName, ListGroup, ListGroupMember, NewGroupMemberRequest,
+        PageRequest, TodoRole,
+    },
+    error::APIError,
+};
+
+use super::find_by_email;
+
+#[derive(Debug)]
+struct GroupQuery {
+    group_id: Uuid,
+    name: String,
+    role: TodoRole,
+    create_time: sqlx::types::time::OffsetDateTime,
+    update_time: sqlx::types::time::OffsetDateTime,
+}
+
+impl TryFrom<GroupQuery> for Group {
+    type Error = APIError;
+    fn try_from(value: GroupQuery) -> Result<Self, Self::Error> {
+        Ok(Self {
+            group_id: value.group_id,
+            name: value.name.try_into()?,
+            role: value.role,
+            create_time: value.create_time,
+            update_time: value.update_time,
+        })
+    }
+}
+
+/// Creates a group owned by the user.
+#[tracing::instrument(name = "Create group in the database", skip(transaction))]
+pub async fn create_group(
+    transaction: &mut PgTransaction<'_>,
+    name: &GroupName,
+    user_id: i32,
+) -> Result<Group, APIError> {
+    let group = sqlx::query_as!(
+        GroupQuery,
+        r#"INSERT INTO user_group (group_id, name) VALUES ($1, $2)
+           RETURNING group_id, name, 'owner'::todo_role as "role!: TodoRole",
+                     create_time, update_time;"#,
+        Uuid::new_v4(),
+        name.as_ref(),
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO group_member (group_id, user_id, role) VALUES ($1, $2, 'owner');"#,
+        group.group_id,
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    group.try_into()
+}
+
+/// The groups the user is a member of, by id.
+#[tracing::instrument(name = "List groups in the database", skip(transaction))]
+pub async fn list_groups(
+    transaction: &mut PgTransaction<'_>,
+    page: &PageRequest,
+    user_id: i32,
+) -> Result<ListGroup, APIError> {
+    let cursor: Option<Uuid> = page.decode_cursor()?;
+    let result = sqlx::query_as!(
+        GroupQuery,
+        r#"SELECT g.group_id, g.name, m.role as "role: TodoRole", g.create_time, g.update_time
+           FROM user_group as g
+           INNER JOIN group_member as m ON m.group_id = g.group_id
+           WHERE m.user_id = $1
+             AND ($2::uuid IS NULL OR g.group_id > $2)
+           ORDER BY g.group_id
+           LIMIT $3;"#,
+        user_id,
+        cursor,
+        page.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let (result, next_cursor) = page.paginate(result, |row| row.group_id);
+    let items: Result<Vec<Group>, APIError> = result.into_iter().map(|g| g.try_into()).collect();
+    Ok(ListGroup {
+        items: items?,
+        next_cursor,
+    })
+}
+
+/// Resolves a group the user is a member of and checks they have at least
+/// the `required` role on it.
+#[tracing::instrument(name = "Get group in the database", skip(transaction))]
+pub async fn get_group(
+    transaction: &mut PgTransaction<'_>,
+    group_id: &Uuid,
+    user_id: i32,
+    required: TodoRole,
+) -> Result<Group, APIError> {
+    let group: Group = sqlx::query_as!(
+        GroupQuery,
+        r#"SELECT g.group_id, g.name, m.role as "role: TodoRole", g.create_time, g.update_time
+           FROM user_group as g
+           INNER JOIN group_member as m ON m.group_id = g.group_id
+           WHERE g.group_id = $1 AND m.user_id = $2;"#,
+        group_id,
+        user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| APIError::NotFound(format!("group: {} is not found", group_id)))?
+    .try_into()?;
+    if group.role < required {
+        return Err(APIError::Forbidden(format!(
+            "group: {} needs the {} role",
+            group_id,
+            required.as_ref()
+        )));
+    }
+    Ok(group)
+}
+
+#[tracing::instrument(name = "Rename group in the database", skip(transaction))]
+pub async fn rename_group(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+    name: &GroupName,
+) -> Result<Group, APIError> {
+    let row = sqlx::query!(
+        r#"UPDATE user_group SET name = $2 WHERE group_id = $1
+           RETURNING update_time;"#,
+        group.group_id,
+        name.as_ref(),
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(Group {
+        name: name.clone(),
+        update_time: row.update_time,
+        ..group.clone()
+    })
+}
+
+/// Deletes the group, its todos go back to the users who created them.
+#[tracing::instrument(name = "Delete group in the database", skip(transaction))]
+pub async fn delete_group(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+) -> Result<(), APIError> {
+    sqlx::query!(
+        r#"DELETE FROM user_group WHERE group_id = $1;"#,
+        group.group_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "List group members in the database", skip(transaction))]
+pub async fn list_group_members(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+    page: &PageRequest,
+) -> Result<ListGroupMember, APIError> {
+    let cursor: Option<i32> = page.decode_cursor()?;
+    let result = sqlx::query_as!(
+        GroupMember,
+        r#"SELECT m.user_id, u.email, m.role as "role: TodoRole", m.create_time
+           FROM group_member as m
+           INNER JOIN users as u ON u.user_id = m.user_id
+           WHERE m.group_id = $1
+             AND ($2::integer IS NULL OR m.user_id > $2)
+           ORDER BY m.user_id
+           LIMIT $3;"#,
+        group.group_id,
+        cursor,
+        page.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let (items, next_cursor) = page.paginate(result, |row| row.user_id);
+    Ok(ListGroupMember { items, next_cursor })
+}
+
+/// Adds the user registered with the email to the group.
+#[tracing::instrument(name = "Add group member in the database", skip(transaction))]
+pub async fn add_group_member(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+    req: &NewGroupMemberRequest,
+) -> Result<GroupMember, APIError> {
+    let user = find_by_email(transaction, &req.email)
+        .await?
+        .ok_or_else(|| APIError::NotFound(format!("user: {} is not found", req.email)))?;
+    match sqlx::query!(
+        r#"INSERT INTO group_member (group_id, user_id, role) VALUES ($1, $2, $3)
+           RETURNING create_time;"#,
+        group.group_id,
+        user.user_id,
+        req.role as _,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    {
+        Ok(row) => Ok(GroupMember {
+            user_id: user.user_id,
+            email: user.email,
+            role: req.role,
+            create_time: row.create_time,
+        }),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Err(APIError::AlreadyExists(format!(
+                "user: {} is already a member of group: {}",
+                req.email, group.group_id
+            )))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// A group can't be left without an owner to manage it, checked after its
+/// members changed.
+async fn check_group_has_owner(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+) -> Result<(), APIError> {
+    let has_owner = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+               SELECT 1 FROM group_member WHERE group_id = $1 AND role = 'owner'
+           ) as "has_owner!";"#,
+        group.group_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    if !has_owner {
+        return Err(APIError::Conflict(format!(
+            "group: {} needs another owner first",
+            group.group_id
+        )));
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Update group member in the database", skip(transaction))]
+pub async fn update_group_member(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+    member_id: i32,
+    role: TodoRole,
+) -> Result<GroupMember, APIError> {
+    let member = sqlx::query_as!(
+        GroupMember,
+        r#"UPDATE group_member as m SET role = $3
+           FROM users as u
+           WHERE u.user_id = m.user_id AND m.group_id = $1 AND m.user_id = $2
+           RETURNING m.user_id, u.email, m.role as "role: TodoRole", m.create_time;"#,
+        group.group_id,
+        member_id,
+        role as _,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| {
+        APIError::NotFound(format!(
+            "user: {} is not a member of group: {}",
+            member_id, group.group_id
+        ))
+    })?;
+    check_group_has_owner(transaction, group).await?;
+    Ok(member)
+}
+
+#[tracing::instrument(name = "Remove group member in the database", skip(transaction))]
+pub async fn remove_group_member(
+    transaction: &mut PgTransaction<'_>,
+    group: &Group,
+    member_id: i32,
+) -> Result<(), APIError> {
+    let r = sqlx::query!(
+        r#"DELETE FROM group_member WHERE group_id = $1 AND user_id = $2;"#,
+        group.group_id,
+        member_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    if r.rows_affected() == 0 {
+        return Err(APIError::NotFound(format!(
+            "user: {} is not a member of group: {}",
+            member_id, group.group_id
+        )));
+    }
+    check_group_has_owner(transaction, group).await
+}
@@ -0,0 +1,50 @@
+use sqlx::PgTransaction;
+
+use crate::{
+    domain::{CompletedLogin, LoginCode, LOGIN_CODE_TTL},
+    error::APIError,
+};
+
+/// Records a login completed now, forgetting the codes that can no longer be
+/// exchanged.
+#[tracing::instrument(
+    name = "Create login code in the database",
+    skip(transaction, code, login)
+)]
+pub async fn create_login_code(
+    transaction: &mut PgTransaction<'_>,
+    code: &LoginCode,
+    login: &CompletedLogin,
+) -> Result<(), APIError> {
+    sqlx::query!(
+        r#"DELETE FROM login_code WHERE create_time < $1;"#,
+        login.create_time - LOGIN_CODE_TTL,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO login_code (code_hash, user_id, create_time) VALUES ($1, $2, $3);"#,
+        code.hash(),
+        login.user_id,
+        login.create_time,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Removes the login with the code so it can only be exchanged once.
+#[tracing::instrument(name = "Take login code from the database", skip(transaction, code))]
+pub async fn take_login_code(
+    transaction: &mut PgTransaction<'_>,
+    code: &LoginCode,
+) -> Result<Option<CompletedLogin>, APIError> {
+    let login = sqlx::query_as!(
+        CompletedLogin,
+        r#"DELETE FROM login_code WHERE code_hash = $1 RETURNING user_id, create_time;"#,
+        code.hash(),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(login)
+}
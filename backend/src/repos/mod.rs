@@ -1,11 +1,29 @@
+mod audit;
+mod group;
 mod health_check;
+mod login_code;
+mod oauth_login;
 mod recurring_template;
+mod session;
+mod sync;
 mod todo;
+mod todo_event;
 mod todo_item;
+mod todo_member;
+mod trash;
 mod user;
 
+pub use audit::*;
+pub use group::*;
 pub use health_check::*;
+pub use login_code::*;
+pub use oauth_login::*;
 pub use recurring_template::*;
+pub use session::*;
+pub use sync::*;
 pub use todo::*;
+pub use todo_event::*;
 pub use todo_item::*;
+pub use todo_member::*;
+pub use trash::*;
 pub use user::*;
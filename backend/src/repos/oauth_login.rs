@@ -0,0 +1,50 @@
+use sqlx::PgTransaction;
+
+use crate::{
+    domain::{OAuthLogin, OAUTH_LOGIN_TTL},
+    error::APIError,
+};
+
+/// Records a login started now, forgetting the ones that can no longer be
+/// completed.
+#[tracing::instrument(name = "Create OAuth login in the database", skip(transaction, login))]
+pub async fn create_oauth_login(
+    transaction: &mut PgTransaction<'_>,
+    login: &OAuthLogin,
+) -> Result<(), APIError> {
+    sqlx::query!(
+        r#"DELETE FROM oauth_login WHERE create_time < $1;"#,
+        login.create_time - OAUTH_LOGIN_TTL,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO oauth_login (state, provider, pkce_verifier, nonce, create_time)
+           VALUES ($1, $2, $3, $4, $5);"#,
+        login.state,
+        login.provider,
+        login.pkce_verifier,
+        login.nonce,
+        login.create_time,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Removes the login with the state so it can only be completed once.
+#[tracing::instrument(name = "Take OAuth login from the database", skip(transaction, state))]
+pub async fn take_oauth_login(
+    transaction: &mut PgTransaction<'_>,
+    state: &str,
+) -> Result<Option<OAuthLogin>, APIError> {
+    let login = sqlx::query_as!(
+        OAuthLogin,
+        r#"DELETE FROM oauth_login WHERE state = $1
+           RETURNING state, provider, pkce_verifier, nonce, create_time;"#,
+        state,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(login)
+}
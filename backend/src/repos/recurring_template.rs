@@ -1,13 +1,15 @@
 use eyre::eyre;
+use serde::{Deserialize, Serialize};
 use sqlx::{postgres::types::PgInterval, PgTransaction};
 use std::time::Duration;
-use time::Date;
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::{
     domain::{
-        ListRecurringTemplate, ListRecurringTemplateSingle, NewRecurringTemplateRequest,
-        RecurringTemplate, TodoName, UpdateRecurringTemplateRequest,
+        Changed, ETagCondition, ListRecurringTemplate, ListRecurringTemplateSingle,
+        NewRecurringTemplateRequest, PageRequest, Recurrence, RecurringTemplate, SyncRequest,
+        TodoName, TodoRef, TodoRole, UpdateRecurringTemplateRequest,
     },
     error::APIError,
 };
@@ -17,9 +19,11 @@ use super::get_todo_by_name;
 #[derive(Debug)]
 struct GetTemplateQuery {
     todo_name: String,
+    todo_owner_id: i32,
     template_id: Uuid,
     title: String,
-    recurrence_period: sqlx::postgres::types::PgInterval,
+    recurrence_period: Option<sqlx::postgres::types::PgInterval>,
+    recurrence_rule: Option<String>,
     start_date: sqlx::types::time::Date,
     end_date: Option<sqlx::types::time::Date>,
     last_generated_date: Option<sqlx::types::time::Date>,
@@ -28,15 +32,38 @@ struct GetTemplateQuery {
     update_time: sqlx::types::time::OffsetDateTime,
 }
 
+impl GetTemplateQuery {
+    fn recurrence(&self) -> Result<Recurrence, APIError> {
+        match (&self.recurrence_period, &self.recurrence_rule) {
+            (Some(period), None) => Ok(Recurrence::Interval(period.into())),
+            (None, Some(rule)) => Ok(Recurrence::Rule(rule.as_str().try_into().map_err(|e| {
+                eyre!(
+                    "Stored RRULE of template {} is invalid: {}",
+                    self.template_id,
+                    e
+                )
+            })?)),
+            _ => Err(APIError::Internal(
+                eyre!(
+                    "Template {} must have exactly one of an interval or a rule",
+                    self.template_id
+                )
+                .into(),
+            )),
+        }
+    }
+}
+
 impl TryFrom<GetTemplateQuery> for RecurringTemplate {
     type Error = APIError;
 
     fn try_from(value: GetTemplateQuery) -> Result<Self, Self::Error> {
         Ok(Self {
-            todo_name: value.todo_name.try_into()?,
+            recurrence: value.recurrence()?,
+            todo_name: TodoName::from_stored(value.todo_name),
+            todo_owner_id: value.todo_owner_id,
             template_id: value.template_id,
             title: value.title,
-            recurrence_interval: (&value.recurrence_period).into(),
             start_date: value.start_date,
             end_date: value.end_date,
             last_generated_date: value.last_generated_date,
@@ -47,6 +74,20 @@ impl TryFrom<GetTemplateQuery> for RecurringTemplate {
     }
 }
 
+fn recurrence_period(recurrence: &Recurrence) -> Option<PgInterval> {
+    match recurrence {
+        Recurrence::Interval(interval) => Some(interval.clone().into()),
+        Recurrence::Rule(_) => None,
+    }
+}
+
+fn recurrence_rule(recurrence: &Recurrence) -> Option<String> {
+    match recurrence {
+        Recurrence::Interval(_) => None,
+        Recurrence::Rule(rule) => Some(rule.to_string()),
+    }
+}
+
 #[tracing::instrument(
     name = "Create recurring template in the database",
     skip(transaction, req)
@@ -56,17 +97,17 @@ pub async fn create_recurring_template(
     req: &NewRecurringTemplateRequest,
     user_id: i32,
 ) -> Result<RecurringTemplate, APIError> {
-    let todo = get_todo_by_name(transaction, &req.todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, &req.todo_ref, user_id, TodoRole::Editor).await?;
     let template_id = Uuid::new_v4();
 
     match sqlx::query_as!(
         GetTemplateQuery,
         r#"WITH insert_qry AS (
-            INSERT INTO recurring_template (template_id, todo_id, title, recurrence_period, start_date, end_date, user_id)
-           VALUES ($1, $2, $3, $4::interval, $5, $6, $7)
-           RETURNING template_id, todo_id, title, recurrence_period, start_date, end_date, last_generated_date, 
+            INSERT INTO recurring_template (template_id, todo_id, title, recurrence_period, recurrence_rule, start_date, end_date, user_id)
+           VALUES ($1, $2, $3, $4::interval, $5, $6, $7, $8)
+           RETURNING template_id, todo_id, title, recurrence_period, recurrence_rule, start_date, end_date, last_generated_date, 
                      is_active, create_time, update_time)
-            SELECT i.template_id, t.name as todo_name, i.title, i.recurrence_period, i.start_date, i.end_date, i.last_generated_date,
+            SELECT i.template_id, t.name as todo_name, t.user_id as todo_owner_id, i.title, i.recurrence_period, i.recurrence_rule, i.start_date, i.end_date, i.last_generated_date,
               i.is_active, i.create_time, i.update_time
             FROM insert_qry as i
             INNER JOIN todo as t ON t.todo_id = i.todo_id
@@ -74,7 +115,8 @@ pub async fn create_recurring_template(
         template_id,
         todo.todo_id,
         req.title,
-        Into::<PgInterval>::into(req.recurrence_interval.clone()),
+        recurrence_period(&req.recurrence),
+        recurrence_rule(&req.recurrence),
         req.start_date,
         req.end_date,
         user_id,
@@ -83,34 +125,34 @@ pub async fn create_recurring_template(
     .await
     {
         Ok(result) => Ok(result.try_into()?),
-        Err(sqlx::Error::RowNotFound) => Err(APIError::AlreadyExists(format!(
+        Err(sqlx::Error::RowNotFound) => Err(APIError::NotFound(format!(
             "template: {} is not found",
-            req.todo_name.as_ref()
+            req.todo_ref
         ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
 #[tracing::instrument(
     name = "Get recurring template in the database",
-    skip(transaction, todo_name, template_id)
+    skip(transaction, todo_ref, template_id)
 )]
 pub async fn get_recurring_template(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     template_id: &Uuid,
     user_id: i32,
 ) -> Result<RecurringTemplate, APIError> {
-    let _ = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Viewer).await?;
 
     match sqlx::query_as!(
         GetTemplateQuery,
-        r#"SELECT r.template_id, t.name as todo_name, r.title, r.recurrence_period, r.start_date, r.end_date, r.last_generated_date,
+        r#"SELECT r.template_id, t.name as todo_name, t.user_id as todo_owner_id, r.title, r.recurrence_period, r.recurrence_rule, r.start_date, r.end_date, r.last_generated_date,
                   r.is_active, r.create_time, r.update_time
            FROM recurring_template as r
            INNER JOIN todo as t ON t.todo_id = r.todo_id
-           WHERE t.name = $1 AND template_id = $2"#,
-        todo_name.as_ref(),
+           WHERE r.todo_id = $1 AND template_id = $2 AND t.deleted_at IS NULL"#,
+        todo.todo_id,
         template_id,
     )
     .fetch_optional(&mut **transaction)
@@ -121,22 +163,45 @@ pub async fn get_recurring_template(
             "Recurring template {} not found",
             template_id
         ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Looks a template up without checking who is asking. Only for callers that
+/// already authorized access, e.g. through one of the template's items.
+#[tracing::instrument(name = "Get recurring template by id", skip(transaction))]
+pub async fn get_recurring_template_by_id(
+    transaction: &mut PgTransaction<'_>,
+    template_id: &Uuid,
+) -> Result<Option<RecurringTemplate>, APIError> {
+    let result = sqlx::query_as!(
+        GetTemplateQuery,
+        r#"SELECT r.template_id, t.name as todo_name, t.user_id as todo_owner_id, r.title, r.recurrence_period, r.recurrence_rule, r.start_date, r.end_date, r.last_generated_date,
+                  r.is_active, r.create_time, r.update_time
+           FROM recurring_template as r
+           INNER JOIN todo as t ON t.todo_id = r.todo_id
+           WHERE r.template_id = $1 AND t.deleted_at IS NULL"#,
+        template_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    result.map(|r| r.try_into()).transpose()
+}
+
 #[tracing::instrument(
     name = "Update recurring template in the database",
-    skip(transaction, todo_name, template_id, req)
+    skip(transaction, todo_ref, template_id, req)
 )]
 pub async fn update_recurring_template(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     template_id: &Uuid,
     req: &UpdateRecurringTemplateRequest,
     user_id: i32,
+    if_match: Option<&ETagCondition>,
 ) -> Result<RecurringTemplate, APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
+    check_template_precondition(transaction, &todo.todo_id, template_id, if_match).await?;
 
     match sqlx::query_as!(
         GetTemplateQuery,
@@ -144,13 +209,14 @@ pub async fn update_recurring_template(
             UPDATE recurring_template SET
                title = $3,
                recurrence_period = $4::interval,
-               start_date = $5,
-               end_date = $6,
-               is_active = $7
+               recurrence_rule = $5,
+               start_date = $6,
+               end_date = $7,
+               is_active = $8
            WHERE todo_id = $1 AND template_id = $2
-           RETURNING template_id, todo_id, title, recurrence_period, start_date, end_date, last_generated_date,
+           RETURNING template_id, todo_id, title, recurrence_period, recurrence_rule, start_date, end_date, last_generated_date,
                      is_active, create_time, update_time)
-        SELECT u.template_id, t.name as todo_name, u.title, u.recurrence_period, u.start_date, u.end_date, u.last_generated_date,
+        SELECT u.template_id, t.name as todo_name, t.user_id as todo_owner_id, u.title, u.recurrence_period, u.recurrence_rule, u.start_date, u.end_date, u.last_generated_date,
                   u.is_active, u.create_time, u.update_time
         FROM update_qry as u
         INNER JOIN todo as t ON t.todo_id = u.todo_id
@@ -158,7 +224,8 @@ pub async fn update_recurring_template(
         todo.todo_id,
         template_id,
         req.title,
-        Into::<PgInterval>::into(req.recurrence_interval.clone()),
+        recurrence_period(&req.recurrence),
+        recurrence_rule(&req.recurrence),
         req.start_date,
         req.end_date,
         req.is_active,
@@ -171,7 +238,7 @@ pub async fn update_recurring_template(
             "Recurring template {} not found",
             template_id
         ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -181,7 +248,10 @@ impl TryFrom<Vec<GetTemplateQuery>> for ListRecurringTemplate {
     fn try_from(value: Vec<GetTemplateQuery>) -> Result<Self, Self::Error> {
         let items: Result<Vec<ListRecurringTemplateSingle>, Self::Error> =
             value.into_iter().map(|i| i.try_into()).collect();
-        Ok(Self { items: items? })
+        Ok(Self {
+            items: items?,
+            next_cursor: None,
+        })
     }
 }
 
@@ -190,10 +260,11 @@ impl TryFrom<GetTemplateQuery> for ListRecurringTemplateSingle {
 
     fn try_from(value: GetTemplateQuery) -> Result<Self, Self::Error> {
         Ok(Self {
-            todo_name: value.todo_name.try_into()?,
+            recurrence: value.recurrence()?,
+            todo_name: TodoName::from_stored(value.todo_name),
+            todo_owner_id: value.todo_owner_id,
             template_id: value.template_id,
             title: value.title,
-            recurrence_interval: (&value.recurrence_period).into(),
             start_date: value.start_date,
             end_date: value.end_date,
             last_generated_date: value.last_generated_date,
@@ -204,46 +275,94 @@ impl TryFrom<GetTemplateQuery> for ListRecurringTemplateSingle {
     }
 }
 
+/// Sort key of `list_recurring_templates`, used as the pagination cursor.
+#[derive(Debug, Serialize, Deserialize)]
+struct ListTemplateCursor {
+    #[serde(with = "time::serde::rfc3339")]
+    create_time: OffsetDateTime,
+    template_id: Uuid,
+}
+
 #[tracing::instrument(
     name = "List recurring templates in the database",
-    skip(transaction, todo_name)
+    skip(transaction, todo_ref)
 )]
 pub async fn list_recurring_templates(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
+    page: &PageRequest,
     user_id: i32,
 ) -> Result<ListRecurringTemplate, APIError> {
-    let _ = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Viewer).await?;
+    let cursor: Option<ListTemplateCursor> = page.decode_cursor()?;
 
-    match sqlx::query_as!(
+    let result = sqlx::query_as!(
         GetTemplateQuery,
-        r#"SELECT r.template_id, t.name as todo_name, r.title, r.recurrence_period, r.start_date, r.end_date, r.last_generated_date,
+        r#"SELECT r.template_id, t.name as todo_name, t.user_id as todo_owner_id, r.title, r.recurrence_period, r.recurrence_rule, r.start_date, r.end_date, r.last_generated_date,
                   r.is_active, r.create_time, r.update_time
            FROM recurring_template as r
            INNER JOIN todo as t on t.todo_id = r.todo_id
-           WHERE t.name = $1
-           ORDER BY create_time DESC"#,
-        todo_name.as_ref(),
+           WHERE r.todo_id = $1
+             AND t.deleted_at IS NULL
+             AND ($2::timestamptz IS NULL OR (r.create_time, r.template_id) < ($2, $3::uuid))
+           ORDER BY r.create_time DESC, r.template_id DESC
+           LIMIT $4"#,
+        todo.todo_id,
+        cursor.as_ref().map(|c| c.create_time),
+        cursor.as_ref().map(|c| c.template_id),
+        page.fetch_limit(),
     )
     .fetch_all(&mut **transaction)
-    .await
-    {
-        Ok(result) => Ok(result.try_into()?),
-        Err(err) => Err(APIError::Internal(err.into())),
-    }
+    .await?;
+    let (result, next_cursor) = page.paginate(result, |row| ListTemplateCursor {
+        create_time: row.create_time,
+        template_id: row.template_id,
+    });
+    let items: Result<Vec<ListRecurringTemplateSingle>, APIError> =
+        result.into_iter().map(|i| i.try_into()).collect();
+    Ok(ListRecurringTemplate {
+        items: items?,
+        next_cursor,
+    })
+}
+
+/// Locks the template until the end of the transaction and checks its
+/// current version against an `If-Match` condition.
+async fn check_template_precondition(
+    transaction: &mut PgTransaction<'_>,
+    todo_id: &Uuid,
+    template_id: &Uuid,
+    if_match: Option<&ETagCondition>,
+) -> Result<(), APIError> {
+    let Some(if_match) = if_match else {
+        return Ok(());
+    };
+    let update_time = sqlx::query_scalar!(
+        r#"SELECT update_time FROM recurring_template
+           WHERE todo_id = $1 AND template_id = $2
+           FOR UPDATE"#,
+        todo_id,
+        template_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| APIError::NotFound(format!("Recurring template {} not found", template_id)))?;
+    if_match.check(&update_time.into())
 }
 
 #[tracing::instrument(
     name = "Delete recurring template in the database",
-    skip(transaction, todo_name, template_id)
+    skip(transaction, todo_ref, template_id)
 )]
 pub async fn delete_recurring_template(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     template_id: &Uuid,
     user_id: i32,
+    if_match: Option<&ETagCondition>,
 ) -> Result<(), APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
+    check_template_precondition(transaction, &todo.todo_id, template_id, if_match).await?;
 
     let result = sqlx::query!(
         r#"DELETE FROM recurring_template
@@ -271,6 +390,7 @@ pub async fn get_templates_due_for_generation(
     transaction: &mut PgTransaction<'_>,
     current_date: Date,
     advance_duration: Duration,
+    time_zone: &str,
 ) -> Result<ListRecurringTemplate, APIError> {
     // Convert Duration to PgInterval
     let advance_interval =
@@ -278,22 +398,30 @@ pub async fn get_templates_due_for_generation(
 
     match sqlx::query_as!(
         GetTemplateQuery,
-        r#"SELECT r.template_id, t.name as todo_name, r.title, r.recurrence_period, r.start_date, r.end_date, r.last_generated_date,
+        r#"SELECT r.template_id, t.name as todo_name, t.user_id as todo_owner_id, r.title, r.recurrence_period, r.recurrence_rule, r.start_date, r.end_date, r.last_generated_date,
                   r.is_active, r.create_time, r.update_time
            FROM recurring_template as r
            INNER JOIN todo as t ON t.todo_id = r.todo_id
+           INNER JOIN users as u ON u.user_id = t.user_id
            WHERE r.is_active = TRUE
-             AND (r.end_date IS NULL OR r.end_date >= ($1::date + $2::interval)::date)
-             AND (r.last_generated_date IS NULL OR $1::date >= (r.last_generated_date + r.recurrence_period - $2::interval)::date)
+             AND t.deleted_at IS NULL
+             AND u.time_zone = $3
+             AND (r.end_date IS NULL
+                  OR (r.recurrence_rule IS NULL AND r.end_date >= ($1::date + $2::interval)::date)
+                  OR (r.recurrence_rule IS NOT NULL AND r.end_date >= $1::date))
+             AND (r.recurrence_rule IS NOT NULL
+                  OR r.last_generated_date IS NULL
+                  OR $1::date >= (r.last_generated_date + r.recurrence_period - $2::interval)::date)
              AND $1::date >= (r.start_date - $2::interval)::date"#,
         current_date,
         advance_interval,
+        time_zone,
     )
     .fetch_all(&mut **transaction)
     .await
     {
         Ok(result) => Ok(result.try_into()?),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -307,6 +435,7 @@ pub async fn check_active_todo_exists_for_template(
             SELECT 1 FROM todo_item ti
             WHERE ti.recurring_template_id = $1
             AND ti.is_complete = FALSE
+            AND ti.deleted_at IS NULL
         ) as exists"#,
         template_id,
     )
@@ -314,10 +443,53 @@ pub async fn check_active_todo_exists_for_template(
     .await
     {
         Ok(result) => Ok(result.exists.unwrap_or(false)),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
+#[tracing::instrument(name = "Get latest due date for template", skip(transaction))]
+pub async fn get_latest_due_date_for_template(
+    transaction: &mut PgTransaction<'_>,
+    template_id: &Uuid,
+) -> Result<Option<Date>, APIError> {
+    // Trashed items count, an occurrence moved to the trash isn't generated
+    // again.
+    let result = sqlx::query!(
+        r#"SELECT MAX(due_date) as latest FROM todo_item WHERE recurring_template_id = $1"#,
+        template_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(result.latest)
+}
+
+/// Moves the open items of the template other than `keep` to the trash,
+/// where they can still be restored.
+#[tracing::instrument(
+    name = "Trash other open items for template",
+    skip(transaction, template_id, keep)
+)]
+pub async fn trash_other_open_items_for_template(
+    transaction: &mut PgTransaction<'_>,
+    template_id: &Uuid,
+    keep: &Uuid,
+    now: OffsetDateTime,
+) -> Result<u64, APIError> {
+    let result = sqlx::query!(
+        r#"UPDATE todo_item SET deleted_at = $3
+           WHERE recurring_template_id = $1
+             AND todo_item_id <> $2
+             AND is_complete = FALSE
+             AND deleted_at IS NULL"#,
+        template_id,
+        keep,
+        now,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(result.rows_affected())
+}
+
 #[tracing::instrument(name = "Update last generated date", skip(transaction, template_id))]
 pub async fn update_last_generated_date(
     transaction: &mut PgTransaction<'_>,
@@ -344,3 +516,53 @@ pub async fn update_last_generated_date(
         )),
     }
 }
+
+#[tracing::instrument(
+    name = "List changed recurring templates in the database",
+    skip(transaction)
+)]
+pub async fn list_changed_recurring_templates(
+    transaction: &mut PgTransaction<'_>,
+    req: &SyncRequest,
+    user_id: i32,
+) -> Result<Vec<Changed<RecurringTemplate>>, APIError> {
+    let result = sqlx::query!(
+        r#"SELECT r.change_seq, r.todo_id, r.template_id, t.name as todo_name, t.user_id as todo_owner_id, r.title, r.recurrence_period,
+                  r.recurrence_rule, r.start_date, r.end_date, r.last_generated_date, r.is_active, r.create_time, r.update_time
+           FROM recurring_template as r
+           INNER JOIN todo as t ON t.todo_id = r.todo_id
+           WHERE t.todo_id IN (SELECT synced_todo_ids($1)) AND r.change_seq > $2
+             AND t.deleted_at IS NULL
+           ORDER BY r.change_seq
+           LIMIT $3;"#,
+        user_id,
+        req.since_change_seq(),
+        req.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    result
+        .into_iter()
+        .map(|row| {
+            Ok(Changed {
+                change_seq: row.change_seq,
+                todo_id: row.todo_id,
+                value: GetTemplateQuery {
+                    todo_name: row.todo_name,
+                    todo_owner_id: row.todo_owner_id,
+                    template_id: row.template_id,
+                    title: row.title,
+                    recurrence_period: row.recurrence_period,
+                    recurrence_rule: row.recurrence_rule,
+                    start_date: row.start_date,
+                    end_date: row.end_date,
+                    last_generated_date: row.last_generated_date,
+                    is_active: row.is_active,
+                    create_time: row.create_time,
+                    update_time: row.update_time,
+                }
+                .try_into()?,
+            })
+        })
+        .collect()
+}
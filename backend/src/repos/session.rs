@@ -0,0 +1,236 @@
+use sqlx::PgTransaction;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    domain::{NewSession, RefreshToken, Session},
+    error::APIError,
+};
+
+/// Opens a session for a login, forgetting the expired sessions of the user.
+#[tracing::instrument(name = "Create session in the database", skip(transaction, session))]
+pub async fn create_session(
+    transaction: &mut PgTransaction<'_>,
+    session: &NewSession,
+) -> Result<Session, APIError> {
+    sqlx::query!(
+        r#"DELETE FROM session WHERE user_id = $1 AND expire_time <= $2;"#,
+        session.user_id,
+        session.create_time,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    let session = sqlx::query_as!(
+        Session,
+        r#"INSERT INTO session (session_id, user_id, refresh_token_hash, refresh_token_salt, user_agent, create_time, refresh_time, expire_time)
+           VALUES ($1, $2, $3, $4, $5, $6, $6, $7)
+           RETURNING session_id, user_id, user_agent, create_time, refresh_time, expire_time;"#,
+        Uuid::new_v4(),
+        session.user_id,
+        session.refresh_token.hash(),
+        session.refresh_token_salt.secret(),
+        session.user_agent,
+        session.create_time,
+        session.expire_time,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(session)
+}
+
+/// Swaps the refresh token of the active session holding it for its
+/// successor, extending the session. The old token is kept to notice its
+/// reuse. `None` when no active session holds the token, also when a
+/// concurrent refresh swapped it first.
+#[tracing::instrument(
+    name = "Refresh session in the database",
+    skip(transaction, refresh_token)
+)]
+pub async fn refresh_session(
+    transaction: &mut PgTransaction<'_>,
+    refresh_token: &RefreshToken,
+    now: OffsetDateTime,
+    expire_time: OffsetDateTime,
+) -> Result<Option<(Session, RefreshToken)>, APIError> {
+    // Locked, a concurrent refresh with the same token waits for this one
+    // and then no longer finds it
+    let salt = sqlx::query!(
+        r#"SELECT session_id, refresh_token_salt FROM session
+           WHERE refresh_token_hash = $1 AND revoke_time IS NULL AND expire_time > $2
+           FOR UPDATE;"#,
+        refresh_token.hash(),
+        now,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(salt) = salt else {
+        return Ok(None);
+    };
+    let new_refresh_token = refresh_token.successor(&salt.refresh_token_salt);
+    let session = sqlx::query_as!(
+        Session,
+        r#"WITH refreshed AS (
+               UPDATE session
+               SET refresh_token_hash = $2,
+                   refresh_time = $3,
+                   expire_time = $4
+               WHERE session_id = $5
+               RETURNING session_id, user_id, user_agent, create_time, refresh_time, expire_time
+           ), swapped AS (
+               INSERT INTO session_refresh_token (refresh_token_hash, session_id, swap_time)
+               SELECT $1, session_id, $3 FROM refreshed
+           )
+           SELECT session_id as "session_id!", user_id as "user_id!", user_agent,
+                  create_time as "create_time!", refresh_time as "refresh_time!",
+                  expire_time as "expire_time!"
+           FROM refreshed;"#,
+        refresh_token.hash(),
+        new_refresh_token.hash(),
+        now,
+        expire_time,
+        salt.session_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(Some((session, new_refresh_token)))
+}
+
+/// The active session a refresh token was swapped out of after `since`, with
+/// the token it was swapped for, as long as that one still is the session's.
+/// Refreshes racing with the same token get the same new token this way.
+#[tracing::instrument(
+    name = "Get refreshed session in the database",
+    skip(transaction, refresh_token)
+)]
+pub async fn get_refreshed_session(
+    transaction: &mut PgTransaction<'_>,
+    refresh_token: &RefreshToken,
+    since: OffsetDateTime,
+    now: OffsetDateTime,
+) -> Result<Option<(Session, RefreshToken)>, APIError> {
+    let row = sqlx::query!(
+        r#"SELECT s.session_id, s.user_id, s.user_agent, s.create_time, s.refresh_time,
+                  s.expire_time, s.refresh_token_hash, s.refresh_token_salt
+           FROM session as s
+           JOIN session_refresh_token as r ON r.session_id = s.session_id
+           WHERE r.refresh_token_hash = $1 AND r.swap_time > $2
+             AND s.revoke_time IS NULL AND s.expire_time > $3;"#,
+        refresh_token.hash(),
+        since,
+        now,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let new_refresh_token = refresh_token.successor(&row.refresh_token_salt);
+    // The new token was already swapped out in turn
+    if new_refresh_token.hash() != row.refresh_token_hash {
+        return Ok(None);
+    }
+    let session = Session {
+        session_id: row.session_id,
+        user_id: row.user_id,
+        user_agent: row.user_agent,
+        create_time: row.create_time,
+        refresh_time: row.refresh_time,
+        expire_time: row.expire_time,
+    };
+    Ok(Some((session, new_refresh_token)))
+}
+
+/// Revokes the session a refresh token was already swapped out of, in any
+/// earlier refresh. Only a client that kept a copy of the token can still use
+/// it, so the session is no longer safe to keep.
+#[tracing::instrument(
+    name = "Revoke reused session in the database",
+    skip(transaction, refresh_token)
+)]
+pub async fn revoke_reused_session(
+    transaction: &mut PgTransaction<'_>,
+    refresh_token: &RefreshToken,
+    now: OffsetDateTime,
+) -> Result<Option<Uuid>, APIError> {
+    let session_id = sqlx::query_scalar!(
+        r#"UPDATE session as s SET revoke_time = $2
+           FROM session_refresh_token as r
+           WHERE r.refresh_token_hash = $1 AND s.session_id = r.session_id
+             AND s.revoke_time IS NULL
+           RETURNING s.session_id;"#,
+        refresh_token.hash(),
+        now,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(session_id)
+}
+
+#[tracing::instrument(name = "Check session in the database", skip(transaction))]
+pub async fn is_session_active(
+    transaction: &mut PgTransaction<'_>,
+    session_id: &Uuid,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<bool, APIError> {
+    let active = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+               SELECT 1 FROM session
+               WHERE session_id = $1 AND user_id = $2 AND revoke_time IS NULL AND expire_time > $3
+           ) AS "active!";"#,
+        session_id,
+        user_id,
+        now,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(active)
+}
+
+/// The active sessions of the user, the most recently refreshed first.
+#[tracing::instrument(name = "List sessions in the database", skip(transaction))]
+pub async fn list_sessions(
+    transaction: &mut PgTransaction<'_>,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<Vec<Session>, APIError> {
+    let sessions = sqlx::query_as!(
+        Session,
+        r#"SELECT session_id, user_id, user_agent, create_time, refresh_time, expire_time
+           FROM session
+           WHERE user_id = $1 AND revoke_time IS NULL AND expire_time > $2
+           ORDER BY refresh_time DESC, session_id;"#,
+        user_id,
+        now,
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(sessions)
+}
+
+/// Revokes an active session of the user, its access and refresh tokens stop
+/// working right away.
+#[tracing::instrument(name = "Revoke session in the database", skip(transaction))]
+pub async fn revoke_session(
+    transaction: &mut PgTransaction<'_>,
+    session_id: &Uuid,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<(), APIError> {
+    let revoked = sqlx::query!(
+        r#"UPDATE session SET revoke_time = $3
+           WHERE session_id = $1 AND user_id = $2 AND revoke_time IS NULL AND expire_time > $3;"#,
+        session_id,
+        user_id,
+        now,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    if revoked.rows_affected() == 0 {
+        return Err(APIError::NotFound(format!(
+            "session: {} is not found",
+            session_id
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use sqlx::PgTransaction;
+
+use crate::{
+    domain::{Changes, SyncEntity, SyncRequest, Tombstone},
+    error::APIError,
+};
+
+use super::{list_changed_recurring_templates, list_changed_todo_items, list_changed_todos};
+
+#[tracing::instrument(name = "List tombstones in the database", skip(transaction))]
+pub async fn list_tombstones(
+    transaction: &mut PgTransaction<'_>,
+    req: &SyncRequest,
+    user_id: i32,
+) -> Result<Vec<Tombstone>, APIError> {
+    // A first sync has nothing to forget
+    if req.since.is_none() {
+        return Ok(vec![]);
+    }
+    // Trashed rows are gone for clients, the ones purged since have a
+    // tombstone. Todo tombstones are written for every user who synced the
+    // todo, the others are synced with their todo.
+    let result = sqlx::query_as!(
+        Tombstone,
+        r#"SELECT change_seq as "change_seq!", entity as "entity!: SyncEntity", entity_id as "entity_id!",
+                  todo_id as "todo_id!", delete_time as "delete_time!"
+           FROM (
+               SELECT change_seq, entity, entity_id, todo_id, delete_time
+               FROM tombstone
+               WHERE user_id = $1 AND entity = 'todo' AND change_seq > $2
+               UNION ALL
+               SELECT change_seq, entity, entity_id, todo_id, delete_time
+               FROM tombstone
+               WHERE todo_id IN (SELECT synced_todo_ids($1)) AND entity <> 'todo' AND change_seq > $2
+               UNION ALL
+               SELECT change_seq, 'todo'::sync_entity, todo_id, todo_id, deleted_at
+               FROM todo
+               WHERE todo_id IN (SELECT synced_todo_ids($1)) AND change_seq > $2
+                 AND deleted_at IS NOT NULL
+               UNION ALL
+               SELECT i.change_seq, 'item'::sync_entity, i.todo_item_id, i.todo_id, i.deleted_at
+               FROM todo_item as i
+               INNER JOIN todo as t ON t.todo_id = i.todo_id
+               WHERE t.todo_id IN (SELECT synced_todo_ids($1)) AND i.change_seq > $2
+                 AND i.deleted_at IS NOT NULL AND t.deleted_at IS NULL
+           ) as deleted
+           ORDER BY change_seq
+           LIMIT $3;"#,
+        user_id,
+        req.since_change_seq(),
+        req.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(result)
+}
+
+/// Everything that changed after the token of `req` in the todos the user
+/// created, is a member of or has through a group. Must be the first
+/// statement of the transaction.
+#[tracing::instrument(name = "List changes in the database", skip(transaction))]
+pub async fn list_changes(
+    transaction: &mut PgTransaction<'_>,
+    req: &SyncRequest,
+    user_id: i32,
+) -> Result<Changes, APIError> {
+    // The tables are read one after the other, a single snapshot keeps a
+    // change committed in between from landing below the next token.
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut **transaction)
+        .await?;
+    let todos = list_changed_todos(transaction, req, user_id).await?;
+    let items = list_changed_todo_items(transaction, req, user_id).await?;
+    let templates = list_changed_recurring_templates(transaction, req, user_id).await?;
+    let tombstones = list_tombstones(transaction, req, user_id).await?;
+    Ok(Changes::paginate(req, todos, items, templates, tombstones))
+}
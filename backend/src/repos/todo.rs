@@ -1,50 +1,55 @@
+use serde::{Deserialize, Serialize};
 use sqlx::PgTransaction;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::{
     domain::{
-        ListTodo, ListTodoSingle, NewTodoRequest, Todo, TodoName, TodoVisibility, UpdateTodoRequest,
+        Changed, ETagCondition, ListTodo, ListTodoSingle, NewTodoRequest, PageRequest, SyncRequest,
+        Todo, TodoName, TodoRef, TodoRole, TodoVisibility, UpdateTodoRequest,
     },
     error::APIError,
 };
 
+use super::get_group;
+
 #[tracing::instrument(name = "Create todo in the database", skip(transaction, req))]
 pub async fn create_todo(
     transaction: &mut PgTransaction<'_>,
     req: &NewTodoRequest,
     user_id: i32,
 ) -> Result<(), APIError> {
+    if let Some(group_id) = &req.group_id {
+        get_group(transaction, group_id, user_id, TodoRole::Editor).await?;
+    }
     match sqlx::query!(
-        r#"INSERT INTO todo (todo_id, name, user_id, visibility) VALUES ($1, $2, $3, $4)
+        r#"INSERT INTO todo (todo_id, name, user_id, visibility, group_id) VALUES ($1, $2, $3, $4, $5)
            RETURNING name;"#,
         Uuid::new_v4(),
         req.name.as_ref(),
         user_id,
         req.visibility.clone() as _,
+        req.group_id,
     )
     .fetch_one(&mut **transaction)
     .await
     {
         Ok(_) => Ok(()),
-        Err(sqlx::Error::Database(e)) => {
-            if e.is_unique_violation() {
-                Err(APIError::AlreadyExists(format!(
-                    "TODO: '{}' already exists",
-                    req.name.as_ref()
-                )))
-            } else {
-                Err(APIError::Internal(sqlx::Error::Database(e).into()))
-            }
-        }
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(APIError::AlreadyExists(
+            format!("TODO: '{}' already exists", req.name.as_ref()),
+        )),
+        Err(err) => Err(err.into()),
     }
 }
 
 #[derive(Debug)]
 struct GetTodoQuery {
     todo_id: Uuid,
+    user_id: i32,
     name: String,
     visibility: crate::domain::TodoVisibility,
+    group_id: Option<Uuid>,
+    role: TodoRole,
     create_time: sqlx::types::time::OffsetDateTime,
     update_time: sqlx::types::time::OffsetDateTime,
 }
@@ -54,55 +59,124 @@ impl TryFrom<GetTodoQuery> for Todo {
     fn try_from(value: GetTodoQuery) -> Result<Self, Self::Error> {
         Ok(Self {
             todo_id: value.todo_id,
-            name: value.name.try_into()?,
+            owner_id: value.user_id,
+            name: TodoName::from_stored(value.name),
             visibility: value.visibility,
+            group_id: value.group_id,
+            role: value.role,
             create_time: value.create_time,
             update_time: value.update_time,
         })
     }
 }
 
+/// Resolves a todo the user can see and checks they have at least the
+/// `required` role on it. A public todo the user has no membership of is
+/// not found when its public role falls short, like a private one.
 #[tracing::instrument(name = "Get todo by name in the database", skip(transaction))]
 pub async fn get_todo_by_name(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     user_id: i32,
+    required: TodoRole,
 ) -> Result<Todo, APIError> {
-    match sqlx::query_as!(
+    let (todo_id, owner_id, name) = match todo_ref {
+        TodoRef::Id(todo_id) => (Some(*todo_id), None, None),
+        TodoRef::Name { owner_id, name } => (None, *owner_id, Some(name.as_ref())),
+    };
+    // The caller's own todo sorts first so it shadows shared and public todos
+    // of other users with the same name.
+    let result = sqlx::query_as!(
         GetTodoQuery,
-        r#"SELECT todo_id, name, visibility as "visibility: TodoVisibility", create_time, update_time from todo WHERE name = $1 AND ((user_id = $2 AND visibility = 'private') OR visibility = 'public');"#,
-        todo_name.as_ref(),
-        user_id
+        r#"SELECT todo_id, user_id, name, visibility as "visibility: TodoVisibility",
+                  group_id, role as "role!: TodoRole", create_time, update_time
+           FROM (SELECT t.*, todo_role_of(t, $3) as role, todo_member_role_of(t, $3) as member_role
+                 FROM todo as t) as t
+           WHERE ($1::uuid IS NULL OR todo_id = $1)
+             AND ($2::text IS NULL OR name = $2)
+             AND ($4::integer IS NULL OR user_id = $4)
+             AND role IS NOT NULL
+             AND (role >= $5 OR member_role IS NOT NULL)
+             AND deleted_at IS NULL
+           ORDER BY user_id = $3 DESC
+           LIMIT 2;"#,
+        todo_id,
+        name,
+        user_id,
+        owner_id,
+        required as _,
     )
-    .fetch_one(&mut **transaction)
-    .await
-    {
-        Ok(result) => Ok(result.try_into()?),
-        Err(sqlx::Error::RowNotFound) => Err(APIError::NotFound(format!(
-            "todo: {} is not found",
-            todo_name.as_ref()
-        ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+    .fetch_all(&mut **transaction)
+    .await?;
+    if result.len() > 1 && result[0].user_id != user_id {
+        return Err(APIError::BadRequest(format!(
+            "todo: {} is ambiguous, prefix it with the owner id",
+            todo_ref
+        )));
+    }
+    let todo: Todo = match result.into_iter().next() {
+        Some(todo) => todo.try_into()?,
+        None => {
+            return Err(APIError::NotFound(format!(
+                "todo: {} is not found",
+                todo_ref
+            )))
+        }
+    };
+    if todo.role < required {
+        return Err(APIError::Forbidden(format!(
+            "todo: {} needs the {} role",
+            todo_ref,
+            required.as_ref()
+        )));
     }
+    Ok(todo)
 }
 
+/// Locks the todo until the end of the transaction and checks its current
+/// version against an `If-Match` condition.
+async fn check_todo_precondition(
+    transaction: &mut PgTransaction<'_>,
+    todo: &Todo,
+    if_match: Option<&ETagCondition>,
+) -> Result<(), APIError> {
+    let Some(if_match) = if_match else {
+        return Ok(());
+    };
+    let update_time = sqlx::query_scalar!(
+        r#"SELECT update_time FROM todo WHERE todo_id = $1 AND deleted_at IS NULL FOR UPDATE"#,
+        todo.todo_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| APIError::NotFound(format!("TODO: '{}' not found", todo.name.as_ref())))?;
+    if_match.check(&update_time.into())
+}
+
+/// Moves the todo to the trash, its items and templates are hidden with it
+/// until it is restored or purged.
 #[tracing::instrument(name = "Delete todo in the database", skip(transaction))]
 pub async fn delete_todo_by_name(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     user_id: i32,
+    if_match: Option<&ETagCondition>,
+    now: OffsetDateTime,
 ) -> Result<(), APIError> {
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Owner).await?;
+    check_todo_precondition(transaction, &todo, if_match).await?;
     let r = sqlx::query!(
-        r#"DELETE from todo WHERE name = $1 AND user_id = $2;"#,
-        todo_name.as_ref(),
-        user_id
+        r#"UPDATE todo SET deleted_at = $2
+           WHERE todo_id = $1 AND deleted_at IS NULL;"#,
+        todo.todo_id,
+        now,
     )
     .execute(&mut **transaction)
     .await?;
     match r.rows_affected() {
         0 => Err(APIError::NotFound(format!(
             "TODO: '{}' not found",
-            todo_name.as_ref()
+            todo_ref
         ))),
         1 => Ok(()),
         _ => Err(APIError::Internal(
@@ -114,65 +188,77 @@ pub async fn delete_todo_by_name(
 #[tracing::instrument(name = "Update todo in the database", skip(transaction, req))]
 pub async fn update_todo(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     req: &UpdateTodoRequest,
     user_id: i32,
-) -> Result<(), APIError> {
-    match sqlx::query!(
+    if_match: Option<&ETagCondition>,
+) -> Result<Todo, APIError> {
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Owner).await?;
+    check_todo_precondition(transaction, &todo, if_match).await?;
+    // Moving a todo into a group also needs a say in that group
+    if let Some(group_id) = req.group_id.as_ref().filter(|g| Some(**g) != todo.group_id) {
+        get_group(transaction, group_id, user_id, TodoRole::Editor).await?;
+    }
+    match sqlx::query_as!(
+        GetTodoQuery,
         r#"UPDATE todo SET
             name = $3
             , visibility = $4
-            WHERE name = $1 AND user_id = $2
-            RETURNING name;"#,
-        todo_name.as_ref(),
+            , group_id = $5
+            WHERE todo_id = $1 AND deleted_at IS NULL
+            RETURNING todo_id, user_id, name, visibility as "visibility: TodoVisibility",
+                      group_id, todo_role_of(todo, $2) as "role!: TodoRole", create_time, update_time;"#,
+        todo.todo_id,
         user_id,
         req.name.as_ref(),
         req.visibility.clone() as _,
+        req.group_id,
     )
     .fetch_one(&mut **transaction)
     .await
     {
-        Ok(_) => Ok(()),
+        Ok(result) => result.try_into(),
         Err(sqlx::Error::RowNotFound) => Err(APIError::NotFound(format!(
             "TODO: '{}' does not exist",
-            todo_name.as_ref(),
+            todo_ref,
         ))),
-        Err(sqlx::Error::Database(e)) => {
-            if e.is_unique_violation() {
-                return Err(APIError::AlreadyExists(format!(
-                    "TODO: '{}' already exists",
-                    req.name.as_ref()
-                )));
-            }
-            Err(APIError::Internal(sqlx::Error::Database(e).into()))
-        }
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(APIError::AlreadyExists(
+            format!("TODO: '{}' already exists", req.name.as_ref()),
+        )),
+        Err(err) => Err(err.into()),
     }
 }
 
 #[derive(Debug)]
 struct ListTodoQuery {
+    todo_id: Uuid,
+    user_id: i32,
     name: String,
     visibility: crate::domain::TodoVisibility,
+    group_id: Option<Uuid>,
+    role: TodoRole,
     create_time: sqlx::types::time::OffsetDateTime,
     update_time: sqlx::types::time::OffsetDateTime,
 }
 
-impl TryFrom<Vec<ListTodoQuery>> for ListTodo {
-    type Error = APIError;
-    fn try_from(value: Vec<ListTodoQuery>) -> Result<Self, Self::Error> {
-        let items: Result<Vec<ListTodoSingle>, Self::Error> =
-            value.into_iter().map(|i| i.try_into()).collect();
-        Ok(Self { items: items? })
-    }
+/// Sort key of `list_todo`, used as the pagination cursor.
+#[derive(Debug, Serialize, Deserialize)]
+struct ListTodoCursor {
+    #[serde(with = "time::serde::rfc3339")]
+    create_time: OffsetDateTime,
+    todo_id: Uuid,
 }
 
 impl TryFrom<ListTodoQuery> for ListTodoSingle {
     type Error = APIError;
     fn try_from(value: ListTodoQuery) -> Result<Self, Self::Error> {
         Ok(Self {
-            name: value.name.try_into()?,
+            todo_id: value.todo_id,
+            owner_id: value.user_id,
+            name: TodoName::from_stored(value.name),
             visibility: value.visibility,
+            group_id: value.group_id,
+            role: value.role,
             create_time: value.create_time,
             update_time: value.update_time,
         })
@@ -182,22 +268,83 @@ impl TryFrom<ListTodoQuery> for ListTodoSingle {
 #[tracing::instrument(name = "list todo in the database", skip(transaction))]
 pub async fn list_todo(
     transaction: &mut PgTransaction<'_>,
+    page: &PageRequest,
     user_id: i32,
 ) -> Result<ListTodo, APIError> {
-    match sqlx::query_as!(
+    let cursor: Option<ListTodoCursor> = page.decode_cursor()?;
+    let result = sqlx::query_as!(
         ListTodoQuery,
         r#"SELECT 
-            name
+            todo_id
+            , user_id
+            , name
             , visibility as "visibility: TodoVisibility"
+            , group_id
+            , role as "role!: TodoRole"
             , create_time
             , update_time 
-            FROM todo WHERE (user_id = $1 AND visibility = 'private') OR visibility = 'public';"#,
-        user_id
+            FROM (SELECT t.*, todo_role_of(t, $1) as role FROM todo as t) as t
+            WHERE role IS NOT NULL
+              AND deleted_at IS NULL
+              AND ($2::timestamptz IS NULL OR (create_time, todo_id) > ($2, $3::uuid))
+            ORDER BY create_time, todo_id
+            LIMIT $4;"#,
+        user_id,
+        cursor.as_ref().map(|c| c.create_time),
+        cursor.as_ref().map(|c| c.todo_id),
+        page.fetch_limit(),
     )
     .fetch_all(&mut **transaction)
-    .await
-    {
-        Ok(result) => Ok(result.try_into()?),
-        Err(err) => Err(APIError::Internal(err.into())),
-    }
+    .await?;
+    let (result, next_cursor) = page.paginate(result, |row| ListTodoCursor {
+        create_time: row.create_time,
+        todo_id: row.todo_id,
+    });
+    let items: Result<Vec<ListTodoSingle>, APIError> =
+        result.into_iter().map(|i| i.try_into()).collect();
+    Ok(ListTodo {
+        items: items?,
+        next_cursor,
+    })
+}
+
+#[tracing::instrument(name = "List changed todos in the database", skip(transaction))]
+pub async fn list_changed_todos(
+    transaction: &mut PgTransaction<'_>,
+    req: &SyncRequest,
+    user_id: i32,
+) -> Result<Vec<Changed<Todo>>, APIError> {
+    let result = sqlx::query!(
+        r#"SELECT change_seq, todo_id, user_id, name, visibility as "visibility: TodoVisibility", group_id,
+                  todo_role_of(t, $1) as "role!: TodoRole", create_time, update_time
+           FROM todo as t
+           WHERE todo_id IN (SELECT synced_todo_ids($1)) AND change_seq > $2 AND deleted_at IS NULL
+           ORDER BY change_seq
+           LIMIT $3;"#,
+        user_id,
+        req.since_change_seq(),
+        req.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    result
+        .into_iter()
+        .map(|row| {
+            Ok(Changed {
+                change_seq: row.change_seq,
+                todo_id: row.todo_id,
+                value: GetTodoQuery {
+                    todo_id: row.todo_id,
+                    user_id: row.user_id,
+                    name: row.name,
+                    visibility: row.visibility,
+                    group_id: row.group_id,
+                    role: row.role,
+                    create_time: row.create_time,
+                    update_time: row.update_time,
+                }
+                .try_into()?,
+            })
+        })
+        .collect()
 }
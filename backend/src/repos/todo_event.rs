@@ -0,0 +1,103 @@
+use sqlx::{PgPool, PgTransaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    domain::{TodoEvent, TodoEventEntity, TodoEventKind},
+    error::APIError,
+};
+
+/// Id of the latest event of the todo, 0 when it has none.
+#[tracing::instrument(name = "Get last todo event id", skip(transaction))]
+pub async fn get_last_todo_event_id(
+    transaction: &mut PgTransaction<'_>,
+    todo_id: &Uuid,
+) -> Result<i64, APIError> {
+    let result = sqlx::query_scalar!(
+        r#"SELECT COALESCE(MAX(event_id), 0) as "event_id!" FROM todo_event WHERE todo_id = $1"#,
+        todo_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(result)
+}
+
+/// Whether the event of the todo is still kept, resuming after an event that
+/// was purged could miss the ones purged with it.
+#[tracing::instrument(name = "Check todo event", skip(transaction))]
+pub async fn todo_event_exists(
+    transaction: &mut PgTransaction<'_>,
+    todo_id: &Uuid,
+    event_id: i64,
+) -> Result<bool, APIError> {
+    let result = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+               SELECT 1 FROM todo_event WHERE todo_id = $1 AND event_id = $2
+           ) AS "exists!""#,
+        todo_id,
+        event_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(result)
+}
+
+/// Deletes the events written before `before`, returning how many.
+#[tracing::instrument(name = "Purge todo events in the database", skip(transaction))]
+pub async fn purge_todo_events(
+    transaction: &mut PgTransaction<'_>,
+    before: OffsetDateTime,
+) -> Result<u64, APIError> {
+    let result = sqlx::query!(r#"DELETE FROM todo_event WHERE create_time < $1;"#, before)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Events of the todo after `after_event_id`, oldest first. Reads from the
+/// pool as event streams outlive the request transaction.
+#[tracing::instrument(name = "List todo events", skip(pool))]
+pub async fn list_todo_events(
+    pool: &PgPool,
+    todo_id: &Uuid,
+    after_event_id: i64,
+    limit: i64,
+) -> Result<Vec<TodoEvent>, APIError> {
+    let result = sqlx::query_as!(
+        TodoEvent,
+        r#"SELECT event_id, todo_id, entity as "entity: TodoEventEntity", entity_id,
+                  kind as "kind: TodoEventKind", create_time
+           FROM todo_event
+           WHERE todo_id = $1 AND event_id > $2
+           ORDER BY event_id
+           LIMIT $3"#,
+        todo_id,
+        after_event_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(result)
+}
+
+/// Whether the user still sees the todo. Event streams check it while open,
+/// as the membership they connected with may be revoked. Reads from the pool
+/// like `list_todo_events`.
+#[tracing::instrument(name = "Check todo event access", skip(pool))]
+pub async fn can_read_todo_events(
+    pool: &PgPool,
+    todo_id: &Uuid,
+    user_id: i32,
+) -> Result<bool, APIError> {
+    let result = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+               SELECT 1 FROM todo as t
+               WHERE todo_id = $1 AND deleted_at IS NULL AND todo_role_of(t, $2) IS NOT NULL
+           ) AS "exists!""#,
+        todo_id,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(result)
+}
@@ -1,32 +1,34 @@
 use eyre::eyre;
+use serde::{Deserialize, Serialize};
 use sqlx::PgTransaction;
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::{
     domain::{
-        ListTodoItem, ListTodoItemSingle, NewTodoItemRequest, TodoItem, TodoName,
-        UpdateTodoItemRequest,
+        Changed, ETagCondition, ListTodoItem, ListTodoItemSingle, NewTodoItemRequest, PageRequest,
+        SyncRequest, TodoItem, TodoItemFilter, TodoRef, TodoRole, UpdateTodoItemRequest,
     },
     error::APIError,
 };
 
-use super::get_todo_by_name;
+use super::{get_todo_by_name, trash_other_open_items_for_template};
 
 #[tracing::instrument(
     name = "Create todo item in the database",
-    skip(transaction, todo_name, req)
+    skip(transaction, todo_ref, req)
 )]
 pub async fn create_todo_item(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     req: &NewTodoItemRequest,
     user_id: i32,
 ) -> Result<TodoItem, APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
     let result = sqlx::query_as!(
         TodoItem,
         r#"INSERT INTO todo_item (todo_item_id, todo_id, title, due_date, recurring_template_id, user_id) VALUES ($1, $2, $3, $4, $5, $6)
-           RETURNING todo_item_id, title, due_date, is_complete, complete_time, create_time, update_time
+           RETURNING todo_item_id, title, due_date, is_complete, complete_time, recurring_template_id, create_time, update_time
            ;"#,
         Uuid::new_v4(),
         todo.todo_id,
@@ -42,22 +44,23 @@ pub async fn create_todo_item(
 
 #[tracing::instrument(
     name = "Get todo item in the database",
-    skip(transaction, todo_name, todo_item)
+    skip(transaction, todo_ref, todo_item)
 )]
 pub async fn get_todo_item(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     todo_item: &Uuid,
     user_id: i32,
 ) -> Result<TodoItem, APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Viewer).await?;
     match sqlx::query_as!(
         TodoItem,
-        r#"SELECT todo_item_id, title, is_complete, due_date, complete_time, create_time, update_time
+        r#"SELECT todo_item_id, title, is_complete, due_date, complete_time, recurring_template_id, create_time, update_time
            FROM todo_item
            WHERE
               todo_id = $1
-              AND todo_item_id = $2;"#,
+              AND todo_item_id = $2
+              AND deleted_at IS NULL;"#,
         todo.todo_id,
         todo_item,
     )
@@ -69,24 +72,25 @@ pub async fn get_todo_item(
             "todo item: {} is not found",
             todo_item
         ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
 #[tracing::instrument(
     name = "Update todo item in the database",
-    skip(transaction, todo_name, todo_item, req)
+    skip(transaction, todo_ref, todo_item, req)
 )]
 pub async fn update_todo_item(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     todo_item: &Uuid,
     req: &UpdateTodoItemRequest,
     user_id: i32,
+    if_match: Option<&ETagCondition>,
 ) -> Result<TodoItem, APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
     let todo_id = todo.todo_id;
-    get_todo_item_for_update(transaction, &todo_id, todo_item).await?;
+    get_todo_item_for_update(transaction, &todo_id, todo_item, if_match).await?;
     match sqlx::query_as!(
         TodoItem,
         r#"UPDATE todo_item SET
@@ -95,7 +99,8 @@ pub async fn update_todo_item(
            WHERE
               todo_id = $1
               AND todo_item_id = $2
-           RETURNING todo_item_id, title, is_complete, due_date, complete_time, create_time, update_time
+              AND deleted_at IS NULL
+           RETURNING todo_item_id, title, is_complete, due_date, complete_time, recurring_template_id, create_time, update_time
             ;"#,
         todo.todo_id,
         todo_item,
@@ -110,42 +115,155 @@ pub async fn update_todo_item(
             "todo item: {} is not found",
             todo_item
         ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
-impl From<Vec<ListTodoItemSingle>> for ListTodoItem {
-    fn from(value: Vec<ListTodoItemSingle>) -> Self {
-        Self {
-            items: value.into_iter().collect(),
+/// Sort key of `list_todo_items`, used as the pagination cursor. Open items
+/// are listed first by due date, then completed items by most recently
+/// completed.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum ListTodoItemCursor {
+    Open {
+        due_date: Date,
+        #[serde(with = "time::serde::rfc3339")]
+        create_time: OffsetDateTime,
+        todo_item_id: Uuid,
+    },
+    Completed {
+        #[serde(with = "time::serde::rfc3339")]
+        complete_time: OffsetDateTime,
+        todo_item_id: Uuid,
+    },
+}
+
+impl From<&ListTodoItemSingle> for ListTodoItemCursor {
+    fn from(row: &ListTodoItemSingle) -> Self {
+        match row.complete_time {
+            Some(complete_time) if row.is_complete => Self::Completed {
+                complete_time,
+                todo_item_id: row.todo_item_id,
+            },
+            _ => Self::Open {
+                due_date: row.due_date,
+                create_time: row.create_time,
+                todo_item_id: row.todo_item_id,
+            },
         }
     }
 }
 
-#[tracing::instrument(name = "List todo items in the database", skip(transaction, todo_name))]
+#[tracing::instrument(name = "List todo items in the database", skip(transaction, todo_ref))]
 pub async fn list_todo_items(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
+    filter: &TodoItemFilter,
+    page: &PageRequest,
     user_id: i32,
 ) -> Result<ListTodoItem, APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
-    match sqlx::query_as!(
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Viewer).await?;
+    let cursor: Option<ListTodoItemCursor> = page.decode_cursor()?;
+    let limit = page.fetch_limit();
+
+    let (open_after, completed_after) = match cursor {
+        None => (None, None),
+        Some(ListTodoItemCursor::Open {
+            due_date,
+            create_time,
+            todo_item_id,
+        }) if filter.includes_open() => (Some((due_date, create_time, todo_item_id)), None),
+        Some(ListTodoItemCursor::Completed {
+            complete_time,
+            todo_item_id,
+        }) if filter.includes_completed() => (None, Some((complete_time, todo_item_id))),
+        Some(_) => return Err(APIError::BadRequest("Invalid cursor".to_string())),
+    };
+
+    // A cursor on a completed item means the open items are exhausted.
+    let mut rows = Vec::new();
+    if filter.includes_open() && completed_after.is_none() {
+        rows = list_open_todo_items(transaction, &todo.todo_id, open_after, limit).await?;
+    }
+    let remaining = limit - rows.len() as i64;
+    if filter.includes_completed() && remaining > 0 {
+        let completed = list_completed_todo_items(
+            transaction,
+            &todo.todo_id,
+            filter,
+            completed_after,
+            remaining,
+        )
+        .await?;
+        rows.extend(completed);
+    }
+
+    let (items, next_cursor) = page.paginate(rows, |row| ListTodoItemCursor::from(row));
+    Ok(ListTodoItem { items, next_cursor })
+}
+
+async fn list_open_todo_items(
+    transaction: &mut PgTransaction<'_>,
+    todo_id: &Uuid,
+    after: Option<(Date, OffsetDateTime, Uuid)>,
+    limit: i64,
+) -> Result<Vec<ListTodoItemSingle>, APIError> {
+    let result = sqlx::query_as!(
         ListTodoItemSingle,
         r#"SELECT todo_item_id, title, is_complete, due_date, complete_time, create_time, update_time
            FROM todo_item
            WHERE
               todo_id = $1
+              AND deleted_at IS NULL
               AND is_complete = FALSE
-            ORDER BY due_date, create_time
+              AND ($2::date IS NULL
+                   OR (due_date, create_time, todo_item_id) > ($2, $3::timestamptz, $4::uuid))
+            ORDER BY due_date, create_time, todo_item_id
+            LIMIT $5
         ;"#,
-        todo.todo_id,
+        todo_id,
+        after.map(|a| a.0),
+        after.map(|a| a.1),
+        after.map(|a| a.2),
+        limit,
     )
     .fetch_all(&mut **transaction)
-    .await
-    {
-        Ok(result) => Ok(result.into()),
-        Err(err) => Err(APIError::Internal(err.into())),
-    }
+    .await?;
+    Ok(result)
+}
+
+async fn list_completed_todo_items(
+    transaction: &mut PgTransaction<'_>,
+    todo_id: &Uuid,
+    filter: &TodoItemFilter,
+    after: Option<(OffsetDateTime, Uuid)>,
+    limit: i64,
+) -> Result<Vec<ListTodoItemSingle>, APIError> {
+    let result = sqlx::query_as!(
+        ListTodoItemSingle,
+        r#"SELECT todo_item_id, title, is_complete, due_date, complete_time, create_time, update_time
+           FROM todo_item
+           WHERE
+              todo_id = $1
+              AND deleted_at IS NULL
+              AND is_complete = TRUE
+              AND ($2::timestamptz IS NULL OR complete_time >= $2)
+              AND ($3::timestamptz IS NULL OR complete_time < $3)
+              AND ($4::timestamptz IS NULL
+                   OR (complete_time, todo_item_id) < ($4, $5::uuid))
+            ORDER BY complete_time DESC, todo_item_id DESC
+            LIMIT $6
+        ;"#,
+        todo_id,
+        filter.completed_since(),
+        filter.completed_before(),
+        after.map(|a| a.0),
+        after.map(|a| a.1),
+        limit,
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(result)
 }
 
 #[tracing::instrument(
@@ -156,14 +274,16 @@ async fn get_todo_item_for_update(
     transaction: &mut PgTransaction<'_>,
     todo_id: &Uuid,
     todo_item: &Uuid,
+    if_match: Option<&ETagCondition>,
 ) -> Result<(), APIError> {
     match sqlx::query!(
         r#"
-            SELECT todo_item_id, is_complete
+            SELECT todo_item_id, is_complete, update_time
             FROM todo_item
             WHERE
                 todo_id = $1
                 AND todo_item_id = $2
+                AND deleted_at IS NULL
             FOR UPDATE;
         "#,
         &todo_id,
@@ -173,8 +293,11 @@ async fn get_todo_item_for_update(
     .await
     {
         Ok(t) => {
+            if let Some(if_match) = if_match {
+                if_match.check(&t.update_time.into())?;
+            }
             if t.is_complete {
-                return Err(APIError::BadRequest(
+                return Err(APIError::Conflict(
                     "Todo item is already complete".to_string(),
                 ));
             }
@@ -187,34 +310,37 @@ async fn get_todo_item_for_update(
             )));
         }
         Err(e) => {
-            return Err(APIError::Internal(e.into()));
+            return Err(e.into());
         }
     }
 }
 
-#[tracing::instrument(name = "Complete todo item", skip(transaction, todo_name, todo_item))]
+#[tracing::instrument(name = "Complete todo item", skip(transaction, todo_ref, todo_item))]
 pub async fn complete_todo_item(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     todo_item: &Uuid,
     user_id: i32,
+    now: OffsetDateTime,
 ) -> Result<TodoItem, APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
     let todo_id = todo.todo_id;
-    get_todo_item_for_update(transaction, &todo_id, todo_item).await?;
+    get_todo_item_for_update(transaction, &todo_id, todo_item, None).await?;
 
     match sqlx::query_as!(
         TodoItem,
         r#"UPDATE todo_item SET
               is_complete = TRUE,
-              complete_time = NOW()
+              complete_time = $3
            WHERE
               todo_id = $1
               AND todo_item_id = $2
-           RETURNING todo_item_id, title, due_date, is_complete, complete_time, create_time, update_time
+              AND deleted_at IS NULL
+           RETURNING todo_item_id, title, due_date, is_complete, complete_time, recurring_template_id, create_time, update_time
             ;"#,
         &todo_id,
         todo_item,
+        now,
     )
     .fetch_one(&mut **transaction)
     .await
@@ -224,28 +350,120 @@ pub async fn complete_todo_item(
             "todo item: {} is not found",
             todo_item
         ))),
-        Err(err) => Err(APIError::Internal(err.into())),
+        Err(err) => Err(err.into()),
     }
 }
 
+#[tracing::instrument(name = "Reopen todo item", skip(transaction, todo_ref, todo_item))]
+pub async fn reopen_todo_item(
+    transaction: &mut PgTransaction<'_>,
+    todo_ref: &TodoRef,
+    todo_item: &Uuid,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<TodoItem, APIError> {
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
+    let todo_id = todo.todo_id;
+
+    let current = match sqlx::query!(
+        r#"
+            SELECT is_complete, recurring_template_id
+            FROM todo_item
+            WHERE
+                todo_id = $1
+                AND todo_item_id = $2
+                AND deleted_at IS NULL
+            FOR UPDATE;
+        "#,
+        &todo_id,
+        todo_item,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    {
+        Ok(t) => t,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(APIError::NotFound(format!(
+                "todo item: {} is not found",
+                todo_item
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !current.is_complete {
+        return Err(APIError::Conflict("Todo item is not complete".to_string()));
+    }
+
+    let result = sqlx::query_as!(
+        TodoItem,
+        r#"UPDATE todo_item SET
+              is_complete = FALSE,
+              complete_time = NULL
+           WHERE
+              todo_id = $1
+              AND todo_item_id = $2
+              AND deleted_at IS NULL
+           RETURNING todo_item_id, title, due_date, is_complete, complete_time, recurring_template_id, create_time, update_time
+            ;"#,
+        &todo_id,
+        todo_item,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+
+    // The reopened item is the template's open occurrence again, so any
+    // occurrence generated after it was completed goes to the trash.
+    if let Some(template_id) = current.recurring_template_id {
+        let trashed =
+            trash_other_open_items_for_template(transaction, &template_id, todo_item, now).await?;
+        if trashed > 0 {
+            tracing::info!(
+                "Trashed {} open item(s) of recurring template {}",
+                trashed,
+                template_id
+            );
+        }
+    }
+
+    Ok(result)
+}
+
+/// Moves the item to the trash of the todo's owner.
 #[tracing::instrument(
     name = "Delete todo items in the database",
-    skip(transaction, todo_name)
+    skip(transaction, todo_ref)
 )]
 pub async fn delete_todo_item(
     transaction: &mut PgTransaction<'_>,
-    todo_name: &TodoName,
+    todo_ref: &TodoRef,
     todo_item: &Uuid,
     user_id: i32,
+    if_match: Option<&ETagCondition>,
+    now: OffsetDateTime,
 ) -> Result<(), APIError> {
-    let todo = get_todo_by_name(transaction, todo_name, user_id).await?;
+    let todo = get_todo_by_name(transaction, todo_ref, user_id, TodoRole::Editor).await?;
+    if let Some(if_match) = if_match {
+        let update_time = sqlx::query_scalar!(
+            r#"SELECT update_time FROM todo_item
+               WHERE todo_id = $1 AND todo_item_id = $2 AND deleted_at IS NULL
+               FOR UPDATE;"#,
+            todo.todo_id,
+            todo_item,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .ok_or_else(|| APIError::NotFound(format!("TodoItem not found: {}", todo_item)))?;
+        if_match.check(&update_time.into())?;
+    }
     let result = sqlx::query!(
-        r#"DELETE from todo_item
+        r#"UPDATE todo_item SET deleted_at = $3
            WHERE
               todo_id = $1
-              AND todo_item_id = $2;"#,
+              AND todo_item_id = $2
+              AND deleted_at IS NULL;"#,
         todo.todo_id,
         todo_item,
+        now,
     )
     .execute(&mut **transaction)
     .await?;
@@ -260,3 +478,43 @@ pub async fn delete_todo_item(
         )),
     }
 }
+
+#[tracing::instrument(name = "List changed todo items in the database", skip(transaction))]
+pub async fn list_changed_todo_items(
+    transaction: &mut PgTransaction<'_>,
+    req: &SyncRequest,
+    user_id: i32,
+) -> Result<Vec<Changed<TodoItem>>, APIError> {
+    let result = sqlx::query!(
+        r#"SELECT i.change_seq, i.todo_id, i.todo_item_id, i.title, i.is_complete, i.due_date, i.complete_time,
+                  i.recurring_template_id, i.create_time, i.update_time
+           FROM todo_item as i
+           INNER JOIN todo as t ON t.todo_id = i.todo_id
+           WHERE t.todo_id IN (SELECT synced_todo_ids($1)) AND i.change_seq > $2
+             AND i.deleted_at IS NULL AND t.deleted_at IS NULL
+           ORDER BY i.change_seq
+           LIMIT $3;"#,
+        user_id,
+        req.since_change_seq(),
+        req.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(result
+        .into_iter()
+        .map(|row| Changed {
+            change_seq: row.change_seq,
+            todo_id: row.todo_id,
+            value: TodoItem {
+                todo_item_id: row.todo_item_id,
+                title: row.title,
+                due_date: row.due_date,
+                is_complete: row.is_complete,
+                complete_time: row.complete_time,
+                recurring_template_id: row.recurring_template_id,
+                create_time: row.create_time,
+                update_time: row.update_time,
+            },
+        })
+        .collect())
+}
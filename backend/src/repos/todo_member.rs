@@ -0,0 +1,161 @@
+use sqlx::PgTransaction;
+
+use crate::{
+    domain::{ListTodoMember, NewTodoMemberRequest, PageRequest, Todo, TodoMember, TodoRole},
+    error::APIError,
+};
+
+use super::find_by_email;
+
+/// The creator of the todo followed by its members, by user id.
+#[tracing::instrument(name = "List todo members in the database", skip(transaction))]
+pub async fn list_todo_members(
+    transaction: &mut PgTransaction<'_>,
+    todo: &Todo,
+    page: &PageRequest,
+) -> Result<ListTodoMember, APIError> {
+    let cursor: Option<i32> = page.decode_cursor()?;
+    let result = sqlx::query_as!(
+        TodoMember,
+        r#"SELECT user_id as "user_id!", email as "email!", role as "role!: TodoRole",
+                  is_creator as "is_creator!", create_time as "create_time!"
+           FROM (
+               SELECT u.user_id, u.email, 'owner'::todo_role as role, true as is_creator, t.create_time
+               FROM todo as t
+               INNER JOIN users as u ON u.user_id = t.user_id
+               WHERE t.todo_id = $1
+               UNION ALL
+               SELECT u.user_id, u.email, m.role, false, m.create_time
+               FROM todo_member as m
+               INNER JOIN users as u ON u.user_id = m.user_id
+               WHERE m.todo_id = $1
+           ) as members
+           WHERE $2::integer IS NULL OR user_id > $2
+           ORDER BY user_id
+           LIMIT $3;"#,
+        todo.todo_id,
+        cursor,
+        page.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let (items, next_cursor) = page.paginate(result, |row| row.user_id);
+    Ok(ListTodoMember { items, next_cursor })
+}
+
+/// Shares the todo with the user registered with the email. An unknown
+/// email fails like any email that can't be added, so owners can't probe for
+/// accounts.
+#[tracing::instrument(name = "Add todo member in the database", skip(transaction))]
+pub async fn add_todo_member(
+    transaction: &mut PgTransaction<'_>,
+    todo: &Todo,
+    req: &NewTodoMemberRequest,
+) -> Result<TodoMember, APIError> {
+    let user = find_by_email(transaction, &req.email)
+        .await?
+        .ok_or_else(|| {
+            APIError::BadRequest(format!(
+                "todo: {} can't be shared with this email",
+                todo.name.as_ref()
+            ))
+        })?;
+    if user.user_id == todo.owner_id {
+        return Err(APIError::AlreadyExists(format!(
+            "user: {} created todo: {}",
+            req.email,
+            todo.name.as_ref()
+        )));
+    }
+    match sqlx::query!(
+        r#"INSERT INTO todo_member (todo_id, user_id, role) VALUES ($1, $2, $3)
+           RETURNING create_time;"#,
+        todo.todo_id,
+        user.user_id,
+        req.role as _,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    {
+        Ok(row) => Ok(TodoMember {
+            user_id: user.user_id,
+            email: user.email,
+            role: req.role,
+            is_creator: false,
+            create_time: row.create_time,
+        }),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Err(APIError::AlreadyExists(format!(
+                "user: {} is already a member of todo: {}",
+                req.email,
+                todo.name.as_ref()
+            )))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn check_not_creator(todo: &Todo, member_id: i32) -> Result<(), APIError> {
+    if member_id == todo.owner_id {
+        return Err(APIError::Conflict(format!(
+            "user: {} created todo: {} and always owns it",
+            member_id,
+            todo.name.as_ref()
+        )));
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Update todo member in the database", skip(transaction))]
+pub async fn update_todo_member(
+    transaction: &mut PgTransaction<'_>,
+    todo: &Todo,
+    member_id: i32,
+    role: TodoRole,
+) -> Result<TodoMember, APIError> {
+    check_not_creator(todo, member_id)?;
+    sqlx::query_as!(
+        TodoMember,
+        r#"UPDATE todo_member as m SET role = $3
+           FROM users as u
+           WHERE u.user_id = m.user_id AND m.todo_id = $1 AND m.user_id = $2
+           RETURNING m.user_id, u.email, m.role as "role: TodoRole",
+                     false as "is_creator!", m.create_time;"#,
+        todo.todo_id,
+        member_id,
+        role as _,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| {
+        APIError::NotFound(format!(
+            "user: {} is not a member of todo: {}",
+            member_id,
+            todo.name.as_ref()
+        ))
+    })
+}
+
+#[tracing::instrument(name = "Remove todo member in the database", skip(transaction))]
+pub async fn remove_todo_member(
+    transaction: &mut PgTransaction<'_>,
+    todo: &Todo,
+    member_id: i32,
+) -> Result<(), APIError> {
+    check_not_creator(todo, member_id)?;
+    let r = sqlx::query!(
+        r#"DELETE FROM todo_member WHERE todo_id = $1 AND user_id = $2;"#,
+        todo.todo_id,
+        member_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    match r.rows_affected() {
+        0 => Err(APIError::NotFound(format!(
+            "user: {} is not a member of todo: {}",
+            member_id,
+            todo.name.as_ref()
+        ))),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgTransaction;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    domain::{ListTrash, PageRequest, TrashEntity, TrashEntry},
+    error::APIError,
+};
+
+/// Sort key of `list_trash`, used as the pagination cursor.
+#[derive(Debug, Serialize, Deserialize)]
+struct ListTrashCursor {
+    #[serde(with = "time::serde::rfc3339")]
+    deleted_at: OffsetDateTime,
+    id: Uuid,
+}
+
+/// The trashed todos the user owns and the trashed items of the other todos
+/// they edit, most recently deleted first. Only owning or being a member
+/// counts, not the editor role every user has on a public todo, or anyone
+/// would see the trash of every public todo.
+#[tracing::instrument(name = "List trash in the database", skip(transaction))]
+pub async fn list_trash(
+    transaction: &mut PgTransaction<'_>,
+    page: &PageRequest,
+    user_id: i32,
+) -> Result<ListTrash, APIError> {
+    let cursor: Option<ListTrashCursor> = page.decode_cursor()?;
+    let result = sqlx::query_as!(
+        TrashEntry,
+        r#"SELECT entity as "entity!: TrashEntity", id as "id!", todo_id as "todo_id!", title as "title!",
+                  deleted_at as "deleted_at!"
+           FROM (
+               SELECT 'todo'::sync_entity as entity, todo_id as id, todo_id, name as title, deleted_at
+               FROM todo as t
+               WHERE todo_member_role_of(t, $1) = 'owner' AND deleted_at IS NOT NULL
+               UNION ALL
+               SELECT 'item'::sync_entity, i.todo_item_id, i.todo_id, i.title, i.deleted_at
+               FROM todo_item as i
+               INNER JOIN todo as t ON t.todo_id = i.todo_id
+               WHERE todo_member_role_of(t, $1) >= 'editor'
+                 AND i.deleted_at IS NOT NULL
+                 AND t.deleted_at IS NULL
+           ) as trash
+           WHERE $2::timestamptz IS NULL OR (deleted_at, id) < ($2, $3::uuid)
+           ORDER BY deleted_at DESC, id DESC
+           LIMIT $4;"#,
+        user_id,
+        cursor.as_ref().map(|c| c.deleted_at),
+        cursor.as_ref().map(|c| c.id),
+        page.fetch_limit(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let (items, next_cursor) = page.paginate(result, |row| ListTrashCursor {
+        deleted_at: row.deleted_at,
+        id: row.id,
+    });
+    Ok(ListTrash { items, next_cursor })
+}
+
+/// Takes a todo or an item out of the trash of the user, as listed by
+/// `list_trash`. A todo fails to come back while another todo of its creator
+/// uses its name.
+#[tracing::instrument(name = "Restore from trash in the database", skip(transaction))]
+pub async fn restore_from_trash(
+    transaction: &mut PgTransaction<'_>,
+    id: &Uuid,
+    user_id: i32,
+) -> Result<TrashEntity, APIError> {
+    let restored_todo = sqlx::query!(
+        r#"UPDATE todo as t SET deleted_at = NULL
+           WHERE todo_id = $1 AND todo_member_role_of(t, $2) = 'owner' AND deleted_at IS NOT NULL;"#,
+        id,
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    if restored_todo.rows_affected() > 0 {
+        // Sync clients dropped the items and templates along with the todo,
+        // moving them past their token brings them back.
+        sqlx::query!(
+            r#"UPDATE todo_item SET change_seq = change_seq
+               WHERE todo_id = $1 AND deleted_at IS NULL;"#,
+            id,
+        )
+        .execute(&mut **transaction)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE recurring_template SET change_seq = change_seq WHERE todo_id = $1;"#,
+            id,
+        )
+        .execute(&mut **transaction)
+        .await?;
+        return Ok(TrashEntity::Todo);
+    }
+
+    let restored_item = sqlx::query!(
+        r#"UPDATE todo_item as i SET deleted_at = NULL
+           FROM todo as t
+           WHERE t.todo_id = i.todo_id
+             AND i.todo_item_id = $1
+             AND todo_member_role_of(t, $2) >= 'editor'
+             AND i.deleted_at IS NOT NULL
+             AND t.deleted_at IS NULL;"#,
+        id,
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    if restored_item.rows_affected() > 0 {
+        return Ok(TrashEntity::Item);
+    }
+
+    Err(APIError::NotFound(format!("{} is not in the trash", id)))
+}
+
+/// Deletes for good what was moved to the trash before `before`, returning
+/// the number of items and of todos deleted.
+#[tracing::instrument(name = "Purge trash in the database", skip(transaction))]
+pub async fn purge_trash(
+    transaction: &mut PgTransaction<'_>,
+    before: OffsetDateTime,
+) -> Result<(u64, u64), APIError> {
+    let items = sqlx::query!(r#"DELETE FROM todo_item WHERE deleted_at < $1;"#, before,)
+        .execute(&mut **transaction)
+        .await?;
+    let todos = sqlx::query!(r#"DELETE FROM todo WHERE deleted_at < $1;"#, before)
+        .execute(&mut **transaction)
+        .await?;
+    Ok((items.rows_affected(), todos.rows_affected()))
+}
@@ -1,14 +1,16 @@
 use eyre::Result;
 use sqlx::PgTransaction;
+use time::{Date, OffsetDateTime};
 
-use crate::domain::User;
+use crate::domain::{TimeZoneName, User};
+use crate::error::APIError;
 
 
 pub async fn find_by_email(transaction: &mut PgTransaction<'_>, email: &str) -> Result<Option<User>> {
     let row = sqlx::query_as!(
         User,
         r#"
-        SELECT user_id, email, create_time, update_time
+        SELECT user_id, email, time_zone, create_time, update_time
         FROM users
         WHERE email = $1
         "#,
@@ -18,3 +20,93 @@ pub async fn find_by_email(transaction: &mut PgTransaction<'_>, email: &str) ->
     .await?;
     Ok(row)
 }
+
+#[tracing::instrument(name = "Get user in the database", skip(transaction))]
+pub async fn get_user(transaction: &mut PgTransaction<'_>, user_id: i32) -> Result<User, APIError> {
+    sqlx::query_as!(
+        User,
+        r#"
+        SELECT user_id, email, time_zone, create_time, update_time
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| APIError::NotFound(format!("user: {} is not found", user_id)))
+}
+
+#[tracing::instrument(name = "Update user time zone in the database", skip(transaction))]
+pub async fn update_user_time_zone(
+    transaction: &mut PgTransaction<'_>,
+    user_id: i32,
+    time_zone: &TimeZoneName,
+) -> Result<User, APIError> {
+    let known = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM pg_timezone_names WHERE name = $1) as "known!""#,
+        time_zone.as_ref(),
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    if !known {
+        return Err(APIError::BadRequest(format!(
+            "Time zone: {} is unknown",
+            time_zone.as_ref()
+        )));
+    }
+
+    sqlx::query_as!(
+        User,
+        r#"
+        UPDATE users SET time_zone = $2
+        WHERE user_id = $1
+        RETURNING user_id, email, time_zone, create_time, update_time
+        "#,
+        user_id,
+        time_zone.as_ref(),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| APIError::NotFound(format!("user: {} is not found", user_id)))
+}
+
+/// The date `now` falls on in the user's time zone.
+#[tracing::instrument(name = "Get today for user", skip(transaction))]
+pub async fn get_user_today(
+    transaction: &mut PgTransaction<'_>,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<Date, APIError> {
+    sqlx::query_scalar!(
+        r#"SELECT ($2::timestamptz AT TIME ZONE time_zone)::date as "today!" FROM users WHERE user_id = $1"#,
+        user_id,
+        now,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| APIError::NotFound(format!("user: {} is not found", user_id)))
+}
+
+/// The time zones of users owning an active recurring template, each with
+/// the local date `now` falls on.
+#[tracing::instrument(name = "List time zones with active templates", skip(transaction))]
+pub async fn list_template_time_zones(
+    transaction: &mut PgTransaction<'_>,
+    now: OffsetDateTime,
+) -> Result<Vec<(String, Date)>, APIError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT DISTINCT u.time_zone, ($1::timestamptz AT TIME ZONE u.time_zone)::date as "today!"
+        FROM recurring_template as r
+        INNER JOIN todo as t ON t.todo_id = r.todo_id
+        INNER JOIN users as u ON u.user_id = t.user_id
+        WHERE r.is_active = TRUE AND t.deleted_at IS NULL
+        ORDER BY u.time_zone
+        "#,
+        now,
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.time_zone, r.today)).collect())
+}
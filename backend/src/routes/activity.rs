@@ -0,0 +1,146 @@
+use axum::{extract, Json};
+use serde::Serialize;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::domain::{self, AuditEntry, ListActivity, TodoRole};
+use crate::error::APIError;
+use crate::extractors::AuthenticatedUser;
+use crate::repos;
+use crate::routes::{PaginationQuery, TodoPath};
+use crate::tx::tx::Tx;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntity {
+    Todo,
+    Item,
+    Template,
+    /// A user's membership of the todo
+    Member,
+}
+
+impl From<domain::AuditEntity> for AuditEntity {
+    fn from(value: domain::AuditEntity) -> Self {
+        match value {
+            domain::AuditEntity::Todo => Self::Todo,
+            domain::AuditEntity::Item => Self::Item,
+            domain::AuditEntity::Template => Self::Template,
+            domain::AuditEntity::Member => Self::Member,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Completed,
+    Reopened,
+    /// Moved to the trash, or deleted for good for a template
+    Deleted,
+    /// Restored from the trash
+    Restored,
+}
+
+impl From<domain::AuditAction> for AuditAction {
+    fn from(value: domain::AuditAction) -> Self {
+        match value {
+            domain::AuditAction::Created => Self::Created,
+            domain::AuditAction::Updated => Self::Updated,
+            domain::AuditAction::Completed => Self::Completed,
+            domain::AuditAction::Reopened => Self::Reopened,
+            domain::AuditAction::Deleted => Self::Deleted,
+            domain::AuditAction::Restored => Self::Restored,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivityResponse {
+    pub activity_id: i64,
+    pub todo_id: Uuid,
+    /// The user who made the change, absent for changes made by the server
+    /// like generated recurring items
+    pub actor_id: Option<i32>,
+    pub actor_email: Option<String>,
+    /// The `x-request-id` of the request that made the change
+    pub request_id: Option<String>,
+    pub entity: AuditEntity,
+    /// The `todo_id`, `todo_item_id` or `template_id` of what changed, the
+    /// `todo_id` for a member whose `user_id` is in `before` and `after`
+    pub entity_id: Uuid,
+    pub action: AuditAction,
+    /// The stored row before the change, absent when it was created
+    pub before: Option<serde_json::Value>,
+    /// The stored row after the change, absent when it was deleted for good
+    pub after: Option<serde_json::Value>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+}
+
+impl From<AuditEntry> for ActivityResponse {
+    fn from(value: AuditEntry) -> Self {
+        Self {
+            activity_id: value.audit_id,
+            todo_id: value.todo_id,
+            actor_id: value.actor_id,
+            actor_email: value.actor_email,
+            request_id: value.request_id,
+            entity: value.entity.into(),
+            entity_id: value.entity_id,
+            action: value.action.into(),
+            before: value.before,
+            after: value.after,
+            create_time: value.create_time,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListActivityResponse {
+    pub items: Vec<ActivityResponse>,
+    pub next_cursor: Option<String>,
+}
+
+impl From<ListActivity> for ListActivityResponse {
+    fn from(value: ListActivity) -> Self {
+        Self {
+            items: value.items.into_iter().map(|a| a.into()).collect(),
+            next_cursor: value.next_cursor.map(|c| c.into()),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/activity",
+    tag = "todo",
+    params(TodoPath, PaginationQuery),
+    responses((
+        status = 200,
+        description = "A page of the changes to the todo, its items, its templates and its members, latest first",
+        body = ListActivityResponse,
+    )),
+)]
+#[tracing::instrument(
+    name = "List TODO activity"
+    skip(tx, todo_str),
+    fields(
+        todo = todo_str
+    )
+)]
+pub async fn todo_activity(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(todo_str): extract::Path<String>,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
+) -> Result<Json<ListActivityResponse>, APIError> {
+    let todo_ref = todo_str.try_into()?;
+    let page = pagination.try_into()?;
+    let todo = repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, TodoRole::Viewer).await?;
+    let activity = repos::list_activity(&mut tx, &todo.todo_id, &page).await?;
+    Ok(Json(activity.into()))
+}
@@ -1,28 +1,43 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Redirect},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
 };
 use eyre::Result;
 use oauth2::{
-    basic::BasicClient, reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId,
-    ClientSecret, CsrfToken, RedirectUrl, RequestTokenError, Scope, TokenResponse, TokenUrl,
+    basic::{BasicClient, BasicErrorResponse},
+    reqwest::async_http_client,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, RequestTokenError, Scope, TokenResponse, TokenUrl,
 };
 use secrecy::ExposeSecret;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tracing::{error, warn};
+use utoipa::IntoParams;
 
-use crate::{domain::User, repos::find_by_email, AppState};
+use crate::{
+    configuration::GoogleOAuthSettings,
+    domain::{CompletedLogin, LoginCode, OAuthLogin, OAUTH_LOGIN_TTL},
+    repos::{self, find_by_email},
+    tx::tx::Tx,
+    AppState,
+};
 
-#[derive(Deserialize)]
-pub struct AuthCallbackQuery {
-    code: String,
-}
+/// Cookie binding a login to the browser that started it, holding the
+/// `state` sent to the provider.
+const OAUTH_STATE_COOKIE: &str = "oauth_state";
 
-#[derive(Serialize)]
-pub struct AuthResponse {
-    token: String,
-    user: User,
+/// Provider name of the logins through Google.
+const GOOGLE_PROVIDER: &str = "google";
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthCallbackQuery {
+    /// Authorization code issued by the provider
+    pub code: String,
+    /// The state sent to the provider when the login started, must match the
+    /// `oauth_state` cookie
+    pub state: String,
 }
 
 #[derive(Deserialize)]
@@ -31,70 +46,75 @@ struct GoogleUserInfo {
     verified_email: bool,
 }
 
-pub async fn google_login(State(state): State<AppState>) -> impl IntoResponse {
+#[utoipa::path(
+    get,
+    path = "/auth/google",
+    tag = "auth",
+    security(()),
+    responses((
+        status = 303,
+        description = "Redirects to the Google consent screen, setting the `oauth_state` cookie"
+    )),
+)]
+pub async fn google_login(
+    State(state): State<AppState>,
+    tx: Tx,
+) -> Result<impl IntoResponse, StatusCode> {
+    let google_oauth = google_oauth_settings(&state);
     let client = create_oauth_client(&state);
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
-    let (auth_url, _csrf_token) = client
+    let (auth_url, csrf_token) = client
         .authorize_url(CsrfToken::new_random)
         .add_scope(Scope::new("email".to_string()))
         .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
         .url();
 
-    Redirect::to(auth_url.as_str())
+    let login = OAuthLogin {
+        state: csrf_token.secret().clone(),
+        provider: GOOGLE_PROVIDER.to_string(),
+        pkce_verifier: pkce_verifier.secret().clone(),
+        nonce: None,
+        create_time: state.clock.now(),
+    };
+    start_login(tx, &login, &google_oauth.redirect_uri, auth_url.as_str()).await
 }
 
+#[utoipa::path(
+    get,
+    path = "/auth/google/callback",
+    tag = "auth",
+    security(()),
+    params(AuthCallbackQuery),
+    responses((
+        status = 303,
+        description = "Redirects to the frontend with a `code` to exchange at `POST /auth/exchange`"
+    )),
+)]
 pub async fn google_callback(
     Query(params): Query<AuthCallbackQuery>,
     State(state): State<AppState>,
+    headers: HeaderMap,
+    mut tx: Tx,
 ) -> Result<impl IntoResponse, StatusCode> {
+    let login = take_login(&mut tx, &state, &headers, &params, GOOGLE_PROVIDER).await?;
+
     let client = create_oauth_client(&state);
 
     // Exchange authorization code for access token
-    let token_result = match client
+    let token_result = client
         .exchange_code(AuthorizationCode::new(params.code))
+        .set_pkce_verifier(PkceCodeVerifier::new(login.pkce_verifier))
         .request_async(async_http_client)
         .await
-    {
-        Ok(token_result) => token_result,
-        Err(err) => {
-            match &err {
-                RequestTokenError::ServerResponse(server_error) => {
-                    error!(
-                        error = %server_error.error(),
-                        error_description = ?server_error.error_description(),
-                        error_uri = ?server_error.error_uri(),
-                        "OAuth token exchange failed with server response"
-                    );
-                }
-                RequestTokenError::Request(request_error) => {
-                    error!(
-                        error = %request_error,
-                        "OAuth token exchange request failed"
-                    );
-                }
-                RequestTokenError::Parse(parse_error, body) => {
-                    error!(
-                        error = %parse_error,
-                        body = %String::from_utf8_lossy(body),
-                        "OAuth token exchange failed to parse response"
-                    );
-                }
-                RequestTokenError::Other(message) => {
-                    error!(
-                        error = %message,
-                        "OAuth token exchange failed with unexpected error"
-                    );
-                }
-            }
-            return Err(StatusCode::BAD_REQUEST);
-        }
-    };
+        .map_err(|err| {
+            log_token_error(&err);
+            StatusCode::BAD_REQUEST
+        })?;
 
     // Get user info from Google
-    let google_oauth = match &state.auth {
-        crate::configuration::AuthSettings::GoogleOAuth { google_oauth, .. } => google_oauth,
-        _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-    };
+    let google_oauth = google_oauth_settings(&state);
 
     let user_info = match get_google_user_info(
         token_result.access_token().secret(),
@@ -114,13 +134,87 @@ pub async fn google_callback(
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    let mut transaction = state.tx_state.transaction().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    complete_login(
+        tx,
+        &state,
+        &user_info.email,
+        &google_oauth.frontend_callback_url,
+        &google_oauth.redirect_uri,
+    )
+    .await
+}
+
+/// Records the login and sends the browser to the provider with the state
+/// in a cookie.
+pub(crate) async fn start_login(
+    mut tx: Tx,
+    login: &OAuthLogin,
+    redirect_uri: &str,
+    auth_url: &str,
+) -> Result<Response, StatusCode> {
+    if let Err(err) = repos::create_oauth_login(&mut tx, login).await {
+        error!(error = %err, "Failed to record OAuth login");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let cookie = state_cookie(redirect_uri, &login.state, OAUTH_LOGIN_TTL.as_secs());
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to(auth_url)).into_response())
+}
+
+/// The login the provider calls back for. The callback must come from the
+/// browser that started it, through the same provider, in time, and only
+/// once.
+pub(crate) async fn take_login(
+    tx: &mut Tx,
+    state: &AppState,
+    headers: &HeaderMap,
+    params: &AuthCallbackQuery,
+    provider: &str,
+) -> Result<OAuthLogin, StatusCode> {
+    if cookie_value(headers, OAUTH_STATE_COOKIE) != Some(params.state.as_str()) {
+        warn!("OAuth callback state does not match the cookie");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let login = match repos::take_oauth_login(tx, &params.state).await {
+        Ok(Some(login)) => login,
+        Ok(None) => {
+            warn!("OAuth callback state is unknown or already used");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(err) => {
+            error!(error = %err, "Failed to look up OAuth login");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if login.provider != provider {
+        warn!(
+            started_with = %login.provider,
+            called_back_by = %provider,
+            "OAuth login called back by another provider"
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if login.is_expired(state.clock.now()) {
+        warn!("OAuth login expired before the callback");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(login)
+}
+
+/// Logs in the existing user with the email, redirecting to the frontend
+/// with a code to exchange at `POST /auth/exchange`.
+pub(crate) async fn complete_login(
+    mut tx: Tx,
+    state: &AppState,
+    email: &str,
+    frontend_callback_url: &str,
+    redirect_uri: &str,
+) -> Result<Response, StatusCode> {
     // Find user (do not create if not exists)
-    let user = match find_by_email(&mut transaction, &user_info.email).await {
+    let user = match find_by_email(&mut tx, email).await {
         Ok(Some(user)) => user,
         Ok(None) => {
             // User not found - return 403 Forbidden
-            warn!(email = %user_info.email, "OAuth user not authorized");
+            warn!(email = %email, "OAuth user not authorized");
             return Err(StatusCode::FORBIDDEN);
         }
         Err(err) => {
@@ -129,39 +223,106 @@ pub async fn google_callback(
         }
     };
 
-    // Generate JWT token
-    let token = match state.jwt_service.generate_token(user.user_id, &user.email) {
-        Ok(token) => token,
-        Err(err) => {
-            error!(error = %err, "Failed to generate JWT token");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+    let code = LoginCode::new_random();
+    let login = CompletedLogin {
+        user_id: user.user_id,
+        create_time: state.clock.now(),
     };
+    if let Err(err) = repos::create_login_code(&mut tx, &code, &login).await {
+        error!(error = %err, "Failed to record the completed login");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
 
-    // Redirect to frontend callback with token and user data as query parameters
-    let frontend_callback_base = &google_oauth.frontend_callback_url;
-    let query_sep = if frontend_callback_base.contains('?') {
+    // Redirect to frontend callback with a code to exchange for the tokens,
+    // which stay out of the URL
+    let query_sep = if frontend_callback_url.contains('?') {
         "&"
     } else {
         "?"
     };
     let frontend_callback_url = format!(
-        "{}{}token={}&user_id={}&email={}",
-        frontend_callback_base,
+        "{}{}code={}",
+        frontend_callback_url,
         query_sep,
-        urlencoding::encode(&token),
-        urlencoding::encode(&user.user_id.to_string()),
-        urlencoding::encode(&user.email)
+        urlencoding::encode(code.secret())
     );
 
-    Ok(Redirect::to(&frontend_callback_url))
+    let cookie = state_cookie(redirect_uri, "", 0);
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Redirect::to(&frontend_callback_url),
+    )
+        .into_response())
 }
 
-fn create_oauth_client(state: &AppState) -> BasicClient {
-    let google_oauth = match &state.auth {
+pub(crate) fn log_token_error<RE: std::error::Error>(
+    err: &RequestTokenError<RE, BasicErrorResponse>,
+) {
+    match err {
+        RequestTokenError::ServerResponse(server_error) => {
+            error!(
+                error = %server_error.error(),
+                error_description = ?server_error.error_description(),
+                error_uri = ?server_error.error_uri(),
+                "OAuth token exchange failed with server response"
+            );
+        }
+        RequestTokenError::Request(request_error) => {
+            error!(
+                error = %request_error,
+                "OAuth token exchange request failed"
+            );
+        }
+        RequestTokenError::Parse(parse_error, body) => {
+            error!(
+                error = %parse_error,
+                body = %String::from_utf8_lossy(body),
+                "OAuth token exchange failed to parse response"
+            );
+        }
+        RequestTokenError::Other(message) => {
+            error!(
+                error = %message,
+                "OAuth token exchange failed with unexpected error"
+            );
+        }
+    }
+}
+
+fn google_oauth_settings(state: &AppState) -> &GoogleOAuthSettings {
+    match &state.auth {
         crate::configuration::AuthSettings::GoogleOAuth { google_oauth, .. } => google_oauth,
         _ => panic!("Google OAuth config should be available when using Google OAuth auth"),
+    }
+}
+
+/// The `oauth_state` cookie, only sent back to the auth routes. `Lax` lets
+/// it through the top-level redirect from the provider.
+fn state_cookie(redirect_uri: &str, value: &str, max_age: u64) -> String {
+    let secure = if redirect_uri.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
     };
+    format!(
+        "{}={}; Path=/auth; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        OAUTH_STATE_COOKIE, value, max_age, secure
+    )
+}
+
+fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn create_oauth_client(state: &AppState) -> BasicClient {
+    let google_oauth = google_oauth_settings(state);
 
     let google_client_id = ClientId::new(google_oauth.client_id.clone());
     let google_client_secret =
@@ -0,0 +1,42 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::domain::{ETag, ETagCondition};
+
+/// A response carrying the `ETag` of the representation it returns.
+pub struct WithETag<T>(pub ETag, pub T);
+
+impl<T: IntoResponse> IntoResponse for WithETag<T> {
+    fn into_response(self) -> Response {
+        ([(header::ETAG, self.0.to_string())], self.1).into_response()
+    }
+}
+
+/// The answer to a GET honoring `If-None-Match`: a bodyless 304 when the
+/// client already holds the current version.
+pub enum ConditionalGet<T> {
+    Modified(WithETag<T>),
+    NotModified(ETag),
+}
+
+impl<T> ConditionalGet<T> {
+    pub fn new(if_none_match: Option<ETagCondition>, etag: ETag, body: T) -> Self {
+        match if_none_match {
+            Some(condition) if condition.matches(&etag) => Self::NotModified(etag),
+            _ => Self::Modified(WithETag(etag, body)),
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for ConditionalGet<T> {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Modified(response) => response.into_response(),
+            Self::NotModified(etag) => {
+                (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.to_string())]).into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use axum::{extract, Json};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::domain::{
+    self, Group, GroupMember, GroupName, ListGroup, ListGroupMember, NewGroupMemberRequest,
+};
+use crate::error::{APIError, FieldContext};
+use crate::extractors::AuthenticatedUser;
+use crate::repos;
+use crate::routes::{PaginationQuery, TodoRole};
+use crate::tx::tx::Tx;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GroupRequest {
+    pub name: String,
+}
+
+impl TryFrom<GroupRequest> for GroupName {
+    type Error = APIError;
+    fn try_from(value: GroupRequest) -> Result<Self, Self::Error> {
+        GroupName::try_from(value.name).field("name")
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupResponse {
+    pub group_id: Uuid,
+    pub name: String,
+    /// The role of the user on the group and its todos
+    pub role: TodoRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub update_time: OffsetDateTime,
+}
+
+impl From<Group> for GroupResponse {
+    fn from(value: Group) -> Self {
+        Self {
+            group_id: value.group_id,
+            name: value.name.as_ref().to_string(),
+            role: value.role.into(),
+            create_time: value.create_time,
+            update_time: value.update_time,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListGroupResponse {
+    pub items: Vec<GroupResponse>,
+    pub next_cursor: Option<String>,
+}
+
+impl From<ListGroup> for ListGroupResponse {
+    fn from(value: ListGroup) -> Self {
+        Self {
+            items: value.items.into_iter().map(|g| g.into()).collect(),
+            next_cursor: value.next_cursor.map(|c| c.into()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateGroupMemberRequest {
+    /// The email the user logs in with
+    pub email: String,
+    /// The role of the member on the group and its todos
+    pub role: TodoRole,
+}
+
+impl TryFrom<CreateGroupMemberRequest> for NewGroupMemberRequest {
+    type Error = APIError;
+    fn try_from(value: CreateGroupMemberRequest) -> Result<Self, Self::Error> {
+        let email = value.email.trim();
+        if email.is_empty() {
+            return Err(APIError::BadRequest("Email cannot be empty".to_string())).field("email");
+        }
+        Ok(Self {
+            email: email.to_string(),
+            role: value.role.into(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateGroupMemberRequest {
+    pub role: TodoRole,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupMemberResponse {
+    pub user_id: i32,
+    pub email: String,
+    pub role: TodoRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+}
+
+impl From<GroupMember> for GroupMemberResponse {
+    fn from(value: GroupMember) -> Self {
+        Self {
+            user_id: value.user_id,
+            email: value.email,
+            role: value.role.into(),
+            create_time: value.create_time,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListGroupMemberResponse {
+    pub items: Vec<GroupMemberResponse>,
+    pub next_cursor: Option<String>,
+}
+
+impl From<ListGroupMember> for ListGroupMemberResponse {
+    fn from(value: ListGroupMember) -> Self {
+        Self {
+            items: value.items.into_iter().map(|m| m.into()).collect(),
+            next_cursor: value.next_cursor.map(|c| c.into()),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct GroupPath {
+    /// Id of a group of the user
+    group_id: Uuid,
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct GroupMemberPath {
+    /// Id of a group of the user
+    group_id: Uuid,
+    /// The `user_id` of the member
+    user_id: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/group",
+    tag = "group",
+    request_body = GroupRequest,
+    responses((status = 200, description = "The group, owned by the user", body = GroupResponse)),
+)]
+#[tracing::instrument(name = "Create group", skip(tx, payload))]
+pub async fn create_group(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    Json(payload): Json<GroupRequest>,
+) -> Result<Json<GroupResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let name = payload.try_into()?;
+    let group = repos::create_group(&mut tx, &name, user.user_id).await?;
+    Ok(Json(group.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/group",
+    tag = "group",
+    params(PaginationQuery),
+    responses((status = 200, description = "A page of the groups of the user", body = ListGroupResponse)),
+)]
+#[tracing::instrument(name = "List groups", skip(tx))]
+pub async fn list_groups(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
+) -> Result<Json<ListGroupResponse>, APIError> {
+    let page = pagination.try_into()?;
+    let groups = repos::list_groups(&mut tx, &page, user.user_id).await?;
+    Ok(Json(groups.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/group/{group_id}",
+    tag = "group",
+    params(GroupPath),
+    responses((status = 200, description = "The group", body = GroupResponse)),
+)]
+#[tracing::instrument(name = "Get group", skip(tx))]
+pub async fn get_group(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(group_id): extract::Path<Uuid>,
+) -> Result<Json<GroupResponse>, APIError> {
+    let group =
+        repos::get_group(&mut tx, &group_id, user.user_id, domain::TodoRole::Viewer).await?;
+    Ok(Json(group.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/group/{group_id}",
+    tag = "group",
+    params(GroupPath),
+    request_body = GroupRequest,
+    responses((status = 200, description = "The renamed group", body = GroupResponse)),
+)]
+#[tracing::instrument(name = "Update group", skip(tx, payload))]
+pub async fn update_group(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(group_id): extract::Path<Uuid>,
+    Json(payload): Json<GroupRequest>,
+) -> Result<Json<GroupResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let name = payload.try_into()?;
+    let group = repos::get_group(&mut tx, &group_id, user.user_id, domain::TodoRole::Owner).await?;
+    let group = repos::rename_group(&mut tx, &group, &name).await?;
+    Ok(Json(group.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/group/{group_id}",
+    tag = "group",
+    params(GroupPath),
+    responses((status = 200, description = "The group is deleted, its todos go back to the users who created them")),
+)]
+#[tracing::instrument(name = "Delete group", skip(tx))]
+pub async fn delete_group(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(group_id): extract::Path<Uuid>,
+) -> Result<(), APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let group = repos::get_group(&mut tx, &group_id, user.user_id, domain::TodoRole::Owner).await?;
+    repos::delete_group(&mut tx, &group).await?;
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/group/{group_id}/member",
+    tag = "group",
+    params(GroupPath, PaginationQuery),
+    responses((status = 200, description = "A page of the members of the group", body = ListGroupMemberResponse)),
+)]
+#[tracing::instrument(name = "List group members", skip(tx))]
+pub async fn list_group_members(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(group_id): extract::Path<Uuid>,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
+) -> Result<Json<ListGroupMemberResponse>, APIError> {
+    let page = pagination.try_into()?;
+    let group =
+        repos::get_group(&mut tx, &group_id, user.user_id, domain::TodoRole::Viewer).await?;
+    let members = repos::list_group_members(&mut tx, &group, &page).await?;
+    Ok(Json(members.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/group/{group_id}/member",
+    tag = "group",
+    params(GroupPath),
+    request_body = CreateGroupMemberRequest,
+    responses((status = 200, description = "The user is a member of the group", body = GroupMemberResponse)),
+)]
+#[tracing::instrument(name = "Add group member", skip(tx, payload))]
+pub async fn add_group_member(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(group_id): extract::Path<Uuid>,
+    Json(payload): Json<CreateGroupMemberRequest>,
+) -> Result<Json<GroupMemberResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let req = payload.try_into()?;
+    let group = repos::get_group(&mut tx, &group_id, user.user_id, domain::TodoRole::Owner).await?;
+    let member = repos::add_group_member(&mut tx, &group, &req).await?;
+    Ok(Json(member.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/group/{group_id}/member/{user_id}",
+    tag = "group",
+    params(GroupMemberPath),
+    request_body = UpdateGroupMemberRequest,
+    responses((status = 200, description = "The member", body = GroupMemberResponse)),
+)]
+#[tracing::instrument(name = "Update group member", skip(tx, payload))]
+pub async fn update_group_member(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path((group_id, member_id)): extract::Path<(Uuid, i32)>,
+    Json(payload): Json<UpdateGroupMemberRequest>,
+) -> Result<Json<GroupMemberResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let group = repos::get_group(&mut tx, &group_id, user.user_id, domain::TodoRole::Owner).await?;
+    let member =
+        repos::update_group_member(&mut tx, &group, member_id, payload.role.into()).await?;
+    Ok(Json(member.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/group/{group_id}/member/{user_id}",
+    tag = "group",
+    params(GroupMemberPath),
+    responses((status = 200, description = "The user is no longer a member of the group")),
+)]
+#[tracing::instrument(name = "Remove group member", skip(tx))]
+pub async fn remove_group_member(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path((group_id, member_id)): extract::Path<(Uuid, i32)>,
+) -> Result<(), APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    // Members can leave a group on their own
+    let required = if member_id == user.user_id {
+        domain::TodoRole::Viewer
+    } else {
+        domain::TodoRole::Owner
+    };
+    let group = repos::get_group(&mut tx, &group_id, user.user_id, required).await?;
+    repos::remove_group_member(&mut tx, &group, member_id).await?;
+    Ok(())
+}
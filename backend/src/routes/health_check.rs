@@ -4,6 +4,13 @@ use crate::error::APIError;
 use crate::repos::health_check as health_check_repo;
 use crate::tx::tx::Tx;
 
+#[utoipa::path(
+    get,
+    path = "/health_check",
+    tag = "health",
+    security(()),
+    responses((status = 200, description = "The service and its database are up")),
+)]
 #[tracing::instrument(name = "Health Check", skip(tx))]
 pub async fn health_check(
     mut tx: Tx,
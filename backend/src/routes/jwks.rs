@@ -0,0 +1,101 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth::{PublicKeyParams, PublishedKey};
+use crate::AppState;
+
+/// How long other services may cache the keys. A new signing key must be
+/// published for at least as long before it signs tokens.
+const JWKS_MAX_AGE_SECONDS: u64 = 5 * 60;
+
+/// A public key of the service, as a JSON Web Key (RFC 7517).
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JwkResponse {
+    /// `RSA` or `OKP`
+    pub kty: &'static str,
+    /// Named in the `kid` header of the tokens the key signed
+    pub kid: String,
+    /// `RS256` or `EdDSA`
+    pub alg: &'static str,
+    #[serde(rename = "use")]
+    pub use_: &'static str,
+    /// Curve of an `OKP` key, `Ed25519`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<&'static str>,
+    /// Public key of an `OKP` key, base64url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    /// Modulus of an `RSA` key, base64url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// Exponent of an `RSA` key, base64url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+}
+
+impl From<PublishedKey> for JwkResponse {
+    fn from(value: PublishedKey) -> Self {
+        let (kty, alg, crv, x, n, e) = match value.params {
+            PublicKeyParams::Rsa { n, e } => (
+                "RSA",
+                "RS256",
+                None,
+                None,
+                Some(URL_SAFE_NO_PAD.encode(n)),
+                Some(URL_SAFE_NO_PAD.encode(e)),
+            ),
+            PublicKeyParams::Ed25519 { x } => (
+                "OKP",
+                "EdDSA",
+                Some("Ed25519"),
+                Some(URL_SAFE_NO_PAD.encode(x)),
+                None,
+                None,
+            ),
+        };
+        Self {
+            kty,
+            kid: value.kid,
+            alg,
+            use_: "sig",
+            crv,
+            x,
+            n,
+            e,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JwksResponse {
+    pub keys: Vec<JwkResponse>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    security(()),
+    responses((status = 200, description = "The keys bearer tokens are signed with, including retired keys in their grace period", body = JwksResponse)),
+)]
+#[tracing::instrument(name = "Get JWKS", skip(state))]
+pub async fn jwks(State(state): State<AppState>) -> Response {
+    let keys = state.jwt_service.published_keys(state.clock.now());
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", JWKS_MAX_AGE_SECONDS),
+        )],
+        Json(JwksResponse {
+            keys: keys.into_iter().map(JwkResponse::from).collect(),
+        }),
+    )
+        .into_response()
+}
@@ -1,11 +1,39 @@
+mod activity;
 mod auth;
+mod etag;
+mod group;
 mod health_check;
+mod jwks;
+mod oidc;
+mod openapi;
+mod pagination;
 mod recurring_template;
+mod session;
+mod sync;
 mod todo;
+mod todo_event;
 mod todo_item;
+mod todo_item_batch;
+mod todo_member;
+mod trash;
+mod user;
 
+pub use activity::*;
 pub use auth::*;
+pub use etag::*;
+pub use group::*;
 pub use health_check::*;
+pub use jwks::*;
+pub use oidc::*;
+pub use openapi::*;
+pub use pagination::*;
 pub use recurring_template::*;
+pub use session::*;
+pub use sync::*;
 pub use todo::*;
+pub use todo_event::*;
 pub use todo_item::*;
+pub use todo_item_batch::*;
+pub use todo_member::*;
+pub use trash::*;
+pub use user::*;
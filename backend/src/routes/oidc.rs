@@ -0,0 +1,140 @@
+use axum::{
+    extract::{self, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use oauth2::{
+    reqwest::async_http_client, AuthorizationCode, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
+    Scope,
+};
+use tracing::{error, warn};
+use utoipa::IntoParams;
+
+use crate::{
+    domain::OAuthLogin,
+    routes::{complete_login, log_token_error, start_login, take_login, AuthCallbackQuery},
+    services::OidcProvider,
+    tx::tx::Tx,
+    AppState,
+};
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct OidcProviderPath {
+    /// Name of a configured OpenID Connect provider
+    provider: String,
+}
+
+fn find_provider<'a>(state: &'a AppState, name: &str) -> Result<&'a OidcProvider, StatusCode> {
+    state.oidc_providers.get(name).ok_or_else(|| {
+        warn!(provider = %name, "Unknown OpenID Connect provider");
+        StatusCode::NOT_FOUND
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/{provider}",
+    tag = "auth",
+    security(()),
+    params(OidcProviderPath),
+    responses((
+        status = 303,
+        description = "Redirects to the login page of the provider, setting the `oauth_state` cookie"
+    )),
+)]
+pub async fn oidc_login(
+    State(state): State<AppState>,
+    extract::Path(provider_name): extract::Path<String>,
+    tx: Tx,
+) -> Result<impl IntoResponse, StatusCode> {
+    let provider = find_provider(&state, &provider_name)?;
+    let client = provider.client().await.map_err(|err| {
+        error!(error = %err, provider = %provider_name, "Failed to discover OpenID Connect provider");
+        StatusCode::BAD_GATEWAY
+    })?;
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let nonce = CsrfToken::new_random();
+
+    let (auth_url, csrf_token) = client
+        .authorize_url(CsrfToken::new_random)
+        .add_scope(Scope::new("openid".to_string()))
+        .add_scopes(provider.settings.scopes.iter().cloned().map(Scope::new))
+        .add_extra_param("nonce", nonce.secret())
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    let login = OAuthLogin {
+        state: csrf_token.secret().clone(),
+        provider: provider_name,
+        pkce_verifier: pkce_verifier.secret().clone(),
+        nonce: Some(nonce.secret().clone()),
+        create_time: state.clock.now(),
+    };
+    start_login(
+        tx,
+        &login,
+        &provider.settings.redirect_uri,
+        auth_url.as_str(),
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/{provider}/callback",
+    tag = "auth",
+    security(()),
+    params(OidcProviderPath, AuthCallbackQuery),
+    responses((
+        status = 303,
+        description = "Redirects to the frontend with a `code` to exchange at `POST /auth/exchange`"
+    )),
+)]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    extract::Path(provider_name): extract::Path<String>,
+    Query(params): Query<AuthCallbackQuery>,
+    headers: HeaderMap,
+    mut tx: Tx,
+) -> Result<impl IntoResponse, StatusCode> {
+    let provider = find_provider(&state, &provider_name)?;
+    let login = take_login(&mut tx, &state, &headers, &params, &provider_name).await?;
+    let client = provider.client().await.map_err(|err| {
+        error!(error = %err, provider = %provider_name, "Failed to discover OpenID Connect provider");
+        StatusCode::BAD_GATEWAY
+    })?;
+
+    let token_result = client
+        .exchange_code(AuthorizationCode::new(params.code))
+        .set_pkce_verifier(PkceCodeVerifier::new(login.pkce_verifier))
+        .request_async(async_http_client)
+        .await
+        .map_err(|err| {
+            log_token_error(&err);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    let nonce = login.nonce.unwrap_or_default();
+    let claims = provider
+        .verify_id_token(&token_result.extra_fields().id_token, &nonce)
+        .await
+        .map_err(|err| {
+            warn!(error = %err, provider = %provider_name, "Rejected ID token");
+            StatusCode::BAD_REQUEST
+        })?;
+    let Some(email) = claims.verified_email() else {
+        warn!(sub = %claims.sub, provider = %provider_name, "OpenID Connect user email not verified");
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    complete_login(
+        tx,
+        &state,
+        email,
+        &provider.settings.frontend_callback_url,
+        &provider.settings.redirect_uri,
+    )
+    .await
+}
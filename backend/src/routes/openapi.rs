@@ -0,0 +1,138 @@
+use axum::extract::State;
+use axum::Json;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{Content, OpenApi as OpenApiDocument, Ref, RefOr, ResponseBuilder};
+use utoipa::{IntoParams, Modify, OpenApi};
+use uuid::Uuid;
+
+use crate::error::{FieldError, Problem, PROBLEM_CONTENT_TYPE};
+use crate::AppState;
+
+/// The parts of the document not derived from the routes. Paths are added
+/// by the `OpenApiRouter` in `lib::run` as the routes are declared, then
+/// `ProblemResponses` is applied to them.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Checklist API",
+        description = "Todos, their items and recurring templates.",
+        license(name = "MIT", identifier = "MIT")
+    ),
+    components(schemas(Problem, FieldError)),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    tags(
+        (name = "health", description = "Service health"),
+        (name = "auth", description = "Login flows issuing bearer tokens"),
+        (name = "user", description = "The authenticated user"),
+        (name = "group", description = "Groups of users owning todos together"),
+        (name = "todo", description = "Todos, identified by name or id"),
+        (name = "todo_item", description = "Items of a todo"),
+        (name = "recurring_template", description = "Templates generating items of a todo"),
+        (name = "sync", description = "Incremental sync of the user's todos for offline clients"),
+        (name = "trash", description = "Deleted todos and items, until they are purged"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Every error is a `Problem`, documented once as the default response of
+/// each operation instead of listing every status on every handler.
+pub struct ProblemResponses;
+
+impl Modify for ProblemResponses {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let response = ResponseBuilder::new()
+            .description("An RFC 7807 problem describing the error")
+            .content(
+                PROBLEM_CONTENT_TYPE,
+                Content::new(Some(Ref::from_schema_name("Problem"))),
+            )
+            .build();
+        for path in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut path.get,
+                &mut path.put,
+                &mut path.post,
+                &mut path.delete,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| RefOr::T(response.clone()));
+            }
+        }
+    }
+}
+
+// The structs below only describe parameters shared by several operations,
+// the handlers extract them with `Path` and the conditional request
+// extractors.
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct TodoPath {
+    /// Name of a todo of the user, or id of any todo visible to them
+    todo_id: String,
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct TodoItemPath {
+    /// Name of a todo of the user, or id of any todo visible to them
+    todo_id: String,
+    item_id: Uuid,
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RecurringTemplatePath {
+    /// Name of a todo of the user, or id of any todo visible to them
+    todo_id: String,
+    template_id: Uuid,
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct IfMatchHeader {
+    /// Only apply the change if the resource still has one of these ETags,
+    /// fails with a 412 otherwise. Weak ETags never match
+    #[param(rename = "If-Match")]
+    if_match: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct IfNoneMatchHeader {
+    /// Answer with a bodyless 304 if the resource still has one of these ETags
+    #[param(rename = "If-None-Match")]
+    if_none_match: Option<String>,
+}
+
+#[tracing::instrument(name = "OpenAPI document", skip(state))]
+pub async fn openapi_json(State(state): State<AppState>) -> Json<OpenApiDocument> {
+    Json(state.openapi.as_ref().clone())
+}
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::domain::{PageRequest, PageSize};
+use crate::error::{APIError, FieldContext};
+
+/// Query parameters accepted by every listing endpoint.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaginationQuery {
+    /// Maximum number of results, defaults to 100 and is capped at 500
+    pub limit: Option<i64>,
+    /// The `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl TryFrom<PaginationQuery> for PageRequest {
+    type Error = APIError;
+    fn try_from(value: PaginationQuery) -> Result<Self, Self::Error> {
+        Ok(Self {
+            size: PageSize::try_from(value.limit).field("limit")?,
+            cursor: value.cursor.map(|c| c.into()),
+        })
+    }
+}
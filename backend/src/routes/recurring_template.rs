@@ -3,23 +3,28 @@ use eyre::Context;
 use serde::{Deserialize, Serialize};
 use time::{Date, OffsetDateTime};
 use tracing::info;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::tx::tx::Tx;
 use crate::{
     domain::{
-        self, ListRecurringTemplate, NewRecurringTemplateRequest, RecurringTemplate, TodoName,
+        self, ETag, ListRecurringTemplate, NewRecurringTemplateRequest, RecurringTemplate, TodoRef,
     },
-    error::APIError,
-    extractors::{AppRecurringSettings, AuthenticatedUser},
+    error::{APIError, FieldContext},
+    extractors::{AppClock, AppRecurringSettings, AuthenticatedUser, IfMatch, IfNoneMatch},
     repos::{
         create_recurring_template, delete_recurring_template, get_recurring_template,
-        list_recurring_templates, update_recurring_template,
+        get_user_today, list_recurring_templates, set_audit_actor, update_recurring_template,
+    },
+    routes::{
+        ConditionalGet, IfMatchHeader, IfNoneMatchHeader, PaginationQuery, RecurringTemplatePath,
+        TodoPath, WithETag,
     },
     services::process_single_template,
 };
-use crate::tx::tx::Tx;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RecurrenceInterval {
     pub months: Option<i32>,
     pub days: Option<i32>,
@@ -46,29 +51,52 @@ impl From<domain::RecurrenceInterval> for RecurrenceInterval {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A template repeats either by `recurrence_interval` or by an RFC 5545
+/// `rrule`, exactly one of them must be given.
+fn recurrence(
+    recurrence_interval: Option<RecurrenceInterval>,
+    rrule: Option<String>,
+) -> Result<domain::Recurrence, APIError> {
+    match (recurrence_interval, rrule) {
+        (Some(interval), None) => Ok(domain::Recurrence::Interval(interval.into())),
+        (None, Some(rrule)) => Ok(domain::Recurrence::Rule(
+            domain::RecurrenceRule::try_from(rrule).field("rrule")?,
+        )),
+        (Some(_), Some(_)) => Err(APIError::BadRequest(
+            "Only one of recurrence_interval or rrule can be given".to_string(),
+        )),
+        (None, None) => Err(APIError::BadRequest(
+            "One of recurrence_interval or rrule is required".to_string(),
+        )),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreateRecurringTemplateRequest {
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence_interval: Option<RecurrenceInterval>,
+    pub rrule: Option<String>,
     pub start_date: Option<Date>,
     pub end_date: Option<Date>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UpdateRecurringTemplateRequestJson {
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence_interval: Option<RecurrenceInterval>,
+    pub rrule: Option<String>,
     pub start_date: Option<Date>,
     pub end_date: Option<Date>,
     pub is_active: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RecurringTemplateResponse {
     pub todo_name: String,
     pub template_id: Uuid,
     pub title: String,
-    pub recurrence_interval: RecurrenceInterval,
+    pub recurrence_interval: Option<RecurrenceInterval>,
+    pub rrule: Option<String>,
     pub start_date: Date,
     pub end_date: Option<Date>,
     pub last_generated_date: Option<Date>,
@@ -79,18 +107,30 @@ pub struct RecurringTemplateResponse {
     pub update_time: OffsetDateTime,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ListRecurringTemplatesResponse {
     pub templates: Vec<RecurringTemplateResponse>,
+    pub next_cursor: Option<String>,
+}
+
+fn split_recurrence(
+    recurrence: domain::Recurrence,
+) -> (Option<RecurrenceInterval>, Option<String>) {
+    match recurrence {
+        domain::Recurrence::Interval(interval) => (Some(interval.into()), None),
+        domain::Recurrence::Rule(rule) => (None, Some(rule.to_string())),
+    }
 }
 
 impl From<RecurringTemplate> for RecurringTemplateResponse {
     fn from(template: RecurringTemplate) -> Self {
+        let (recurrence_interval, rrule) = split_recurrence(template.recurrence);
         Self {
             todo_name: template.todo_name.into(),
             template_id: template.template_id,
             title: template.title,
-            recurrence_interval: template.recurrence_interval.into(),
+            recurrence_interval,
+            rrule,
             start_date: template.start_date,
             end_date: template.end_date,
             last_generated_date: template.last_generated_date,
@@ -107,26 +147,39 @@ impl From<ListRecurringTemplate> for ListRecurringTemplatesResponse {
             templates: list
                 .items
                 .into_iter()
-                .map(|template| RecurringTemplateResponse {
-                    todo_name: template.todo_name.into(),
-                    template_id: template.template_id,
-                    title: template.title,
-                    recurrence_interval: template.recurrence_interval.into(),
-                    start_date: template.start_date,
-                    end_date: template.end_date,
-                    last_generated_date: template.last_generated_date,
-                    is_active: template.is_active,
-                    create_time: template.create_time,
-                    update_time: template.update_time,
+                .map(|template| {
+                    let (recurrence_interval, rrule) = split_recurrence(template.recurrence);
+                    RecurringTemplateResponse {
+                        todo_name: template.todo_name.into(),
+                        template_id: template.template_id,
+                        title: template.title,
+                        recurrence_interval,
+                        rrule,
+                        start_date: template.start_date,
+                        end_date: template.end_date,
+                        last_generated_date: template.last_generated_date,
+                        is_active: template.is_active,
+                        create_time: template.create_time,
+                        update_time: template.update_time,
+                    }
                 })
                 .collect(),
+            next_cursor: list.next_cursor.map(|c| c.into()),
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/todo/{todo_id}/recurring",
+    tag = "recurring_template",
+    params(TodoPath),
+    request_body = CreateRecurringTemplateRequest,
+    responses((status = 200, description = "The template", body = RecurringTemplateResponse)),
+)]
 #[tracing::instrument(
     name = "Create recurring template",
-    skip(tx),
+    skip(tx, clock),
     fields(
         todo_name = %todo_name,
         title = %req.title,
@@ -134,32 +187,36 @@ impl From<ListRecurringTemplate> for ListRecurringTemplatesResponse {
 )]
 pub async fn create_recurring_template_handler(
     mut tx: Tx,
+    AppClock(clock): AppClock,
     AppRecurringSettings(recurring_settings): AppRecurringSettings,
-    AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+    user @ AuthenticatedUser { user_id, .. }: AuthenticatedUser,
     extract::Path(todo_name): extract::Path<String>,
     Json(req): Json<CreateRecurringTemplateRequest>,
 ) -> Result<Json<RecurringTemplateResponse>, APIError> {
-    let todo_name = TodoName::try_from(todo_name)?;
+    set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = TodoRef::try_from(todo_name)?;
 
     let new_template_request = NewRecurringTemplateRequest {
-        todo_name,
+        todo_ref,
+        recurrence: recurrence(req.recurrence_interval, req.rrule)?,
         title: req.title,
-        recurrence_interval: req.recurrence_interval.into(),
-        start_date: req
-            .start_date
-            .unwrap_or_else(|| OffsetDateTime::now_utc().date()),
+        start_date: match req.start_date {
+            Some(start_date) => start_date,
+            None => get_user_today(&mut tx, user_id, clock.now()).await?,
+        },
         end_date: req.end_date,
     };
 
-    let template =
-        create_recurring_template(&mut tx, &new_template_request, user_id).await?;
+    let template = create_recurring_template(&mut tx, &new_template_request, user_id).await?;
 
     // Generate any todos that should be created within the advance window
     let template_single = (&template).into();
+    let current_date = get_user_today(&mut tx, template.todo_owner_id, clock.now()).await?;
     process_single_template(
         &mut tx,
         &template_single,
         recurring_settings.look_ahead_duration,
+        current_date,
         user_id,
     )
     .await
@@ -170,6 +227,16 @@ pub async fn create_recurring_template_handler(
     Ok(Json(template.into()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/recurring/{template_id}",
+    tag = "recurring_template",
+    params(RecurringTemplatePath, IfNoneMatchHeader),
+    responses(
+        (status = 200, description = "The template", body = RecurringTemplateResponse, headers(("ETag" = String))),
+        (status = 304, description = "The template did not change", headers(("ETag" = String))),
+    ),
+)]
 #[tracing::instrument(
     name = "Get recurring template",
     skip(tx),
@@ -181,19 +248,32 @@ pub async fn create_recurring_template_handler(
 pub async fn get_recurring_template_handler(
     mut tx: Tx,
     AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+    IfNoneMatch(if_none_match): IfNoneMatch,
     extract::Path((todo_name, template_id)): extract::Path<(String, Uuid)>,
-) -> Result<Json<RecurringTemplateResponse>, APIError> {
-    let todo_name = TodoName::try_from(todo_name)?;
+) -> Result<ConditionalGet<Json<RecurringTemplateResponse>>, APIError> {
+    let todo_ref = TodoRef::try_from(todo_name)?;
 
-    let template =
-        get_recurring_template(&mut tx, &todo_name, &template_id, user_id).await?;
+    let template = get_recurring_template(&mut tx, &todo_ref, &template_id, user_id).await?;
+    let etag = ETag::from(template.update_time);
 
-    Ok(Json(template.into()))
+    Ok(ConditionalGet::new(
+        if_none_match,
+        etag,
+        Json(template.into()),
+    ))
 }
 
+#[utoipa::path(
+    put,
+    path = "/todo/{todo_id}/recurring/{template_id}",
+    tag = "recurring_template",
+    params(RecurringTemplatePath, IfMatchHeader),
+    request_body = UpdateRecurringTemplateRequestJson,
+    responses((status = 200, description = "The template", body = RecurringTemplateResponse, headers(("ETag" = String)))),
+)]
 #[tracing::instrument(
     name = "Update recurring template",
-    skip(tx),
+    skip(tx, clock),
     fields(
         todo_name = %todo_name,
         template_id = %template_id
@@ -201,39 +281,46 @@ pub async fn get_recurring_template_handler(
 )]
 pub async fn update_recurring_template_handler(
     mut tx: Tx,
+    AppClock(clock): AppClock,
     AppRecurringSettings(recurring_settings): AppRecurringSettings,
-    AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+    user @ AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+    IfMatch(if_match): IfMatch,
     extract::Path((todo_name, template_id)): extract::Path<(String, Uuid)>,
     Json(req): Json<UpdateRecurringTemplateRequestJson>,
-) -> Result<Json<RecurringTemplateResponse>, APIError> {
-    let todo_name = TodoName::try_from(todo_name)?;
+) -> Result<WithETag<Json<RecurringTemplateResponse>>, APIError> {
+    set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = TodoRef::try_from(todo_name)?;
 
     let update_request = crate::domain::UpdateRecurringTemplateRequest {
+        recurrence: recurrence(req.recurrence_interval, req.rrule)?,
         title: req.title,
-        recurrence_interval: req.recurrence_interval.into(),
-        start_date: req
-            .start_date
-            .unwrap_or_else(|| OffsetDateTime::now_utc().date()),
+        start_date: match req.start_date {
+            Some(start_date) => start_date,
+            None => get_user_today(&mut tx, user_id, clock.now()).await?,
+        },
         end_date: req.end_date,
         is_active: req.is_active,
     };
 
     let template = update_recurring_template(
         &mut tx,
-        &todo_name,
+        &todo_ref,
         &template_id,
         &update_request,
         user_id,
+        if_match.as_ref(),
     )
     .await?;
 
     // Generate any todos that should be created within the advance window
     // after the template update
     let template_single = (&template).into();
+    let current_date = get_user_today(&mut tx, template.todo_owner_id, clock.now()).await?;
     process_single_template(
         &mut tx,
         &template_single,
         recurring_settings.look_ahead_duration,
+        current_date,
         user_id,
     )
     .await
@@ -241,9 +328,18 @@ pub async fn update_recurring_template_handler(
 
     info!("Updated recurring template {}", template.template_id);
 
-    Ok(Json(template.into()))
+    // Generating occurrences may touch the template again, but within the
+    // same transaction so its update time, and ETag, stay the same.
+    Ok(WithETag(template.update_time.into(), Json(template.into())))
 }
 
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/recurring",
+    tag = "recurring_template",
+    params(TodoPath, PaginationQuery),
+    responses((status = 200, description = "A page of templates", body = ListRecurringTemplatesResponse)),
+)]
 #[tracing::instrument(
     name = "List recurring templates",
     skip(tx),
@@ -253,14 +349,23 @@ pub async fn list_recurring_templates_handler(
     mut tx: Tx,
     AuthenticatedUser { user_id, .. }: AuthenticatedUser,
     extract::Path(todo_name): extract::Path<String>,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
 ) -> Result<Json<ListRecurringTemplatesResponse>, APIError> {
-    let todo_name = TodoName::try_from(todo_name)?;
+    let todo_ref = TodoRef::try_from(todo_name)?;
+    let page = pagination.try_into()?;
 
-    let templates = list_recurring_templates(&mut tx, &todo_name, user_id).await?;
+    let templates = list_recurring_templates(&mut tx, &todo_ref, &page, user_id).await?;
 
     Ok(Json(templates.into()))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/todo/{todo_id}/recurring/{template_id}",
+    tag = "recurring_template",
+    params(RecurringTemplatePath, IfMatchHeader),
+    responses((status = 204, description = "The template was deleted")),
+)]
 #[tracing::instrument(
     name = "Delete recurring template",
     skip(tx),
@@ -271,12 +376,14 @@ pub async fn list_recurring_templates_handler(
 )]
 pub async fn delete_recurring_template_handler(
     mut tx: Tx,
-    AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+    user @ AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+    IfMatch(if_match): IfMatch,
     extract::Path((todo_name, template_id)): extract::Path<(String, Uuid)>,
 ) -> Result<StatusCode, APIError> {
-    let todo_name = TodoName::try_from(todo_name)?;
+    set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = TodoRef::try_from(todo_name)?;
 
-    delete_recurring_template(&mut tx, &todo_name, &template_id, user_id).await?;
+    delete_recurring_template(&mut tx, &todo_ref, &template_id, user_id, if_match.as_ref()).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,317 @@
+use axum::{
+    extract::{self, State},
+    http::{header, HeaderMap},
+    Json,
+};
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::configuration::SessionSettings;
+use crate::domain::{
+    self, LoginCode, NewSession, OpaqueToken, RefreshToken, Session, REFRESH_GRACE_PERIOD,
+};
+use crate::error::APIError;
+use crate::extractors::{AppClock, AuthenticatedUser};
+use crate::repos;
+use crate::tx::tx::Tx;
+use crate::AppState;
+
+/// Longest `User-Agent` kept to tell sessions apart.
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExchangeRequest {
+    /// The `code` the login redirected the frontend with
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginResponse {
+    /// Bearer token, valid until the `exp` claim or until the session is
+    /// revoked
+    pub token: String,
+    /// Refresh token of the session, to get new bearer tokens with
+    pub refresh_token: String,
+    pub user_id: i32,
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    /// The refresh token of the session, from the login or the last refresh
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionTokensResponse {
+    /// Bearer token, valid until the `exp` claim or until the session is
+    /// revoked
+    pub token: String,
+    /// Refresh token replacing the one sent. Sending the old one again within
+    /// a few seconds gets this one again, later it revokes the session
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionResponse {
+    pub session_id: Uuid,
+    /// The `User-Agent` of the browser that logged in
+    pub user_agent: Option<String>,
+    /// Whether the bearer token of the request belongs to the session
+    pub current: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub refresh_time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expire_time: OffsetDateTime,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current: Option<Uuid>) -> Self {
+        Self {
+            current: current == Some(session.session_id),
+            session_id: session.session_id,
+            user_agent: session.user_agent,
+            create_time: session.create_time,
+            refresh_time: session.refresh_time,
+            expire_time: session.expire_time,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListSessionResponse {
+    pub items: Vec<SessionResponse>,
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct SessionPath {
+    /// Id of an active session of the user
+    session_id: Uuid,
+}
+
+/// The tokens of a new session.
+struct SessionTokens {
+    token: String,
+    refresh_token: RefreshToken,
+}
+
+fn session_settings(state: &AppState) -> &SessionSettings {
+    state
+        .auth
+        .session()
+        .expect("Session config should be available when users log in through the API")
+}
+
+fn expire_time(settings: &SessionSettings, now: OffsetDateTime) -> OffsetDateTime {
+    now + settings.refresh_token_lifetime
+}
+
+fn access_token(
+    state: &AppState,
+    user: &domain::User,
+    session: &Session,
+    now: OffsetDateTime,
+) -> Result<String, APIError> {
+    let token = state
+        .jwt_service
+        .generate_session_token(
+            user.user_id,
+            &user.email,
+            session.session_id,
+            now,
+            session_settings(state).access_token_lifetime,
+        )
+        .context("Failed to generate the access token")?;
+    Ok(token)
+}
+
+/// Opens a session for the user logging in from the browser sending the
+/// headers.
+async fn open_session(
+    tx: &mut Tx,
+    state: &AppState,
+    user: &domain::User,
+    headers: &HeaderMap,
+) -> Result<SessionTokens, APIError> {
+    let now = state.clock.now();
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+    let new_session = NewSession {
+        user_id: user.user_id,
+        user_agent,
+        refresh_token: RefreshToken::new_random(),
+        refresh_token_salt: OpaqueToken::new_random(),
+        create_time: now,
+        expire_time: expire_time(session_settings(state), now),
+    };
+    let session = repos::create_session(tx, &new_session).await?;
+    Ok(SessionTokens {
+        token: access_token(state, user, &session, now)?,
+        refresh_token: new_session.refresh_token,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/exchange",
+    tag = "auth",
+    security(()),
+    request_body = ExchangeRequest,
+    responses((status = 200, description = "The tokens of a new session of the user who logged in", body = LoginResponse)),
+)]
+#[tracing::instrument(name = "Exchange login code", skip(tx, state, headers, payload))]
+pub async fn exchange_login_code(
+    mut tx: Tx,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<ExchangeRequest>,
+) -> Result<Json<LoginResponse>, APIError> {
+    let code = LoginCode::from(payload.code);
+    let login = repos::take_login_code(&mut tx, &code)
+        .await?
+        .filter(|login| !login.is_expired(state.clock.now()))
+        .ok_or_else(|| {
+            APIError::Unauthorized("Invalid, expired or already used login code".to_string())
+        })?;
+    let user = repos::get_user(&mut tx, login.user_id).await?;
+    let tokens = open_session(&mut tx, &state, &user, &headers).await?;
+    Ok(Json(LoginResponse {
+        token: tokens.token,
+        refresh_token: tokens.refresh_token.secret().to_string(),
+        user_id: user.user_id,
+        email: user.email,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    security(()),
+    request_body = RefreshRequest,
+    responses((status = 200, description = "A new access token and refresh token of the session", body = SessionTokensResponse)),
+)]
+#[tracing::instrument(name = "Refresh session", skip(state, payload))]
+pub async fn refresh_session(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<SessionTokensResponse>, APIError> {
+    let refresh_token = RefreshToken::from(payload.refresh_token);
+    let now = state.clock.now();
+    // Not the request transaction: revoking a session whose refresh token
+    // was reused must stick even though the request fails.
+    let mut transaction = state
+        .tx_state
+        .transaction()
+        .await
+        .context("Failed to start the refresh transaction")?;
+    let mut refreshed = repos::refresh_session(
+        &mut transaction,
+        &refresh_token,
+        now,
+        expire_time(session_settings(&state), now),
+    )
+    .await?;
+    // Another refresh with the same token just won the race
+    if refreshed.is_none() {
+        refreshed = repos::get_refreshed_session(
+            &mut transaction,
+            &refresh_token,
+            now - REFRESH_GRACE_PERIOD,
+            now,
+        )
+        .await?;
+    }
+    let Some((session, new_refresh_token)) = refreshed else {
+        if let Some(session_id) =
+            repos::revoke_reused_session(&mut transaction, &refresh_token, now).await?
+        {
+            warn!(%session_id, "Refresh token reused, session revoked");
+        }
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the refresh transaction")?;
+        return Err(APIError::Unauthorized(
+            "Invalid, expired or revoked refresh token".to_string(),
+        ));
+    };
+    let user = repos::get_user(&mut transaction, session.user_id).await?;
+    let token = access_token(&state, &user, &session, now)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the refresh transaction")?;
+    Ok(Json(SessionTokensResponse {
+        token,
+        refresh_token: new_refresh_token.secret().to_string(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    responses((status = 200, description = "The session of the bearer token is revoked")),
+)]
+#[tracing::instrument(name = "Log out", skip(tx, clock))]
+pub async fn logout(
+    mut tx: Tx,
+    AppClock(clock): AppClock,
+    user: AuthenticatedUser,
+) -> Result<(), APIError> {
+    // Sessions are set up, every accepted token has one
+    if let Some(session_id) = &user.session_id {
+        repos::revoke_session(&mut tx, session_id, user.user_id, clock.now()).await?;
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/sessions",
+    tag = "auth",
+    responses((status = 200, description = "The active sessions of the user, the most recently refreshed first", body = ListSessionResponse)),
+)]
+#[tracing::instrument(name = "List sessions", skip(tx, clock))]
+pub async fn list_sessions(
+    mut tx: Tx,
+    AppClock(clock): AppClock,
+    user: AuthenticatedUser,
+) -> Result<Json<ListSessionResponse>, APIError> {
+    let sessions = repos::list_sessions(&mut tx, user.user_id, clock.now()).await?;
+    Ok(Json(ListSessionResponse {
+        items: sessions
+            .into_iter()
+            .map(|session| SessionResponse::new(session, user.session_id))
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/sessions/{session_id}",
+    tag = "auth",
+    params(SessionPath),
+    responses((status = 200, description = "The session is revoked, its tokens no longer work")),
+)]
+#[tracing::instrument(name = "Revoke session", skip(tx, clock))]
+pub async fn revoke_session(
+    mut tx: Tx,
+    AppClock(clock): AppClock,
+    user: AuthenticatedUser,
+    extract::Path(session_id): extract::Path<Uuid>,
+) -> Result<(), APIError> {
+    repos::revoke_session(&mut tx, &session_id, user.user_id, clock.now()).await?;
+    Ok(())
+}
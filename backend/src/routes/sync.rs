@@ -0,0 +1,160 @@
+use axum::{extract, Json};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::domain::{self, Changed, Changes, PageSize, SyncRequest, SyncToken, Tombstone};
+use crate::error::{APIError, FieldContext};
+use crate::extractors::AuthenticatedUser;
+use crate::repos;
+use crate::routes::{GetTodoResponse, RecurringTemplateResponse, TodoItemSingleResponse};
+use crate::tx::tx::Tx;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SyncQuery {
+    /// The `next_token` of the previous sync, omitted for a first sync
+    pub since: Option<String>,
+    /// Maximum number of changes, defaults to 100 and is capped at 500
+    pub limit: Option<i64>,
+}
+
+impl TryFrom<SyncQuery> for SyncRequest {
+    type Error = APIError;
+    fn try_from(value: SyncQuery) -> Result<Self, Self::Error> {
+        Ok(Self {
+            since: value
+                .since
+                .map(SyncToken::try_from)
+                .transpose()
+                .field("since")?,
+            size: PageSize::try_from(value.limit).field("limit")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncEntity {
+    Todo,
+    Item,
+    Template,
+}
+
+impl From<domain::SyncEntity> for SyncEntity {
+    fn from(value: domain::SyncEntity) -> Self {
+        match value {
+            domain::SyncEntity::Todo => Self::Todo,
+            domain::SyncEntity::Item => Self::Item,
+            domain::SyncEntity::Template => Self::Template,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncTodoItem {
+    pub todo_id: Uuid,
+    #[serde(flatten)]
+    pub item: TodoItemSingleResponse,
+}
+
+impl From<Changed<domain::TodoItem>> for SyncTodoItem {
+    fn from(value: Changed<domain::TodoItem>) -> Self {
+        Self {
+            todo_id: value.todo_id,
+            item: value.value.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncRecurringTemplate {
+    pub todo_id: Uuid,
+    #[serde(flatten)]
+    pub template: RecurringTemplateResponse,
+}
+
+impl From<Changed<domain::RecurringTemplate>> for SyncRecurringTemplate {
+    fn from(value: Changed<domain::RecurringTemplate>) -> Self {
+        Self {
+            todo_id: value.todo_id,
+            template: value.value.into(),
+        }
+    }
+}
+
+/// A deleted todo, item or template. Deleting a todo deletes its items and
+/// templates, only the todo gets a tombstone.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TombstoneResponse {
+    pub entity: SyncEntity,
+    /// The `todo_id`, `todo_item_id` or `template_id` of what was deleted
+    pub entity_id: Uuid,
+    pub todo_id: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub delete_time: OffsetDateTime,
+}
+
+impl From<Tombstone> for TombstoneResponse {
+    fn from(value: Tombstone) -> Self {
+        Self {
+            entity: value.entity.into(),
+            entity_id: value.entity_id,
+            todo_id: value.todo_id,
+            delete_time: value.delete_time,
+        }
+    }
+}
+
+/// The current state of everything created or updated since the token, and
+/// what was deleted.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncResponse {
+    pub todos: Vec<GetTodoResponse>,
+    pub items: Vec<SyncTodoItem>,
+    pub templates: Vec<SyncRecurringTemplate>,
+    pub deleted: Vec<TombstoneResponse>,
+    /// The `since` of the next sync
+    pub next_token: String,
+    /// More changes are waiting, sync again right away
+    pub has_more: bool,
+}
+
+impl From<Changes> for SyncResponse {
+    fn from(value: Changes) -> Self {
+        Self {
+            todos: value.todos.into_iter().map(|c| c.value.into()).collect(),
+            items: value.items.into_iter().map(|c| c.into()).collect(),
+            templates: value.templates.into_iter().map(|c| c.into()).collect(),
+            deleted: value.tombstones.into_iter().map(|t| t.into()).collect(),
+            next_token: value.next_token.into(),
+            has_more: value.has_more,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/sync",
+    tag = "sync",
+    params(SyncQuery),
+    responses((
+        status = 200,
+        description = "The changes to the todos of the user since the token",
+        body = SyncResponse,
+    )),
+)]
+#[tracing::instrument(
+    name = "Sync"
+    skip(tx),
+)]
+pub async fn sync(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Query(query): extract::Query<SyncQuery>,
+) -> Result<Json<SyncResponse>, APIError> {
+    let req = query.try_into()?;
+    let changes = repos::list_changes(&mut tx, &req, user.user_id).await?;
+    Ok(Json(changes.into()))
+}
@@ -3,16 +3,21 @@ use serde::{Deserialize, Serialize};
 use axum::extract;
 use axum::Json;
 use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
 use crate::domain;
 use crate::domain::NewTodoRequest;
-use crate::domain::{ListTodo, ListTodoSingle, Todo};
-use crate::error::APIError;
-use crate::extractors::AuthenticatedUser;
+use crate::domain::{ETag, ListTodo, ListTodoSingle, Todo, TodoName};
+use crate::error::{APIError, FieldContext};
+use crate::extractors::{AppClock, AuthenticatedUser, IfMatch, IfNoneMatch};
 use crate::repos;
+use crate::routes::{
+    ConditionalGet, IfMatchHeader, IfNoneMatchHeader, PaginationQuery, TodoPath, TodoRole, WithETag,
+};
 use crate::tx::tx::Tx;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum TodoVisibility {
     Public,
@@ -37,42 +42,56 @@ impl From<domain::TodoVisibility> for TodoVisibility {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateTodoRequest {
     pub name: String,
     pub visibility: TodoVisibility,
+    /// A group the user edits todos of, to own the todo with its members
+    #[serde(default)]
+    pub group_id: Option<Uuid>,
 }
 
 impl TryFrom<CreateTodoRequest> for NewTodoRequest {
     type Error = APIError;
     fn try_from(value: CreateTodoRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            name: value.name.try_into()?,
+            name: TodoName::try_from(value.name).field("name")?,
             visibility: value.visibility.into(),
+            group_id: value.group_id,
         })
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateTodoRequest {
     pub name: String,
     pub visibility: TodoVisibility,
+    /// The group owning the todo, none to only keep its creator and members
+    #[serde(default)]
+    pub group_id: Option<Uuid>,
 }
 
 impl TryFrom<UpdateTodoRequest> for domain::UpdateTodoRequest {
     type Error = APIError;
     fn try_from(value: UpdateTodoRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            name: value.name.try_into()?,
+            name: TodoName::try_from(value.name).field("name")?,
             visibility: value.visibility.into(),
+            group_id: value.group_id,
         })
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct GetTodoResponse {
+    pub todo_id: Uuid,
+    pub owner_id: i32,
     pub name: String,
     pub visibility: TodoVisibility,
+    /// The group owning the todo with its creator
+    pub group_id: Option<Uuid>,
+    /// The role of the user on the todo
+    pub role: TodoRole,
     #[serde(with = "time::serde::rfc3339")]
     pub create_time: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
@@ -82,23 +101,32 @@ pub struct GetTodoResponse {
 impl From<Todo> for GetTodoResponse {
     fn from(value: Todo) -> Self {
         Self {
+            todo_id: value.todo_id,
+            owner_id: value.owner_id,
             name: value.name.as_ref().to_string(),
             visibility: value.visibility.into(),
+            group_id: value.group_id,
+            role: value.role.into(),
             create_time: value.create_time,
             update_time: value.update_time,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ListTodoResponse {
     items: Vec<ListTodoSingleItem>,
+    next_cursor: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ListTodoSingleItem {
+    todo_id: Uuid,
+    owner_id: i32,
     name: String,
     visibility: TodoVisibility,
+    group_id: Option<Uuid>,
+    role: TodoRole,
     #[serde(with = "time::serde::rfc3339")]
     pub create_time: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
@@ -108,8 +136,12 @@ pub struct ListTodoSingleItem {
 impl From<ListTodoSingle> for ListTodoSingleItem {
     fn from(value: ListTodoSingle) -> Self {
         Self {
+            todo_id: value.todo_id,
+            owner_id: value.owner_id,
             name: value.name.as_ref().to_string(),
             visibility: value.visibility.into(),
+            group_id: value.group_id,
+            role: value.role.into(),
             create_time: value.create_time,
             update_time: value.update_time,
         }
@@ -120,10 +152,18 @@ impl From<ListTodo> for ListTodoResponse {
     fn from(value: ListTodo) -> Self {
         Self {
             items: value.items.into_iter().map(|i| i.into()).collect(),
+            next_cursor: value.next_cursor.map(|c| c.into()),
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/todo",
+    tag = "todo",
+    request_body = CreateTodoRequest,
+    responses((status = 200, description = "The todo was created")),
+)]
 #[tracing::instrument(
     name = "Create TODO"
     skip(tx, payload),
@@ -136,11 +176,22 @@ pub async fn create_todo(
     user: AuthenticatedUser,
     Json(payload): Json<CreateTodoRequest>,
 ) -> Result<(), APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
     let todo = payload.try_into()?;
     repos::create_todo(&mut tx, &todo, user.user_id).await?;
     Ok(())
 }
 
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}",
+    tag = "todo",
+    params(TodoPath, IfNoneMatchHeader),
+    responses(
+        (status = 200, description = "The todo", body = GetTodoResponse, headers(("ETag" = String))),
+        (status = 304, description = "The todo did not change", headers(("ETag" = String))),
+    ),
+)]
 #[tracing::instrument(
     name = "Get TODO"
     skip(tx, todo_str),
@@ -151,32 +202,58 @@ pub async fn create_todo(
 pub async fn get_todo(
     mut tx: Tx,
     user: AuthenticatedUser,
+    IfNoneMatch(if_none_match): IfNoneMatch,
     extract::Path(todo_str): extract::Path<String>,
-) -> Result<Json<GetTodoResponse>, APIError> {
-    let todo_name = todo_str.try_into()?;
-    let todo_response = repos::get_todo_by_name(&mut tx, &todo_name, user.user_id)
-        .await?
-        .into();
-    Ok(Json(todo_response))
+) -> Result<ConditionalGet<Json<GetTodoResponse>>, APIError> {
+    let todo_ref = todo_str.try_into()?;
+    let todo =
+        repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, domain::TodoRole::Viewer).await?;
+    let etag = ETag::from(todo.update_time);
+    Ok(ConditionalGet::new(if_none_match, etag, Json(todo.into())))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/todo/{todo_id}",
+    tag = "todo",
+    params(TodoPath, IfMatchHeader),
+    responses((status = 200, description = "The todo was moved to the trash")),
+)]
 #[tracing::instrument(
     name = "Delete TODO"
-    skip(tx, todo_str),
+    skip(tx, clock, todo_str),
     fields(
         todo = todo_str
     )
 )]
 pub async fn delete_todo(
     mut tx: Tx,
+    AppClock(clock): AppClock,
     user: AuthenticatedUser,
+    IfMatch(if_match): IfMatch,
     extract::Path(todo_str): extract::Path<String>,
 ) -> Result<(), APIError> {
-    let todo_name = todo_str.try_into()?;
-    repos::delete_todo_by_name(&mut tx, &todo_name, user.user_id).await?;
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
+    repos::delete_todo_by_name(
+        &mut tx,
+        &todo_ref,
+        user.user_id,
+        if_match.as_ref(),
+        clock.now(),
+    )
+    .await?;
     Ok(())
 }
 
+#[utoipa::path(
+    put,
+    path = "/todo/{todo_id}",
+    tag = "todo",
+    params(TodoPath, IfMatchHeader),
+    request_body = UpdateTodoRequest,
+    responses((status = 200, description = "The todo was updated", headers(("ETag" = String)))),
+)]
 #[tracing::instrument(
     name = "Update TODO"
     skip(tx, todo_str, payload),
@@ -188,15 +265,25 @@ pub async fn delete_todo(
 pub async fn update_todo(
     mut tx: Tx,
     user: AuthenticatedUser,
+    IfMatch(if_match): IfMatch,
     extract::Path(todo_str): extract::Path<String>,
     Json(payload): Json<UpdateTodoRequest>,
-) -> Result<(), APIError> {
-    let todo_name = todo_str.try_into()?;
+) -> Result<WithETag<()>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
     let todo = payload.try_into()?;
-    repos::update_todo(&mut tx, &todo_name, &todo, user.user_id).await?;
-    Ok(())
+    let todo =
+        repos::update_todo(&mut tx, &todo_ref, &todo, user.user_id, if_match.as_ref()).await?;
+    Ok(WithETag(todo.update_time.into(), ()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/todo",
+    tag = "todo",
+    params(PaginationQuery),
+    responses((status = 200, description = "A page of todos", body = ListTodoResponse)),
+)]
 #[tracing::instrument(
     name = "List TODO"
     skip(tx),
@@ -204,9 +291,9 @@ pub async fn update_todo(
 pub async fn list_todo(
     mut tx: Tx,
     user: AuthenticatedUser,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
 ) -> Result<Json<ListTodoResponse>, APIError> {
-    let todo_response = repos::list_todo(&mut tx, user.user_id)
-        .await?
-        .into();
+    let page = pagination.try_into()?;
+    let todo_response = repos::list_todo(&mut tx, &page, user.user_id).await?.into();
     Ok(Json(todo_response))
 }
@@ -0,0 +1,198 @@
+use axum::extract;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::BoxError;
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::domain::{self, TodoEvent, TodoRole};
+use crate::error::APIError;
+use crate::extractors::{AppTodoEvents, AuthenticatedUser, LastEventId};
+use crate::repos;
+use crate::routes::TodoPath;
+use crate::tx::tx::Tx;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoEventEntity {
+    Item,
+    Template,
+}
+
+impl TodoEventEntity {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Item => "item",
+            Self::Template => "template",
+        }
+    }
+}
+
+impl From<domain::TodoEventEntity> for TodoEventEntity {
+    fn from(value: domain::TodoEventEntity) -> Self {
+        match value {
+            domain::TodoEventEntity::Item => Self::Item,
+            domain::TodoEventEntity::Template => Self::Template,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoEventKind {
+    Created,
+    Updated,
+    Completed,
+    Deleted,
+}
+
+impl TodoEventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Completed => "completed",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+impl From<domain::TodoEventKind> for TodoEventKind {
+    fn from(value: domain::TodoEventKind) -> Self {
+        match value {
+            domain::TodoEventKind::Created => Self::Created,
+            domain::TodoEventKind::Updated => Self::Updated,
+            domain::TodoEventKind::Completed => Self::Completed,
+            domain::TodoEventKind::Deleted => Self::Deleted,
+        }
+    }
+}
+
+/// The data of a server-sent event. The SSE `id` is the `event_id` and the
+/// SSE `event` is `{entity}.{kind}`, e.g. `item.completed`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TodoEventResponse {
+    pub event_id: i64,
+    pub todo_id: Uuid,
+    pub entity: TodoEventEntity,
+    /// The `todo_item_id` or `template_id` of what changed
+    pub entity_id: Uuid,
+    pub kind: TodoEventKind,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+}
+
+impl From<TodoEvent> for TodoEventResponse {
+    fn from(value: TodoEvent) -> Self {
+        Self {
+            event_id: value.event_id,
+            todo_id: value.todo_id,
+            entity: value.entity.into(),
+            entity_id: value.entity_id,
+            kind: value.kind.into(),
+            create_time: value.create_time,
+        }
+    }
+}
+
+impl TodoEventResponse {
+    fn into_sse_event(self) -> Result<Event, axum::Error> {
+        Event::default()
+            .id(self.event_id.to_string())
+            .event(format!("{}.{}", self.entity.name(), self.kind.name()))
+            .json_data(self)
+    }
+}
+
+/// The data of the `reset` event starting a stream that couldn't resume after
+/// the `Last-Event-ID`. Changes since may be missing, the client reads the
+/// todo again and the stream goes on after `event_id`, also the SSE `id`.
+#[derive(Debug, Serialize)]
+struct TodoEventResetResponse {
+    todo_id: Uuid,
+    event_id: i64,
+}
+
+impl TodoEventResetResponse {
+    fn into_sse_event(self) -> Result<Event, axum::Error> {
+        Event::default()
+            .id(self.event_id.to_string())
+            .event("reset")
+            .json_data(self)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct LastEventIdHeader {
+    /// Resume after this event, sent by `EventSource` when it reconnects. An
+    /// event that is no longer kept, like one older than the retention,
+    /// starts the stream with a `reset` event and goes on from the latest
+    /// event.
+    #[param(rename = "Last-Event-ID")]
+    last_event_id: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/events",
+    tag = "todo",
+    params(TodoPath, LastEventIdHeader),
+    responses((
+        status = 200,
+        description = "A stream of server-sent events, one per change to an item or a template. Ends once the user no longer sees the todo",
+        content_type = "text/event-stream",
+        body = TodoEventResponse,
+    )),
+)]
+#[tracing::instrument(
+    name = "Stream TODO events"
+    skip(tx, todo_events, todo_str),
+    fields(
+        todo = todo_str
+    )
+)]
+pub async fn todo_events(
+    mut tx: Tx,
+    AppTodoEvents(todo_events): AppTodoEvents,
+    user: AuthenticatedUser,
+    LastEventId(last_event_id): LastEventId,
+    extract::Path(todo_str): extract::Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, BoxError>>>, APIError> {
+    let todo_ref = todo_str.try_into()?;
+    let todo = repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, TodoRole::Viewer).await?;
+    // New clients only get what happens from now on, and so do clients
+    // resuming after an event that is no longer kept, told by a reset: the
+    // events since may be purged too.
+    let last_event = repos::get_last_todo_event_id(&mut tx, &todo.todo_id).await?;
+    let (after_event_id, reset) = match last_event_id {
+        None => (last_event, None),
+        Some(event_id)
+            if event_id == last_event
+                || repos::todo_event_exists(&mut tx, &todo.todo_id, event_id).await? =>
+        {
+            (event_id, None)
+        }
+        Some(_) => (
+            last_event,
+            Some(TodoEventResetResponse {
+                todo_id: todo.todo_id,
+                event_id: last_event,
+            }),
+        ),
+    };
+
+    // An error aborts the response, the client reconnects with the id of
+    // the last event it received.
+    let reset =
+        stream::iter(reset).map(|reset| -> Result<Event, BoxError> { Ok(reset.into_sse_event()?) });
+    let events = todo_events
+        .stream(todo.todo_id, user.user_id, after_event_id)
+        .map(|event| -> Result<Event, BoxError> {
+            Ok(TodoEventResponse::from(event?).into_sse_event()?)
+        });
+    Ok(Sse::new(reset.chain(events)).keep_alive(KeepAlive::default()))
+}
@@ -1,30 +1,79 @@
 use axum::extract;
 use axum::Json;
+use eyre::Context;
 use serde::{Deserialize, Serialize};
+use sqlx::PgTransaction;
 use time::Date;
 use time::OffsetDateTime;
-use time::UtcOffset;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::domain;
-use crate::domain::NewTodoItemRequest;
+use crate::domain::{ETag, NewTodoItemRequest, TodoRef};
 use crate::error::APIError;
-use crate::extractors::AuthenticatedUser;
+use crate::extractors::{AppClock, AuthenticatedUser, IfMatch, IfNoneMatch};
 use crate::repos;
+use crate::routes::{
+    ConditionalGet, IfMatchHeader, IfNoneMatchHeader, PaginationQuery, TodoItemPath, TodoPath,
+    WithETag,
+};
+use crate::services;
 use crate::tx::tx::Tx;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateTodoItemRequest {
     pub title: String,
     pub due_date: Option<Date>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoItemStatus {
+    #[default]
+    Open,
+    Completed,
+    All,
+}
+
+impl From<TodoItemStatus> for domain::TodoItemStatus {
+    fn from(value: TodoItemStatus) -> Self {
+        match value {
+            TodoItemStatus::Open => Self::Open,
+            TodoItemStatus::Completed => Self::Completed,
+            TodoItemStatus::All => Self::All,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListTodoItemQuery {
+    #[serde(default)]
+    pub status: TodoItemStatus,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub completed_since: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub completed_before: Option<OffsetDateTime>,
+}
+
+impl TryFrom<ListTodoItemQuery> for domain::TodoItemFilter {
+    type Error = APIError;
+    fn try_from(value: ListTodoItemQuery) -> Result<Self, Self::Error> {
+        Self::new(
+            value.status.into(),
+            value.completed_since,
+            value.completed_before,
+        )
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ListTodoItemResponse {
     pub items: Vec<TodoItemSingleResponse>,
+    pub next_cursor: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct TodoItemSingleResponse {
     pub todo_item_id: Uuid,
     pub title: String,
@@ -38,7 +87,7 @@ pub struct TodoItemSingleResponse {
     pub update_time: OffsetDateTime,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateTodoItemRequest {
     pub title: String,
     pub due_date: Date,
@@ -50,29 +99,13 @@ pub type CreateTodoItemResponse = TodoItemSingleResponse;
 pub type GetTodoItemResponse = TodoItemSingleResponse;
 pub type UpdateTodoItemResponse = TodoItemSingleResponse;
 
-impl TryFrom<CreateTodoItemRequest> for NewTodoItemRequest {
-    type Error = APIError;
-    fn try_from(value: CreateTodoItemRequest) -> Result<Self, Self::Error> {
-        let due_date = match value.due_date {
-            Some(x) => x,
-            // This is probably wrong
-            None => {
-                let offset = match UtcOffset::current_local_offset() {
-                    Ok(x) => x,
-                    Err(e) => {
-                        tracing::warn!("Could not find the time offset, default to UTC: {}", e);
-                        UtcOffset::UTC
-                    }
-                };
-                OffsetDateTime::now_utc().to_offset(offset).date()
-            }
-        };
-        Ok(Self {
-            title: value.title,
-            due_date,
-            recurring_template_id: None, // Regular todo items are not linked to templates
-        })
-    }
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompleteTodoItemResponse {
+    #[serde(flatten)]
+    pub item: TodoItemSingleResponse,
+    /// The next occurrence generated when the item belongs to a recurring
+    /// template.
+    pub next_item: Option<TodoItemSingleResponse>,
 }
 
 impl TryFrom<UpdateTodoItemRequest> for domain::UpdateTodoItemRequest {
@@ -89,6 +122,7 @@ impl From<domain::ListTodoItem> for ListTodoItemResponse {
     fn from(value: domain::ListTodoItem) -> Self {
         Self {
             items: value.items.into_iter().map(|i| i.into()).collect(),
+            next_cursor: value.next_cursor.map(|c| c.into()),
         }
     }
 }
@@ -131,6 +165,13 @@ impl TryFrom<TodoItemSingleResponse> for domain::UpdateTodoItemRequest {
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/item",
+    tag = "todo_item",
+    params(TodoPath, ListTodoItemQuery, PaginationQuery),
+    responses((status = 200, description = "A page of items", body = ListTodoItemResponse)),
+)]
 #[tracing::instrument(
     name = "List TODO Item"
     skip(tx, todo_str),
@@ -142,37 +183,81 @@ pub async fn list_todo_items(
     mut tx: Tx,
     user: AuthenticatedUser,
     extract::Path(todo_str): extract::Path<String>,
+    extract::Query(query): extract::Query<ListTodoItemQuery>,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
 ) -> Result<Json<ListTodoItemResponse>, APIError> {
-    let todo_name = todo_str.try_into()?;
+    let todo_ref = todo_str.try_into()?;
+    let filter = query.try_into()?;
+    let page = pagination.try_into()?;
 
-    let result = repos::list_todo_items(&mut tx, &todo_name, user.user_id)
+    let result = repos::list_todo_items(&mut tx, &todo_ref, &filter, &page, user.user_id)
         .await?
         .into();
     Ok(Json(result))
 }
 
+#[utoipa::path(
+    post,
+    path = "/todo/{todo_id}/item",
+    tag = "todo_item",
+    params(TodoPath),
+    request_body = CreateTodoItemRequest,
+    responses((status = 200, description = "The item", body = TodoItemSingleResponse)),
+)]
 #[tracing::instrument(
     name = "Create TODO Item"
-    skip(tx, todo_str, payload),
+    skip(tx, clock, todo_str, payload),
     fields(
         todo_name = %todo_str
     )
 )]
 pub async fn create_todo_item(
     mut tx: Tx,
+    AppClock(clock): AppClock,
     user: AuthenticatedUser,
     extract::Path(todo_str): extract::Path<String>,
     Json(payload): Json<CreateTodoItemRequest>,
 ) -> Result<Json<CreateTodoItemResponse>, APIError> {
-    let todo_name = todo_str.try_into()?;
-    let todo = payload.try_into()?;
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
 
-    let todo_item = repos::create_todo_item(&mut tx, &todo_name, &todo, user.user_id)
+    let todo_item = create_item(&mut tx, &todo_ref, payload, user.user_id, clock.now())
         .await?
         .into();
     Ok(Json(todo_item))
 }
 
+pub(super) async fn create_item(
+    tx: &mut PgTransaction<'_>,
+    todo_ref: &TodoRef,
+    payload: CreateTodoItemRequest,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<domain::TodoItem, APIError> {
+    // Without an explicit due date the item is due today, as seen by the user
+    let due_date = match payload.due_date {
+        Some(due_date) => due_date,
+        None => repos::get_user_today(tx, user_id, now).await?,
+    };
+    let todo = NewTodoItemRequest {
+        title: payload.title,
+        due_date,
+        recurring_template_id: None, // Regular todo items are not linked to templates
+    };
+
+    repos::create_todo_item(tx, todo_ref, &todo, user_id).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/item/{item_id}",
+    tag = "todo_item",
+    params(TodoItemPath, IfNoneMatchHeader),
+    responses(
+        (status = 200, description = "The item", body = TodoItemSingleResponse, headers(("ETag" = String))),
+        (status = 304, description = "The item did not change", headers(("ETag" = String))),
+    ),
+)]
 #[tracing::instrument(
     name = "Get TODO Item"
     skip(tx, todo_str, todo_item),
@@ -184,16 +269,28 @@ pub async fn create_todo_item(
 pub async fn get_todo_item(
     mut tx: Tx,
     user: AuthenticatedUser,
+    IfNoneMatch(if_none_match): IfNoneMatch,
     extract::Path((todo_str, todo_item)): extract::Path<(String, Uuid)>,
-) -> Result<Json<GetTodoItemResponse>, APIError> {
-    let todo_name = todo_str.try_into()?;
+) -> Result<ConditionalGet<Json<GetTodoItemResponse>>, APIError> {
+    let todo_ref = todo_str.try_into()?;
 
-    let todo_item = repos::get_todo_item(&mut tx, &todo_name, &todo_item, user.user_id)
-        .await?
-        .into();
-    Ok(Json(todo_item))
+    let todo_item = repos::get_todo_item(&mut tx, &todo_ref, &todo_item, user.user_id).await?;
+    let etag = ETag::from(todo_item.update_time);
+    Ok(ConditionalGet::new(
+        if_none_match,
+        etag,
+        Json(todo_item.into()),
+    ))
 }
 
+#[utoipa::path(
+    put,
+    path = "/todo/{todo_id}/item/{item_id}",
+    tag = "todo_item",
+    params(TodoItemPath, IfMatchHeader),
+    request_body = UpdateTodoItemRequest,
+    responses((status = 200, description = "The item", body = TodoItemSingleResponse, headers(("ETag" = String)))),
+)]
 #[tracing::instrument(
     name = "Update TODO Item"
     skip(tx, todo_str, todo_item, payload),
@@ -205,27 +302,39 @@ pub async fn get_todo_item(
 pub async fn update_todo_item(
     mut tx: Tx,
     user: AuthenticatedUser,
+    IfMatch(if_match): IfMatch,
     extract::Path((todo_str, todo_item)): extract::Path<(String, Uuid)>,
     Json(payload): Json<UpdateTodoItemRequest>,
-) -> Result<Json<UpdateTodoItemResponse>, APIError> {
-    let todo_name = todo_str.try_into()?;
+) -> Result<WithETag<Json<UpdateTodoItemResponse>>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
     let item = payload.try_into()?;
 
     let todo_item = repos::update_todo_item(
         &mut tx,
-        &todo_name,
+        &todo_ref,
         &todo_item,
         &item,
         user.user_id,
+        if_match.as_ref(),
     )
-    .await?
-    .into();
-    Ok(Json(todo_item))
+    .await?;
+    Ok(WithETag(
+        todo_item.update_time.into(),
+        Json(todo_item.into()),
+    ))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/todo/{todo_id}/item/{item_id}",
+    tag = "todo_item",
+    params(TodoItemPath, IfMatchHeader),
+    responses((status = 200, description = "The item was moved to the trash")),
+)]
 #[tracing::instrument(
     name = "Delete TODO Item"
-    skip(tx, todo_str, todo_item),
+    skip(tx, clock, todo_str, todo_item),
     fields(
         todo_name = %todo_str,
         todo_item = %todo_item,
@@ -233,18 +342,36 @@ pub async fn update_todo_item(
 )]
 pub async fn delete_todo_item(
     mut tx: Tx,
+    AppClock(clock): AppClock,
     user: AuthenticatedUser,
+    IfMatch(if_match): IfMatch,
     extract::Path((todo_str, todo_item)): extract::Path<(String, Uuid)>,
 ) -> Result<(), APIError> {
-    let todo_name = todo_str.try_into()?;
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
 
-    repos::delete_todo_item(&mut tx, &todo_name, &todo_item, user.user_id).await?;
+    repos::delete_todo_item(
+        &mut tx,
+        &todo_ref,
+        &todo_item,
+        user.user_id,
+        if_match.as_ref(),
+        clock.now(),
+    )
+    .await?;
     Ok(())
 }
 
+#[utoipa::path(
+    post,
+    path = "/todo/{todo_id}/item/{item_id}/complete",
+    tag = "todo_item",
+    params(TodoItemPath),
+    responses((status = 200, description = "The completed item and its next occurrence", body = CompleteTodoItemResponse)),
+)]
 #[tracing::instrument(
     name = "Complete TODO Item"
-    skip(tx, todo_str, todo_item),
+    skip(tx, clock, todo_str, todo_item),
     fields(
         todo_name = %todo_str,
         todo_item = %todo_item,
@@ -252,13 +379,65 @@ pub async fn delete_todo_item(
 )]
 pub async fn complete_todo_item(
     mut tx: Tx,
+    AppClock(clock): AppClock,
+    user: AuthenticatedUser,
+    extract::Path((todo_str, todo_item)): extract::Path<(String, Uuid)>,
+) -> Result<Json<CompleteTodoItemResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
+
+    let response = complete_item(&mut tx, &todo_ref, &todo_item, user.user_id, clock.now()).await?;
+    Ok(Json(response))
+}
+
+pub(super) async fn complete_item(
+    tx: &mut PgTransaction<'_>,
+    todo_ref: &TodoRef,
+    todo_item: &Uuid,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<CompleteTodoItemResponse, APIError> {
+    let todo_item = repos::complete_todo_item(tx, todo_ref, todo_item, user_id, now).await?;
+    let next_item = match todo_item.recurring_template_id {
+        Some(template_id) => {
+            services::generate_next_occurrence(tx, &template_id, todo_item.due_date, now)
+                .await
+                .context("Failed to generate the next recurring occurrence")?
+        }
+        None => None,
+    };
+    Ok(CompleteTodoItemResponse {
+        item: todo_item.into(),
+        next_item: next_item.map(|i| i.into()),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/todo/{todo_id}/item/{item_id}/reopen",
+    tag = "todo_item",
+    params(TodoItemPath),
+    responses((status = 200, description = "The item", body = TodoItemSingleResponse)),
+)]
+#[tracing::instrument(
+    name = "Reopen TODO Item"
+    skip(tx, clock, todo_str, todo_item),
+    fields(
+        todo_name = %todo_str,
+        todo_item = %todo_item,
+    )
+)]
+pub async fn reopen_todo_item(
+    mut tx: Tx,
+    AppClock(clock): AppClock,
     user: AuthenticatedUser,
     extract::Path((todo_str, todo_item)): extract::Path<(String, Uuid)>,
 ) -> Result<Json<GetTodoItemResponse>, APIError> {
-    let todo_name = todo_str.try_into()?;
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
 
     let todo_item =
-        repos::complete_todo_item(&mut tx, &todo_name, &todo_item, user.user_id)
+        repos::reopen_todo_item(&mut tx, &todo_ref, &todo_item, user.user_id, clock.now())
             .await?
             .into();
     Ok(Json(todo_item))
@@ -0,0 +1,167 @@
+use axum::extract;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use time::Date;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::domain::{self, TodoRef};
+use crate::error::{APIError, FieldError};
+use crate::extractors::{AppClock, AuthenticatedUser};
+use crate::repos;
+use crate::routes::todo_item::{complete_item, create_item};
+use crate::routes::{CreateTodoItemRequest, TodoItemSingleResponse, TodoPath};
+use crate::tx::tx::Tx;
+
+/// A single operation of a batch, tagged by `op`.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TodoItemOperation {
+    Create {
+        title: String,
+        due_date: Option<Date>,
+    },
+    Update {
+        todo_item_id: Uuid,
+        title: String,
+        due_date: Date,
+    },
+    Complete {
+        todo_item_id: Uuid,
+    },
+    Delete {
+        todo_item_id: Uuid,
+    },
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchTodoItemRequest {
+    pub operations: Vec<TodoItemOperation>,
+}
+
+/// The result of an operation, in the same position as in the request.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TodoItemOperationResult {
+    Create {
+        item: TodoItemSingleResponse,
+    },
+    Update {
+        item: TodoItemSingleResponse,
+    },
+    Complete {
+        item: TodoItemSingleResponse,
+        next_item: Option<TodoItemSingleResponse>,
+    },
+    Delete {
+        todo_item_id: Uuid,
+    },
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchTodoItemResponse {
+    pub results: Vec<TodoItemOperationResult>,
+}
+
+/// Tells the caller which operation of the batch failed, whatever the error.
+fn operation_error(index: usize, error: APIError) -> APIError {
+    let prefix = |x: String| format!("operation {}: {}", index, x);
+    match error {
+        APIError::BadRequest(x) => APIError::BadRequest(prefix(x)),
+        APIError::AlreadyExists(x) => APIError::AlreadyExists(prefix(x)),
+        APIError::NotFound(x) => APIError::NotFound(prefix(x)),
+        APIError::Conflict(x) => APIError::Conflict(prefix(x)),
+        APIError::PreconditionFailed(x) => APIError::PreconditionFailed(prefix(x)),
+        APIError::Unauthorized(x) => APIError::Unauthorized(prefix(x)),
+        APIError::Forbidden(x) => APIError::Forbidden(prefix(x)),
+        APIError::Validation(errors) => APIError::Validation(
+            errors
+                .into_iter()
+                .map(|e| FieldError {
+                    field: format!("operations[{}].{}", index, e.field),
+                    message: e.message,
+                })
+                .collect(),
+        ),
+        // Only logged, clients get a generic problem
+        APIError::Internal(e) => eyre::Report::new(e)
+            .wrap_err(format!("operation {}", index))
+            .into(),
+    }
+}
+
+/// Runs every operation in the request transaction. The first failing
+/// operation fails the whole batch, so the transaction is rolled back and
+/// none of the operations are applied.
+#[utoipa::path(
+    post,
+    path = "/todo/{todo_id}/item:batch",
+    tag = "todo_item",
+    params(TodoPath),
+    request_body = BatchTodoItemRequest,
+    responses((status = 200, description = "The result of each operation", body = BatchTodoItemResponse)),
+)]
+#[tracing::instrument(
+    name = "Batch TODO Items"
+    skip(tx, clock, todo_str, payload),
+    fields(
+        todo_name = %todo_str,
+        operations = payload.operations.len(),
+    )
+)]
+pub async fn batch_todo_items(
+    mut tx: Tx,
+    AppClock(clock): AppClock,
+    user: AuthenticatedUser,
+    extract::Path(todo_str): extract::Path<String>,
+    Json(payload): Json<BatchTodoItemRequest>,
+) -> Result<Json<BatchTodoItemResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref: TodoRef = todo_str.try_into()?;
+    let now = clock.now();
+
+    let mut results = Vec::with_capacity(payload.operations.len());
+    for (index, operation) in payload.operations.into_iter().enumerate() {
+        let result = match operation {
+            TodoItemOperation::Create { title, due_date } => {
+                let payload = CreateTodoItemRequest { title, due_date };
+                create_item(&mut tx, &todo_ref, payload, user.user_id, now)
+                    .await
+                    .map(|item| TodoItemOperationResult::Create { item: item.into() })
+            }
+            TodoItemOperation::Update {
+                todo_item_id,
+                title,
+                due_date,
+            } => {
+                let item = domain::UpdateTodoItemRequest { title, due_date };
+                repos::update_todo_item(
+                    &mut tx,
+                    &todo_ref,
+                    &todo_item_id,
+                    &item,
+                    user.user_id,
+                    None,
+                )
+                .await
+                .map(|item| TodoItemOperationResult::Update { item: item.into() })
+            }
+            TodoItemOperation::Complete { todo_item_id } => {
+                complete_item(&mut tx, &todo_ref, &todo_item_id, user.user_id, now)
+                    .await
+                    .map(|response| TodoItemOperationResult::Complete {
+                        item: response.item,
+                        next_item: response.next_item,
+                    })
+            }
+            TodoItemOperation::Delete { todo_item_id } => {
+                repos::delete_todo_item(&mut tx, &todo_ref, &todo_item_id, user.user_id, None, now)
+                    .await
+                    .map(|_| TodoItemOperationResult::Delete { todo_item_id })
+            }
+        };
+        results.push(result.map_err(|e| operation_error(index, e))?);
+    }
+
+    Ok(Json(BatchTodoItemResponse { results }))
+}
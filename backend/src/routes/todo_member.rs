@@ -0,0 +1,235 @@
+use axum::{extract, Json};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::domain::{self, ListTodoMember, NewTodoMemberRequest, TodoMember};
+use crate::error::{APIError, FieldContext};
+use crate::extractors::AuthenticatedUser;
+use crate::repos;
+use crate::routes::{PaginationQuery, TodoPath};
+use crate::tx::tx::Tx;
+
+/// What a user may do with a todo. Viewers read it, editors also change its
+/// items and templates, owners also change the todo and its members. Every
+/// user is an editor of a public todo.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl From<TodoRole> for domain::TodoRole {
+    fn from(value: TodoRole) -> Self {
+        match value {
+            TodoRole::Viewer => Self::Viewer,
+            TodoRole::Editor => Self::Editor,
+            TodoRole::Owner => Self::Owner,
+        }
+    }
+}
+
+impl From<domain::TodoRole> for TodoRole {
+    fn from(value: domain::TodoRole) -> Self {
+        match value {
+            domain::TodoRole::Viewer => Self::Viewer,
+            domain::TodoRole::Editor => Self::Editor,
+            domain::TodoRole::Owner => Self::Owner,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateTodoMemberRequest {
+    /// The email the user logs in with
+    pub email: String,
+    pub role: TodoRole,
+}
+
+impl TryFrom<CreateTodoMemberRequest> for NewTodoMemberRequest {
+    type Error = APIError;
+    fn try_from(value: CreateTodoMemberRequest) -> Result<Self, Self::Error> {
+        let email = value.email.trim();
+        if email.is_empty() {
+            return Err(APIError::BadRequest("Email cannot be empty".to_string())).field("email");
+        }
+        Ok(Self {
+            email: email.to_string(),
+            role: value.role.into(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateTodoMemberRequest {
+    pub role: TodoRole,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TodoMemberResponse {
+    pub user_id: i32,
+    pub email: String,
+    pub role: TodoRole,
+    /// The creator of the todo always owns it
+    pub is_creator: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+}
+
+impl From<TodoMember> for TodoMemberResponse {
+    fn from(value: TodoMember) -> Self {
+        Self {
+            user_id: value.user_id,
+            email: value.email,
+            role: value.role.into(),
+            is_creator: value.is_creator,
+            create_time: value.create_time,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListTodoMemberResponse {
+    pub items: Vec<TodoMemberResponse>,
+    pub next_cursor: Option<String>,
+}
+
+impl From<ListTodoMember> for ListTodoMemberResponse {
+    fn from(value: ListTodoMember) -> Self {
+        Self {
+            items: value.items.into_iter().map(|m| m.into()).collect(),
+            next_cursor: value.next_cursor.map(|c| c.into()),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct TodoMemberPath {
+    /// Name of a todo of the user, or id of any todo visible to them
+    todo_id: String,
+    /// The `user_id` of the member
+    user_id: i32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/todo/{todo_id}/member",
+    tag = "todo",
+    params(TodoPath, PaginationQuery),
+    responses((status = 200, description = "A page of the users the todo is shared with, listed to its owners only", body = ListTodoMemberResponse)),
+)]
+#[tracing::instrument(
+    name = "List TODO members"
+    skip(tx, todo_str),
+    fields(
+        todo = todo_str
+    )
+)]
+pub async fn list_todo_members(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(todo_str): extract::Path<String>,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
+) -> Result<Json<ListTodoMemberResponse>, APIError> {
+    let todo_ref = todo_str.try_into()?;
+    let page = pagination.try_into()?;
+    let todo =
+        repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, domain::TodoRole::Owner).await?;
+    let members = repos::list_todo_members(&mut tx, &todo, &page).await?;
+    Ok(Json(members.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/todo/{todo_id}/member",
+    tag = "todo",
+    params(TodoPath),
+    request_body = CreateTodoMemberRequest,
+    responses((status = 200, description = "The todo is shared with the user", body = TodoMemberResponse)),
+)]
+#[tracing::instrument(
+    name = "Add TODO member"
+    skip(tx, todo_str, payload),
+    fields(
+        todo = todo_str
+    )
+)]
+pub async fn add_todo_member(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(todo_str): extract::Path<String>,
+    Json(payload): Json<CreateTodoMemberRequest>,
+) -> Result<Json<TodoMemberResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
+    let req = payload.try_into()?;
+    let todo =
+        repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, domain::TodoRole::Owner).await?;
+    let member = repos::add_todo_member(&mut tx, &todo, &req).await?;
+    Ok(Json(member.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/todo/{todo_id}/member/{user_id}",
+    tag = "todo",
+    params(TodoMemberPath),
+    request_body = UpdateTodoMemberRequest,
+    responses((status = 200, description = "The member", body = TodoMemberResponse)),
+)]
+#[tracing::instrument(
+    name = "Update TODO member"
+    skip(tx, todo_str, payload),
+    fields(
+        todo = todo_str
+    )
+)]
+pub async fn update_todo_member(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path((todo_str, member_id)): extract::Path<(String, i32)>,
+    Json(payload): Json<UpdateTodoMemberRequest>,
+) -> Result<Json<TodoMemberResponse>, APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
+    let todo =
+        repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, domain::TodoRole::Owner).await?;
+    let member = repos::update_todo_member(&mut tx, &todo, member_id, payload.role.into()).await?;
+    Ok(Json(member.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todo/{todo_id}/member/{user_id}",
+    tag = "todo",
+    params(TodoMemberPath),
+    responses((status = 200, description = "The todo is no longer shared with the user")),
+)]
+#[tracing::instrument(
+    name = "Remove TODO member"
+    skip(tx, todo_str),
+    fields(
+        todo = todo_str
+    )
+)]
+pub async fn remove_todo_member(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path((todo_str, member_id)): extract::Path<(String, i32)>,
+) -> Result<(), APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    let todo_ref = todo_str.try_into()?;
+    // Members can leave a todo on their own
+    let required = if member_id == user.user_id {
+        domain::TodoRole::Viewer
+    } else {
+        domain::TodoRole::Owner
+    };
+    let todo = repos::get_todo_by_name(&mut tx, &todo_ref, user.user_id, required).await?;
+    repos::remove_todo_member(&mut tx, &todo, member_id).await?;
+    Ok(())
+}
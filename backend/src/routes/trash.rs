@@ -0,0 +1,133 @@
+use axum::{extract, Json};
+use serde::Serialize;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::domain::{self, ListTrash, TrashEntry};
+use crate::error::APIError;
+use crate::extractors::{AppTrashSettings, AuthenticatedUser};
+use crate::repos;
+use crate::routes::PaginationQuery;
+use crate::tx::tx::Tx;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashEntity {
+    Todo,
+    Item,
+}
+
+impl From<domain::TrashEntity> for TrashEntity {
+    fn from(value: domain::TrashEntity) -> Self {
+        match value {
+            domain::TrashEntity::Todo => Self::Todo,
+            domain::TrashEntity::Item => Self::Item,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrashEntryResponse {
+    pub entity: TrashEntity,
+    /// The `todo_id` or `todo_item_id`, restored with `POST /trash/{id}/restore`
+    pub id: Uuid,
+    pub todo_id: Uuid,
+    /// The name of a todo or the title of an item
+    pub title: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted_at: OffsetDateTime,
+    /// When it will be deleted for good
+    #[serde(with = "time::serde::rfc3339")]
+    pub purge_time: OffsetDateTime,
+}
+
+impl TrashEntryResponse {
+    fn new(entry: TrashEntry, retention: std::time::Duration) -> Self {
+        Self {
+            entity: entry.entity.into(),
+            id: entry.id,
+            todo_id: entry.todo_id,
+            title: entry.title,
+            deleted_at: entry.deleted_at,
+            purge_time: entry.deleted_at + retention,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListTrashResponse {
+    pub items: Vec<TrashEntryResponse>,
+    pub next_cursor: Option<String>,
+}
+
+impl ListTrashResponse {
+    fn new(list: ListTrash, retention: std::time::Duration) -> Self {
+        Self {
+            items: list
+                .items
+                .into_iter()
+                .map(|entry| TrashEntryResponse::new(entry, retention))
+                .collect(),
+            next_cursor: list.next_cursor.map(|c| c.into()),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct TrashPath {
+    /// The `id` of a trash entry
+    id: Uuid,
+}
+
+#[utoipa::path(
+    get,
+    path = "/trash",
+    tag = "trash",
+    params(PaginationQuery),
+    responses((
+        status = 200,
+        description = "A page of the trashed todos the user owns and items of the todos they edit, most recently deleted first",
+        body = ListTrashResponse,
+    )),
+)]
+#[tracing::instrument(
+    name = "List trash"
+    skip(tx, trash_settings),
+)]
+pub async fn list_trash(
+    mut tx: Tx,
+    AppTrashSettings(trash_settings): AppTrashSettings,
+    user: AuthenticatedUser,
+    extract::Query(pagination): extract::Query<PaginationQuery>,
+) -> Result<Json<ListTrashResponse>, APIError> {
+    let page = pagination.try_into()?;
+    let trash = repos::list_trash(&mut tx, &page, user.user_id).await?;
+    Ok(Json(ListTrashResponse::new(
+        trash,
+        trash_settings.retention,
+    )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/trash/{id}/restore",
+    tag = "trash",
+    params(TrashPath),
+    responses((status = 200, description = "The todo or item was restored")),
+)]
+#[tracing::instrument(
+    name = "Restore from trash"
+    skip(tx),
+)]
+pub async fn restore_from_trash(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    extract::Path(id): extract::Path<Uuid>,
+) -> Result<(), APIError> {
+    repos::set_audit_actor(&mut tx, &user.audit_actor()).await?;
+    repos::restore_from_trash(&mut tx, &id, user.user_id).await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use axum::Json;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::domain;
+use crate::domain::TimeZoneName;
+use crate::error::{APIError, FieldContext};
+use crate::extractors::AuthenticatedUser;
+use crate::repos;
+use crate::tx::tx::Tx;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct UserResponse {
+    pub email: String,
+    pub time_zone: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub create_time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub update_time: OffsetDateTime,
+}
+
+impl From<domain::User> for UserResponse {
+    fn from(value: domain::User) -> Self {
+        Self {
+            email: value.email,
+            time_zone: value.time_zone,
+            create_time: value.create_time,
+            update_time: value.update_time,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateUserRequest {
+    pub time_zone: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/me",
+    tag = "user",
+    responses((status = 200, description = "The user", body = UserResponse)),
+)]
+#[tracing::instrument(
+    name = "Get current user"
+    skip(tx),
+)]
+pub async fn get_current_user(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+) -> Result<Json<UserResponse>, APIError> {
+    let user_response = repos::get_user(&mut tx, user.user_id).await?.into();
+    Ok(Json(user_response))
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/me",
+    tag = "user",
+    request_body = UpdateUserRequest,
+    responses((status = 200, description = "The user", body = UserResponse)),
+)]
+#[tracing::instrument(
+    name = "Update current user"
+    skip(tx, payload),
+    fields(
+        time_zone = %payload.time_zone,
+    )
+)]
+pub async fn update_current_user(
+    mut tx: Tx,
+    user: AuthenticatedUser,
+    Json(payload): Json<UpdateUserRequest>,
+) -> Result<Json<UserResponse>, APIError> {
+    let time_zone = TimeZoneName::try_from(payload.time_zone).field("time_zone")?;
+    let user_response = repos::update_user_time_zone(&mut tx, user.user_id, &time_zone)
+        .await?
+        .into();
+    Ok(Json(user_response))
+}
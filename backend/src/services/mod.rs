@@ -1,3 +1,9 @@
+mod oidc;
 mod recurring;
+mod todo_events;
+mod trash;
 
+pub use oidc::*;
 pub use recurring::*;
+pub use todo_events::*;
+pub use trash::*;
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use eyre::{bail, eyre, Context, Result};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use oauth2::basic::{
+    BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+    BasicTokenType,
+};
+use oauth2::{
+    AuthUrl, ClientId, ClientSecret, ExtraTokenFields, RedirectUrl, StandardRevocableToken,
+    StandardTokenResponse, TokenUrl,
+};
+use parking_lot::RwLock;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+use tracing::info;
+
+use crate::configuration::OidcProviderSettings;
+
+/// The ID token returned along with the access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenFields {
+    pub id_token: String,
+}
+
+impl ExtraTokenFields for IdTokenFields {}
+
+pub type OidcClient = oauth2::Client<
+    BasicErrorResponse,
+    StandardTokenResponse<IdTokenFields, BasicTokenType>,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/// The claims of an ID token used to find the user.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub nonce: Option<String>,
+}
+
+impl IdTokenClaims {
+    /// The email of the user, if the provider checked they own it.
+    pub fn verified_email(&self) -> Option<&str> {
+        match self.email_verified {
+            Some(true) => self.email.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// What `.well-known/openid-configuration` tells about a provider.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// The providers of the `oidc` auth type by name. Their endpoints are
+/// discovered on first use, their keys are fetched again when an ID token is
+/// signed with a key they didn't publish yet.
+#[derive(Clone, Default)]
+pub struct OidcProviders(Arc<HashMap<String, OidcProvider>>);
+
+impl OidcProviders {
+    pub fn new(settings: &[OidcProviderSettings]) -> Self {
+        let providers = settings
+            .iter()
+            .map(|settings| (settings.name.clone(), OidcProvider::new(settings.clone())))
+            .collect();
+        Self(Arc::new(providers))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.0.get(name)
+    }
+}
+
+pub struct OidcProvider {
+    pub settings: OidcProviderSettings,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<JwkSet>,
+    http: reqwest::Client,
+}
+
+impl OidcProvider {
+    fn new(settings: OidcProviderSettings) -> Self {
+        Self {
+            settings,
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(JwkSet { keys: Vec::new() }),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.settings.issuer_url.trim_end_matches('/')
+                );
+                let metadata: ProviderMetadata = self
+                    .http
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .with_context(|| format!("Failed to read {}", url))?;
+                if metadata.issuer.trim_end_matches('/')
+                    != self.settings.issuer_url.trim_end_matches('/')
+                {
+                    bail!(
+                        "Provider {} claims to be issuer {}",
+                        self.settings.issuer_url,
+                        metadata.issuer
+                    );
+                }
+                info!(provider = %self.settings.name, "Discovered OpenID Connect provider");
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// A client for the endpoints of the provider.
+    pub async fn client(&self) -> Result<OidcClient> {
+        let metadata = self.metadata().await?;
+        Ok(OidcClient::new(
+            ClientId::new(self.settings.client_id.clone()),
+            Some(ClientSecret::new(
+                self.settings.client_secret.expose_secret().to_string(),
+            )),
+            AuthUrl::new(metadata.authorization_endpoint.clone())?,
+            Some(TokenUrl::new(metadata.token_endpoint.clone())?),
+        )
+        .set_redirect_uri(RedirectUrl::new(self.settings.redirect_uri.clone())?))
+    }
+
+    async fn refresh_jwks(&self) -> Result<()> {
+        let metadata = self.metadata().await?;
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to read {}", metadata.jwks_uri))?;
+        *self.jwks.write() = jwks;
+        Ok(())
+    }
+
+    /// The published key with the id, the only key when the token doesn't
+    /// name one.
+    fn find_key(&self, kid: Option<&str>) -> Option<Jwk> {
+        let jwks = self.jwks.read();
+        match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+            None => None,
+        }
+    }
+
+    /// Checks the signature of the ID token against the keys of the
+    /// provider, that it was issued by the provider to this client, and that
+    /// it carries back the nonce of the login.
+    pub async fn verify_id_token(&self, id_token: &str, nonce: &str) -> Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+        let header = decode_header(id_token)?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            bail!("ID token is signed with a shared secret");
+        }
+        let kid = header.kid.as_deref();
+        let jwk = match self.find_key(kid) {
+            Some(jwk) => jwk,
+            None => {
+                self.refresh_jwks().await?;
+                self.find_key(kid)
+                    .ok_or_else(|| eyre!("ID token is signed with unknown key {:?}", kid))?
+            }
+        };
+        let key = DecodingKey::from_jwk(&jwk)?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.settings.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .context("Invalid ID token")?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            bail!("ID token nonce does not match the login");
+        }
+        Ok(claims)
+    }
+}
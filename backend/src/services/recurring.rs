@@ -1,89 +1,95 @@
 use eyre::{Context, Result};
 use sqlx::PgPool;
 use std::time::Duration;
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
 use crate::{
-    domain::{ListRecurringTemplateSingle, NewTodoItemRequest},
+    domain::{ListRecurringTemplateSingle, NewTodoItemRequest, Recurrence, TodoItem, TodoRef},
     repos::{
-        check_active_todo_exists_for_template, create_todo_item, get_templates_due_for_generation,
-        update_last_generated_date,
+        check_active_todo_exists_for_template, create_todo_item, get_latest_due_date_for_template,
+        get_recurring_template_by_id, get_templates_due_for_generation, get_user_today,
+        list_template_time_zones, update_last_generated_date,
     },
 };
 
+/// Generates due items for every active template. Each template is processed
+/// against the current date in its owner's time zone, so running this often
+/// picks up every zone shortly after its local midnight.
 #[tracing::instrument(name = "Process recurring templates", skip(pool))]
-pub async fn process_recurring_templates(pool: &PgPool, advance_duration: Duration) -> Result<()> {
-    let current_date = OffsetDateTime::now_utc().date();
-
+pub async fn process_recurring_templates(
+    pool: &PgPool,
+    advance_duration: Duration,
+    now: OffsetDateTime,
+) -> Result<()> {
     let advance_days = advance_duration.as_secs() / (24 * 60 * 60);
-    info!(
-        "Starting recurring template processing for date: {} (advance_days: {})",
-        current_date, advance_days
-    );
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire database transaction")?;
 
-    let templates =
-        get_templates_due_for_generation(&mut transaction, current_date, advance_duration)
-            .await
-            .context("Failed to get templates due for generation")?;
-
-    if templates.items.is_empty() {
-        info!("No recurring templates due for generation");
-        transaction.commit().await?;
-        return Ok(());
-    }
-
-    info!(
-        "Found {} templates due for generation",
-        templates.items.len()
-    );
+    let time_zones = list_template_time_zones(&mut transaction, now)
+        .await
+        .context("Failed to list time zones with active templates")?;
 
     let mut generated_count = 0;
     let mut error_count = 0;
 
-    for template in templates.items {
-        // Get user_id from the todo table
-        let user_id = match sqlx::query!(
-            "SELECT user_id FROM todo WHERE name = $1",
-            template.todo_name.as_ref()
+    for (time_zone, current_date) in time_zones {
+        info!(
+            "Starting recurring template processing for {} on date: {} (advance_days: {})",
+            time_zone, current_date, advance_days
+        );
+
+        let templates = get_templates_due_for_generation(
+            &mut transaction,
+            current_date,
+            advance_duration,
+            &time_zone,
         )
-        .fetch_one(&mut *transaction)
         .await
-        {
-            Ok(row) => row.user_id,
-            Err(e) => {
-                error_count += 1;
-                error!(
-                    "Failed to get user_id for todo {} template: {} ({}): {}",
-                    template.todo_name.as_ref(),
-                    template.title,
-                    template.template_id,
-                    e
-                );
-                continue;
-            }
-        };
-
-        match process_single_template(&mut transaction, &template, advance_duration, user_id).await
-        {
-            Ok(_) => {
-                generated_count += 1;
-                info!(
-                    "Successfully generated todo item for template: {} ({})",
-                    template.title, template.template_id
-                );
-            }
-            Err(e) => {
-                error_count += 1;
-                error!(
-                    "Failed to generate todo item for template: {} ({}): {}",
-                    template.title, template.template_id, e
-                );
+        .context("Failed to get templates due for generation")?;
+
+        if templates.items.is_empty() {
+            info!("No recurring templates due for generation in {}", time_zone);
+            continue;
+        }
+
+        info!(
+            "Found {} templates due for generation in {}",
+            templates.items.len(),
+            time_zone
+        );
+
+        for template in templates.items {
+            // Items generated by the scheduler belong to the owner of the todo
+            let user_id = template.todo_owner_id;
+
+            match process_single_template(
+                &mut transaction,
+                &template,
+                advance_duration,
+                current_date,
+                user_id,
+            )
+            .await
+            {
+                Ok(_) => {
+                    generated_count += 1;
+                    info!(
+                        "Successfully generated todo item for template: {} ({})",
+                        template.title, template.template_id
+                    );
+                }
+                Err(e) => {
+                    error_count += 1;
+                    error!(
+                        "Failed to generate todo item for template: {} ({}): {}",
+                        template.title, template.template_id, e
+                    );
+                }
             }
         }
     }
@@ -114,10 +120,9 @@ pub async fn process_single_template(
     transaction: &mut sqlx::PgTransaction<'_>,
     template: &ListRecurringTemplateSingle,
     advance_duration: Duration,
+    current_date: Date,
     user_id: i32,
 ) -> Result<()> {
-    let current_date = OffsetDateTime::now_utc().date();
-
     if current_date < template.start_date {
         warn!(
             "Template {} hasn't started yet (start_date: {}), skipping generation",
@@ -152,7 +157,42 @@ pub async fn process_single_template(
 
     // Set due date based on configured advance duration
     let advance_days = advance_duration.as_secs() / (24 * 60 * 60);
-    let due_date = current_date + time::Duration::days(advance_days as i64);
+    let horizon = current_date + time::Duration::days(advance_days as i64);
+    let due_date = match &template.recurrence {
+        Recurrence::Interval(_) => horizon,
+        // Rules have fixed occurrences: take the first one not generated yet,
+        // if it falls within the advance window.
+        Recurrence::Rule(rule) => {
+            let latest_due_date =
+                get_latest_due_date_for_template(transaction, &template.template_id)
+                    .await
+                    .context("Failed to get latest due date for template")?;
+            let from = match latest_due_date {
+                Some(latest) => current_date.max(latest + time::Duration::days(1)),
+                None => current_date,
+            };
+            let next = rule
+                .first_on_or_after(template.start_date, from)
+                .filter(|d| template.end_date.is_none_or(|end_date| *d <= end_date));
+            match next {
+                Some(next) if next <= horizon => next,
+                Some(next) => {
+                    info!(
+                        "Next occurrence of template {} is on {}, outside the advance window",
+                        template.template_id, next
+                    );
+                    return Ok(());
+                }
+                None => {
+                    info!(
+                        "Template {} has no further occurrences, skipping generation",
+                        template.template_id
+                    );
+                    return Ok(());
+                }
+            }
+        }
+    };
 
     let new_item_request = NewTodoItemRequest {
         title: template.title.clone(),
@@ -160,7 +200,8 @@ pub async fn process_single_template(
         recurring_template_id: Some(template.template_id),
     };
 
-    create_todo_item(transaction, &template.todo_name, &new_item_request, user_id)
+    let todo_ref = TodoRef::owned_by(template.todo_owner_id, template.todo_name.clone());
+    create_todo_item(transaction, &todo_ref, &new_item_request, user_id)
         .await
         .context("Failed to create todo item from template")?;
 
@@ -170,3 +211,80 @@ pub async fn process_single_template(
 
     Ok(())
 }
+
+/// Creates the occurrence that follows a completed item of a template, due one
+/// recurrence period after the completed item's due date, or on the rule's
+/// next occurrence. Returns `None` when the template is inactive, exhausted,
+/// past its end date, or already has an open item.
+#[tracing::instrument(name = "Generate next recurring occurrence", skip(transaction))]
+pub async fn generate_next_occurrence(
+    transaction: &mut sqlx::PgTransaction<'_>,
+    template_id: &Uuid,
+    completed_due_date: Date,
+    now: OffsetDateTime,
+) -> Result<Option<TodoItem>> {
+    let Some(template) = get_recurring_template_by_id(transaction, template_id)
+        .await
+        .context("Failed to get recurring template")?
+    else {
+        return Ok(None);
+    };
+
+    if !template.is_active {
+        info!("Template {} is inactive, skipping generation", template_id);
+        return Ok(None);
+    }
+
+    let Some(due_date) = template
+        .recurrence
+        .next_after(template.start_date, completed_due_date)
+    else {
+        info!("Template {} has no further occurrences", template_id);
+        return Ok(None);
+    };
+
+    if let Some(end_date) = template.end_date {
+        if due_date > end_date {
+            info!(
+                "Template {} ends on {}, not generating an item due {}",
+                template_id, end_date, due_date
+            );
+            return Ok(None);
+        }
+    }
+
+    let active_todo_exists = check_active_todo_exists_for_template(transaction, template_id)
+        .await
+        .context("Failed to check for existing active todo")?;
+    if active_todo_exists {
+        info!(
+            "Active todo already exists for template {}, skipping generation",
+            template_id
+        );
+        return Ok(None);
+    }
+
+    let new_item_request = NewTodoItemRequest {
+        title: template.title.clone(),
+        due_date,
+        recurring_template_id: Some(template.template_id),
+    };
+    let todo_ref = TodoRef::owned_by(template.todo_owner_id, template.todo_name.clone());
+    let item = create_todo_item(
+        transaction,
+        &todo_ref,
+        &new_item_request,
+        template.todo_owner_id,
+    )
+    .await
+    .context("Failed to create todo item from template")?;
+
+    let current_date = get_user_today(transaction, template.todo_owner_id, now)
+        .await
+        .context("Failed to get the current date of the template owner")?;
+    update_last_generated_date(transaction, template_id, current_date)
+        .await
+        .context("Failed to update last generated date")?;
+
+    Ok(Some(item))
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use eyre::{Context, Result};
+use futures_util::{stream, Stream};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::domain::TodoEvent;
+use crate::error::APIError;
+use crate::repos::{self, can_read_todo_events, list_todo_events};
+
+/// Channel the `todo_event_trigger` notifies with the id of the changed todo.
+const CHANNEL: &str = "todo_event";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// Notifications are tiny, a subscriber lagging behind this many only
+// re-reads its todo's events.
+const CHANGES_CAPACITY: usize = 1024;
+const EVENTS_PAGE_SIZE: i64 = 100;
+// How long an idle stream goes without checking the user still sees its
+// todo, streams with events check before reading them.
+const ACCESS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoChange {
+    Todo(Uuid),
+    /// Notifications may have been missed while reconnecting.
+    All,
+}
+
+/// Fans out the `todo_event` notifications of this instance's single
+/// listener connection to every open event stream.
+#[derive(Clone)]
+pub struct TodoEvents {
+    pool: PgPool,
+    sender: broadcast::Sender<TodoChange>,
+}
+
+impl TodoEvents {
+    /// Starts listening on a connection of `pool`, reconnecting for as long
+    /// as the process runs.
+    pub fn listen(pool: PgPool) -> Self {
+        let (sender, _) = broadcast::channel(CHANGES_CAPACITY);
+        tokio::spawn(listen(pool.clone(), sender.clone()));
+        Self { pool, sender }
+    }
+
+    /// The events of `todo_id` after `after_event_id`, then the ones written
+    /// while the stream is open. Ends on the first database error, clients
+    /// resume from the last event they received, and once `user_id` no
+    /// longer sees the todo.
+    pub fn stream(
+        &self,
+        todo_id: Uuid,
+        user_id: i32,
+        after_event_id: i64,
+    ) -> impl Stream<Item = Result<TodoEvent, APIError>> + Send + 'static {
+        let state = StreamState {
+            pool: self.pool.clone(),
+            changes: self.sender.subscribe(),
+            todo_id,
+            user_id,
+            next_access_check: Instant::now(),
+            last_event_id: after_event_id,
+            pending: VecDeque::new(),
+            caught_up: false,
+        };
+        stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            match state.next().await {
+                Some(Ok(event)) => Some((Ok(event), Some(state))),
+                Some(Err(e)) => {
+                    error!("Todo event stream of {} failed: {}", state.todo_id, e);
+                    Some((Err(e), None))
+                }
+                None => None,
+            }
+        })
+    }
+}
+
+struct StreamState {
+    pool: PgPool,
+    changes: broadcast::Receiver<TodoChange>,
+    todo_id: Uuid,
+    user_id: i32,
+    next_access_check: Instant,
+    last_event_id: i64,
+    pending: VecDeque<TodoEvent>,
+    caught_up: bool,
+}
+
+impl StreamState {
+    async fn next(&mut self) -> Option<Result<TodoEvent, APIError>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                self.last_event_id = event.event_id;
+                return Some(Ok(event));
+            }
+            if self.caught_up {
+                let change =
+                    tokio::time::timeout_at(self.next_access_check, self.changes.recv()).await;
+                // Timing out is the idle stream's turn to check access
+                match change {
+                    Ok(Ok(TodoChange::Todo(todo_id))) if todo_id != self.todo_id => continue,
+                    Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) | Err(_) => {}
+                    Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                }
+            }
+            match can_read_todo_events(&self.pool, &self.todo_id, self.user_id).await {
+                Ok(true) => self.next_access_check = Instant::now() + ACCESS_CHECK_INTERVAL,
+                Ok(false) => {
+                    info!(
+                        "Todo event stream of {} closed, user {} lost access",
+                        self.todo_id, self.user_id
+                    );
+                    return None;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+            let events = match list_todo_events(
+                &self.pool,
+                &self.todo_id,
+                self.last_event_id,
+                EVENTS_PAGE_SIZE,
+            )
+            .await
+            {
+                Ok(events) => events,
+                Err(e) => return Some(Err(e)),
+            };
+            self.caught_up = (events.len() as i64) < EVENTS_PAGE_SIZE;
+            self.pending.extend(events);
+        }
+    }
+}
+
+async fn listen(pool: PgPool, sender: broadcast::Sender<TodoChange>) {
+    loop {
+        match PgListener::connect_with(&pool).await {
+            Ok(mut listener) => match listener.listen(CHANNEL).await {
+                Ok(()) => {
+                    info!("Listening for todo events");
+                    // Anything may have changed before we were listening
+                    let _ = sender.send(TodoChange::All);
+                    relay(&mut listener, &sender).await;
+                }
+                Err(e) => error!("Failed to listen for todo events: {}", e),
+            },
+            Err(e) => error!("Failed to connect the todo events listener: {}", e),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn relay(listener: &mut PgListener, sender: &broadcast::Sender<TodoChange>) {
+    loop {
+        // Sending only fails when no stream is open
+        match listener.try_recv().await {
+            Ok(Some(notification)) => match notification.payload().parse() {
+                Ok(todo_id) => {
+                    let _ = sender.send(TodoChange::Todo(todo_id));
+                }
+                Err(_) => warn!("Invalid todo event payload: {}", notification.payload()),
+            },
+            // The connection was lost and re-established
+            Ok(None) => {
+                let _ = sender.send(TodoChange::All);
+            }
+            Err(e) => {
+                error!("Todo events listener failed: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Deletes the todo events written more than `retention` ago. Clients
+/// resuming after one of them get a `reset` event and start over from the
+/// latest event.
+#[tracing::instrument(name = "Purge todo events", skip(pool))]
+pub async fn purge_todo_events(
+    pool: &PgPool,
+    retention: Duration,
+    now: OffsetDateTime,
+) -> Result<()> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire database transaction")?;
+
+    let events = repos::purge_todo_events(&mut transaction, now - retention)
+        .await
+        .context("Failed to purge the todo events")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit todo events purge transaction")?;
+
+    info!("Todo events purged: {}", events);
+    Ok(())
+}
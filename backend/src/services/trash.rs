@@ -0,0 +1,29 @@
+use eyre::{Context, Result};
+use sqlx::PgPool;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::repos;
+
+/// Deletes for good the todos and items that have been in the trash for
+/// longer than `retention`.
+#[tracing::instrument(name = "Purge trash", skip(pool))]
+pub async fn purge_trash(pool: &PgPool, retention: Duration, now: OffsetDateTime) -> Result<()> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire database transaction")?;
+
+    let (items, todos) = repos::purge_trash(&mut transaction, now - retention)
+        .await
+        .context("Failed to purge the trash")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit trash purge transaction")?;
+
+    info!("Trash purged: {} todo(s) and {} item(s)", todos, items);
+    Ok(())
+}
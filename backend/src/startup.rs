@@ -1,4 +1,5 @@
 use std::future::IntoFuture;
+use std::sync::Arc;
 
 use eyre::{Context, Result};
 use sqlx::postgres::{PgPool, PgPoolOptions};
@@ -8,10 +9,12 @@ use tokio_cron_scheduler::{Job, JobScheduler};
 use tracing::{error, info};
 
 use crate::auth::JwtService;
-use crate::configuration::{DatabaseSettings, Settings};
-use crate::services::process_recurring_templates;
+use crate::clock::{Clock, SystemClock};
+use crate::configuration::{
+    DatabaseSettings, EventSettings, RecurringSettings, Settings, TrashSettings,
+};
+use crate::services::{process_recurring_templates, purge_todo_events, purge_trash};
 use crate::{run, Server};
-use secrecy::ExposeSecret;
 
 pub struct Application {
     server: Server,
@@ -21,6 +24,15 @@ pub struct Application {
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Application> {
+        Self::build_with_clock(configuration, Arc::new(SystemClock)).await
+    }
+
+    /// Like `build`, but every handler and the scheduler read the time from
+    /// `clock`.
+    pub async fn build_with_clock(
+        configuration: Settings,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Application> {
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
@@ -37,29 +49,27 @@ impl Application {
                 .context("Attempting to execute query on DB")?;
         }
 
-        // Setup recurring templates scheduler
-        let scheduler =
-            setup_recurring_scheduler(&pool, configuration.recurring.look_ahead_duration).await?;
+        let scheduler = setup_scheduler(
+            &pool,
+            &configuration.recurring,
+            &configuration.trash,
+            &configuration.events,
+            clock.clone(),
+        )
+        .await?;
 
         // Setup JWT service based on auth configuration
-        let jwt_service = match &configuration.auth {
-            crate::configuration::AuthSettings::Jwt {
-                jwt_secret,
-                jwt_expiration_hours,
-            } => JwtService::new(jwt_secret.expose_secret(), *jwt_expiration_hours),
-            crate::configuration::AuthSettings::GoogleOAuth {
-                jwt_secret,
-                jwt_expiration_hours,
-                ..
-            } => JwtService::new(jwt_secret.expose_secret(), *jwt_expiration_hours),
-        };
+        let jwt_service = JwtService::from_auth_settings(&configuration.auth)
+            .context("Failed to load the JWT keys")?;
 
         let server = run(
             listener,
             pool,
             configuration.recurring,
+            configuration.trash,
             configuration.auth,
             jwt_service,
+            clock,
         )
         .await?;
         Ok(Application {
@@ -97,23 +107,29 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
         .connect_lazy_with(configuration.connection_options())
 }
 
-async fn setup_recurring_scheduler(
+async fn setup_scheduler(
     pool: &PgPool,
-    advance_duration: std::time::Duration,
+    recurring: &RecurringSettings,
+    trash: &TrashSettings,
+    events: &EventSettings,
+    clock: Arc<dyn Clock>,
 ) -> Result<JobScheduler> {
     let scheduler = JobScheduler::new()
         .await
         .context("Failed to create job scheduler")?;
 
+    let advance_duration = recurring.look_ahead_duration;
     let pool_clone = pool.clone();
-    let job = Job::new_async("0 0 0 * * *", move |_uuid, _l| {
+    let recurring_clock = clock.clone();
+    let job = Job::new_async("0 */15 * * * *", move |_uuid, _l| {
         let pool = pool_clone.clone();
+        let now = recurring_clock.now();
         Box::pin(async move {
-            info!("Starting daily recurring templates job");
-            if let Err(e) = process_recurring_templates(&pool, advance_duration).await {
+            info!("Starting recurring templates job");
+            if let Err(e) = process_recurring_templates(&pool, advance_duration, now).await {
                 error!("Recurring templates processing failed: {}", e);
             } else {
-                info!("Daily recurring templates job completed successfully");
+                info!("Recurring templates job completed successfully");
             }
         })
     })
@@ -124,12 +140,59 @@ async fn setup_recurring_scheduler(
         .await
         .context("Failed to add recurring templates job to scheduler")?;
 
+    let retention = trash.retention;
+    let pool_clone = pool.clone();
+    let trash_clock = clock.clone();
+    let job = Job::new_async("0 0 * * * *", move |_uuid, _l| {
+        let pool = pool_clone.clone();
+        let now = trash_clock.now();
+        Box::pin(async move {
+            info!("Starting trash purge job");
+            if let Err(e) = purge_trash(&pool, retention, now).await {
+                error!("Trash purge failed: {}", e);
+            }
+        })
+    })
+    .context("Failed to create trash purge job")?;
+
+    scheduler
+        .add(job)
+        .await
+        .context("Failed to add trash purge job to scheduler")?;
+
+    let event_retention = events.retention;
+    let pool_clone = pool.clone();
+    let job = Job::new_async("0 30 * * * *", move |_uuid, _l| {
+        let pool = pool_clone.clone();
+        let now = clock.now();
+        Box::pin(async move {
+            info!("Starting todo events purge job");
+            if let Err(e) = purge_todo_events(&pool, event_retention, now).await {
+                error!("Todo events purge failed: {}", e);
+            }
+        })
+    })
+    .context("Failed to create todo events purge job")?;
+
+    scheduler
+        .add(job)
+        .await
+        .context("Failed to add todo events purge job to scheduler")?;
+
     scheduler
         .start()
         .await
         .context("Failed to start job scheduler")?;
 
-    info!("Recurring templates scheduler started (runs daily at midnight)");
+    info!("Recurring templates scheduler started (runs every 15 minutes, per user time zone)");
+    info!(
+        "Trash purge scheduler started (runs every hour, retention: {:?})",
+        retention
+    );
+    info!(
+        "Todo events purge scheduler started (runs every hour, retention: {:?})",
+        event_retention
+    );
 
     Ok(scheduler)
 }
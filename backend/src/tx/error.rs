@@ -1,5 +1,3 @@
-use axum::http;
-
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 
@@ -15,6 +13,7 @@ pub enum Error {
 
 impl axum_core::response::IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        (http::StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()  
+        tracing::error!("Transaction error: {}", self);
+        crate::error::Problem::internal().into_response()
     }
 }
@@ -92,14 +92,4 @@ impl LazyTransaction {
             },
         }
     }
-
-    pub async fn commit(&mut self) -> Result<(), sqlx::Error> {
-        match std::mem::replace(&mut self.0, LazyTransactionState::Resolved) {
-            LazyTransactionState::Unacquired { .. } => {
-            panic!("BUG: tries to commit an unaquired transaction")
-        },
-        LazyTransactionState::Acquired { tx } => tx.commit().await,
-        LazyTransactionState::Resolved => panic!("BUG: tries to commit a resolved transaction"),
-    }
-    }
 }
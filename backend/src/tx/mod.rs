@@ -2,6 +2,7 @@ mod error;
 mod extension;
 pub mod layer;
 pub mod state;
+#[allow(clippy::module_inception)]
 pub mod tx;
 
 pub fn setup(pool: sqlx::Pool<sqlx::Postgres>)-> (state::State, layer::Layer) 
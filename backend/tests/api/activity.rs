@@ -0,0 +1,258 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, TestApp};
+
+async fn activity(app: &TestApp, todo_name: &str) -> Vec<JsonValue> {
+    let response = app.todo_activity(todo_name, &[]).await;
+    assert_response(&response, StatusCode::OK);
+    let activity: JsonValue = response.json().await.unwrap();
+    activity["items"].as_array().unwrap().clone()
+}
+
+fn actions(activity: &[JsonValue]) -> Vec<(&str, &str)> {
+    activity
+        .iter()
+        .map(|a| (a["entity"].as_str().unwrap(), a["action"].as_str().unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn activity_records_every_change() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    app.complete_todo_item("banana", &item_id).await;
+    app.reopen_todo_item("banana", &item_id).await;
+    app.update_todo_item(
+        "banana",
+        &item_id,
+        &json!({"title": "slice", "due_date": "2020-10-02", "is_complete": false}),
+    )
+    .await;
+    app.delete_todo_item("banana", &item_id).await;
+    app.restore_from_trash(&item_id).await;
+
+    let response = app.todo_activity("banana", &[]).await;
+    assert_response(&response, StatusCode::OK);
+    let body: JsonValue = response.json().await.unwrap();
+    app.golden.check_diff_json("todo_activity", &body);
+
+    let activity = body["items"].as_array().unwrap();
+    assert_eq!(
+        actions(activity),
+        vec![
+            ("item", "restored"),
+            ("item", "deleted"),
+            ("item", "updated"),
+            ("item", "reopened"),
+            ("item", "completed"),
+            ("item", "created"),
+            ("todo", "created"),
+        ]
+    );
+    for entry in activity {
+        assert_eq!(entry["actor_id"], app.test_user_id);
+        assert_eq!(entry["actor_email"], "test@example.com");
+    }
+    let updated = &activity[2];
+    assert_eq!(updated["entity_id"], item_id.as_str());
+    assert_eq!(updated["before"]["title"], "peel");
+    assert_eq!(updated["after"]["title"], "slice");
+    assert_eq!(activity[5]["before"], JsonValue::Null);
+}
+
+#[tokio::test]
+async fn activity_records_the_request_id() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .send_with_headers(
+            Method::POST,
+            "/todo/banana/item",
+            &[("x-request-id", &request_id)],
+            Some(&json!({"title": "peel", "due_date": "2020-10-01"})),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    let activity = activity(&app, "banana").await;
+    assert_eq!(activity[0]["action"], "created");
+    assert_eq!(activity[0]["request_id"], request_id.as_str());
+    // Generated by the server when the client doesn't send one
+    assert!(activity[1]["request_id"].is_string());
+    assert_ne!(activity[1]["request_id"], request_id.as_str());
+}
+
+#[tokio::test]
+async fn activity_tells_who_changed_a_shared_todo() {
+    let app = spawn_app().await;
+    let owner_id = app.create_user("owner@example.com").await;
+    let owner_auth = app.get_auth_header_for_user(owner_id, "owner@example.com");
+    let response = app
+        .client
+        .post(format!("{}/todo", app.address))
+        .header("Authorization", &owner_auth)
+        .json(&json!({"name": "shared", "visibility": "private"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+    let response = app
+        .client
+        .post(format!("{}/todo/shared/member", app.address))
+        .header("Authorization", &owner_auth)
+        .json(&json!({"email": "test@example.com", "role": "editor"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+
+    let item_id = app.create_item("shared", "peel").await;
+    app.complete_todo_item("shared", &item_id).await;
+
+    let activity = activity(&app, "shared").await;
+    assert_eq!(
+        actions(&activity),
+        vec![
+            ("item", "completed"),
+            ("item", "created"),
+            ("member", "created"),
+            ("todo", "created")
+        ]
+    );
+    assert_eq!(activity[0]["actor_id"], app.test_user_id);
+    assert_eq!(activity[0]["actor_email"], "test@example.com");
+    assert_eq!(activity[2]["actor_id"], owner_id);
+    assert_eq!(activity[3]["actor_id"], owner_id);
+    assert_eq!(activity[3]["actor_email"], "owner@example.com");
+}
+
+#[tokio::test]
+async fn activity_records_membership_changes() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let member_id = app.create_user("member@example.com").await;
+    let response = app
+        .add_todo_member(
+            "banana",
+            &json!({"email": "member@example.com", "role": "viewer"}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = app
+        .update_todo_member("banana", member_id, &json!({"role": "editor"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = app.remove_todo_member("banana", member_id).await;
+    assert_response(&response, StatusCode::OK);
+
+    let activity = activity(&app, "banana").await;
+    assert_eq!(
+        actions(&activity),
+        vec![
+            ("member", "deleted"),
+            ("member", "updated"),
+            ("member", "created"),
+            ("todo", "created")
+        ]
+    );
+    for entry in &activity[..3] {
+        assert_eq!(entry["actor_id"], app.test_user_id);
+        assert_eq!(entry["entity_id"], activity[3]["entity_id"]);
+    }
+    let updated = &activity[1];
+    assert_eq!(updated["before"]["user_id"], member_id);
+    assert_eq!(updated["before"]["role"], "viewer");
+    assert_eq!(updated["after"]["role"], "editor");
+    assert_eq!(activity[0]["after"], JsonValue::Null);
+}
+
+#[tokio::test]
+async fn failed_changes_are_not_recorded() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+
+    let response = app
+        .send_with_headers(
+            Method::PUT,
+            &format!("/todo/banana/item/{}", item_id),
+            &[("If-Match", "\"stale\"")],
+            Some(&json!({"title": "slice", "due_date": "2020-10-02", "is_complete": false})),
+        )
+        .await;
+    assert_response(&response, StatusCode::PRECONDITION_FAILED);
+
+    let activity = activity(&app, "banana").await;
+    assert_eq!(
+        actions(&activity),
+        vec![("item", "created"), ("todo", "created")]
+    );
+}
+
+#[tokio::test]
+async fn activity_pagination() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    for title in ["a", "b", "c", "d"] {
+        app.create_item("banana", title).await;
+    }
+
+    let mut titles = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", "2")];
+        if let Some(cursor) = &cursor {
+            query.push(("cursor", cursor));
+        }
+        let response = app.todo_activity("banana", &query).await;
+        assert_response(&response, StatusCode::OK);
+        let page: JsonValue = response.json().await.unwrap();
+        for entry in page["items"].as_array().unwrap() {
+            titles.push(entry["after"]["title"].as_str().map(|t| t.to_string()));
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    let expected: Vec<Option<String>> = ["d", "c", "b", "a"]
+        .iter()
+        .map(|t| Some(t.to_string()))
+        .chain([None])
+        .collect();
+    assert_eq!(titles, expected);
+}
+
+#[tokio::test]
+async fn activity_failure() {
+    let app = spawn_app().await;
+    let response = app.todo_activity("banana", &[]).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let response = app
+        .todo_activity("banana", &[("cursor", "not a cursor")])
+        .await;
+    assert_problem(response, StatusCode::BAD_REQUEST, "bad_request").await;
+
+    // Private todos of other users
+    let other_id = app.create_user("other@example.com").await;
+    let other_auth = app.get_auth_header_for_user(other_id, "other@example.com");
+    let response = app
+        .client
+        .get(format!("{}/todo/banana/activity", app.address))
+        .header("Authorization", &other_auth)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
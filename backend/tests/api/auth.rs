@@ -1,3 +1,4 @@
+use checklist::clock::Clock;
 use reqwest::StatusCode;
 use serde_json::Value as JsonValue;
 
@@ -137,7 +138,11 @@ async fn routes_return_403_when_user_does_not_exist() {
     let nonexistent_user_id = 999999;
     let token = test_app
         .jwt_service
-        .generate_token(nonexistent_user_id, "nonexistent@example.com")
+        .generate_token(
+            nonexistent_user_id,
+            "nonexistent@example.com",
+            test_app.clock.now(),
+        )
         .expect("Failed to generate test JWT token");
     let auth_header = format!("Bearer {}", token);
 
@@ -181,6 +186,24 @@ async fn routes_return_403_when_user_does_not_exist() {
     }
 }
 
+#[tokio::test]
+async fn tokens_expire_on_the_app_clock() {
+    let app = spawn_app().await;
+    let auth_header = app.get_auth_header();
+
+    app.clock.advance(time::Duration::days(2));
+
+    let response = app
+        .client
+        .get(format!("{}/user/me", app.address))
+        .header("Authorization", &auth_header)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[allow(dead_code)]
 impl TestApp {
     /// Helper method to make requests without authentication
     pub async fn request_without_auth(
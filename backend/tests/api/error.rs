@@ -0,0 +1,142 @@
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::helpers::{assert_problem, spawn_app};
+
+#[tokio::test]
+async fn not_found_is_a_problem() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_todo("missing").await;
+    let problem = assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    test_app
+        .golden
+        .check_diff_json("problem_not_found", &problem);
+}
+
+#[tokio::test]
+async fn validation_failure_lists_the_field() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_todo(&json!({"name": "", "visibility": "private"}))
+        .await;
+    let problem = assert_problem(response, StatusCode::BAD_REQUEST, "validation_failed").await;
+    assert_eq!(problem["errors"][0]["field"], "name");
+    test_app
+        .golden
+        .check_diff_json("problem_validation_failed", &problem);
+
+    let response = test_app
+        .update_current_user(&json!({"time_zone": "Mars/Olympus_Mons"}))
+        .await;
+    assert_problem(response, StatusCode::BAD_REQUEST, "bad_request").await;
+}
+
+#[tokio::test]
+async fn already_exists_has_its_own_code() {
+    let test_app = spawn_app().await;
+
+    let payload = json!({"name": "banana", "visibility": "private"});
+    test_app.post_todo(&payload).await;
+    let response = test_app.post_todo(&payload).await;
+    assert_problem(response, StatusCode::CONFLICT, "already_exists").await;
+
+    // Renaming onto an existing name hits the unique constraint
+    test_app
+        .post_todo(&json!({"name": "banana2", "visibility": "private"}))
+        .await;
+    let response = test_app.update_todo("banana2", &payload).await;
+    assert_problem(response, StatusCode::CONFLICT, "already_exists").await;
+}
+
+#[tokio::test]
+async fn state_conflicts_have_their_own_code() {
+    let test_app = spawn_app().await;
+
+    let payload = json!({"name": "banana", "visibility": "private"});
+    test_app.post_todo(&payload).await;
+    let response = test_app
+        .post_todo_item("banana", &json!({"title": "item"}))
+        .await;
+    let item: serde_json::Value = response.json().await.unwrap();
+    let item_id = item["todo_item_id"].as_str().unwrap();
+
+    let response = test_app.reopen_todo_item("banana", item_id).await;
+    assert_problem(response, StatusCode::CONFLICT, "conflict").await;
+
+    test_app.complete_todo_item("banana", item_id).await;
+    let response = test_app.complete_todo_item("banana", item_id).await;
+    let problem = assert_problem(response, StatusCode::CONFLICT, "conflict").await;
+    test_app
+        .golden
+        .check_diff_json("problem_conflict", &problem);
+}
+
+#[tokio::test]
+async fn authentication_failures_are_problems() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .client
+        .get(format!("{}/todo", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+
+    let response = test_app
+        .client
+        .get(format!("{}/todo", test_app.address))
+        .header("Authorization", "Bearer not-a-token")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+
+    let response = test_app
+        .client
+        .get(format!("{}/todo", test_app.address))
+        .header(
+            "Authorization",
+            test_app.get_auth_header_for_user(-1, "nobody@example.com"),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+}
+
+#[tokio::test]
+async fn extractor_rejections_are_problems() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .client
+        .post(format!("{}/todo", test_app.address))
+        .header("Authorization", test_app.get_auth_header())
+        .header("content-type", "application/json")
+        .body("{not json")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_problem(response, StatusCode::BAD_REQUEST, "bad_request").await;
+
+    let response = test_app.post_todo(&json!({"name": "banana"})).await;
+    let problem = assert_problem(
+        response,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "unprocessable_entity",
+    )
+    .await;
+    assert!(
+        problem["detail"].as_str().unwrap().contains("visibility"),
+        "{}",
+        problem
+    );
+
+    let response = test_app
+        .post_todo(&json!({"name": "a".repeat(2 * 1024 * 1024), "visibility": "private"}))
+        .await;
+    assert_problem(response, StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large").await;
+}
@@ -0,0 +1,243 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
+
+fn etag_of(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get("etag")
+        .expect("no etag header found in response")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Walks a resource through the conditional request flow: GET returns an
+/// ETag, If-None-Match on it gives a 304 even when weak, a PUT with it
+/// succeeds unless weak and changes it, and a PUT or DELETE with the old one
+/// fails with a 412.
+async fn check_conditional_requests(app: &TestApp, path: &str, payload: &JsonValue) {
+    let response = app.send_with_headers(Method::GET, path, &[], None).await;
+    assert_response(&response, StatusCode::OK);
+    let etag = etag_of(&response);
+
+    let response = app
+        .send_with_headers(Method::GET, path, &[("If-None-Match", &etag)], None)
+        .await;
+    assert_response(&response, StatusCode::NOT_MODIFIED);
+    assert_eq!(etag_of(&response), etag);
+    assert_eq!(response.text().await.unwrap(), "");
+
+    let response = app
+        .send_with_headers(Method::GET, path, &[("If-None-Match", "\"1\"")], None)
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    // Weak tags only match when comparing weakly
+    let weak_etag = format!("W/{}", etag);
+    let response = app
+        .send_with_headers(Method::GET, path, &[("If-None-Match", &weak_etag)], None)
+        .await;
+    assert_response(&response, StatusCode::NOT_MODIFIED);
+    let response = app
+        .send_with_headers(
+            Method::PUT,
+            path,
+            &[("If-Match", &weak_etag)],
+            Some(payload),
+        )
+        .await;
+    assert_problem(
+        response,
+        StatusCode::PRECONDITION_FAILED,
+        "precondition_failed",
+    )
+    .await;
+
+    let response = app
+        .send_with_headers(Method::PUT, path, &[("If-Match", &etag)], Some(payload))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let new_etag = etag_of(&response);
+    assert_ne!(new_etag, etag);
+
+    let response = app.send_with_headers(Method::GET, path, &[], None).await;
+    assert_eq!(etag_of(&response), new_etag);
+
+    let response = app
+        .send_with_headers(Method::PUT, path, &[("If-Match", &etag)], Some(payload))
+        .await;
+    let problem = assert_problem(
+        response,
+        StatusCode::PRECONDITION_FAILED,
+        "precondition_failed",
+    )
+    .await;
+    assert!(
+        problem["detail"].as_str().unwrap().contains(&new_etag),
+        "{}",
+        problem
+    );
+
+    let response = app
+        .send_with_headers(Method::DELETE, path, &[("If-Match", &etag)], None)
+        .await;
+    assert_problem(
+        response,
+        StatusCode::PRECONDITION_FAILED,
+        "precondition_failed",
+    )
+    .await;
+
+    // The failed requests changed nothing
+    let response = app.send_with_headers(Method::GET, path, &[], None).await;
+    assert_eq!(etag_of(&response), new_etag);
+}
+
+#[tokio::test]
+async fn todo_supports_conditional_requests() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+
+    check_conditional_requests(
+        &app,
+        "/todo/banana",
+        &json!({"name": "banana", "visibility": "public"}),
+    )
+    .await;
+
+    let response = app
+        .send_with_headers(Method::DELETE, "/todo/banana", &[("If-Match", "*")], None)
+        .await;
+    assert_response(&response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn todo_item_supports_conditional_requests() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let response = app
+        .post_todo_item(
+            "banana",
+            &json!({"title": "item", "due_date": "2020-10-01"}),
+        )
+        .await;
+    let item: JsonValue = response.json().await.unwrap();
+    let path = format!(
+        "/todo/banana/item/{}",
+        item["todo_item_id"].as_str().unwrap()
+    );
+
+    check_conditional_requests(
+        &app,
+        &path,
+        &json!({"title": "renamed", "due_date": "2020-10-02"}),
+    )
+    .await;
+
+    let response = app.send_with_headers(Method::GET, &path, &[], None).await;
+    let etag = etag_of(&response);
+    let response = app
+        .send_with_headers(Method::DELETE, &path, &[("If-Match", &etag)], None)
+        .await;
+    assert_response(&response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn recurring_template_supports_conditional_requests() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let response = app
+        .post_recurring_template(
+            "banana",
+            &json!({"title": "Daily task", "recurrence_interval": {"days": 1}}),
+        )
+        .await;
+    let template: JsonValue = response.json().await.unwrap();
+    let path = format!(
+        "/todo/banana/recurring/{}",
+        template["template_id"].as_str().unwrap()
+    );
+
+    check_conditional_requests(
+        &app,
+        &path,
+        &json!({
+            "title": "Weekly task",
+            "recurrence_interval": {"days": 7},
+            "is_active": true,
+        }),
+    )
+    .await;
+
+    let response = app
+        .send_with_headers(Method::DELETE, &path, &[("If-Match", "*")], None)
+        .await;
+    assert_response(&response, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn requests_without_preconditions_are_unconditional() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+
+    let response = app
+        .update_todo("banana", &json!({"name": "banana", "visibility": "public"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = app.delete_todo("banana").await;
+    assert_response(&response, StatusCode::OK);
+
+    // A condition on a missing resource is still a 404
+    let response = app
+        .send_with_headers(Method::DELETE, "/todo/banana", &[("If-Match", "*")], None)
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
+
+#[tokio::test]
+async fn sharing_a_todo_keeps_the_etags_of_its_items() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    let path = format!("/todo/banana/item/{}", item_id);
+    let response = app.send_with_headers(Method::GET, &path, &[], None).await;
+    let etag = etag_of(&response);
+    let events_before: i64 = sqlx::query_scalar("SELECT count(*) FROM todo_event")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    Member::create(&app, "member@example.com").await;
+    let response = app
+        .add_todo_member(
+            "banana",
+            &json!({"email": "member@example.com", "role": "editor"}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    // Resending the item to the member's sync is not a change
+    let response = app.send_with_headers(Method::GET, &path, &[], None).await;
+    assert_eq!(etag_of(&response), etag);
+    let response = app
+        .send_with_headers(
+            Method::PUT,
+            &path,
+            &[("If-Match", &etag)],
+            Some(&json!({"title": "renamed", "due_date": "2020-10-02"})),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let events_after: i64 = sqlx::query_scalar("SELECT count(*) FROM todo_event")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events_after, events_before + 1);
+}
@@ -95,7 +95,6 @@ fn dummify(value: &mut Value) {
             }
             if Date::parse(s, &date_description).is_ok() {
                 *s = "2020-10-01".to_string();
-                return;
             }
         }
         Value::Array(a) => {
@@ -0,0 +1,193 @@
+use reqwest::StatusCode;
+use serde_json::Value as JsonValue;
+
+use crate::helpers::{spawn_google_oauth_app, TestApp};
+use crate::oauth_server::{exchange, location, query_of, set_cookie, Login, OAuthServer};
+
+async fn start_login(app: &TestApp) -> Login {
+    Login::start(app, "/auth/google").await
+}
+
+async fn call_back(
+    app: &TestApp,
+    query: &[(&str, &str)],
+    cookie: Option<&str>,
+) -> reqwest::Response {
+    Login::call_back(app, "/auth/google/callback", query, cookie).await
+}
+
+async fn spawn() -> (TestApp, OAuthServer) {
+    let server = OAuthServer::start().await;
+    let app = spawn_google_oauth_app(server.google_oauth_settings()).await;
+    (app, server)
+}
+
+#[tokio::test]
+async fn login_sends_state_and_pkce_challenge() {
+    let (app, server) = spawn().await;
+
+    let login = start_login(&app).await;
+
+    assert!(login
+        .authorization_url
+        .as_str()
+        .starts_with(&format!("{}/authorize", server.address)));
+    let query = query_of(&login.authorization_url);
+    assert_eq!(query["code_challenge_method"], "S256");
+    assert!(!query["code_challenge"].is_empty());
+    assert_eq!(login.cookie, format!("oauth_state={}", login.state()));
+}
+
+#[tokio::test]
+async fn callback_with_matching_state_logs_in() {
+    let (app, server) = spawn().await;
+    let login = start_login(&app).await;
+    let code = server.authorize(&login.authorization_url, "test@example.com");
+
+    let state = login.state();
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(set_cookie(&response).starts_with("oauth_state=; Path=/auth; Max-Age=0"));
+    let redirect = location(&response);
+    assert!(redirect
+        .as_str()
+        .starts_with("http://127.0.0.1/frontend/callback?"));
+    let query = query_of(&redirect);
+    assert_eq!(query.keys().collect::<Vec<_>>(), ["code"]);
+    let response = exchange(&app, &query["code"]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: JsonValue = response.json().await.unwrap();
+    assert_eq!(body["user_id"], app.test_user_id);
+    assert_eq!(body["email"], "test@example.com");
+    assert!(!body["token"].as_str().unwrap().is_empty());
+    assert!(!body["refresh_token"].as_str().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn callback_without_state_is_rejected() {
+    let (app, server) = spawn().await;
+    let login = start_login(&app).await;
+    let code = server.authorize(&login.authorization_url, "test@example.com");
+    let state = login.state();
+
+    let response = call_back(&app, &[("code", &code)], Some(&login.cookie)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = call_back(&app, &[("code", &code), ("state", &state)], None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(server.token_requests(), 0);
+}
+
+#[tokio::test]
+async fn callback_with_state_of_another_login_is_rejected() {
+    let (app, server) = spawn().await;
+    // The attacker starts a login and sends their callback to the victim
+    let attacker = start_login(&app).await;
+    let code = server.authorize(&attacker.authorization_url, "test@example.com");
+    let victim = start_login(&app).await;
+
+    let state = attacker.state();
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", &state)],
+        Some(&victim.cookie),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.token_requests(), 0);
+}
+
+#[tokio::test]
+async fn callback_with_made_up_state_is_rejected() {
+    let (app, server) = spawn().await;
+    let login = start_login(&app).await;
+    let code = server.authorize(&login.authorization_url, "test@example.com");
+
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", "made-up")],
+        Some("oauth_state=made-up"),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.token_requests(), 0);
+}
+
+#[tokio::test]
+async fn state_is_only_used_once() {
+    let (app, server) = spawn().await;
+    let login = start_login(&app).await;
+    let state = login.state();
+    let code = server.authorize(&login.authorization_url, "test@example.com");
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let code = server.authorize(&login.authorization_url, "test@example.com");
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.token_requests(), 1);
+}
+
+#[tokio::test]
+async fn expired_login_is_rejected() {
+    let (app, server) = spawn().await;
+    let login = start_login(&app).await;
+    let state = login.state();
+    let code = server.authorize(&login.authorization_url, "test@example.com");
+
+    app.clock.advance(time::Duration::minutes(11));
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.token_requests(), 0);
+}
+
+#[tokio::test]
+async fn code_exchanged_without_the_verifier_fails() {
+    let (app, server) = spawn().await;
+    let login = start_login(&app).await;
+    let state = login.state();
+    // The provider saw another challenge than the one of this login
+    let mut authorization_url = login.authorization_url.clone();
+    authorization_url
+        .query_pairs_mut()
+        .clear()
+        .append_pair("code_challenge", "another-challenge")
+        .append_pair("code_challenge_method", "S256");
+    let code = server.authorize(&authorization_url, "test@example.com");
+
+    let response = call_back(
+        &app,
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.token_requests(), 1);
+}
@@ -0,0 +1,253 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
+
+async fn create_group(app: &TestApp, name: &str) -> String {
+    let response = app.post_group(&json!({"name": name})).await;
+    assert_response(&response, StatusCode::OK);
+    let group: JsonValue = response.json().await.unwrap();
+    group["group_id"].as_str().unwrap().to_string()
+}
+
+async fn join(app: &TestApp, group_id: &str, email: &str, role: &str) {
+    let response = app
+        .add_group_member(group_id, &json!({"email": email, "role": role}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+}
+
+async fn create_group_todo(app: &TestApp, name: &str, group_id: &str) -> String {
+    let response = app
+        .post_todo(&json!({"name": name, "visibility": "private", "group_id": group_id}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = app.get_todo(name).await;
+    let todo: JsonValue = response.json().await.unwrap();
+    todo["todo_id"].as_str().unwrap().to_string()
+}
+
+async fn listed_todos(member: &Member<'_>) -> Vec<JsonValue> {
+    let response = member.send(Method::GET, "/todo", None).await;
+    assert_response(&response, StatusCode::OK);
+    let todos: JsonValue = response.json().await.unwrap();
+    todos["items"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn group_todos_are_listed_for_every_member() {
+    let app = spawn_app().await;
+    let group_id = create_group(&app, "home").await;
+    let editor = Member::create(&app, "editor@example.com").await;
+    let viewer = Member::create(&app, "viewer@example.com").await;
+    let stranger = Member::create(&app, "stranger@example.com").await;
+    join(&app, &group_id, "editor@example.com", "editor").await;
+    join(&app, &group_id, "viewer@example.com", "viewer").await;
+    let todo_id = create_group_todo(&app, "groceries", &group_id).await;
+
+    let response = app.list_groups().await;
+    assert_response(&response, StatusCode::OK);
+    let groups: JsonValue = response.json().await.unwrap();
+    app.golden.check_diff_json("list_groups", &groups);
+
+    let response = app.list_group_members(&group_id).await;
+    assert_response(&response, StatusCode::OK);
+    let members: JsonValue = response.json().await.unwrap();
+    app.golden.check_diff_json("list_group_members", &members);
+
+    let todos = listed_todos(&editor).await;
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0]["todo_id"], todo_id.as_str());
+    assert_eq!(todos[0]["group_id"], group_id.as_str());
+    assert_eq!(todos[0]["role"], "editor");
+    let todos = listed_todos(&viewer).await;
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0]["role"], "viewer");
+    assert!(listed_todos(&stranger).await.is_empty());
+
+    let response = stranger
+        .send(Method::GET, &format!("/group/{}", group_id), None)
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
+
+#[tokio::test]
+async fn group_editors_create_recurring_templates() {
+    let app = spawn_app().await;
+    let group_id = create_group(&app, "home").await;
+    let editor = Member::create(&app, "editor@example.com").await;
+    let viewer = Member::create(&app, "viewer@example.com").await;
+    join(&app, &group_id, "editor@example.com", "editor").await;
+    join(&app, &group_id, "viewer@example.com", "viewer").await;
+    let todo_id = create_group_todo(&app, "chores", &group_id).await;
+
+    let path = format!("/todo/{}/recurring", todo_id);
+    let template = json!({
+        "title": "Take out the bins",
+        "recurrence_interval": {"days": 7},
+        "start_date": "2020-01-01",
+        "end_date": null
+    });
+    let response = viewer.send(Method::POST, &path, Some(&template)).await;
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+
+    let response = editor.send(Method::POST, &path, Some(&template)).await;
+    assert_response(&response, StatusCode::OK);
+
+    let response = app.list_recurring_templates("chores").await;
+    let templates: JsonValue = response.json().await.unwrap();
+    assert_eq!(templates["templates"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn todos_join_groups_the_user_edits() {
+    let app = spawn_app().await;
+    let member = Member::create(&app, "member@example.com").await;
+    let group_id = create_group(&app, "home").await;
+    join(&app, &group_id, "member@example.com", "viewer").await;
+
+    // Viewers can't add todos to the group
+    let todo = json!({"name": "mine", "visibility": "private", "group_id": group_id});
+    let response = member.send(Method::POST, "/todo", Some(&todo)).await;
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+
+    // Nor can users outside of it
+    let response = member
+        .send(Method::POST, "/group", Some(&json!({"name": "theirs"})))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let other_group: JsonValue = response.json().await.unwrap();
+    let other_group_id = other_group["group_id"].as_str().unwrap();
+    let response = app
+        .post_todo(&json!({"name": "banana", "visibility": "private", "group_id": other_group_id}))
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+
+    // An existing todo moves into the group
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    assert!(listed_todos(&member).await.is_empty());
+    let response = app
+        .update_todo(
+            "banana",
+            &json!({"name": "banana", "visibility": "private", "group_id": group_id}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let todos = listed_todos(&member).await;
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0]["name"], "banana");
+}
+
+#[tokio::test]
+async fn deleting_a_group_gives_its_todos_back_to_their_creators() {
+    let app = spawn_app().await;
+    let member = Member::create(&app, "member@example.com").await;
+    let group_id = create_group(&app, "home").await;
+    join(&app, &group_id, "member@example.com", "owner").await;
+    create_group_todo(&app, "groceries", &group_id).await;
+
+    let response = member
+        .send(Method::DELETE, &format!("/group/{}", group_id), None)
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    assert!(listed_todos(&member).await.is_empty());
+    let response = app.get_todo("groceries").await;
+    assert_response(&response, StatusCode::OK);
+    let todo: JsonValue = response.json().await.unwrap();
+    assert_eq!(todo["group_id"], JsonValue::Null);
+    assert_eq!(todo["role"], "owner");
+}
+
+#[tokio::test]
+async fn a_group_keeps_an_owner() {
+    let app = spawn_app().await;
+    let member = Member::create(&app, "member@example.com").await;
+    let group_id = create_group(&app, "home").await;
+    join(&app, &group_id, "member@example.com", "editor").await;
+
+    // Only owners manage members
+    let path = format!("/group/{}/member/{}", group_id, app.test_user_id);
+    let response = member
+        .send(Method::PUT, &path, Some(&json!({"role": "viewer"})))
+        .await;
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+
+    let response = app
+        .update_group_member(&group_id, app.test_user_id, &json!({"role": "editor"}))
+        .await;
+    assert_problem(response, StatusCode::CONFLICT, "conflict").await;
+    let response = app.remove_group_member(&group_id, app.test_user_id).await;
+    assert_problem(response, StatusCode::CONFLICT, "conflict").await;
+
+    let response = app
+        .update_group_member(&group_id, member.user_id, &json!({"role": "owner"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = app.remove_group_member(&group_id, app.test_user_id).await;
+    assert_response(&response, StatusCode::OK);
+
+    let response = app.list_groups().await;
+    let groups: JsonValue = response.json().await.unwrap();
+    assert!(groups["items"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn group_changes_are_audited() {
+    let app = spawn_app().await;
+    let member = Member::create(&app, "member@example.com").await;
+    let group_id = create_group(&app, "home").await;
+    let path = format!("/group/{}", group_id);
+    let response = app
+        .send_with_headers(Method::PUT, &path, &[], Some(&json!({"name": "house"})))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    join(&app, &group_id, "member@example.com", "viewer").await;
+    let response = app
+        .update_group_member(&group_id, member.user_id, &json!({"role": "editor"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = member
+        .send(
+            Method::DELETE,
+            &format!("/group/{}/member/{}", group_id, member.user_id),
+            None,
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    // The members deleted along with the group are not recorded on their own
+    let response = app
+        .send_with_headers(Method::DELETE, &path, &[], None)
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    let group_uuid: uuid::Uuid = group_id.parse().unwrap();
+    let rows = sqlx::query!(
+        r#"SELECT entity::text as "entity!", action::text as "action!", actor_id
+           FROM audit_log
+           WHERE group_id = $1
+           ORDER BY audit_id"#,
+        group_uuid,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let actions: Vec<(&str, &str, Option<i32>)> = rows
+        .iter()
+        .map(|r| (r.entity.as_str(), r.action.as_str(), r.actor_id))
+        .collect();
+    let owner = Some(app.test_user_id);
+    assert_eq!(
+        actions,
+        vec![
+            ("group", "created", owner),
+            ("group_member", "created", owner),
+            ("group", "updated", owner),
+            ("group_member", "created", owner),
+            ("group_member", "updated", owner),
+            ("group_member", "deleted", Some(member.user_id)),
+            ("group", "deleted", owner),
+        ]
+    );
+}
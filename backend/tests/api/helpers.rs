@@ -1,10 +1,14 @@
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
 use checklist::auth::JwtService;
-use checklist::configuration::{get_configuration, DatabaseSettings};
+use checklist::clock::{Clock, FixedClock};
+use checklist::configuration::{
+    get_configuration, AuthSettings, DatabaseSettings, GoogleOAuthSettings, JwtKeysSettings,
+    OidcProviderSettings, SessionSettings, Settings,
+};
 use checklist::startup::{get_connection_pool, Application};
 use checklist::telemetry::{get_subscriber, init_subscriber};
-use secrecy::{ExposeSecret, SecretString};
+use secrecy::SecretString;
 use serde_json::Value as JsonValue;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 
@@ -17,6 +21,8 @@ pub struct TestApp {
     pub db_pool: PgPool,
     pub jwt_service: JwtService,
     pub test_user_id: i32,
+    /// The time seen by the application, move it to time-travel
+    pub clock: FixedClock,
 }
 
 static TRACING: LazyLock<()> = LazyLock::new(|| {
@@ -69,10 +75,52 @@ async fn configure_database(configuration: &DatabaseSettings) -> PgPool {
 }
 
 pub async fn spawn_app() -> TestApp {
-    spawn_app_with_config(true).await
+    spawn_app_with_config(true, |_| {}).await
 }
 
-async fn spawn_app_with_config(valid_app: bool) -> TestApp {
+/// An app logging users in through the Google OAuth flow, against the
+/// endpoints in `google_oauth`.
+pub async fn spawn_google_oauth_app(google_oauth: GoogleOAuthSettings) -> TestApp {
+    spawn_app_with_config(true, |c| {
+        c.auth = AuthSettings::GoogleOAuth {
+            jwt_secret: SecretString::new("oauth-test-secret".into()),
+            jwt_expiration_hours: 24,
+            jwt_keys: JwtKeysSettings::default(),
+            google_oauth,
+            session: SessionSettings::default(),
+        }
+    })
+    .await
+}
+
+/// An app signing tokens with the asymmetric `jwt_keys` instead of its
+/// secret.
+pub async fn spawn_app_with_jwt_keys(jwt_keys: JwtKeysSettings) -> TestApp {
+    spawn_app_with_config(true, |c| {
+        c.auth = AuthSettings::Jwt {
+            jwt_secret: SecretString::new("jwt-test-secret".into()),
+            jwt_expiration_hours: 24,
+            jwt_keys,
+        }
+    })
+    .await
+}
+
+/// An app logging users in through the OpenID Connect `providers`.
+pub async fn spawn_oidc_app(providers: Vec<OidcProviderSettings>) -> TestApp {
+    spawn_app_with_config(true, |c| {
+        c.auth = AuthSettings::Oidc {
+            jwt_secret: SecretString::new("oauth-test-secret".into()),
+            jwt_expiration_hours: 24,
+            jwt_keys: JwtKeysSettings::default(),
+            providers,
+            session: SessionSettings::default(),
+        }
+    })
+    .await
+}
+
+async fn spawn_app_with_config(valid_app: bool, configure: impl FnOnce(&mut Settings)) -> TestApp {
     LazyLock::force(&TRACING);
 
     let mut configuration = {
@@ -80,6 +128,7 @@ async fn spawn_app_with_config(valid_app: bool) -> TestApp {
             .expect("Unable to read configuration");
         c.database.database = uuid::Uuid::new_v4().to_string();
         c.application.port = 0;
+        configure(&mut c);
         c
     };
     let db_pool = configure_database(&configuration.database).await;
@@ -87,24 +136,17 @@ async fn spawn_app_with_config(valid_app: bool) -> TestApp {
         configuration.database.database = "INVALIDDB".to_string();
         configuration.application.validate_db_on_startup = Some(false);
     }
-    let application = Application::build(configuration.clone())
+    let now = time::OffsetDateTime::now_utc();
+    let clock = FixedClock::new(now.replace_nanosecond(0).unwrap_or(now));
+    let application = Application::build_with_clock(configuration.clone(), Arc::new(clock.clone()))
         .await
         .expect("Failed to bind address");
     let address = format!("http://127.0.0.1:{}", application.port());
     tokio::spawn(application.run_until_stopped());
 
     // Create JWT service for testing
-    let jwt_service = match &configuration.auth {
-        checklist::configuration::AuthSettings::Jwt {
-            jwt_secret,
-            jwt_expiration_hours,
-        } => JwtService::new(jwt_secret.expose_secret(), *jwt_expiration_hours),
-        checklist::configuration::AuthSettings::GoogleOAuth {
-            jwt_secret,
-            jwt_expiration_hours,
-            ..
-        } => JwtService::new(jwt_secret.expose_secret(), *jwt_expiration_hours),
-    };
+    let jwt_service =
+        JwtService::from_auth_settings(&configuration.auth).expect("Failed to load the JWT keys");
 
     // Create test user in database and get auto-generated user_id
     let test_user_id: i32 = sqlx::query_scalar!(
@@ -122,11 +164,12 @@ async fn spawn_app_with_config(valid_app: bool) -> TestApp {
         db_pool,
         jwt_service,
         test_user_id,
+        clock,
     }
 }
 
 pub async fn spawn_invalid_db_app() -> TestApp {
-    spawn_app_with_config(false).await
+    spawn_app_with_config(false, |_| {}).await
 }
 
 impl TestApp {
@@ -134,7 +177,7 @@ impl TestApp {
     pub fn get_auth_header(&self) -> String {
         let token = self
             .jwt_service
-            .generate_token(self.test_user_id, "test@example.com")
+            .generate_token(self.test_user_id, "test@example.com", self.clock.now())
             .expect("Failed to generate test JWT token");
         format!("Bearer {}", token)
     }
@@ -143,7 +186,7 @@ impl TestApp {
     pub fn get_auth_header_for_user(&self, user_id: i32, email: &str) -> String {
         let token = self
             .jwt_service
-            .generate_token(user_id, email)
+            .generate_token(user_id, email, self.clock.now())
             .expect("Failed to generate JWT token");
         format!("Bearer {}", token)
     }
@@ -159,6 +202,35 @@ impl TestApp {
         .expect("Failed to create user")
     }
 
+    pub async fn get_current_user(&self) -> reqwest::Response {
+        self.client
+            .get(format!("{}/user/me", self.address))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn update_current_user(&self, payload: &JsonValue) -> reqwest::Response {
+        self.client
+            .put(format!("{}/user/me", self.address))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// The current date of the test clock in `time_zone`, as computed by the database
+    pub async fn today_in(&self, time_zone: &str) -> time::Date {
+        sqlx::query_scalar::<_, time::Date>("SELECT ($2::timestamptz AT TIME ZONE $1)::date")
+            .bind(time_zone)
+            .bind(self.clock.now())
+            .fetch_one(&self.db_pool)
+            .await
+            .expect("Failed to compute the current date")
+    }
+
     pub async fn post_todo(&self, payload: &JsonValue) -> reqwest::Response {
         self.client
             .post(format!("{}/todo", self.address))
@@ -169,6 +241,28 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    /// Sends an authenticated request with extra headers, e.g. conditional
+    /// request headers.
+    pub async fn send_with_headers(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        payload: Option<&JsonValue>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.address, path))
+            .header("Authorization", self.get_auth_header());
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        if let Some(payload) = payload {
+            request = request.json(payload);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
     pub async fn get_todo(&self, todo_name: &str) -> reqwest::Response {
         self.client
             .get(format!("{}/todo/{}", self.address, todo_name))
@@ -206,6 +300,16 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn list_todo_page(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.client
+            .get(format!("{}/todo", self.address))
+            .query(query)
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn post_todo_item(&self, todo_name: &str, payload: &JsonValue) -> reqwest::Response {
         self.client
             .post(format!("{}/todo/{}/item", self.address, todo_name))
@@ -216,6 +320,33 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    /// Creates an item due on 2020-10-01, returning its `todo_item_id`.
+    pub async fn create_item(&self, todo_name: &str, title: &str) -> String {
+        let response = self
+            .post_todo_item(
+                todo_name,
+                &serde_json::json!({"title": title, "due_date": "2020-10-01"}),
+            )
+            .await;
+        assert_response(&response, reqwest::StatusCode::OK);
+        let item: JsonValue = response.json().await.unwrap();
+        item["todo_item_id"].as_str().unwrap().to_string()
+    }
+
+    pub async fn batch_todo_items(
+        &self,
+        todo_name: &str,
+        payload: &JsonValue,
+    ) -> reqwest::Response {
+        self.client
+            .post(format!("{}/todo/{}/item:batch", self.address, todo_name))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn get_todo_item(&self, todo_name: &str, todo_item_id: &str) -> reqwest::Response {
         self.client
             .get(format!(
@@ -244,6 +375,18 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn reopen_todo_item(&self, todo_name: &str, todo_item_id: &str) -> reqwest::Response {
+        self.client
+            .post(format!(
+                "{}/todo/{}/item/{}/reopen",
+                self.address, todo_name, todo_item_id
+            ))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn update_todo_item(
         &self,
         todo_name: &str,
@@ -271,6 +414,20 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn list_todo_items_page(
+        &self,
+        todo_name: &str,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.client
+            .get(format!("{}/todo/{}/item", self.address, todo_name))
+            .query(query)
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn delete_todo_item(&self, todo_name: &str, todo_item_id: &str) -> reqwest::Response {
         self.client
             .delete(format!(
@@ -283,6 +440,174 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn list_todo_members(&self, todo_name: &str) -> reqwest::Response {
+        self.client
+            .get(format!("{}/todo/{}/member", self.address, todo_name))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn add_todo_member(&self, todo_name: &str, payload: &JsonValue) -> reqwest::Response {
+        self.client
+            .post(format!("{}/todo/{}/member", self.address, todo_name))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn update_todo_member(
+        &self,
+        todo_name: &str,
+        user_id: i32,
+        payload: &JsonValue,
+    ) -> reqwest::Response {
+        self.client
+            .put(format!(
+                "{}/todo/{}/member/{}",
+                self.address, todo_name, user_id
+            ))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn remove_todo_member(&self, todo_name: &str, user_id: i32) -> reqwest::Response {
+        self.client
+            .delete(format!(
+                "{}/todo/{}/member/{}",
+                self.address, todo_name, user_id
+            ))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_group(&self, payload: &JsonValue) -> reqwest::Response {
+        self.client
+            .post(format!("{}/group", self.address))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn list_groups(&self) -> reqwest::Response {
+        self.client
+            .get(format!("{}/group", self.address))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn list_group_members(&self, group_id: &str) -> reqwest::Response {
+        self.client
+            .get(format!("{}/group/{}/member", self.address, group_id))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn add_group_member(&self, group_id: &str, payload: &JsonValue) -> reqwest::Response {
+        self.client
+            .post(format!("{}/group/{}/member", self.address, group_id))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn update_group_member(
+        &self,
+        group_id: &str,
+        user_id: i32,
+        payload: &JsonValue,
+    ) -> reqwest::Response {
+        self.client
+            .put(format!(
+                "{}/group/{}/member/{}",
+                self.address, group_id, user_id
+            ))
+            .header("Authorization", self.get_auth_header())
+            .json(payload)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn remove_group_member(&self, group_id: &str, user_id: i32) -> reqwest::Response {
+        self.client
+            .delete(format!(
+                "{}/group/{}/member/{}",
+                self.address, group_id, user_id
+            ))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn todo_activity(
+        &self,
+        todo_name: &str,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.client
+            .get(format!("{}/todo/{}/activity", self.address, todo_name))
+            .query(query)
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn list_trash(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.client
+            .get(format!("{}/trash", self.address))
+            .query(query)
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn restore_from_trash(&self, id: &str) -> reqwest::Response {
+        self.client
+            .post(format!("{}/trash/{}/restore", self.address, id))
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn purge_trash(&self, retention: std::time::Duration) -> eyre::Result<()> {
+        checklist::services::purge_trash(&self.db_pool, retention, self.clock.now()).await
+    }
+
+    pub async fn purge_todo_events(&self, retention: std::time::Duration) -> eyre::Result<()> {
+        checklist::services::purge_todo_events(&self.db_pool, retention, self.clock.now()).await
+    }
+
+    pub async fn sync(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.client
+            .get(format!("{}/sync", self.address))
+            .query(query)
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn post_recurring_template(
         &self,
         todo_name: &str,
@@ -301,7 +626,12 @@ impl TestApp {
         &self,
         advance_duration: std::time::Duration,
     ) -> eyre::Result<()> {
-        checklist::services::process_recurring_templates(&self.db_pool, advance_duration).await
+        checklist::services::process_recurring_templates(
+            &self.db_pool,
+            advance_duration,
+            self.clock.now(),
+        )
+        .await
     }
 
     pub async fn list_recurring_templates(&self, todo_name: &str) -> reqwest::Response {
@@ -313,6 +643,20 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn list_recurring_templates_page(
+        &self,
+        todo_name: &str,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.client
+            .get(format!("{}/todo/{}/recurring", self.address, todo_name))
+            .query(query)
+            .header("Authorization", self.get_auth_header())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn get_recurring_template(
         &self,
         todo_name: &str,
@@ -364,6 +708,41 @@ impl TestApp {
     }
 }
 
+/// Another user, sending requests with their own token.
+pub struct Member<'a> {
+    app: &'a TestApp,
+    pub user_id: i32,
+    auth: String,
+}
+
+impl<'a> Member<'a> {
+    pub async fn create(app: &'a TestApp, email: &str) -> Self {
+        let user_id = app.create_user(email).await;
+        Self {
+            app,
+            user_id,
+            auth: app.get_auth_header_for_user(user_id, email),
+        }
+    }
+
+    pub async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        payload: Option<&JsonValue>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .app
+            .client
+            .request(method, format!("{}{}", self.app.address, path))
+            .header("Authorization", &self.auth);
+        if let Some(payload) = payload {
+            request = request.json(payload);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+}
+
 pub fn assert_response(response: &reqwest::Response, status_code: reqwest::StatusCode) {
     assert_eq!(response.status(), status_code);
     match response.headers().get("x-request-id") {
@@ -376,3 +755,28 @@ pub fn assert_response(response: &reqwest::Response, status_code: reqwest::Statu
         }
     }
 }
+
+/// Checks that `response` is an RFC 7807 problem with the given status and
+/// code, tagged with the request id. Returns the problem body.
+pub async fn assert_problem(
+    response: reqwest::Response,
+    status_code: reqwest::StatusCode,
+    code: &str,
+) -> JsonValue {
+    assert_response(&response, status_code);
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .expect("no content-type header found in response");
+    assert_eq!(content_type, "application/problem+json");
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let problem: JsonValue = response.json().await.expect("Failed to read json");
+    assert_eq!(problem["status"], status_code.as_u16());
+    assert_eq!(problem["code"], code, "{}", problem);
+    assert_eq!(problem["request_id"], request_id);
+    problem
+}
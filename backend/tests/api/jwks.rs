@@ -0,0 +1,218 @@
+use checklist::auth::{Claims, JwtService};
+use checklist::clock::Clock;
+use checklist::configuration::{JwtKeyAlgorithm, JwtKeySettings, JwtKeysSettings};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header};
+use reqwest::{header, StatusCode};
+use serde_json::Value as JsonValue;
+use time::OffsetDateTime;
+
+use crate::helpers::{assert_problem, spawn_app, spawn_app_with_jwt_keys, TestApp};
+
+fn key(kid: &str, algorithm: JwtKeyAlgorithm, file: &str) -> JwtKeySettings {
+    JwtKeySettings {
+        kid: kid.to_string(),
+        algorithm,
+        public_key_path: format!("tests/keys/{}.pub.pem", file).into(),
+        private_key_path: None,
+        retire_time: None,
+    }
+}
+
+/// Signs with `current`, still trusts `retired` and no longer trusts
+/// `expired`.
+async fn spawn() -> TestApp {
+    let now = OffsetDateTime::now_utc();
+    let mut current = key("current", JwtKeyAlgorithm::EdDSA, "ed25519-current");
+    current.private_key_path = Some("tests/keys/ed25519-current.pem".into());
+    let mut retired = key("retired", JwtKeyAlgorithm::RS256, "rsa-retired");
+    retired.retire_time = Some(now - time::Duration::hours(1));
+    let mut expired = key("expired", JwtKeyAlgorithm::RS256, "rsa-expired");
+    expired.retire_time = Some(now - time::Duration::days(2));
+    spawn_app_with_jwt_keys(JwtKeysSettings {
+        keys: vec![current, retired, expired],
+        retired_key_grace_period: std::time::Duration::from_secs(24 * 60 * 60),
+    })
+    .await
+}
+
+/// A token of the test user signed by a key of the test keys.
+fn sign(app: &TestApp, kid: Option<&str>, algorithm: Algorithm, key: &EncodingKey) -> String {
+    let now = app.clock.now();
+    let claims = Claims {
+        sub: app.test_user_id.to_string(),
+        email: "test@example.com".to_string(),
+        exp: (now + time::Duration::hours(1)).unix_timestamp(),
+        iat: now.unix_timestamp(),
+        sid: None,
+    };
+    let mut header = Header::new(algorithm);
+    header.kid = kid.map(|kid| kid.to_string());
+    encode(&header, &claims, key).unwrap()
+}
+
+fn rsa_key(file: &str) -> EncodingKey {
+    let pem = std::fs::read(format!("tests/keys/{}.pem", file)).unwrap();
+    EncodingKey::from_rsa_pem(&pem).unwrap()
+}
+
+async fn get_user(app: &TestApp, token: &str) -> reqwest::Response {
+    app.client
+        .get(format!("{}/user/me", app.address))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn get_jwks(app: &TestApp) -> reqwest::Response {
+    app.client
+        .get(format!("{}/.well-known/jwks.json", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+#[tokio::test]
+async fn jwks_publishes_keys_until_their_grace_period_ends() {
+    let app = spawn().await;
+
+    let response = get_jwks(&app).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CACHE_CONTROL],
+        "public, max-age=300"
+    );
+    let jwks: JsonValue = response.json().await.unwrap();
+    app.golden.check_diff_json("jwks", &jwks);
+}
+
+#[tokio::test]
+async fn tokens_verify_with_the_published_keys() {
+    let app = spawn().await;
+    let jwks: JwkSet = get_jwks(&app).await.json().await.unwrap();
+    let tokens = [
+        app.jwt_service
+            .generate_token(app.test_user_id, "test@example.com", app.clock.now())
+            .unwrap(),
+        sign(
+            &app,
+            Some("retired"),
+            Algorithm::RS256,
+            &rsa_key("rsa-retired"),
+        ),
+    ];
+
+    for token in tokens {
+        let header = decode_header(&token).unwrap();
+        let jwk = jwks.find(header.kid.as_deref().unwrap()).unwrap();
+        let key = DecodingKey::from_jwk(jwk).unwrap();
+        let validation = jsonwebtoken::Validation::new(header.alg);
+        let claims = decode::<Claims>(&token, &key, &validation).unwrap().claims;
+        assert_eq!(claims.sub, app.test_user_id.to_string());
+    }
+}
+
+#[tokio::test]
+async fn tokens_are_signed_with_the_signing_key() {
+    let app = spawn().await;
+    let token = app
+        .jwt_service
+        .generate_token(app.test_user_id, "test@example.com", app.clock.now())
+        .unwrap();
+
+    let header = decode_header(&token).unwrap();
+    assert_eq!(header.alg, Algorithm::EdDSA);
+    assert_eq!(header.kid.as_deref(), Some("current"));
+    let response = get_user(&app, &token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn retired_key_is_trusted_during_the_grace_period() {
+    let app = spawn().await;
+
+    let token = sign(
+        &app,
+        Some("retired"),
+        Algorithm::RS256,
+        &rsa_key("rsa-retired"),
+    );
+    let response = get_user(&app, &token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let token = sign(
+        &app,
+        Some("expired"),
+        Algorithm::RS256,
+        &rsa_key("rsa-expired"),
+    );
+    let response = get_user(&app, &token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn retired_key_stops_being_trusted_on_the_app_clock() {
+    let app = spawn().await;
+
+    // The grace period of the retired key ends in 23 hours
+    app.clock.advance(time::Duration::days(1));
+
+    let jwks: JwkSet = get_jwks(&app).await.json().await.unwrap();
+    assert!(jwks.find("retired").is_none());
+    let token = sign(
+        &app,
+        Some("retired"),
+        Algorithm::RS256,
+        &rsa_key("rsa-retired"),
+    );
+    let response = get_user(&app, &token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    let token = app
+        .jwt_service
+        .generate_token(app.test_user_id, "test@example.com", app.clock.now())
+        .unwrap();
+    let response = get_user(&app, &token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn tokens_not_signed_by_a_published_key_are_rejected() {
+    let app = spawn().await;
+    let secret = JwtService::new("jwt-test-secret", 1)
+        .generate_token(app.test_user_id, "test@example.com", app.clock.now())
+        .unwrap();
+    let tokens = [
+        // Signed with the secret the app was using before its keys
+        secret,
+        sign(&app, None, Algorithm::RS256, &rsa_key("rsa-retired")),
+        sign(
+            &app,
+            Some("unknown"),
+            Algorithm::RS256,
+            &rsa_key("rsa-retired"),
+        ),
+        // Signed with another key than the one named
+        sign(
+            &app,
+            Some("retired"),
+            Algorithm::RS256,
+            &rsa_key("rsa-expired"),
+        ),
+    ];
+
+    for token in tokens {
+        let response = get_user(&app, &token).await;
+        assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    }
+}
+
+#[tokio::test]
+async fn jwks_is_not_served_when_tokens_are_signed_with_a_secret() {
+    let app = spawn_app().await;
+
+    let response = get_jwks(&app).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
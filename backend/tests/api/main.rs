@@ -1,7 +1,22 @@
+mod activity;
 mod auth;
+mod error;
+mod etag;
 mod golden;
+mod google_oauth;
+mod group;
 mod health_check;
 mod helpers;
+mod jwks;
+mod oauth_server;
+mod oidc;
+mod openapi;
 mod recurring_template;
+mod session;
+mod sync;
 mod todo;
+mod todo_event;
 mod todo_item;
+mod todo_member;
+mod trash;
+mod user;
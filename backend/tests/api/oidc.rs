@@ -0,0 +1,156 @@
+use checklist::clock::Clock;
+use reqwest::StatusCode;
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{spawn_oidc_app, TestApp};
+use crate::oauth_server::{exchange, location, query_of, Login, OAuthServer};
+
+/// An app with two providers, `corp` and `home`, each its own issuer.
+async fn spawn() -> (TestApp, OAuthServer, OAuthServer) {
+    let corp = OAuthServer::start().await;
+    let home = OAuthServer::start().await;
+    let app = spawn_oidc_app(vec![
+        corp.oidc_provider_settings("corp"),
+        home.oidc_provider_settings("home"),
+    ])
+    .await;
+    (app, corp, home)
+}
+
+/// Goes through the login at `provider`, the issuer answering with the ID
+/// token claims.
+async fn log_in(
+    app: &TestApp,
+    issuer: &OAuthServer,
+    provider: &str,
+    claims: serde_json::Value,
+) -> reqwest::Response {
+    let login = Login::start(app, &format!("/auth/oidc/{}", provider)).await;
+    let code = issuer.authorize_with_claims(&login.authorization_url, "test@example.com", claims);
+    let state = login.state();
+    Login::call_back(
+        app,
+        &format!("/auth/oidc/{}/callback", provider),
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn login_goes_to_the_discovered_endpoints_of_the_provider() {
+    let (app, corp, home) = spawn().await;
+
+    let login = Login::start(&app, "/auth/oidc/home").await;
+
+    let url = login.authorization_url.as_str();
+    assert!(url.starts_with(&format!("{}/authorize", home.address)));
+    let query = query_of(&login.authorization_url);
+    assert_eq!(query["scope"], "openid email");
+    assert_eq!(query["code_challenge_method"], "S256");
+    assert!(!query["nonce"].is_empty());
+    let login = Login::start(&app, "/auth/oidc/corp").await;
+    assert!(login
+        .authorization_url
+        .as_str()
+        .starts_with(&format!("{}/authorize", corp.address)));
+
+    let response = Login::call_back(&app, "/auth/oidc/unknown", &[], None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn verified_id_token_logs_in() {
+    let (app, corp, home) = spawn().await;
+
+    for (provider, issuer) in [("corp", &corp), ("home", &home)] {
+        let response = log_in(&app, issuer, provider, json!({})).await;
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let query = query_of(&location(&response));
+        let response = exchange(&app, &query["code"]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: JsonValue = response.json().await.unwrap();
+        assert_eq!(body["email"], "test@example.com");
+        let claims = app
+            .jwt_service
+            .validate_token(body["token"].as_str().unwrap(), app.clock.now())
+            .unwrap();
+        assert_eq!(claims.sub, app.test_user_id.to_string());
+    }
+}
+
+#[tokio::test]
+async fn unverified_or_unknown_email_is_rejected() {
+    let (app, corp, _) = spawn().await;
+
+    let response = log_in(&app, &corp, "corp", json!({"email_verified": false})).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = log_in(&app, &corp, "corp", json!({"email_verified": null})).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = log_in(&app, &corp, "corp", json!({"email": "nobody@example.com"})).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn id_token_for_someone_else_is_rejected() {
+    let (app, corp, home) = spawn().await;
+    let tampered = [
+        json!({"aud": "another-client"}),
+        json!({"iss": home.address}),
+        json!({"nonce": "another-nonce"}),
+        json!({"exp": time::OffsetDateTime::now_utc().unix_timestamp() - 3600}),
+    ];
+
+    for claims in tampered {
+        let response = log_in(&app, &corp, "corp", claims.clone()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", claims);
+    }
+}
+
+#[tokio::test]
+async fn id_token_signed_with_an_unpublished_key_is_rejected() {
+    let (app, corp, _) = spawn().await;
+    corp.sign_with_unpublished_key();
+
+    let response = log_in(&app, &corp, "corp", json!({})).await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn keys_are_fetched_again_when_the_issuer_rotates_them() {
+    let (app, corp, _) = spawn().await;
+    let response = log_in(&app, &corp, "corp", json!({})).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let response = log_in(&app, &corp, "corp", json!({})).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(corp.jwks_requests(), 1);
+
+    corp.rotate_key();
+    let response = log_in(&app, &corp, "corp", json!({})).await;
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(corp.jwks_requests(), 2);
+}
+
+#[tokio::test]
+async fn callback_must_come_from_the_provider_of_the_login() {
+    let (app, corp, _) = spawn().await;
+    let login = Login::start(&app, "/auth/oidc/corp").await;
+    let code = corp.authorize(&login.authorization_url, "test@example.com");
+    let state = login.state();
+
+    let response = Login::call_back(
+        &app,
+        "/auth/oidc/home/callback",
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(corp.token_requests(), 0);
+}
@@ -0,0 +1,41 @@
+use reqwest::StatusCode;
+use serde_json::Value as JsonValue;
+
+use crate::golden::GoldenTest;
+use crate::helpers::{assert_response, spawn_app};
+
+/// The committed `openapi.json` is what clients are generated from, this
+/// fails when a route or one of its types changes without updating it.
+/// Regenerate it with `GOLDEN_OVERWRITE=1 cargo test --test api openapi`.
+#[tokio::test]
+async fn openapi_document_matches_committed_copy() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!("{}/openapi.json", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+    let document: JsonValue = response.json().await.expect("Failed to read json");
+    assert_eq!(document["openapi"], "3.1.0");
+
+    GoldenTest::new_with_dir(".").check_diff_json("openapi", &document);
+}
+
+#[cfg(feature = "api-viewer")]
+#[tokio::test]
+async fn openapi_viewer_is_served() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!("{}/docs", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("/openapi.json") || body.contains("\"openapi\""));
+}
@@ -1,3 +1,4 @@
+use checklist::clock::Clock;
 use reqwest::StatusCode;
 use serde_json::json;
 use serde_json::Value as JsonValue;
@@ -46,7 +47,7 @@ async fn create_recurring_template_works() {
         &time::format_description::well_known::Iso8601::DATE,
     )
     .unwrap();
-    let expected_due_date = time::OffsetDateTime::now_utc().date() + time::Duration::days(7);
+    let expected_due_date = app.clock.now().date() + time::Duration::days(7);
     assert_eq!(due_date, expected_due_date);
 }
 
@@ -59,7 +60,7 @@ async fn recurring_template_creates_todo_one_week_in_advance() {
 
     // Create a template with start date far in the future (beyond 7-day window)
     // so no immediate todo creation happens
-    let far_future_start = time::OffsetDateTime::now_utc().date() + time::Duration::days(20);
+    let far_future_start = app.clock.now().date() + time::Duration::days(20);
     let payload = json!({
         "title": "Weekly advance task",
         "recurrence_interval": {
@@ -78,23 +79,9 @@ async fn recurring_template_creates_todo_one_week_in_advance() {
     let initial_todo_items = initial_items_json["items"].as_array().unwrap();
     assert_eq!(initial_todo_items.len(), 0);
 
-    // Simulate time passing: update the template to have start_date = today
-    // This makes the template eligible for the scheduler's 7-day advance processing
-    let template_response: serde_json::Value = response.json().await.unwrap();
-    let template_id_str = template_response["template_id"].as_str().unwrap();
-    let template_id = uuid::Uuid::parse_str(template_id_str).unwrap();
-
-    let current_date = time::OffsetDateTime::now_utc().date();
-
-    // Update the database to simulate the template becoming active today
-    sqlx::query!(
-        "UPDATE recurring_template SET start_date = $1 WHERE template_id = $2",
-        current_date,
-        template_id
-    )
-    .execute(&app.db_pool)
-    .await
-    .unwrap();
+    // Three weeks later the template has started and is eligible for the
+    // scheduler's 7-day advance processing
+    app.clock.advance(time::Duration::weeks(3));
 
     // Process recurring templates - this tests the scheduler's 7-day advance logic
     app.process_recurring_templates(std::time::Duration::from_secs(7 * 24 * 60 * 60))
@@ -119,7 +106,7 @@ async fn recurring_template_creates_todo_one_week_in_advance() {
         &time::format_description::well_known::Iso8601::DATE,
     )
     .unwrap();
-    let current_date = time::OffsetDateTime::now_utc().date();
+    let current_date = app.clock.now().date();
     let expected_due_date = current_date + time::Duration::days(7);
     assert_eq!(due_date, expected_due_date);
 
@@ -135,6 +122,201 @@ async fn recurring_template_creates_todo_one_week_in_advance() {
     assert_eq!(todo_items_second.len(), 1); // Still only one item
 }
 
+#[tokio::test]
+async fn scheduler_uses_the_owner_time_zone() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_tz";
+    let time_zone = "Pacific/Kiritimati";
+
+    create_todo(&app, todo_name).await;
+    let response = app
+        .update_current_user(&json!({"time_zone": time_zone}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    let today = app.today_in(time_zone).await;
+    let response = app
+        .post_recurring_template(
+            todo_name,
+            &json!({
+                "title": "Local task",
+                "recurrence_interval": {"days": 1},
+                "start_date": (today + time::Duration::days(20)).to_string(),
+            }),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let template: JsonValue = response.json().await.unwrap();
+    let template_id = template["template_id"].as_str().unwrap();
+
+    app.clock.advance(time::Duration::days(20));
+    let today = app.today_in(time_zone).await;
+
+    app.process_recurring_templates(std::time::Duration::from_secs(7 * 24 * 60 * 60))
+        .await
+        .unwrap();
+
+    let items = open_items(&app, todo_name).await;
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0]["due_date"].as_str().unwrap(),
+        (today + time::Duration::days(7)).to_string()
+    );
+
+    let response = app.get_recurring_template(todo_name, template_id).await;
+    let template: JsonValue = response.json().await.unwrap();
+    assert_eq!(
+        template["last_generated_date"].as_str().unwrap(),
+        today.to_string()
+    );
+}
+
+#[tokio::test]
+async fn completing_recurring_item_generates_next_occurrence() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_complete";
+
+    create_todo(&app, todo_name).await;
+
+    let today = app.clock.now().date();
+    let payload = json!({
+        "title": "Monthly task",
+        "recurrence_interval": {"months": 1, "days": 2},
+        "start_date": today.to_string(),
+        "end_date": null
+    });
+    let response = app.post_recurring_template(todo_name, &payload).await;
+    assert_response(&response, StatusCode::OK);
+    let template: serde_json::Value = response.json().await.unwrap();
+
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    let first = &items["items"][0];
+    let first_id = first["todo_item_id"].as_str().unwrap();
+    let first_due = time::Date::parse(
+        first["due_date"].as_str().unwrap(),
+        &time::format_description::well_known::Iso8601::DATE,
+    )
+    .unwrap();
+
+    let complete_response = app.complete_todo_item(todo_name, first_id).await;
+    assert_response(&complete_response, StatusCode::OK);
+    let completed: serde_json::Value = complete_response.json().await.unwrap();
+    app.golden
+        .check_diff_json("complete_recurring_todo_item", &completed);
+    assert_eq!(completed["todo_item_id"].as_str().unwrap(), first_id);
+    assert_eq!(completed["is_complete"], true);
+
+    let next = &completed["next_item"];
+    assert_eq!(next["title"], "Monthly task");
+    assert_eq!(next["is_complete"], false);
+    let expected_due: time::Date =
+        sqlx::query_scalar("SELECT ($1::date + interval '1 month 2 days')::date")
+            .bind(first_due)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(next["due_date"].as_str().unwrap(), expected_due.to_string());
+
+    // The generated item is the only open one, and it is linked to the template
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    let items = items["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["todo_item_id"], next["todo_item_id"]);
+    let linked: Option<uuid::Uuid> =
+        sqlx::query_scalar("SELECT recurring_template_id FROM todo_item WHERE todo_item_id = $1")
+            .bind(uuid::Uuid::parse_str(next["todo_item_id"].as_str().unwrap()).unwrap())
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        linked.unwrap().to_string(),
+        template["template_id"].as_str().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn completing_recurring_item_stops_at_end_date() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_ending";
+
+    create_todo(&app, todo_name).await;
+
+    let today = app.clock.now().date();
+    let payload = json!({
+        "title": "Ending task",
+        "recurrence_interval": {"days": 30},
+        "start_date": today.to_string(),
+        "end_date": (today + time::Duration::days(10)).to_string()
+    });
+    let response = app.post_recurring_template(todo_name, &payload).await;
+    assert_response(&response, StatusCode::OK);
+
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    let first_id = items["items"][0]["todo_item_id"].as_str().unwrap();
+
+    let complete_response = app.complete_todo_item(todo_name, first_id).await;
+    assert_response(&complete_response, StatusCode::OK);
+    let completed: serde_json::Value = complete_response.json().await.unwrap();
+    assert!(completed["next_item"].is_null());
+
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    assert_eq!(items["items"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn reopening_recurring_item_drops_the_next_occurrence() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_reopen";
+    let advance = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+
+    create_todo(&app, todo_name).await;
+
+    let today = app.clock.now().date();
+    let payload = json!({
+        "title": "Daily task",
+        "recurrence_interval": {"days": 1},
+        "start_date": today.to_string(),
+        "end_date": null
+    });
+    let response = app.post_recurring_template(todo_name, &payload).await;
+    assert_response(&response, StatusCode::OK);
+
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    let first_id = items["items"][0]["todo_item_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let complete_response = app.complete_todo_item(todo_name, &first_id).await;
+    assert_response(&complete_response, StatusCode::OK);
+
+    // Completing the occurrence generates the next one
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    let items = items["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    let next_id = items[0]["todo_item_id"].as_str().unwrap().to_string();
+    assert_ne!(next_id, first_id);
+
+    // Reopening the first occurrence leaves a single open item for the template
+    let reopen_response = app.reopen_todo_item(todo_name, &first_id).await;
+    assert_response(&reopen_response, StatusCode::OK);
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    let items = items["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["todo_item_id"].as_str().unwrap(), first_id);
+
+    // The dropped occurrence went to the trash rather than away for good
+    let trash: serde_json::Value = app.list_trash(&[]).await.json().await.unwrap();
+    let trash = trash["items"].as_array().unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0]["id"].as_str().unwrap(), next_id);
+
+    // And the scheduler doesn't generate a duplicate while it is open
+    app.process_recurring_templates(advance).await.unwrap();
+    let items: serde_json::Value = app.list_todo_items(todo_name).await.json().await.unwrap();
+    assert_eq!(items["items"].as_array().unwrap().len(), 1);
+}
+
 #[tokio::test]
 async fn recurring_template_creates_todo_with_proper_relationship() {
     let app = spawn_app().await;
@@ -222,7 +404,7 @@ async fn recurring_template_with_future_start_date_creates_no_todos() {
     create_todo(&app, todo_name).await;
 
     // Create a template with start date far in the future
-    let future_date = time::OffsetDateTime::now_utc().date() + time::Duration::days(30);
+    let future_date = app.clock.now().date() + time::Duration::days(30);
     let payload = json!({
         "title": "Future task",
         "recurrence_interval": {
@@ -250,7 +432,7 @@ async fn updating_recurring_template_creates_todo_when_necessary() {
     create_todo(&app, todo_name).await;
 
     // Create a template with start date far in the future (no immediate todo creation)
-    let far_future_start = time::OffsetDateTime::now_utc().date() + time::Duration::days(20);
+    let far_future_start = app.clock.now().date() + time::Duration::days(20);
     let payload = json!({
         "title": "Update test task",
         "recurrence_interval": {
@@ -272,7 +454,7 @@ async fn updating_recurring_template_creates_todo_when_necessary() {
     assert_eq!(initial_todo_items.len(), 0);
 
     // Update the template to have start_date = today (this should trigger todo creation)
-    let current_date = time::OffsetDateTime::now_utc().date();
+    let current_date = app.clock.now().date();
     let update_payload = json!({
         "title": "Updated task title",
         "recurrence_interval": {
@@ -327,6 +509,147 @@ async fn updating_recurring_template_creates_todo_when_necessary() {
     );
 }
 
+async fn open_items(app: &TestApp, todo_name: &str) -> Vec<JsonValue> {
+    let items: JsonValue = app.list_todo_items(todo_name).await.json().await.unwrap();
+    items["items"].as_array().unwrap().clone()
+}
+
+/// The first date on or after `from` that falls on one of `weekdays`.
+fn next_weekday(from: time::Date, weekdays: &[time::Weekday]) -> time::Date {
+    let mut date = from;
+    while !weekdays.contains(&date.weekday()) {
+        date = date.next_day().unwrap();
+    }
+    date
+}
+
+#[tokio::test]
+async fn create_recurring_template_with_rrule_works() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_rrule";
+    create_todo(&app, todo_name).await;
+
+    let today = app.clock.now().date();
+    let payload = json!({
+        "title": "Standup",
+        "rrule": "RRULE:freq=weekly;byday=mo,we,fr",
+        "start_date": today.to_string(),
+        "end_date": null
+    });
+    let response = app.post_recurring_template(todo_name, &payload).await;
+    assert_response(&response, StatusCode::OK);
+    let template: JsonValue = response.json().await.unwrap();
+    app.golden
+        .check_diff_json("create_recurring_template_rrule", &template);
+    assert_eq!(template["rrule"], "FREQ=WEEKLY;BYDAY=MO,WE,FR");
+    assert!(template["recurrence_interval"].is_null());
+
+    // The first occurrence is within the look ahead window
+    let weekdays = [
+        time::Weekday::Monday,
+        time::Weekday::Wednesday,
+        time::Weekday::Friday,
+    ];
+    let first_due = next_weekday(today, &weekdays);
+    let items = open_items(&app, todo_name).await;
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0]["due_date"].as_str().unwrap(),
+        first_due.to_string()
+    );
+
+    // Completing it generates the rule's next occurrence
+    let complete_response = app
+        .complete_todo_item(todo_name, items[0]["todo_item_id"].as_str().unwrap())
+        .await;
+    assert_response(&complete_response, StatusCode::OK);
+    let completed: JsonValue = complete_response.json().await.unwrap();
+    let second_due = next_weekday(first_due.next_day().unwrap(), &weekdays);
+    assert_eq!(
+        completed["next_item"]["due_date"].as_str().unwrap(),
+        second_due.to_string()
+    );
+}
+
+#[tokio::test]
+async fn rrule_template_stops_after_count() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_count";
+    create_todo(&app, todo_name).await;
+
+    let today = app.clock.now().date();
+    let payload = json!({
+        "title": "Twice",
+        "rrule": "FREQ=DAILY;COUNT=2",
+        "start_date": today.to_string(),
+        "end_date": null
+    });
+    let response = app.post_recurring_template(todo_name, &payload).await;
+    assert_response(&response, StatusCode::OK);
+
+    let items = open_items(&app, todo_name).await;
+    let first_id = items[0]["todo_item_id"].as_str().unwrap();
+    let completed: JsonValue = app
+        .complete_todo_item(todo_name, first_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let second_id = completed["next_item"]["todo_item_id"].as_str().unwrap();
+
+    let completed: JsonValue = app
+        .complete_todo_item(todo_name, second_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(completed["next_item"].is_null());
+
+    app.process_recurring_templates(std::time::Duration::from_secs(7 * 24 * 60 * 60))
+        .await
+        .unwrap();
+    assert!(open_items(&app, todo_name).await.is_empty());
+}
+
+#[tokio::test]
+async fn scheduler_generates_next_rrule_occurrence() {
+    let app = spawn_app().await;
+    let todo_name = "rrule_scheduler";
+    create_todo(&app, todo_name).await;
+
+    let today = app.clock.now().date();
+    let payload = json!({
+        "title": "Daily",
+        "rrule": "FREQ=DAILY",
+        "start_date": today.to_string(),
+        "end_date": null
+    });
+    let response = app.post_recurring_template(todo_name, &payload).await;
+    assert_response(&response, StatusCode::OK);
+    let items = open_items(&app, todo_name).await;
+    assert_eq!(items[0]["due_date"].as_str().unwrap(), today.to_string());
+
+    // Complete the item behind the API's back so that only the scheduler
+    // can generate the next occurrence
+    sqlx::query(
+        "UPDATE todo_item SET is_complete = TRUE, complete_time = NOW() WHERE todo_item_id = $1",
+    )
+    .bind(uuid::Uuid::parse_str(items[0]["todo_item_id"].as_str().unwrap()).unwrap())
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.process_recurring_templates(std::time::Duration::from_secs(7 * 24 * 60 * 60))
+        .await
+        .unwrap();
+    let items = open_items(&app, todo_name).await;
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0]["due_date"].as_str().unwrap(),
+        today.next_day().unwrap().to_string()
+    );
+}
+
 #[tokio::test]
 async fn create_recurring_template_fails() {
     let app = spawn_app().await;
@@ -352,6 +675,33 @@ async fn create_recurring_template_fails() {
             })),
             expected_status_code: StatusCode::UNPROCESSABLE_ENTITY,
         },
+        FailCall {
+            json: Some(json!({
+                "title": "No recurrence",
+                "start_date": "2025-01-01",
+                "end_date": null
+            })),
+            expected_status_code: StatusCode::BAD_REQUEST,
+        },
+        FailCall {
+            json: Some(json!({
+                "title": "Both recurrences",
+                "recurrence_interval": {"days": 1},
+                "rrule": "FREQ=DAILY",
+                "start_date": "2025-01-01",
+                "end_date": null
+            })),
+            expected_status_code: StatusCode::BAD_REQUEST,
+        },
+        FailCall {
+            json: Some(json!({
+                "title": "Invalid rule",
+                "rrule": "FREQ=WEEKLY;BYDAY=2TU",
+                "start_date": "2025-01-01",
+                "end_date": null
+            })),
+            expected_status_code: StatusCode::BAD_REQUEST,
+        },
     ];
     create_todo(&app, todo_name).await;
 
@@ -416,6 +766,53 @@ async fn list_recurring_templates_works() {
     app.golden.check_diff_json("list_templates", &expected);
 }
 
+#[tokio::test]
+async fn list_recurring_templates_paginates() {
+    let app = spawn_app().await;
+    let todo_name = "recurring_pages";
+    create_todo(&app, todo_name).await;
+
+    for i in 0..3 {
+        let response = app
+            .post_recurring_template(
+                todo_name,
+                &json!({
+                    "title": format!("task{i}"),
+                    "recurrence_interval": {"days": 1},
+                    "start_date": "2025-01-01",
+                    "end_date": null
+                }),
+            )
+            .await;
+        assert_response(&response, StatusCode::OK);
+    }
+
+    let first = app
+        .list_recurring_templates_page(todo_name, &[("limit", "2")])
+        .await;
+    assert_response(&first, StatusCode::OK);
+    let first: serde_json::Value = first.json().await.unwrap();
+    assert_eq!(first["templates"].as_array().unwrap().len(), 2);
+    let cursor = first["next_cursor"].as_str().expect("Missing next_cursor");
+
+    let second = app
+        .list_recurring_templates_page(todo_name, &[("limit", "2"), ("cursor", cursor)])
+        .await;
+    assert_response(&second, StatusCode::OK);
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(second["templates"].as_array().unwrap().len(), 1);
+    assert!(second["next_cursor"].is_null());
+
+    let titles: Vec<&str> = first["templates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .chain(second["templates"].as_array().unwrap())
+        .map(|t| t["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, vec!["task2", "task1", "task0"]);
+}
+
 #[tokio::test]
 async fn get_recurring_template_works() {
     let app = spawn_app().await;
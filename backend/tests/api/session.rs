@@ -0,0 +1,306 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, spawn_google_oauth_app, TestApp};
+use crate::oauth_server::{exchange, location, query_of, Login, OAuthServer, BROWSER_USER_AGENT};
+
+/// The tokens the frontend gets from a login.
+struct Tokens {
+    token: String,
+    refresh_token: String,
+}
+
+async fn spawn() -> (TestApp, OAuthServer) {
+    let server = OAuthServer::start().await;
+    let app = spawn_google_oauth_app(server.google_oauth_settings()).await;
+    (app, server)
+}
+
+/// The code the frontend is redirected with at the end of a login.
+async fn login_code(app: &TestApp, server: &OAuthServer, email: &str) -> String {
+    let login = Login::start(app, "/auth/google").await;
+    let code = server.authorize(&login.authorization_url, email);
+    let state = login.state();
+    let response = Login::call_back(
+        app,
+        "/auth/google/callback",
+        &[("code", &code), ("state", &state)],
+        Some(&login.cookie),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    query_of(&location(&response))["code"].clone()
+}
+
+async fn log_in(app: &TestApp, server: &OAuthServer, email: &str) -> Tokens {
+    let code = login_code(app, server, email).await;
+    let response = exchange(app, &code).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: JsonValue = response.json().await.unwrap();
+    Tokens {
+        token: body["token"].as_str().unwrap().to_string(),
+        refresh_token: body["refresh_token"].as_str().unwrap().to_string(),
+    }
+}
+
+async fn send(app: &TestApp, method: Method, path: &str, token: &str) -> reqwest::Response {
+    app.client
+        .request(method, format!("{}{}", app.address, path))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn refresh(app: &TestApp, refresh_token: &str) -> reqwest::Response {
+    app.client
+        .post(format!("{}/auth/refresh", app.address))
+        .json(&json!({"refresh_token": refresh_token}))
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn refreshed(app: &TestApp, refresh_token: &str) -> Tokens {
+    let response = refresh(app, refresh_token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: JsonValue = response.json().await.unwrap();
+    Tokens {
+        token: body["token"].as_str().unwrap().to_string(),
+        refresh_token: body["refresh_token"].as_str().unwrap().to_string(),
+    }
+}
+
+async fn list_sessions(app: &TestApp, token: &str) -> JsonValue {
+    let response = send(app, Method::GET, "/auth/sessions", token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+async fn assert_revoked(app: &TestApp, token: &str) {
+    let response = send(app, Method::GET, "/user/me", token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn login_opens_a_session() {
+    let (app, server) = spawn().await;
+
+    let tokens = log_in(&app, &server, "test@example.com").await;
+
+    let response = send(&app, Method::GET, "/user/me", &tokens.token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions = list_sessions(&app, &tokens.token).await;
+    let sessions = sessions["items"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["current"], true);
+    assert_eq!(sessions[0]["user_agent"], BROWSER_USER_AGENT);
+    let row = sqlx::query!("SELECT refresh_token_hash FROM session")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(row.refresh_token_hash, tokens.refresh_token);
+}
+
+#[tokio::test]
+async fn login_code_is_only_exchanged_once() {
+    let (app, server) = spawn().await;
+    let code = login_code(&app, &server, "test@example.com").await;
+    let response = exchange(&app, &code).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = exchange(&app, &code).await;
+
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    let response = exchange(&app, "made-up").await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn expired_login_code_is_rejected() {
+    let (app, server) = spawn().await;
+    let code = login_code(&app, &server, "test@example.com").await;
+
+    app.clock.advance(time::Duration::minutes(2));
+    let response = exchange(&app, &code).await;
+
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM session"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn refresh_rotates_the_refresh_token() {
+    let (app, server) = spawn().await;
+    let tokens = log_in(&app, &server, "test@example.com").await;
+
+    let refreshed = refreshed(&app, &tokens.refresh_token).await;
+
+    assert_ne!(refreshed.refresh_token, tokens.refresh_token);
+    let response = send(&app, Method::GET, "/user/me", &refreshed.token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed = self::refreshed(&app, &refreshed.refresh_token).await;
+    let sessions = list_sessions(&app, &refreshed.token).await;
+    assert_eq!(sessions["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn reused_refresh_token_revokes_the_session() {
+    let (app, server) = spawn().await;
+    let tokens = log_in(&app, &server, "test@example.com").await;
+    let refreshed = refreshed(&app, &tokens.refresh_token).await;
+
+    // Someone else kept a copy of the first refresh token
+    app.clock.advance(time::Duration::minutes(1));
+    let response = refresh(&app, &tokens.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+
+    let response = refresh(&app, &refreshed.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    assert_revoked(&app, &refreshed.token).await;
+}
+
+#[tokio::test]
+async fn refresh_token_reused_after_several_refreshes_revokes_the_session() {
+    let (app, server) = spawn().await;
+    let tokens = log_in(&app, &server, "test@example.com").await;
+    let second = refreshed(&app, &tokens.refresh_token).await;
+    let third = refreshed(&app, &second.refresh_token).await;
+
+    let response = refresh(&app, &tokens.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+
+    let response = refresh(&app, &third.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    assert_revoked(&app, &third.token).await;
+}
+
+#[tokio::test]
+async fn concurrent_refreshes_get_the_same_refresh_token() {
+    let (app, server) = spawn().await;
+    let tokens = log_in(&app, &server, "test@example.com").await;
+
+    let (first, second) = tokio::join!(
+        refreshed(&app, &tokens.refresh_token),
+        refreshed(&app, &tokens.refresh_token)
+    );
+
+    assert_eq!(first.refresh_token, second.refresh_token);
+    assert_ne!(first.refresh_token, tokens.refresh_token);
+    app.clock.advance(time::Duration::seconds(5));
+    let late = refreshed(&app, &tokens.refresh_token).await;
+    assert_eq!(late.refresh_token, first.refresh_token);
+    let response = send(&app, Method::GET, "/user/me", &late.token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let next = refreshed(&app, &second.refresh_token).await;
+    let sessions = list_sessions(&app, &next.token).await;
+    assert_eq!(sessions["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn tokens_without_a_session_are_rejected() {
+    let (app, server) = spawn().await;
+    log_in(&app, &server, "test@example.com").await;
+
+    let response = app
+        .client
+        .get(format!("{}/user/me", app.address))
+        .header("Authorization", app.get_auth_header())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn unknown_refresh_token_is_rejected() {
+    let (app, _server) = spawn().await;
+
+    let response = refresh(&app, "made-up").await;
+
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn logout_revokes_the_session() {
+    let (app, server) = spawn().await;
+    let tokens = log_in(&app, &server, "test@example.com").await;
+    let other = log_in(&app, &server, "test@example.com").await;
+
+    let response = send(&app, Method::POST, "/auth/logout", &tokens.token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_revoked(&app, &tokens.token).await;
+    let response = refresh(&app, &tokens.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    let sessions = list_sessions(&app, &other.token).await;
+    assert_eq!(sessions["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn revoke_another_session() {
+    let (app, server) = spawn().await;
+    let laptop = log_in(&app, &server, "test@example.com").await;
+    let phone = log_in(&app, &server, "test@example.com").await;
+    let sessions = list_sessions(&app, &laptop.token).await;
+    let sessions = sessions["items"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let phone_session = sessions
+        .iter()
+        .find(|session| session["current"] == false)
+        .unwrap();
+    let path = format!(
+        "/auth/sessions/{}",
+        phone_session["session_id"].as_str().unwrap()
+    );
+
+    let response = send(&app, Method::DELETE, &path, &laptop.token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_revoked(&app, &phone.token).await;
+    let response = refresh(&app, &phone.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    let response = send(&app, Method::GET, "/user/me", &laptop.token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(&app, Method::DELETE, &path, &laptop.token).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_are_not_found() {
+    let (app, server) = spawn().await;
+    app.create_user("other@example.com").await;
+    let tokens = log_in(&app, &server, "test@example.com").await;
+    let other = log_in(&app, &server, "other@example.com").await;
+    let sessions = list_sessions(&app, &other.token).await;
+    let session_id = sessions["items"][0]["session_id"].as_str().unwrap();
+
+    let path = format!("/auth/sessions/{}", session_id);
+    let response = send(&app, Method::DELETE, &path, &tokens.token).await;
+
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let sessions = list_sessions(&app, &tokens.token).await;
+    assert_eq!(sessions["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn session_expires_unless_refreshed() {
+    let (app, server) = spawn().await;
+    let kept = log_in(&app, &server, "test@example.com").await;
+    let left = log_in(&app, &server, "test@example.com").await;
+
+    app.clock.advance(time::Duration::days(20));
+    let kept = refreshed(&app, &kept.refresh_token).await;
+    app.clock.advance(time::Duration::days(20));
+
+    let kept = refreshed(&app, &kept.refresh_token).await;
+    let response = refresh(&app, &left.refresh_token).await;
+    assert_problem(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+    assert_revoked(&app, &left.token).await;
+    let sessions = list_sessions(&app, &kept.token).await;
+    assert_eq!(sessions["items"].as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,307 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
+
+async fn sync(app: &TestApp, query: &[(&str, &str)]) -> JsonValue {
+    let response = app.sync(query).await;
+    assert_response(&response, StatusCode::OK);
+    response.json().await.expect("Failed to read json")
+}
+
+async fn member_sync(member: &Member<'_>, since: Option<&str>) -> JsonValue {
+    let path = match since {
+        Some(token) => format!("/sync?since={}", token),
+        None => "/sync".to_string(),
+    };
+    let response = member.send(Method::GET, &path, None).await;
+    assert_response(&response, StatusCode::OK);
+    response.json().await.expect("Failed to read json")
+}
+
+async fn first_todo_id(app: &TestApp) -> String {
+    let todos: JsonValue = app.list_todo().await.json().await.unwrap();
+    todos["items"][0]["todo_id"].as_str().unwrap().to_string()
+}
+
+fn ids(changes: &JsonValue, key: &str, id: &str) -> Vec<String> {
+    changes[key]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x[id].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn first_sync_returns_everything() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    app.create_item("banana", "item").await;
+    app.post_recurring_template(
+        "banana",
+        &json!({
+            "title": "Daily task",
+            "recurrence_interval": {"days": 1},
+            "start_date": "2020-01-01",
+            "end_date": null
+        }),
+    )
+    .await;
+
+    let changes = sync(&app, &[]).await;
+    app.golden.check_diff_json("first_sync", &changes);
+    assert_eq!(changes["todos"].as_array().unwrap().len(), 1);
+    // The template generated an item when created
+    assert_eq!(changes["items"].as_array().unwrap().len(), 2);
+    assert_eq!(changes["templates"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["has_more"], false);
+}
+
+#[tokio::test]
+async fn sync_returns_changes_and_tombstones_since_the_token() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let kept = app.create_item("banana", "kept").await;
+    let updated = app.create_item("banana", "updated").await;
+    let deleted = app.create_item("banana", "deleted").await;
+    let token = sync(&app, &[]).await["next_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    app.update_todo_item(
+        "banana",
+        &updated,
+        &json!({"title": "renamed", "due_date": "2020-10-02"}),
+    )
+    .await;
+    app.delete_todo_item("banana", &deleted).await;
+    let created = app.create_item("banana", "created").await;
+
+    let changes = sync(&app, &[("since", &token)]).await;
+    assert!(changes["todos"].as_array().unwrap().is_empty());
+    assert_eq!(
+        ids(&changes, "items", "todo_item_id"),
+        vec![updated, created]
+    );
+    assert!(!ids(&changes, "items", "todo_item_id").contains(&kept));
+    assert_eq!(changes["items"][0]["title"], "renamed");
+    assert_eq!(changes["deleted"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["deleted"][0]["entity"], "item");
+    assert_eq!(changes["deleted"][0]["entity_id"], deleted.as_str());
+    assert_ne!(changes["next_token"], token.as_str());
+
+    // Nothing changed since
+    let next_token = changes["next_token"].as_str().unwrap();
+    let changes = sync(&app, &[("since", next_token)]).await;
+    assert!(changes["items"].as_array().unwrap().is_empty());
+    assert!(changes["deleted"].as_array().unwrap().is_empty());
+    assert_eq!(changes["next_token"], next_token);
+}
+
+#[tokio::test]
+async fn deleting_a_todo_leaves_a_single_tombstone() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    app.create_item("banana", "item").await;
+    let first = sync(&app, &[]).await;
+    let todo_id = first["todos"][0]["todo_id"].as_str().unwrap();
+    let token = first["next_token"].as_str().unwrap();
+
+    assert_response(&app.delete_todo("banana").await, StatusCode::OK);
+
+    let changes = sync(&app, &[("since", token)]).await;
+    assert_eq!(changes["deleted"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["deleted"][0]["entity"], "todo");
+    assert_eq!(changes["deleted"][0]["entity_id"], todo_id);
+    assert_eq!(changes["deleted"][0]["todo_id"], todo_id);
+}
+
+#[tokio::test]
+async fn sync_pages_through_changes() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let mut expected = vec![];
+    for title in ["a", "b", "c"] {
+        expected.push(app.create_item("banana", title).await);
+    }
+
+    let mut items = vec![];
+    let mut todos = 0;
+    let mut query: Vec<(&str, String)> = vec![("limit", "2".to_string())];
+    loop {
+        let query_ref: Vec<(&str, &str)> = query.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let changes = sync(&app, &query_ref).await;
+        todos += changes["todos"].as_array().unwrap().len();
+        let page = ids(&changes, "items", "todo_item_id");
+        assert!(page.len() + changes["todos"].as_array().unwrap().len() <= 2);
+        items.extend(page);
+        query = vec![
+            ("limit", "2".to_string()),
+            ("since", changes["next_token"].as_str().unwrap().to_string()),
+        ];
+        if changes["has_more"] == false {
+            break;
+        }
+    }
+    assert_eq!(todos, 1);
+    assert_eq!(items, expected);
+}
+
+#[tokio::test]
+async fn sync_only_includes_the_user_todos() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "public"}))
+        .await;
+    let second_user_id = app.create_user("second@example.com").await;
+    let second_auth = app.get_auth_header_for_user(second_user_id, "second@example.com");
+    app.add_todo_member(
+        "banana",
+        &json!({"email": "second@example.com", "role": "editor"}),
+    )
+    .await;
+
+    // Another user's todo is not synced, their item in the user's todo is
+    let response = app
+        .client
+        .post(format!("{}/todo", app.address))
+        .header("Authorization", &second_auth)
+        .json(&json!({"name": "apple", "visibility": "public"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+    let response = app
+        .client
+        .post(format!(
+            "{}/todo/{}:banana/item",
+            app.address, app.test_user_id
+        ))
+        .header("Authorization", &second_auth)
+        .json(&json!({"title": "from a friend", "due_date": "2020-10-01"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+
+    let changes = sync(&app, &[]).await;
+    assert_eq!(changes["todos"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["todos"][0]["name"], "banana");
+    assert_eq!(changes["items"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["items"][0]["title"], "from a friend");
+}
+
+#[tokio::test]
+async fn shared_todos_are_synced_until_revoked() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    let member = Member::create(&app, "member@example.com").await;
+    let first = member_sync(&member, None).await;
+    assert!(first["todos"].as_array().unwrap().is_empty());
+    let token = first["next_token"].as_str().unwrap();
+
+    // Shared after the token, the todo comes in full
+    let response = app
+        .add_todo_member(
+            "banana",
+            &json!({"email": "member@example.com", "role": "viewer"}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let changes = member_sync(&member, Some(token)).await;
+    assert_eq!(changes["todos"][0]["name"], "banana");
+    assert_eq!(changes["todos"][0]["role"], "viewer");
+    assert_eq!(
+        ids(&changes, "items", "todo_item_id"),
+        vec![item_id.clone()]
+    );
+    let token = changes["next_token"].as_str().unwrap();
+
+    // Along with the changes of the owner
+    app.delete_todo_item("banana", &item_id).await;
+    let created = app.create_item("banana", "slice").await;
+    let changes = member_sync(&member, Some(token)).await;
+    assert_eq!(ids(&changes, "items", "todo_item_id"), vec![created]);
+    assert_eq!(changes["deleted"][0]["entity_id"], item_id.as_str());
+    let token = changes["next_token"].as_str().unwrap();
+
+    let response = app.remove_todo_member("banana", member.user_id).await;
+    assert_response(&response, StatusCode::OK);
+    let changes = member_sync(&member, Some(token)).await;
+    assert!(changes["todos"].as_array().unwrap().is_empty());
+    assert_eq!(changes["deleted"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["deleted"][0]["entity"], "todo");
+    assert_eq!(
+        changes["deleted"][0]["entity_id"],
+        first_todo_id(&app).await
+    );
+
+    // The owner keeps syncing the todo
+    let changes = sync(&app, &[]).await;
+    assert_eq!(changes["todos"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn group_todos_are_synced_to_every_member() {
+    let app = spawn_app().await;
+    let member = Member::create(&app, "member@example.com").await;
+    let response = app.post_group(&json!({"name": "home"})).await;
+    assert_response(&response, StatusCode::OK);
+    let group: JsonValue = response.json().await.unwrap();
+    let group_id = group["group_id"].as_str().unwrap();
+    let response = app
+        .add_group_member(
+            group_id,
+            &json!({"email": "member@example.com", "role": "editor"}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    app.post_todo(&json!({"name": "groceries", "visibility": "private", "group_id": group_id}))
+        .await;
+    let item_id = app.create_item("groceries", "milk").await;
+
+    let changes = member_sync(&member, None).await;
+    assert_eq!(changes["todos"][0]["name"], "groceries");
+    assert_eq!(changes["todos"][0]["role"], "editor");
+    assert_eq!(ids(&changes, "items", "todo_item_id"), vec![item_id]);
+    let token = changes["next_token"].as_str().unwrap();
+
+    // Deleting the group gives the todo back to its creator
+    let response = app
+        .client
+        .delete(format!("{}/group/{}", app.address, group_id))
+        .header("Authorization", app.get_auth_header())
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&response, StatusCode::OK);
+    let changes = member_sync(&member, Some(token)).await;
+    assert_eq!(changes["deleted"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["deleted"][0]["entity"], "todo");
+    assert_eq!(
+        changes["deleted"][0]["entity_id"],
+        first_todo_id(&app).await
+    );
+}
+
+#[tokio::test]
+async fn sync_failure() {
+    let app = spawn_app().await;
+    let test_cases = [
+        (vec![("since", "banana")], "since"),
+        (vec![("since", "LTE")], "since"),
+        (vec![("limit", "0")], "limit"),
+    ];
+    for (query, field) in test_cases {
+        let response = app.sync(&query).await;
+        let problem = assert_problem(response, StatusCode::BAD_REQUEST, "validation_failed").await;
+        assert_eq!(problem["errors"][0]["field"], field);
+    }
+}
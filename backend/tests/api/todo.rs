@@ -67,6 +67,12 @@ async fn create_todo_fails() {
             ),
             expected_status_code: StatusCode::BAD_REQUEST,
         },
+        FailCall {
+            json: Some(
+                serde_json::from_str(r#"{"name": "2024:taxes", "visibility": "private"}"#).unwrap(),
+            ),
+            expected_status_code: StatusCode::BAD_REQUEST,
+        },
     ];
     // Forgot to include a body
     for case in cases {
@@ -121,7 +127,7 @@ async fn create_todo_fails_if_already_exists() {
     assert_response(&get_response, StatusCode::OK);
 
     let create_response = test_app.post_todo(&payload).await;
-    assert_response(&create_response, StatusCode::BAD_REQUEST);
+    assert_response(&create_response, StatusCode::CONFLICT);
 }
 
 #[tokio::test]
@@ -184,6 +190,79 @@ async fn list_todo() {
     test_app.golden.check_diff_json("list_todo", &expected);
 }
 
+#[tokio::test]
+async fn list_todo_paginates() {
+    let test_app = spawn_app().await;
+    for i in 0..25 {
+        let payload = serde_json::json!({"name": format!("banana{i}"), "visibility": "private"});
+        let create_response = test_app.post_todo(&payload).await;
+        assert_response(&create_response, StatusCode::OK);
+    }
+
+    let mut names = Vec::new();
+    let mut pages = 0;
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", "10")];
+        if let Some(cursor) = cursor.as_deref() {
+            query.push(("cursor", cursor));
+        }
+        let list_response = test_app.list_todo_page(&query).await;
+        assert_response(&list_response, StatusCode::OK);
+        let page: JsonValue = list_response.json().await.expect("Failed to read json");
+        pages += 1;
+        let items = page["items"].as_array().unwrap();
+        assert!(items.len() <= 10);
+        names.extend(
+            items
+                .iter()
+                .map(|i| i["name"].as_str().unwrap().to_string()),
+        );
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 3);
+    let expected: Vec<String> = (0..25).map(|i| format!("banana{i}")).collect();
+    assert_eq!(names, expected);
+}
+
+#[tokio::test]
+async fn list_todo_exact_page_has_no_next_cursor() {
+    let test_app = spawn_app().await;
+    for i in 0..5 {
+        let payload = serde_json::json!({"name": format!("banana{i}"), "visibility": "private"});
+        let create_response = test_app.post_todo(&payload).await;
+        assert_response(&create_response, StatusCode::OK);
+    }
+
+    let list_response = test_app.list_todo_page(&[("limit", "5")]).await;
+    assert_response(&list_response, StatusCode::OK);
+    let page: JsonValue = list_response.json().await.expect("Failed to read json");
+    assert_eq!(page["items"].as_array().unwrap().len(), 5);
+    assert!(page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn list_todo_rejects_invalid_page() {
+    let test_app = spawn_app().await;
+    let test_cases = vec![
+        ("zero limit", vec![("limit", "0")]),
+        ("negative limit", vec![("limit", "-1")]),
+        ("garbage cursor", vec![("cursor", "not-a-cursor")]),
+    ];
+    for (description, query) in test_cases {
+        let list_response = test_app.list_todo_page(&query).await;
+        assert_eq!(
+            list_response.status(),
+            StatusCode::BAD_REQUEST,
+            "{description}"
+        );
+    }
+}
+
 #[tokio::test]
 async fn test_update_todo_works() {
     let test_app = spawn_app().await;
@@ -237,7 +316,7 @@ async fn update_todo_fails() {
                 "name": "banana",
                 "visibility": "private"
             }),
-            StatusCode::BAD_REQUEST,
+            StatusCode::CONFLICT,
         ),
     ];
     for test_case in test_cases {
@@ -365,3 +444,192 @@ async fn get_private_todo_owned_by_another_user_fails() {
     let get_response = test_app.get_todo("private_todo").await;
     assert_response(&get_response, StatusCode::NOT_FOUND);
 }
+
+#[tokio::test]
+async fn todo_names_are_unique_per_user() {
+    let test_app = spawn_app().await;
+    let second_user_id = test_app.create_user("second@example.com").await;
+    let second_auth = test_app.get_auth_header_for_user(second_user_id, "second@example.com");
+
+    let payload = serde_json::json!({"name": "groceries", "visibility": "public"});
+    let create_response = test_app.post_todo(&payload).await;
+    assert_response(&create_response, StatusCode::OK);
+
+    // The same name in another namespace is not a conflict
+    let create_response = test_app
+        .client
+        .post(format!("{}/todo", test_app.address))
+        .header("Authorization", &second_auth)
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_response(&create_response, StatusCode::OK);
+
+    // The bare name resolves to the caller's own todo
+    let get_response = test_app.get_todo("groceries").await;
+    assert_response(&get_response, StatusCode::OK);
+    let todo: JsonValue = get_response.json().await.expect("Failed to read json");
+    assert_eq!(todo["owner_id"], test_app.test_user_id);
+
+    // The owner qualified name resolves to the other user's todo
+    let get_response = test_app
+        .get_todo(&format!("{}:groceries", second_user_id))
+        .await;
+    assert_response(&get_response, StatusCode::OK);
+    let todo: JsonValue = get_response.json().await.expect("Failed to read json");
+    assert_eq!(todo["owner_id"], second_user_id);
+
+    // Deleting the caller's todo leaves the other namespace untouched
+    let delete_response = test_app.delete_todo("groceries").await;
+    assert_response(&delete_response, StatusCode::OK);
+    let get_response = test_app.get_todo("groceries").await;
+    assert_response(&get_response, StatusCode::OK);
+    let todo: JsonValue = get_response.json().await.expect("Failed to read json");
+    assert_eq!(todo["owner_id"], second_user_id);
+}
+
+#[tokio::test]
+async fn mutating_todo_of_another_user_fails() {
+    let test_app = spawn_app().await;
+    let second_user_id = test_app.create_user("second@example.com").await;
+
+    sqlx::query(
+        r#"INSERT INTO todo (todo_id, name, user_id, visibility) VALUES ($1, $2, $3, $4::todo_visibility)"#
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind("public_todo")
+    .bind(second_user_id)
+    .bind("public")
+    .execute(&test_app.db_pool)
+    .await
+    .expect("Failed to create public todo for second user");
+
+    let qualified_name = format!("{}:public_todo", second_user_id);
+    let payload = serde_json::json!({"name": "renamed", "visibility": "public"});
+    let update_response = test_app.update_todo(&qualified_name, &payload).await;
+    assert_response(&update_response, StatusCode::NOT_FOUND);
+
+    let delete_response = test_app.delete_todo(&qualified_name).await;
+    assert_response(&delete_response, StatusCode::NOT_FOUND);
+
+    let get_response = test_app.get_todo(&qualified_name).await;
+    assert_response(&get_response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn get_todo_fails_if_public_name_is_ambiguous() {
+    let test_app = spawn_app().await;
+
+    for email in ["second@example.com", "third@example.com"] {
+        let user_id = test_app.create_user(email).await;
+        sqlx::query(
+            r#"INSERT INTO todo (todo_id, name, user_id, visibility) VALUES ($1, $2, $3, $4::todo_visibility)"#
+        )
+        .bind(uuid::Uuid::new_v4())
+        .bind("shared")
+        .bind(user_id)
+        .bind("public")
+        .execute(&test_app.db_pool)
+        .await
+        .expect("Failed to create public todo");
+    }
+
+    let get_response = test_app.get_todo("shared").await;
+    assert_response(&get_response, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn legacy_todo_names_stay_readable() {
+    let test_app = spawn_app().await;
+    let todo_id = uuid::Uuid::new_v4();
+    // Written before ':' was reserved for owner-qualified references
+    sqlx::query(
+        r#"INSERT INTO todo (todo_id, name, user_id, visibility) VALUES ($1, $2, $3, 'private')"#,
+    )
+    .bind(todo_id)
+    .bind("12:groceries")
+    .bind(test_app.test_user_id)
+    .execute(&test_app.db_pool)
+    .await
+    .expect("Failed to create legacy todo");
+
+    let list_response = test_app.list_todo().await;
+    assert_response(&list_response, StatusCode::OK);
+    let todos: JsonValue = list_response.json().await.expect("Failed to read json");
+    assert_eq!(todos["items"][0]["name"], "12:groceries");
+
+    let get_response = test_app.get_todo(&todo_id.to_string()).await;
+    assert_response(&get_response, StatusCode::OK);
+    let payload = serde_json::json!({"name": "groceries", "visibility": "private"});
+    let update_response = test_app.update_todo(&todo_id.to_string(), &payload).await;
+    assert_response(&update_response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn todo_is_addressable_by_id_after_rename() {
+    let test_app = spawn_app().await;
+    let payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    let create_response = test_app.post_todo(&payload).await;
+    assert_response(&create_response, StatusCode::OK);
+
+    let get_response = test_app.get_todo("banana").await;
+    assert_response(&get_response, StatusCode::OK);
+    let todo: JsonValue = get_response.json().await.expect("Failed to read json");
+    let todo_id = todo["todo_id"].as_str().unwrap().to_string();
+
+    let payload = serde_json::json!({"name": "banana2", "visibility": "private"});
+    let update_response = test_app.update_todo(&todo_id, &payload).await;
+    assert_response(&update_response, StatusCode::OK);
+
+    let get_response = test_app.get_todo(&todo_id).await;
+    assert_response(&get_response, StatusCode::OK);
+    let todo: JsonValue = get_response.json().await.expect("Failed to read json");
+    assert_eq!(todo["name"], "banana2");
+
+    let item_payload = serde_json::json!({"title": "todo_item"});
+    let create_item_response = test_app.post_todo_item(&todo_id, &item_payload).await;
+    assert_response(&create_item_response, StatusCode::OK);
+    let item: CreateResponse = create_item_response.json().await.unwrap();
+    let get_item_response = test_app.get_todo_item(&todo_id, &item.todo_item_id).await;
+    assert_response(&get_item_response, StatusCode::OK);
+
+    let template_payload = serde_json::json!({
+        "title": "Daily task",
+        "recurrence_interval": {"days": 1},
+        "start_date": "2025-01-01",
+        "end_date": null
+    });
+    let create_template_response = test_app
+        .post_recurring_template(&todo_id, &template_payload)
+        .await;
+    assert_response(&create_template_response, StatusCode::OK);
+    let list_templates_response = test_app.list_recurring_templates(&todo_id).await;
+    assert_response(&list_templates_response, StatusCode::OK);
+
+    let delete_response = test_app.delete_todo(&todo_id).await;
+    assert_response(&delete_response, StatusCode::OK);
+    let get_response = test_app.get_todo(&todo_id).await;
+    assert_response(&get_response, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn get_private_todo_of_another_user_by_id_fails() {
+    let test_app = spawn_app().await;
+    let second_user_id = test_app.create_user("second@example.com").await;
+    let todo_id = uuid::Uuid::new_v4();
+
+    sqlx::query(
+        r#"INSERT INTO todo (todo_id, name, user_id, visibility) VALUES ($1, $2, $3, $4::todo_visibility)"#
+    )
+    .bind(todo_id)
+    .bind("private_todo")
+    .bind(second_user_id)
+    .bind("private")
+    .execute(&test_app.db_pool)
+    .await
+    .expect("Failed to create private todo for second user");
+
+    let get_response = test_app.get_todo(&todo_id.to_string()).await;
+    assert_response(&get_response, StatusCode::NOT_FOUND);
+}
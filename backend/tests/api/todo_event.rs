@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
+
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct ServerSentEvent {
+    id: String,
+    event: String,
+    data: JsonValue,
+}
+
+/// Parses the server-sent events of a streaming response, skipping the
+/// keep-alive comments.
+struct EventReader {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventReader {
+    async fn open(app: &TestApp, todo_name: &str, last_event_id: Option<&str>) -> Self {
+        let headers: Vec<(&str, &str)> = last_event_id
+            .map(|id| vec![("Last-Event-ID", id)])
+            .unwrap_or_default();
+        let response = app
+            .send_with_headers(
+                Method::GET,
+                &format!("/todo/{}/events", todo_name),
+                &headers,
+                None,
+            )
+            .await;
+        Self::new(response)
+    }
+
+    fn new(response: reqwest::Response) -> Self {
+        assert_response(&response, StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        Self {
+            response,
+            buffer: String::new(),
+        }
+    }
+
+    /// Waits for the server to end the stream, skipping what it still sends.
+    async fn wait_for_end(mut self) {
+        while tokio::time::timeout(EVENT_TIMEOUT, self.response.chunk())
+            .await
+            .expect("Timed out waiting for the event stream to end")
+            .expect("Failed to read the event stream")
+            .is_some()
+        {}
+    }
+
+    async fn next(&mut self) -> ServerSentEvent {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                let (mut id, mut event, mut data) = (None, None, None);
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("id:") {
+                        id = Some(value.trim().to_string());
+                    } else if let Some(value) = line.strip_prefix("event:") {
+                        event = Some(value.trim().to_string());
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data = Some(serde_json::from_str(value.trim()).unwrap());
+                    }
+                }
+                if let (Some(id), Some(event), Some(data)) = (id, event, data) {
+                    return ServerSentEvent { id, event, data };
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(EVENT_TIMEOUT, self.response.chunk())
+                .await
+                .expect("Timed out waiting for an event")
+                .expect("Failed to read the event stream")
+                .expect("The event stream ended");
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+#[tokio::test]
+async fn todo_events_stream_item_changes() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    // Only what happens after connecting is streamed
+    app.create_item("banana", "before").await;
+    let mut events = EventReader::open(&app, "banana", None).await;
+
+    let item_id = app.create_item("banana", "item").await;
+    app.update_todo_item(
+        "banana",
+        &item_id,
+        &json!({"title": "renamed", "due_date": "2020-10-02"}),
+    )
+    .await;
+    app.complete_todo_item("banana", &item_id).await;
+    app.delete_todo_item("banana", &item_id).await;
+
+    let mut last_id = 0;
+    for expected in [
+        "item.created",
+        "item.updated",
+        "item.completed",
+        "item.deleted",
+    ] {
+        let event = events.next().await;
+        assert_eq!(event.event, expected);
+        assert_eq!(event.data["entity_id"], item_id.as_str());
+        let id: i64 = event.id.parse().unwrap();
+        assert_eq!(event.data["event_id"], id);
+        assert!(id > last_id);
+        last_id = id;
+    }
+}
+
+#[tokio::test]
+async fn todo_events_stream_template_changes() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let mut events = EventReader::open(&app, "banana", None).await;
+
+    let response = app
+        .post_recurring_template(
+            "banana",
+            &json!({
+                "title": "Daily task",
+                "recurrence_interval": {"days": 1},
+                "start_date": "2020-01-01",
+                "end_date": null
+            }),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let template: JsonValue = response.json().await.unwrap();
+    let template_id = template["template_id"].as_str().unwrap();
+    app.delete_recurring_template("banana", template_id).await;
+
+    let event = events.next().await;
+    assert_eq!(event.event, "template.created");
+    assert_eq!(event.data["entity"], "template");
+    assert_eq!(event.data["entity_id"], template_id);
+    let mut seen = vec![];
+    loop {
+        let event = events.next().await;
+        if event.event == "template.deleted" {
+            assert_eq!(event.data["entity_id"], template_id);
+            break;
+        }
+        seen.push(event.event);
+    }
+    // Creating the template generated its first item
+    assert!(seen.contains(&"item.created".to_string()), "{:?}", seen);
+}
+
+#[tokio::test]
+async fn todo_events_resume_after_last_event_id() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let mut events = EventReader::open(&app, "banana", None).await;
+    let first = app.create_item("banana", "first").await;
+    let first_event = events.next().await;
+    assert_eq!(first_event.data["entity_id"], first.as_str());
+    drop(events);
+
+    // Written while the client is disconnected
+    let second = app.create_item("banana", "second").await;
+    let third = app.create_item("banana", "third").await;
+
+    let mut events = EventReader::open(&app, "banana", Some(&first_event.id)).await;
+    assert_eq!(events.next().await.data["entity_id"], second.as_str());
+    assert_eq!(events.next().await.data["entity_id"], third.as_str());
+    let fourth = app.create_item("banana", "fourth").await;
+    assert_eq!(events.next().await.data["entity_id"], fourth.as_str());
+}
+
+#[tokio::test]
+async fn todo_events_restart_from_the_latest_after_a_purged_event() {
+    let app = spawn_app().await;
+    let retention = Duration::from_secs(7 * 24 * 60 * 60);
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let mut events = EventReader::open(&app, "banana", None).await;
+    app.create_item("banana", "first").await;
+    let first_event = events.next().await;
+    drop(events);
+    app.create_item("banana", "second").await;
+
+    // Only the event the client last received is older than the retention
+    sqlx::query(
+        "UPDATE todo_event SET create_time = create_time - interval '8 days' WHERE event_id = $1",
+    )
+    .bind(first_event.id.parse::<i64>().unwrap())
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.purge_todo_events(retention).await.unwrap();
+
+    // Events may be missing since, the client is told to read the todo
+    // again and only gets what happens from now on
+    let mut events = EventReader::open(&app, "banana", Some(&first_event.id)).await;
+    let reset = events.next().await;
+    assert_eq!(reset.event, "reset");
+    let second_event_id: i64 = reset.id.parse().unwrap();
+    assert!(second_event_id > first_event.id.parse().unwrap());
+    assert_eq!(reset.data["event_id"], second_event_id);
+    let third = app.create_item("banana", "third").await;
+    assert_eq!(events.next().await.data["entity_id"], third.as_str());
+    drop(events);
+
+    // Resuming from the reset needs no other
+    let mut events = EventReader::open(&app, "banana", Some(&reset.id)).await;
+    let fourth = app.create_item("banana", "fourth").await;
+    assert_eq!(events.next().await.data["entity_id"], third.as_str());
+    assert_eq!(events.next().await.data["entity_id"], fourth.as_str());
+}
+
+#[tokio::test]
+async fn todo_events_end_when_the_member_is_removed() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let member = Member::create(&app, "member@example.com").await;
+    let response = app
+        .add_todo_member(
+            "banana",
+            &json!({"email": "member@example.com", "role": "viewer"}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let events_path = format!("/todo/{}:banana/events", app.test_user_id);
+    let mut events = EventReader::new(member.send(Method::GET, &events_path, None).await);
+    let item_id = app.create_item("banana", "peel").await;
+    assert_eq!(events.next().await.data["entity_id"], item_id.as_str());
+
+    let response = app.remove_todo_member("banana", member.user_id).await;
+    assert_response(&response, StatusCode::OK);
+    app.create_item("banana", "slice").await;
+    events.wait_for_end().await;
+}
+
+#[tokio::test]
+async fn todo_events_only_include_the_todo() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    app.post_todo(&json!({"name": "apple", "visibility": "private"}))
+        .await;
+    let mut events = EventReader::open(&app, "banana", None).await;
+
+    app.create_item("apple", "other").await;
+    let item_id = app.create_item("banana", "mine").await;
+
+    let event = events.next().await;
+    assert_eq!(event.data["entity_id"], item_id.as_str());
+}
+
+#[tokio::test]
+async fn todo_events_failure() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+
+    let response = app
+        .send_with_headers(Method::GET, "/todo/missing/events", &[], None)
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+
+    let response = app
+        .send_with_headers(
+            Method::GET,
+            "/todo/banana/events",
+            &[("Last-Event-ID", "banana")],
+            None,
+        )
+        .await;
+    let problem = assert_problem(response, StatusCode::BAD_REQUEST, "validation_failed").await;
+    assert_eq!(problem["errors"][0]["field"], "Last-Event-ID");
+}
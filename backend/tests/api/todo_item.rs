@@ -1,9 +1,10 @@
-use reqwest::StatusCode;
+use checklist::clock::Clock;
+use reqwest::{Method, StatusCode};
 use serde::Deserialize;
 use time::Date;
 use uuid::Uuid;
 
-use crate::helpers::{assert_response, spawn_app};
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
 
 #[derive(Deserialize)]
 struct CreateResponse {
@@ -243,6 +244,243 @@ async fn list_todo_items_only_show_incomplete() {
     assert_eq!(response.items.len(), 49);
 }
 
+#[tokio::test]
+async fn list_todo_items_paginates() {
+    let test_app = spawn_app().await;
+
+    let todo_payload: serde_json::Value =
+        serde_json::from_str(r#"{"name": "banana", "visibility": "private"}"#).unwrap();
+    let create_todo_response = test_app.post_todo(&todo_payload).await;
+    assert_response(&create_todo_response, StatusCode::OK);
+
+    // Items share a due date, so the page boundary falls between equal sort keys.
+    for i in 0..7 {
+        let payload = serde_json::json!({"title": format!("banana{i}"), "due_date": "2030-01-01"});
+        let create_response = test_app.post_todo_item("banana", &payload).await;
+        assert_response(&create_response, StatusCode::OK);
+    }
+
+    let first = test_app
+        .list_todo_items_page("banana", &[("limit", "4")])
+        .await;
+    assert_response(&first, StatusCode::OK);
+    let first: serde_json::Value = first.json().await.expect("Failed to read json");
+    assert_eq!(first["items"].as_array().unwrap().len(), 4);
+    let cursor = first["next_cursor"].as_str().expect("Missing next_cursor");
+
+    let second = test_app
+        .list_todo_items_page("banana", &[("limit", "4"), ("cursor", cursor)])
+        .await;
+    assert_response(&second, StatusCode::OK);
+    let second: serde_json::Value = second.json().await.expect("Failed to read json");
+    assert_eq!(second["items"].as_array().unwrap().len(), 3);
+    assert!(second["next_cursor"].is_null());
+
+    let titles: Vec<&str> = first["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .chain(second["items"].as_array().unwrap())
+        .map(|i| i["title"].as_str().unwrap())
+        .collect();
+    let expected: Vec<String> = (0..7).map(|i| format!("banana{i}")).collect();
+    assert_eq!(titles, expected);
+}
+
+/// Creates `titles` as items of the todo "banana" and completes the ones
+/// listed in `completed` at the given times.
+async fn create_items_with_history(
+    test_app: &TestApp,
+    titles: &[&str],
+    completed: &[(&str, &str)],
+) {
+    let todo_payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    let create_todo_response = test_app.post_todo(&todo_payload).await;
+    assert_response(&create_todo_response, StatusCode::OK);
+
+    for title in titles {
+        let payload = serde_json::json!({"title": title, "due_date": "2030-01-01"});
+        let create_response = test_app.post_todo_item("banana", &payload).await;
+        assert_response(&create_response, StatusCode::OK);
+        let item: CreateResponse = create_response.json().await.unwrap();
+
+        if let Some((_, complete_time)) = completed.iter().find(|(t, _)| t == title) {
+            let complete_response = test_app
+                .complete_todo_item("banana", &item.todo_item_id)
+                .await;
+            assert_response(&complete_response, StatusCode::OK);
+            sqlx::query(
+                "UPDATE todo_item SET complete_time = $1::timestamptz WHERE todo_item_id = $2",
+            )
+            .bind(complete_time)
+            .bind(Uuid::parse_str(&item.todo_item_id).unwrap())
+            .execute(&test_app.db_pool)
+            .await
+            .expect("Failed to set complete_time");
+        }
+    }
+}
+
+fn titles(value: &serde_json::Value) -> Vec<&str> {
+    value["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["title"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn list_todo_items_filters_by_status() {
+    let test_app = spawn_app().await;
+    create_items_with_history(
+        &test_app,
+        &["a", "b", "c", "d", "e"],
+        &[("b", "2025-01-02T10:00:00Z"), ("d", "2025-01-03T10:00:00Z")],
+    )
+    .await;
+
+    let test_cases = vec![
+        (vec![], vec!["a", "c", "e"]),
+        (vec![("status", "open")], vec!["a", "c", "e"]),
+        (vec![("status", "completed")], vec!["d", "b"]),
+        (vec![("status", "all")], vec!["a", "c", "e", "d", "b"]),
+    ];
+    for (query, expected) in test_cases {
+        let list_response = test_app.list_todo_items_page("banana", &query).await;
+        assert_response(&list_response, StatusCode::OK);
+        let value: serde_json::Value = list_response.json().await.unwrap();
+        assert_eq!(titles(&value), expected, "{query:?}");
+    }
+}
+
+#[tokio::test]
+async fn list_completed_todo_items_in_range() {
+    let test_app = spawn_app().await;
+    create_items_with_history(
+        &test_app,
+        &["a", "b", "c", "d"],
+        &[
+            ("a", "2025-01-01T10:00:00Z"),
+            ("b", "2025-01-02T10:00:00Z"),
+            ("c", "2025-01-03T10:00:00Z"),
+            ("d", "2025-01-04T10:00:00Z"),
+        ],
+    )
+    .await;
+
+    let test_cases = vec![
+        (
+            vec![("completed_since", "2025-01-02T10:00:00Z")],
+            vec!["d", "c", "b"],
+        ),
+        (
+            vec![("completed_before", "2025-01-02T10:00:00Z")],
+            vec!["a"],
+        ),
+        (
+            vec![
+                ("completed_since", "2025-01-02T00:00:00Z"),
+                ("completed_before", "2025-01-04T00:00:00Z"),
+            ],
+            vec!["c", "b"],
+        ),
+    ];
+    for (mut query, expected) in test_cases {
+        query.push(("status", "completed"));
+        let list_response = test_app.list_todo_items_page("banana", &query).await;
+        assert_response(&list_response, StatusCode::OK);
+        let value: serde_json::Value = list_response.json().await.unwrap();
+        assert_eq!(titles(&value), expected, "{query:?}");
+    }
+
+    let list_response = test_app
+        .list_todo_items_page("banana", &[("status", "completed")])
+        .await;
+    let value: serde_json::Value = list_response.json().await.unwrap();
+    test_app
+        .golden
+        .check_diff_json("list_completed_todo_item", &value);
+}
+
+#[tokio::test]
+async fn list_all_todo_items_paginates_from_open_to_completed() {
+    let test_app = spawn_app().await;
+    create_items_with_history(
+        &test_app,
+        &["a", "b", "c", "d", "e"],
+        &[("b", "2025-01-02T10:00:00Z"), ("d", "2025-01-03T10:00:00Z")],
+    )
+    .await;
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("status", "all"), ("limit", "2")];
+        if let Some(cursor) = cursor.as_deref() {
+            query.push(("cursor", cursor));
+        }
+        let list_response = test_app.list_todo_items_page("banana", &query).await;
+        assert_response(&list_response, StatusCode::OK);
+        let value: serde_json::Value = list_response.json().await.unwrap();
+        seen.extend(titles(&value).into_iter().map(String::from));
+        match value["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    assert_eq!(seen, vec!["a", "c", "e", "d", "b"]);
+}
+
+#[tokio::test]
+async fn list_todo_items_rejects_invalid_filters() {
+    let test_app = spawn_app().await;
+    create_items_with_history(
+        &test_app,
+        &["a", "b", "c"],
+        &[("b", "2025-01-02T10:00:00Z")],
+    )
+    .await;
+
+    let open_page = test_app
+        .list_todo_items_page("banana", &[("limit", "1")])
+        .await;
+    let open_page: serde_json::Value = open_page.json().await.unwrap();
+    let open_cursor = open_page["next_cursor"].as_str().unwrap().to_string();
+
+    let test_cases = vec![
+        ("unknown status", vec![("status", "done")]),
+        (
+            "range with open status",
+            vec![("completed_since", "2025-01-01T00:00:00Z")],
+        ),
+        (
+            "inverted range",
+            vec![
+                ("status", "completed"),
+                ("completed_since", "2025-01-02T00:00:00Z"),
+                ("completed_before", "2025-01-01T00:00:00Z"),
+            ],
+        ),
+        (
+            "malformed timestamp",
+            vec![("status", "completed"), ("completed_since", "yesterday")],
+        ),
+        (
+            "open cursor on completed listing",
+            vec![("status", "completed"), ("cursor", open_cursor.as_str())],
+        ),
+    ];
+    for (description, query) in test_cases {
+        let list_response = test_app.list_todo_items_page("banana", &query).await;
+        assert_eq!(
+            list_response.status(),
+            StatusCode::BAD_REQUEST,
+            "{description}"
+        );
+    }
+}
+
 #[tokio::test]
 async fn list_todo_items_fails() {
     let test_app = spawn_app().await;
@@ -405,7 +643,7 @@ async fn update_todo_item_fails_if_already_complete() {
     let update_response = test_app
         .update_todo_item("banana", &response.todo_item_id, &valid_payload)
         .await;
-    assert_response(&update_response, StatusCode::BAD_REQUEST);
+    assert_response(&update_response, StatusCode::CONFLICT);
 }
 
 #[tokio::test]
@@ -446,6 +684,43 @@ async fn complete_todo_item_works() {
         .check_diff_json("complete_todo_item", &value);
 }
 
+#[tokio::test]
+async fn todo_item_times_follow_the_clock() {
+    let test_app = spawn_app().await;
+
+    let todo_payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    let create_todo_response = test_app.post_todo(&todo_payload).await;
+    assert_response(&create_todo_response, StatusCode::OK);
+
+    test_app.clock.advance(time::Duration::weeks(3));
+    let now = test_app.clock.now();
+
+    let create_todo_item_response = test_app
+        .post_todo_item("banana", &serde_json::json!({"title": "todo_item"}))
+        .await;
+    assert_response(&create_todo_item_response, StatusCode::OK);
+    let response: CreateResponse = create_todo_item_response
+        .json()
+        .await
+        .expect("Error parsing json");
+    assert_eq!(response.due_date, now.date());
+
+    let complete_todo_item_response = test_app
+        .complete_todo_item("banana", &response.todo_item_id)
+        .await;
+    assert_response(&complete_todo_item_response, StatusCode::OK);
+    let value: serde_json::Value = complete_todo_item_response
+        .json()
+        .await
+        .expect("Failed to read json");
+    let complete_time = time::OffsetDateTime::parse(
+        value["complete_time"].as_str().unwrap(),
+        &time::format_description::well_known::Rfc3339,
+    )
+    .unwrap();
+    assert_eq!(complete_time, now);
+}
+
 #[tokio::test]
 async fn complete_todo_item_fails() {
     let test_app = spawn_app().await;
@@ -526,7 +801,84 @@ async fn complete_todo_item_fails_if_already_complete() {
     let complete_todo_item_response = test_app
         .complete_todo_item("banana", &response.todo_item_id)
         .await;
-    assert_response(&complete_todo_item_response, StatusCode::BAD_REQUEST);
+    assert_response(&complete_todo_item_response, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn reopen_todo_item_works() {
+    let test_app = spawn_app().await;
+
+    let todo_payload: serde_json::Value =
+        serde_json::from_str(r#"{"name": "banana", "visibility": "private"}"#).unwrap();
+    let create_todo_response = test_app.post_todo(&todo_payload).await;
+    assert_response(&create_todo_response, StatusCode::OK);
+
+    let payload = serde_json::json!({"title": "todo_item"});
+    let create_response = test_app.post_todo_item("banana", &payload).await;
+    assert_response(&create_response, StatusCode::OK);
+    let item: CreateResponse = create_response.json().await.unwrap();
+
+    let complete_response = test_app
+        .complete_todo_item("banana", &item.todo_item_id)
+        .await;
+    assert_response(&complete_response, StatusCode::OK);
+
+    let reopen_response = test_app
+        .reopen_todo_item("banana", &item.todo_item_id)
+        .await;
+    assert_response(&reopen_response, StatusCode::OK);
+    let value: serde_json::Value = reopen_response.json().await.unwrap();
+    test_app.golden.check_diff_json("reopen_todo_item", &value);
+    assert_eq!(value["is_complete"], false);
+    assert!(value["complete_time"].is_null());
+
+    // A reopened item can be edited again
+    let update_payload = serde_json::json!({"title": "renamed", "due_date": item.due_date});
+    let update_response = test_app
+        .update_todo_item("banana", &item.todo_item_id, &update_payload)
+        .await;
+    assert_response(&update_response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reopen_todo_item_fails() {
+    let test_app = spawn_app().await;
+
+    let todo_payload: serde_json::Value =
+        serde_json::from_str(r#"{"name": "banana", "visibility": "private"}"#).unwrap();
+    let create_todo_response = test_app.post_todo(&todo_payload).await;
+    assert_response(&create_todo_response, StatusCode::OK);
+
+    let payload = serde_json::json!({"title": "todo_item"});
+    let create_response = test_app.post_todo_item("banana", &payload).await;
+    assert_response(&create_response, StatusCode::OK);
+    let item: CreateResponse = create_response.json().await.unwrap();
+    let missing_item = Uuid::new_v4().to_string();
+
+    let test_cases = vec![
+        (
+            "item is not complete",
+            "banana",
+            item.todo_item_id.as_str(),
+            StatusCode::CONFLICT,
+        ),
+        (
+            "item does not exist",
+            "banana",
+            missing_item.as_str(),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "todo does not exist",
+            "NOT_EXISTS",
+            item.todo_item_id.as_str(),
+            StatusCode::NOT_FOUND,
+        ),
+    ];
+    for (description, todo_name, todo_item_id, status) in test_cases {
+        let reopen_response = test_app.reopen_todo_item(todo_name, todo_item_id).await;
+        assert_eq!(reopen_response.status(), status, "{description}");
+    }
 }
 
 #[tokio::test]
@@ -610,3 +962,135 @@ async fn delete_todo_item_fails() {
         assert_eq!(delete_response.status(), test_case.3, "{}", test_case.0);
     }
 }
+
+#[tokio::test]
+async fn batch_todo_items_works() {
+    let test_app = spawn_app().await;
+
+    let todo_payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    assert_response(&test_app.post_todo(&todo_payload).await, StatusCode::OK);
+    let mut ids = vec![];
+    for title in ["first", "second"] {
+        let response = test_app
+            .post_todo_item(
+                "banana",
+                &serde_json::json!({"title": title, "due_date": "2025-01-01"}),
+            )
+            .await;
+        assert_response(&response, StatusCode::OK);
+        let response: CreateResponse = response.json().await.expect("Error parsing json");
+        ids.push(response.todo_item_id);
+    }
+
+    let response = test_app
+        .batch_todo_items(
+            "banana",
+            &serde_json::json!({"operations": [
+                {"op": "create", "title": "third", "due_date": "2025-01-03"},
+                {"op": "update", "todo_item_id": ids[0], "title": "first updated", "due_date": "2025-01-02"},
+                {"op": "complete", "todo_item_id": ids[1]},
+                {"op": "delete", "todo_item_id": ids[0]},
+            ]}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let value: serde_json::Value = response.json().await.expect("Failed to read json");
+    test_app.golden.check_diff_json("batch_todo_items", &value);
+
+    let response = test_app
+        .list_todo_items_page("banana", &[("status", "all")])
+        .await;
+    let list: ListResponse = response.json().await.expect("Error parsing json");
+    let titles: Vec<_> = list
+        .items
+        .iter()
+        .map(|i| i["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, vec!["third", "second"]);
+}
+
+#[tokio::test]
+async fn batch_todo_items_is_all_or_nothing() {
+    let test_app = spawn_app().await;
+
+    let todo_payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    assert_response(&test_app.post_todo(&todo_payload).await, StatusCode::OK);
+
+    let response = test_app
+        .batch_todo_items(
+            "banana",
+            &serde_json::json!({"operations": [
+                {"op": "create", "title": "created", "due_date": "2025-01-01"},
+                {"op": "complete", "todo_item_id": Uuid::new_v4()},
+            ]}),
+        )
+        .await;
+    let problem = assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let detail = problem["detail"].as_str().unwrap();
+    assert!(detail.starts_with("operation 1:"), "{}", detail);
+
+    let response = test_app
+        .list_todo_items_page("banana", &[("status", "all")])
+        .await;
+    let list: ListResponse = response.json().await.expect("Error parsing json");
+    assert!(list.items.is_empty());
+}
+
+#[tokio::test]
+async fn batch_todo_items_tells_which_operation_is_forbidden() {
+    let test_app = spawn_app().await;
+    let todo_payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    assert_response(&test_app.post_todo(&todo_payload).await, StatusCode::OK);
+    let viewer = Member::create(&test_app, "viewer@example.com").await;
+    let response = test_app
+        .add_todo_member(
+            "banana",
+            &serde_json::json!({"email": "viewer@example.com", "role": "viewer"}),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    let response = viewer
+        .send(
+            Method::POST,
+            &format!("/todo/{}:banana/item:batch", test_app.test_user_id),
+            Some(&serde_json::json!({"operations": [
+                {"op": "create", "title": "created", "due_date": "2025-01-01"},
+            ]})),
+        )
+        .await;
+
+    let problem = assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+    let detail = problem["detail"].as_str().unwrap();
+    assert!(detail.starts_with("operation 0:"), "{}", detail);
+}
+
+#[tokio::test]
+async fn batch_todo_items_failure() {
+    let test_app = spawn_app().await;
+
+    let todo_payload = serde_json::json!({"name": "banana", "visibility": "private"});
+    assert_response(&test_app.post_todo(&todo_payload).await, StatusCode::OK);
+
+    let unknown_op =
+        serde_json::json!({"operations": [{"op": "archive", "todo_item_id": Uuid::new_v4()}]});
+    let response = test_app.batch_todo_items("banana", &unknown_op).await;
+    assert_response(&response, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = test_app
+        .batch_todo_items(
+            "nonexistent",
+            &serde_json::json!({"operations": [{"op": "create", "title": "x"}]}),
+        )
+        .await;
+    assert_response(&response, StatusCode::NOT_FOUND);
+
+    // The request body is capped like every other endpoint
+    let operations: Vec<_> = (0..20_000)
+        .map(|i| serde_json::json!({"op": "create", "title": format!("item {}", i), "due_date": "2025-01-01"}))
+        .collect();
+    let response = test_app
+        .batch_todo_items("banana", &serde_json::json!({"operations": operations}))
+        .await;
+    assert_response(&response, StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -0,0 +1,317 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
+
+async fn share(app: &TestApp, todo_name: &str, email: &str, role: &str) {
+    let response = app
+        .add_todo_member(todo_name, &json!({"email": email, "role": role}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn roles_limit_what_members_can_do() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    let viewer = Member::create(&app, "viewer@example.com").await;
+    let editor = Member::create(&app, "editor@example.com").await;
+    share(&app, "banana", "viewer@example.com", "viewer").await;
+    share(&app, "banana", "editor@example.com", "editor").await;
+
+    let response = app.list_todo_members("banana").await;
+    assert_response(&response, StatusCode::OK);
+    let members: JsonValue = response.json().await.unwrap();
+    app.golden.check_diff_json("list_todo_members", &members);
+
+    let todo_path = format!("/todo/{}:banana", app.test_user_id);
+    let item_path = format!("{}/item", todo_path);
+    let new_item = json!({"title": "slice", "due_date": "2020-10-01"});
+    let rename = json!({"name": "renamed", "visibility": "private"});
+
+    // Viewers read
+    let response = viewer.send(Method::GET, &todo_path, None).await;
+    assert_response(&response, StatusCode::OK);
+    let todo: JsonValue = response.json().await.unwrap();
+    assert_eq!(todo["role"], "viewer");
+    let response = viewer.send(Method::GET, &item_path, None).await;
+    assert_response(&response, StatusCode::OK);
+    let response = viewer.send(Method::POST, &item_path, Some(&new_item)).await;
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+    let response = viewer
+        .send(Method::DELETE, &format!("{}/{}", item_path, item_id), None)
+        .await;
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+
+    // Editors change items and templates
+    let response = editor.send(Method::POST, &item_path, Some(&new_item)).await;
+    assert_response(&response, StatusCode::OK);
+    let response = editor
+        .send(
+            Method::POST,
+            &format!("{}/{}/complete", item_path, item_id),
+            None,
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = editor
+        .send(
+            Method::POST,
+            &format!("{}/recurring", todo_path),
+            Some(&json!({
+                "title": "Daily task",
+                "recurrence_interval": {"days": 1},
+                "start_date": "2020-01-01",
+                "end_date": null
+            })),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    // Only owners change the todo and its members
+    for member in [&viewer, &editor] {
+        let response = member.send(Method::PUT, &todo_path, Some(&rename)).await;
+        assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+        let response = member.send(Method::DELETE, &todo_path, None).await;
+        assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+        let response = member
+            .send(
+                Method::POST,
+                &format!("{}/member", todo_path),
+                Some(&json!({"email": "test@example.com", "role": "viewer"})),
+            )
+            .await;
+        assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+        let response = member
+            .send(Method::GET, &format!("{}/member", todo_path), None)
+            .await;
+        assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+    }
+}
+
+#[tokio::test]
+async fn co_owners_manage_the_todo() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let owner = Member::create(&app, "owner@example.com").await;
+    Member::create(&app, "viewer@example.com").await;
+    share(&app, "banana", "owner@example.com", "owner").await;
+
+    let todo_path = format!("/todo/{}:banana", app.test_user_id);
+    let response = owner
+        .send(
+            Method::POST,
+            &format!("{}/member", todo_path),
+            Some(&json!({"email": "viewer@example.com", "role": "viewer"})),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    // The creator always owns the todo
+    let response = owner
+        .send(
+            Method::DELETE,
+            &format!("{}/member/{}", todo_path, app.test_user_id),
+            None,
+        )
+        .await;
+    assert_problem(response, StatusCode::CONFLICT, "conflict").await;
+
+    let response = owner
+        .send(
+            Method::PUT,
+            &todo_path,
+            Some(&json!({"name": "renamed", "visibility": "private"})),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = owner
+        .send(
+            Method::GET,
+            &format!("/todo/{}:renamed", app.test_user_id),
+            None,
+        )
+        .await;
+    let todo: JsonValue = response.json().await.unwrap();
+    assert_eq!(todo["owner_id"], app.test_user_id);
+    assert_eq!(todo["role"], "owner");
+
+    // Deleting it moves it to the creator's trash
+    let response = owner
+        .send(
+            Method::DELETE,
+            &format!("/todo/{}:renamed", app.test_user_id),
+            None,
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let trash: JsonValue = app.list_trash(&[]).await.json().await.unwrap();
+    assert_eq!(trash["items"][0]["title"], "renamed");
+}
+
+#[tokio::test]
+async fn roles_can_be_changed_and_revoked() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let member = Member::create(&app, "member@example.com").await;
+    share(&app, "banana", "member@example.com", "viewer").await;
+
+    let todo_path = format!("/todo/{}:banana", app.test_user_id);
+    let item_path = format!("{}/item", todo_path);
+    let new_item = json!({"title": "slice", "due_date": "2020-10-01"});
+    let response = member.send(Method::POST, &item_path, Some(&new_item)).await;
+    assert_problem(response, StatusCode::FORBIDDEN, "forbidden").await;
+
+    let response = app
+        .update_todo_member("banana", member.user_id, &json!({"role": "editor"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let updated: JsonValue = response.json().await.unwrap();
+    assert_eq!(updated["role"], "editor");
+    assert_eq!(updated["email"], "member@example.com");
+    let response = member.send(Method::POST, &item_path, Some(&new_item)).await;
+    assert_response(&response, StatusCode::OK);
+
+    let response = app.remove_todo_member("banana", member.user_id).await;
+    assert_response(&response, StatusCode::OK);
+    let response = member.send(Method::GET, &todo_path, None).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
+
+#[tokio::test]
+async fn members_can_leave_a_todo() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let member = Member::create(&app, "member@example.com").await;
+    share(&app, "banana", "member@example.com", "viewer").await;
+
+    // Shared todos are listed and resolve by their bare name
+    let response = member.send(Method::GET, "/todo", None).await;
+    let todos: JsonValue = response.json().await.unwrap();
+    assert_eq!(todos["items"][0]["name"], "banana");
+    assert_eq!(todos["items"][0]["role"], "viewer");
+    let response = member.send(Method::GET, "/todo/banana", None).await;
+    assert_response(&response, StatusCode::OK);
+
+    let response = member
+        .send(
+            Method::DELETE,
+            &format!("/todo/banana/member/{}", member.user_id),
+            None,
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = member.send(Method::GET, "/todo/banana", None).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let members: JsonValue = app.list_todo_members("banana").await.json().await.unwrap();
+    assert_eq!(members["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn public_todos_are_editable_by_other_users() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "public"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    let other = Member::create(&app, "other@example.com").await;
+
+    let todo_path = format!("/todo/{}:banana", app.test_user_id);
+    let response = other.send(Method::GET, &todo_path, None).await;
+    assert_response(&response, StatusCode::OK);
+    let todo: JsonValue = response.json().await.unwrap();
+    assert_eq!(todo["role"], "editor");
+    let new_item = json!({"title": "slice", "due_date": "2020-10-01"});
+    let response = other
+        .send(
+            Method::POST,
+            &format!("{}/item", todo_path),
+            Some(&new_item),
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let response = other
+        .send(
+            Method::POST,
+            &format!("{}/item/{}/complete", todo_path, item_id),
+            None,
+        )
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    // The todo itself stays with its owners, as if it wasn't there
+    let rename = json!({"name": "renamed", "visibility": "public"});
+    let response = other.send(Method::PUT, &todo_path, Some(&rename)).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let response = other.send(Method::DELETE, &todo_path, None).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let response = other
+        .send(Method::GET, &format!("{}/member", todo_path), None)
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+
+    // A member role goes beyond the public one
+    share(&app, "banana", "other@example.com", "owner").await;
+    let response = other.send(Method::PUT, &todo_path, Some(&rename)).await;
+    assert_response(&response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn todo_member_failure() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let member = Member::create(&app, "member@example.com").await;
+    share(&app, "banana", "member@example.com", "viewer").await;
+
+    let test_cases = [
+        (
+            json!({"email": "unknown@example.com", "role": "viewer"}),
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+        ),
+        (
+            json!({"email": "member@example.com", "role": "editor"}),
+            StatusCode::CONFLICT,
+            "already_exists",
+        ),
+        (
+            json!({"email": "test@example.com", "role": "editor"}),
+            StatusCode::CONFLICT,
+            "already_exists",
+        ),
+        (
+            json!({"email": " ", "role": "editor"}),
+            StatusCode::BAD_REQUEST,
+            "validation_failed",
+        ),
+    ];
+    for (payload, status, code) in test_cases {
+        let response = app.add_todo_member("banana", &payload).await;
+        assert_problem(response, status, code).await;
+    }
+    let response = app
+        .add_todo_member(
+            "banana",
+            &json!({"email": "member@example.com", "role": "admin"}),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = app
+        .update_todo_member("banana", app.test_user_id, &json!({"role": "viewer"}))
+        .await;
+    assert_problem(response, StatusCode::CONFLICT, "conflict").await;
+    let response = app
+        .update_todo_member("banana", member.user_id + 1, &json!({"role": "viewer"}))
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let response = app.remove_todo_member("banana", member.user_id + 1).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let response = app.list_todo_members("apple").await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
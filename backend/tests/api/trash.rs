@@ -0,0 +1,317 @@
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value as JsonValue};
+
+use crate::helpers::{assert_problem, assert_response, spawn_app, Member, TestApp};
+
+const RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+async fn list_trash(app: &TestApp) -> Vec<JsonValue> {
+    let response = app.list_trash(&[]).await;
+    assert_response(&response, StatusCode::OK);
+    let trash: JsonValue = response.json().await.unwrap();
+    trash["items"].as_array().unwrap().clone()
+}
+
+async fn todo_id(app: &TestApp, todo_name: &str) -> String {
+    let todo: JsonValue = app.get_todo(todo_name).await.json().await.unwrap();
+    todo["todo_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn deleted_todos_and_items_are_moved_to_the_trash() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    app.post_todo(&json!({"name": "apple", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    app.create_item("apple", "core").await;
+    let apple_id = todo_id(&app, "apple").await;
+
+    assert_response(
+        &app.delete_todo_item("banana", &item_id).await,
+        StatusCode::OK,
+    );
+    app.clock.advance(time::Duration::MINUTE);
+    assert_response(&app.delete_todo("apple").await, StatusCode::OK);
+
+    // Gone from every read
+    assert_response(
+        &app.get_todo_item("banana", &item_id).await,
+        StatusCode::NOT_FOUND,
+    );
+    let items: JsonValue = app.list_todo_items("banana").await.json().await.unwrap();
+    assert!(items["items"].as_array().unwrap().is_empty());
+    assert_response(&app.get_todo("apple").await, StatusCode::NOT_FOUND);
+    assert_response(&app.get_todo(&apple_id).await, StatusCode::NOT_FOUND);
+    let todos: JsonValue = app.list_todo().await.json().await.unwrap();
+    assert_eq!(todos["items"].as_array().unwrap().len(), 1);
+
+    // The items of the trashed todo are not listed on their own
+    let response = app.list_trash(&[]).await;
+    assert_response(&response, StatusCode::OK);
+    let trash: JsonValue = response.json().await.unwrap();
+    app.golden.check_diff_json("list_trash", &trash);
+    let entries = trash["items"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["entity"], "todo");
+    assert_eq!(entries[0]["id"], apple_id.as_str());
+    assert_eq!(entries[1]["entity"], "item");
+    assert_eq!(entries[1]["id"], item_id.as_str());
+    assert_eq!(entries[1]["title"], "peel");
+    let deleted_at = time::OffsetDateTime::parse(
+        entries[1]["deleted_at"].as_str().unwrap(),
+        &time::format_description::well_known::Rfc3339,
+    )
+    .unwrap();
+    let purge_time = time::OffsetDateTime::parse(
+        entries[1]["purge_time"].as_str().unwrap(),
+        &time::format_description::well_known::Rfc3339,
+    )
+    .unwrap();
+    assert_eq!(purge_time - deleted_at, RETENTION);
+}
+
+#[tokio::test]
+async fn restoring_a_todo_brings_back_its_items_and_templates() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    app.create_item("banana", "peel").await;
+    let trashed_item = app.create_item("banana", "trashed first").await;
+    app.post_recurring_template(
+        "banana",
+        &json!({
+            "title": "Daily task",
+            "recurrence_interval": {"days": 1},
+            "start_date": "2020-01-01",
+            "end_date": null
+        }),
+    )
+    .await;
+    let banana_id = todo_id(&app, "banana").await;
+    app.delete_todo_item("banana", &trashed_item).await;
+    app.delete_todo("banana").await;
+
+    let response = app.restore_from_trash(&banana_id).await;
+    assert_response(&response, StatusCode::OK);
+
+    assert_response(&app.get_todo("banana").await, StatusCode::OK);
+    let items: JsonValue = app.list_todo_items("banana").await.json().await.unwrap();
+    // The item trashed on its own stays in the trash
+    assert_eq!(items["items"].as_array().unwrap().len(), 2);
+    let templates: JsonValue = app
+        .list_recurring_templates("banana")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(templates["templates"].as_array().unwrap().len(), 1);
+    let trash = list_trash(&app).await;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0]["id"], trashed_item.as_str());
+
+    assert_response(&app.restore_from_trash(&trashed_item).await, StatusCode::OK);
+    assert_response(
+        &app.get_todo_item("banana", &trashed_item).await,
+        StatusCode::OK,
+    );
+    assert!(list_trash(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn editors_restore_the_items_of_shared_todos() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    let editor = Member::create(&app, "editor@example.com").await;
+    let viewer = Member::create(&app, "viewer@example.com").await;
+    for (email, role) in [
+        ("editor@example.com", "editor"),
+        ("viewer@example.com", "viewer"),
+    ] {
+        let response = app
+            .add_todo_member("banana", &json!({"email": email, "role": role}))
+            .await;
+        assert_response(&response, StatusCode::OK);
+    }
+    let item_path = format!("/todo/{}:banana/item/{}", app.test_user_id, item_id);
+    let response = app.get_todo_item("banana", &item_id).await;
+    let etag = response.headers()["etag"].clone();
+    let response = editor.send(Method::DELETE, &item_path, None).await;
+    assert_response(&response, StatusCode::OK);
+
+    let response = viewer.send(Method::GET, "/trash", None).await;
+    let trash: JsonValue = response.json().await.unwrap();
+    assert!(trash["items"].as_array().unwrap().is_empty());
+    let restore_path = format!("/trash/{}/restore", item_id);
+    let response = viewer.send(Method::POST, &restore_path, None).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+
+    let response = editor.send(Method::GET, "/trash", None).await;
+    let trash: JsonValue = response.json().await.unwrap();
+    assert_eq!(trash["items"][0]["id"], item_id.as_str());
+    let response = editor.send(Method::POST, &restore_path, None).await;
+    assert_response(&response, StatusCode::OK);
+    assert!(list_trash(&app).await.is_empty());
+    let response = app.get_todo_item("banana", &item_id).await;
+    assert_ne!(response.headers()["etag"], etag);
+
+    // Restoring the todo resends its items to sync without changing them
+    let response = app.get_todo_item("banana", &item_id).await;
+    let etag = response.headers()["etag"].clone();
+    let banana_id = todo_id(&app, "banana").await;
+    app.delete_todo("banana").await;
+    let response = app.restore_from_trash(&banana_id).await;
+    assert_response(&response, StatusCode::OK);
+    let response = app.get_todo_item("banana", &item_id).await;
+    assert_eq!(response.headers()["etag"], etag);
+}
+
+#[tokio::test]
+async fn trashed_todo_names_can_be_reused() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let banana_id = todo_id(&app, "banana").await;
+    app.delete_todo("banana").await;
+
+    let response = app
+        .post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+
+    // The name is taken, the trashed todo can't come back
+    let response = app.restore_from_trash(&banana_id).await;
+    assert_problem(response, StatusCode::CONFLICT, "already_exists").await;
+    assert_eq!(list_trash(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn trash_is_purged_after_the_retention() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    app.post_todo(&json!({"name": "apple", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    app.create_item("apple", "core").await;
+    let apple_id = todo_id(&app, "apple").await;
+    app.delete_todo_item("banana", &item_id).await;
+    app.delete_todo("apple").await;
+
+    app.purge_trash(RETENTION).await.unwrap();
+    assert_eq!(list_trash(&app).await.len(), 2);
+
+    app.clock
+        .advance(time::Duration::try_from(RETENTION).unwrap() + time::Duration::SECOND);
+    app.purge_trash(RETENTION).await.unwrap();
+    assert!(list_trash(&app).await.is_empty());
+    let remaining: i64 = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM todo_item"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+    let response = app.restore_from_trash(&apple_id).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
+
+#[tokio::test]
+async fn trash_pagination() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let mut expected = vec![];
+    for title in ["a", "b", "c"] {
+        let item_id = app.create_item("banana", title).await;
+        app.delete_todo_item("banana", &item_id).await;
+        expected.push(item_id);
+    }
+
+    let mut ids = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", "2")];
+        if let Some(cursor) = &cursor {
+            query.push(("cursor", cursor));
+        }
+        let response = app.list_trash(&query).await;
+        assert_response(&response, StatusCode::OK);
+        let page: JsonValue = response.json().await.unwrap();
+        for entry in page["items"].as_array().unwrap() {
+            ids.push(entry["id"].as_str().unwrap().to_string());
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    ids.sort();
+    expected.sort();
+    assert_eq!(ids, expected);
+}
+
+#[tokio::test]
+async fn sync_sees_the_trash() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+    let banana_id = todo_id(&app, "banana").await;
+    let first: JsonValue = app.sync(&[]).await.json().await.unwrap();
+
+    app.delete_todo("banana").await;
+    let changes: JsonValue = app
+        .sync(&[("since", first["next_token"].as_str().unwrap())])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(changes["todos"].as_array().unwrap().is_empty());
+    assert_eq!(changes["deleted"][0]["entity"], "todo");
+    assert_eq!(changes["deleted"][0]["entity_id"], banana_id.as_str());
+
+    // The restored todo comes back with its items
+    app.restore_from_trash(&banana_id).await;
+    let changes: JsonValue = app
+        .sync(&[("since", changes["next_token"].as_str().unwrap())])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(changes["todos"][0]["todo_id"], banana_id.as_str());
+    assert_eq!(changes["items"][0]["todo_item_id"], item_id.as_str());
+    assert!(changes["deleted"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn restore_failure() {
+    let app = spawn_app().await;
+    app.post_todo(&json!({"name": "banana", "visibility": "private"}))
+        .await;
+    let item_id = app.create_item("banana", "peel").await;
+
+    // Not in the trash
+    let response = app.restore_from_trash(&item_id).await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+    let response = app
+        .restore_from_trash(&uuid::Uuid::new_v4().to_string())
+        .await;
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+
+    // Someone else's trash
+    let second_user_id = app.create_user("second@example.com").await;
+    let second_auth = app.get_auth_header_for_user(second_user_id, "second@example.com");
+    app.delete_todo_item("banana", &item_id).await;
+    let response = app
+        .client
+        .post(format!("{}/trash/{}/restore", app.address, item_id))
+        .header("Authorization", &second_auth)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_problem(response, StatusCode::NOT_FOUND, "not_found").await;
+}
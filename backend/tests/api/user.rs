@@ -0,0 +1,81 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use time::Date;
+
+use crate::helpers::{assert_response, spawn_app};
+
+#[derive(Deserialize)]
+struct CreateItemResponse {
+    due_date: Date,
+}
+
+#[tokio::test]
+async fn get_current_user_works() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_current_user().await;
+    assert_response(&response, StatusCode::OK);
+    let value: serde_json::Value = response.json().await.expect("Failed to read json");
+    test_app.golden.check_diff_json("get_current_user", &value);
+}
+
+#[tokio::test]
+async fn update_user_time_zone_works() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .update_current_user(&serde_json::json!({"time_zone": "America/New_York"}))
+        .await;
+    assert_response(&response, StatusCode::OK);
+    let value: serde_json::Value = response.json().await.expect("Failed to read json");
+    test_app
+        .golden
+        .check_diff_json("update_current_user", &value);
+
+    let response = test_app.get_current_user().await;
+    assert_response(&response, StatusCode::OK);
+    let value: serde_json::Value = response.json().await.expect("Failed to read json");
+    assert_eq!(value["time_zone"], "America/New_York");
+}
+
+#[tokio::test]
+async fn update_user_time_zone_failure() {
+    let test_app = spawn_app().await;
+
+    for time_zone in ["", "Mars/Olympus_Mons", "America/New York", "EST5EDT; --"] {
+        let response = test_app
+            .update_current_user(&serde_json::json!({"time_zone": time_zone}))
+            .await;
+        assert_response(&response, StatusCode::BAD_REQUEST);
+    }
+
+    let response = test_app.get_current_user().await;
+    let value: serde_json::Value = response.json().await.expect("Failed to read json");
+    assert_eq!(value["time_zone"], "UTC");
+}
+
+#[tokio::test]
+async fn default_due_date_follows_user_time_zone() {
+    let test_app = spawn_app().await;
+
+    let todo_payload = serde_json::json!({"name": "tz", "visibility": "private"});
+    assert_response(&test_app.post_todo(&todo_payload).await, StatusCode::OK);
+
+    // UTC+14 and UTC-11 are always on different dates
+    let mut due_dates = vec![];
+    for time_zone in ["Pacific/Kiritimati", "Pacific/Pago_Pago"] {
+        let response = test_app
+            .update_current_user(&serde_json::json!({"time_zone": time_zone}))
+            .await;
+        assert_response(&response, StatusCode::OK);
+
+        let response = test_app
+            .post_todo_item("tz", &serde_json::json!({"title": time_zone}))
+            .await;
+        assert_response(&response, StatusCode::OK);
+        let item: CreateItemResponse = response.json().await.expect("Failed to read json");
+        assert_eq!(item.due_date, test_app.today_in(time_zone).await);
+        due_dates.push(item.due_date);
+    }
+    assert_eq!(due_dates[0] - due_dates[1], time::Duration::days(1));
+}
@@ -0,0 +1,12 @@
+import { ExchangeLoginCodeRequest, LoginResponse } from ".";
+import { authenticatedPost } from "./authenticated-client";
+
+// Trades the one-time code of the login redirect for the session tokens
+export const exchangeLoginCode = async (
+  code: string
+): Promise<LoginResponse> => {
+  const r: ExchangeLoginCodeRequest = { code };
+  return await authenticatedPost<LoginResponse>("/auth/exchange", r, {
+    skipAuth: true,
+  });
+};
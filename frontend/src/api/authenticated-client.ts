@@ -17,6 +17,90 @@ export const setAuthErrorHandler = (handler: () => void) => {
 
 export const getAuthToken = () => authToken;
 
+// RFC 7807 problem details returned by the backend on every error
+export interface FieldError {
+  field: string;
+  message: string;
+}
+
+export interface Problem {
+  type: string;
+  title: string;
+  status: number;
+  code: string;
+  detail: string;
+  request_id: string | null;
+  errors?: FieldError[];
+}
+
+export class ApiError extends Error {
+  constructor(public readonly status: number, public readonly problem?: Problem) {
+    super(problem ? problem.detail : `HTTP error! status: ${status}`);
+    this.name = "ApiError";
+  }
+
+  get code(): string | undefined {
+    return this.problem?.code;
+  }
+}
+
+export const toApiError = async (response: Response): Promise<ApiError> => {
+  if (response.headers.get("content-type")?.startsWith("application/problem+json")) {
+    try {
+      return new ApiError(response.status, (await response.json()) as Problem);
+    } catch {
+      // Fall through to the status only error
+    }
+  }
+  return new ApiError(response.status);
+};
+
+const TOKEN_STORAGE_KEY = "checklist_auth_token";
+const REFRESH_TOKEN_STORAGE_KEY = "checklist_refresh_token";
+const USER_STORAGE_KEY = "checklist_auth_user";
+
+type SessionTokensResponse = {
+  token: string;
+  refresh_token: string;
+};
+
+// Only one refresh runs at a time: the refresh token changes on every use
+// and sending an old one again revokes the whole session
+let refreshInFlight: Promise<string | null> | null = null;
+
+const refreshAuthToken = async (): Promise<string | null> => {
+  const refreshToken = localStorage.getItem(REFRESH_TOKEN_STORAGE_KEY);
+  if (!refreshToken) {
+    return null;
+  }
+  try {
+    const response = await fetch(`${BASE_URL}/auth/refresh`, {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ refresh_token: refreshToken }),
+    });
+    if (!response.ok) {
+      return null;
+    }
+    const tokens = (await response.json()) as SessionTokensResponse;
+    authToken = tokens.token;
+    localStorage.setItem(TOKEN_STORAGE_KEY, tokens.token);
+    localStorage.setItem(REFRESH_TOKEN_STORAGE_KEY, tokens.refresh_token);
+    return tokens.token;
+  } catch {
+    return null;
+  }
+};
+
+const refreshAuthTokenOnce = (): Promise<string | null> => {
+  if (!refreshInFlight) {
+    refreshInFlight = refreshAuthToken().finally(() => {
+      refreshInFlight = null;
+    });
+  }
+  return refreshInFlight;
+};
+
 export const authenticatedFetch = async (
   url: string,
   options: AuthenticatedFetchOptions = {}
@@ -26,7 +110,7 @@ export const authenticatedFetch = async (
   if (!skipAuth) {
     token = authToken;
     if (!token && typeof window !== "undefined") {
-      token = localStorage.getItem("checklist_auth_token");
+      token = localStorage.getItem(TOKEN_STORAGE_KEY);
     }
     if (token) {
       authToken = token;
@@ -36,22 +120,38 @@ export const authenticatedFetch = async (
   // Add base URL if not already present
   const fullUrl = url.startsWith("http") ? url : `${BASE_URL}${url}`;
 
-  // Add authentication header if we have a token and not skipping auth
-  if (!skipAuth && authToken) {
-    fetchOptions.headers = {
-      ...fetchOptions.headers,
-      Authorization: `Bearer ${authToken}`,
-    };
-  }
+  const send = (bearer: string | null) => {
+    // Add authentication header if we have a token and not skipping auth
+    if (!skipAuth && bearer) {
+      return fetch(fullUrl, {
+        ...fetchOptions,
+        headers: {
+          ...fetchOptions.headers,
+          Authorization: `Bearer ${bearer}`,
+        },
+      });
+    }
+    return fetch(fullUrl, fetchOptions);
+  };
 
-  const response = await fetch(fullUrl, fetchOptions);
+  let response = await send(authToken);
+
+  // The access token is short-lived, get a new one from the session and
+  // try again before giving up
+  if (response.status === 401 && token) {
+    const refreshedToken = await refreshAuthTokenOnce();
+    if (refreshedToken) {
+      response = await send(refreshedToken);
+    }
+  }
 
   // Check for authentication errors
   if ((response.status === 401 || response.status === 403) && token) {
     // Clear stored auth data
     authToken = null;
-    localStorage.removeItem("checklist_auth_token");
-    localStorage.removeItem("checklist_auth_user");
+    localStorage.removeItem(TOKEN_STORAGE_KEY);
+    localStorage.removeItem(REFRESH_TOKEN_STORAGE_KEY);
+    localStorage.removeItem(USER_STORAGE_KEY);
 
     // Trigger auth error handler if available
     if (onAuthError) {
@@ -71,7 +171,7 @@ export const authenticatedFetchJSON = async <T = unknown>(
   const response = await authenticatedFetch(url, options);
 
   if (!response.ok) {
-    throw new Error(`HTTP error! status: ${response.status}`);
+    throw await toApiError(response);
   }
 
   return response.json() as Promise<T>;
@@ -129,6 +229,6 @@ export const authenticatedDelete = async (
   });
 
   if (!response.ok) {
-    throw new Error(`HTTP error! status: ${response.status}`);
+    throw await toApiError(response);
   }
 };
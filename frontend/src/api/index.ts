@@ -105,14 +105,16 @@ export type RecurrenceInterval = {
 
 export type CreateRecurringTemplateRequest = {
   title: string;
-  recurrence_interval: RecurrenceInterval;
+  recurrence_interval?: RecurrenceInterval;
+  rrule?: string;
   start_date?: string;
   end_date?: string;
 };
 
 export type UpdateRecurringTemplateRequest = {
   title: string;
-  recurrence_interval: RecurrenceInterval;
+  recurrence_interval?: RecurrenceInterval;
+  rrule?: string;
   start_date?: string;
   end_date?: string;
   is_active: boolean;
@@ -122,7 +124,8 @@ export type RecurringTemplateResponse = {
   todo_name: string;
   template_id: string;
   title: string;
-  recurrence_interval: RecurrenceInterval;
+  recurrence_interval: RecurrenceInterval | null;
+  rrule: string | null;
   start_date: string;
   end_date: string | null;
   last_generated_date: string | null;
@@ -158,6 +161,17 @@ interface RecurringTemplateAPI {
   ): Promise<void>;
 }
 
+export type ExchangeLoginCodeRequest = {
+  code: string;
+};
+
+export type LoginResponse = {
+  token: string;
+  refresh_token: string;
+  user_id: number;
+  email: string;
+};
+
 export const FinalTodoAPI: TodoAPI = BackendTodoAPI;
 export const FinalTodoItemAPI: TodoItemAPI = BackendTodoItemAPI;
 export const FinalRecurringTemplateAPI: RecurringTemplateAPI =
@@ -12,6 +12,7 @@ import {
   authenticatedPut,
   authenticatedDelete,
   authenticatedFetch,
+  toApiError,
 } from "./authenticated-client";
 
 export const BackendTodoAPI = {
@@ -31,11 +32,9 @@ export const BackendTodoAPI = {
     console.log("CreateTodo API response:", response);
 
     if (!response.ok) {
-      const errorText = await response.text();
-      console.error("CreateTodo API error response:", errorText);
-      throw new Error(
-        `HTTP error! status: ${response.status}, body: ${errorText}`
-      );
+      const error = await toApiError(response);
+      console.error("CreateTodo API error response:", error.problem);
+      throw error;
     }
 
     // Backend returns empty response for create todo
@@ -17,7 +17,7 @@ interface AuthContextType {
   token: string | null;
   isAuthenticated: boolean;
   isLoading: boolean;
-  login: (token: string, user: User) => void;
+  login: (token: string, refreshToken: string, user: User) => void;
   logout: () => void;
   redirectToLogin: () => void;
 }
@@ -38,6 +38,7 @@ interface AuthProviderProps {
 
 const TOKEN_STORAGE_KEY = "checklist_auth_token";
 const USER_STORAGE_KEY = "checklist_auth_user";
+const REFRESH_TOKEN_STORAGE_KEY = "checklist_refresh_token";
 
 export const AuthProvider = ({ children }: AuthProviderProps) => {
   const [user, setUser] = useState<User | null>(null);
@@ -79,11 +80,16 @@ export const AuthProvider = ({ children }: AuthProviderProps) => {
     void initAuth();
   }, []);
 
-  const login = (newToken: string, newUser: User) => {
+  const login = (
+    newToken: string,
+    newRefreshToken: string,
+    newUser: User
+  ) => {
     setToken(newToken);
     setUser(newUser);
     setAuthToken(newToken);
     localStorage.setItem(TOKEN_STORAGE_KEY, newToken);
+    localStorage.setItem(REFRESH_TOKEN_STORAGE_KEY, newRefreshToken);
     localStorage.setItem(USER_STORAGE_KEY, JSON.stringify(newUser));
   };
 
@@ -92,6 +98,7 @@ export const AuthProvider = ({ children }: AuthProviderProps) => {
     setUser(null);
     setAuthToken(null);
     localStorage.removeItem(TOKEN_STORAGE_KEY);
+    localStorage.removeItem(REFRESH_TOKEN_STORAGE_KEY);
     localStorage.removeItem(USER_STORAGE_KEY);
   };
 
@@ -1,6 +1,7 @@
 import { createFileRoute } from "@tanstack/react-router";
 import { useAuth } from "@/contexts/AuthContext";
 import { useEffect, useRef, useState } from "react";
+import { exchangeLoginCode } from "@/api/auth_api";
 
 export const Route = createFileRoute("/auth/callback")({
   component: AuthCallback,
@@ -13,7 +14,7 @@ function AuthCallback() {
   const handledRef = useRef(false);
 
   useEffect(() => {
-    const handleCallback = () => {
+    const handleCallback = async () => {
       if (handledRef.current) {
         return;
       }
@@ -51,9 +52,7 @@ function AuthCallback() {
 
       try {
         const urlParams = new URLSearchParams(window.location.search);
-        const token = urlParams.get("token");
-        const user_id = urlParams.get("user_id");
-        const email = urlParams.get("email");
+        const code = urlParams.get("code");
         const error = urlParams.get("error");
 
         if (error) {
@@ -62,15 +61,20 @@ function AuthCallback() {
           return;
         }
 
-        if (!token || !user_id || !email) {
+        if (!code) {
           setError("Missing authentication data");
           setIsLoading(false);
           return;
         }
 
-        // Process the token and user data received from backend redirect
-        const user = { user_id, email };
-        login(token, user);
+        // The redirect only carries a one-time code, the tokens come from
+        // exchanging it
+        const response = await exchangeLoginCode(code);
+        const user = {
+          user_id: String(response.user_id),
+          email: response.email,
+        };
+        login(response.token, response.refresh_token, user);
 
         // Redirect to intended destination or home
         const storedRedirect = localStorage.getItem(
@@ -29,6 +29,7 @@ function RouteComponent() {
   const [endDate, setEndDate] = React.useState<Date | undefined>();
   const [recurrenceInterval, setRecurrenceInterval] =
     React.useState<RecurrenceInterval>({ days: 1 });
+  const [rrule, setRrule] = React.useState<string | null>(null);
   const [isActive, setIsActive] = React.useState(true);
 
   // Initialize form with existing template data
@@ -41,7 +42,10 @@ function RouteComponent() {
           ? new Date(templateQuery.data.end_date)
           : undefined
       );
-      setRecurrenceInterval(templateQuery.data.recurrence_interval);
+      if (templateQuery.data.recurrence_interval) {
+        setRecurrenceInterval(templateQuery.data.recurrence_interval);
+      }
+      setRrule(templateQuery.data.rrule);
       setIsActive(templateQuery.data.is_active);
     }
   }, [templateQuery.data]);
@@ -56,7 +60,7 @@ function RouteComponent() {
 
       const updateRequest: UpdateRecurringTemplateRequest = {
         title: titleValue.trim(),
-        recurrence_interval: recurrenceInterval,
+        ...(rrule ? { rrule } : { recurrence_interval: recurrenceInterval }),
         start_date: startDate ? format(startDate, "yyyy-MM-dd") : undefined,
         end_date: endDate ? format(endDate, "yyyy-MM-dd") : undefined,
         is_active: isActive,
@@ -85,6 +89,7 @@ function RouteComponent() {
       startDate,
       endDate,
       recurrenceInterval,
+      rrule,
       isActive,
       navigate,
     ]
@@ -136,8 +136,11 @@ function RouteComponent() {
                     </div>
                     <div className="space-y-1 text-sm text-gray-500">
                       <p>
-                        <span className="font-medium">Repeats:</span> Every{" "}
-                        {formatRecurrenceInterval(template.recurrence_interval)}
+                        <span className="font-medium">Repeats:</span>{" "}
+                        {template.rrule ??
+                          `Every ${formatRecurrenceInterval(
+                            template.recurrence_interval ?? {}
+                          )}`}
                       </p>
                       <p>
                         <span className="font-medium">Start Date:</span>{" "}